@@ -0,0 +1,64 @@
+//! Throughput of the terminal output pump: `cargo bench --bench terminal_output`.
+//!
+//! Feeds PTY-sized reads through the coalescing pump and reports MB/s and the number
+//! of batches for the base64 event payload and the raw binary channel payload.
+
+#[path = "../src/terminal_output.rs"]
+#[allow(dead_code)]
+mod terminal_output;
+
+use base64::{engine::general_purpose, Engine as _};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+use terminal_output::{BatchConfig, FlowControl};
+
+const TOTAL_BYTES: usize = 256 * 1024 * 1024;
+
+fn run(label: &str, read_size: usize, encode_base64: bool) {
+    let cfg = BatchConfig::default();
+    let flow = FlowControl::new();
+    let (tx, rx) = mpsc::sync_channel::<Vec<u8>>(32);
+
+    let producer = std::thread::spawn(move || {
+        let chunk: Vec<u8> = (0..read_size).map(|i| b' ' + (i % 90) as u8).collect();
+        let mut sent = 0usize;
+        while sent < TOTAL_BYTES {
+            tx.send(chunk.clone()).unwrap();
+            sent += chunk.len();
+        }
+    });
+
+    let started = Instant::now();
+    let mut batches = 0usize;
+    let mut delivered = 0usize;
+    terminal_output::pump(rx, &cfg, &flow, |offset, bytes| {
+        batches += 1;
+        delivered += if encode_base64 {
+            general_purpose::STANDARD.encode(bytes).len()
+        } else {
+            let mut message = Vec::with_capacity(8 + bytes.len());
+            message.extend_from_slice(&offset.to_be_bytes());
+            message.extend_from_slice(bytes);
+            message.len()
+        };
+    });
+    producer.join().unwrap();
+
+    let elapsed = started.elapsed().max(Duration::from_micros(1));
+    let mb = TOTAL_BYTES as f64 / (1024.0 * 1024.0);
+    println!(
+        "{:<28} read={:>6}B  {:>9.1} MB/s  {:>6} batches  {:>7.1} MB payload",
+        label,
+        read_size,
+        mb / elapsed.as_secs_f64(),
+        batches,
+        delivered as f64 / (1024.0 * 1024.0),
+    );
+}
+
+fn main() {
+    for read_size in [1024, 4096, 64 * 1024] {
+        run("base64 events", read_size, true);
+        run("binary channel", read_size, false);
+    }
+}
@@ -0,0 +1,1118 @@
+use chrono::{Datelike, Timelike};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tauri::{async_runtime, AppHandle, Emitter};
+use walkdir::WalkDir;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::{config, jobs, quarantine, secrets, site, sync};
+
+const MANIFEST_FILE: &str = "manifest.json";
+const MANIFEST_FORMAT: &str = "xnote-backup";
+const MANIFEST_VERSION: u32 = 1;
+const MAX_MANIFEST_BYTES: u64 = 64 * 1024 * 1024;
+/// Archive folder holding the workspace; config.json and the secret store sit next to it.
+const WORKSPACE_PREFIX: &str = "workspace/";
+const DEFAULT_DIR: &str = "backups";
+const FILE_PREFIX: &str = "xnote-backup-";
+const ZSTD_LEVEL: i32 = 3;
+const SKIPPED_FILES: &[&str] = &[".DS_Store", "Thumbs.db"];
+/// Config keys whose plaintext values are left out of backups made without secrets.
+const SECRET_KEY_WORDS: &[&str] = &["apikey", "password", "token", "secret"];
+/// How often the scheduler checks whether a backup is due.
+const TICK: Duration = Duration::from_secs(60);
+/// Wait after a failed scheduled backup, so a full disk is not retried every minute.
+const RETRY_AFTER: Duration = Duration::from_secs(30 * 60);
+
+/// Set while a backup is being written; backups never run concurrently.
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// `backup` in config.json.
+#[derive(Deserialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+struct BackupConfig {
+    /// Whether backups are made on a schedule.
+    enabled: bool,
+    /// Workspace to back up; empty for the default `doc` folder.
+    workspace: String,
+    interval_hours: u64,
+    /// Archives kept in `directory`; older ones are deleted after each backup. 0 keeps all.
+    keep: usize,
+    /// `zip` or `tar.zst`.
+    format: String,
+    /// Where archives go; empty for `backups` in the app folder.
+    directory: String,
+    include_secrets: bool,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            workspace: String::new(),
+            interval_hours: 24,
+            keep: 7,
+            format: "zip".to_string(),
+            directory: String::new(),
+            include_secrets: false,
+        }
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct ConfigWithBackup {
+    backup: BackupConfig,
+}
+
+fn load_config() -> BackupConfig {
+    crate::get_config()
+        .ok()
+        .and_then(|text| serde_json::from_str::<ConfigWithBackup>(&text).ok())
+        .map(|c| c.backup)
+        .unwrap_or_default()
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Zip,
+    TarZst,
+}
+
+impl Format {
+    fn parse(name: &str) -> Result<Self, String> {
+        match name.trim().to_lowercase().as_str() {
+            "" | "zip" => Ok(Format::Zip),
+            "tar.zst" | "tzst" | "zst" => Ok(Format::TarZst),
+            other => Err(format!("Unknown backup format `{}`", other)),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Format::Zip => "zip",
+            Format::TarZst => "tar.zst",
+        }
+    }
+
+    /// Tells the formats apart by their magic bytes rather than trusting the file name.
+    fn detect(path: &Path) -> Result<Self, String> {
+        let mut magic = [0u8; 4];
+        File::open(path)
+            .and_then(|mut f| f.read_exact(&mut magic))
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        match magic {
+            [b'P', b'K', 3, 4] => Ok(Format::Zip),
+            [0x28, 0xb5, 0x2f, 0xfd] => Ok(Format::TarZst),
+            _ => Err(format!("{} is not a zip or tar.zst archive", path.display())),
+        }
+    }
+}
+
+/// `manifest.json` in every archive. Restores check each entry against it.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Manifest {
+    format: String,
+    version: u32,
+    created: String,
+    /// The workspace folder the backup was made from.
+    workspace: String,
+    includes_secrets: bool,
+    files: Vec<ManifestFile>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct ManifestFile {
+    /// Archive entry name, e.g. `workspace/notes/a.md` or `config.json`.
+    path: String,
+    size: u64,
+    sha256: String,
+    /// Seconds since the Unix epoch.
+    modified: Option<i64>,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupReport {
+    /// Set by the caller; `run_backup` does not know about jobs.
+    job_id: u64,
+    path: String,
+    format: &'static str,
+    /// Workspace files in the archive.
+    files: usize,
+    size: u64,
+    includes_secrets: bool,
+    scheduled: bool,
+    /// Older archives deleted to honour `keep`.
+    removed: Vec<String>,
+    warnings: Vec<String>,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RestoreReport {
+    /// Set by the caller; `run_restore` does not know about jobs.
+    job_id: u64,
+    archive: String,
+    target: String,
+    /// When the backup was made.
+    created: String,
+    restored: usize,
+    /// Files already identical in the target, which were not touched.
+    unchanged: usize,
+    /// Files that differed in the target; the old versions are in `quarantine`.
+    replaced: usize,
+    quarantine: Option<String>,
+    config_restored: bool,
+    secrets_restored: bool,
+    warnings: Vec<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupInfo {
+    path: String,
+    name: String,
+    format: &'static str,
+    size: u64,
+    modified: String,
+}
+
+/// What to back up and where; built from config.json and the command's arguments.
+struct Plan {
+    workspace: PathBuf,
+    directory: PathBuf,
+    format: Format,
+    include_secrets: bool,
+    keep: usize,
+    scheduled: bool,
+}
+
+/// Holds `RUNNING` for the duration of one backup.
+struct RunningGuard;
+
+impl RunningGuard {
+    fn acquire() -> Result<Self, String> {
+        RUNNING
+            .compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+            .map(|_| RunningGuard)
+            .map_err(|_| "A backup is already running".to_string())
+    }
+}
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        RUNNING.store(false, Ordering::Release);
+    }
+}
+
+/// Feeds everything read through it into a SHA-256 hash.
+struct Hashing<R> {
+    inner: R,
+    hasher: Sha256,
+    size: u64,
+}
+
+impl<R: Read> Hashing<R> {
+    fn new(inner: R) -> Self {
+        Self { inner, hasher: Sha256::new(), size: 0 }
+    }
+
+    fn finish(self) -> (u64, String) {
+        let hash = self.hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect();
+        (self.size, hash)
+    }
+}
+
+impl<R: Read> Read for Hashing<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        self.size += n as u64;
+        Ok(n)
+    }
+}
+
+enum Writer {
+    Zip(Box<ZipWriter<File>>),
+    TarZst(tar::Builder<zstd::Encoder<'static, File>>),
+}
+
+impl Writer {
+    fn create(path: &Path, format: Format) -> Result<Self, String> {
+        let file = File::create(path).map_err(|e| e.to_string())?;
+        Ok(match format {
+            Format::Zip => Writer::Zip(Box::new(ZipWriter::new(file))),
+            Format::TarZst => {
+                let encoder = zstd::Encoder::new(file, ZSTD_LEVEL).map_err(|e| e.to_string())?;
+                Writer::TarZst(tar::Builder::new(encoder))
+            }
+        })
+    }
+
+    fn add(&mut self, name: &str, data: &mut dyn Read, size: u64, modified: Option<i64>) -> Result<(), String> {
+        match self {
+            Writer::Zip(zip) => {
+                let mut options = SimpleFileOptions::default()
+                    .compression_method(CompressionMethod::Deflated)
+                    .large_file(size >= u32::MAX as u64);
+                if let Some(time) = modified.and_then(zip_time) {
+                    options = options.last_modified_time(time);
+                }
+                zip.start_file(name, options).map_err(|e| e.to_string())?;
+                io::copy(data, zip.as_mut()).map_err(|e| e.to_string())?;
+            }
+            Writer::TarZst(tar) => {
+                let mut header = tar::Header::new_gnu();
+                header.set_size(size);
+                header.set_mode(0o644);
+                header.set_mtime(modified.unwrap_or(0).max(0) as u64);
+                header.set_cksum();
+                tar.append_data(&mut header, name, data.take(size)).map_err(|e| e.to_string())?;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<(), String> {
+        let file = match self {
+            Writer::Zip(zip) => zip.finish().map_err(|e| e.to_string())?,
+            Writer::TarZst(tar) => {
+                let encoder = tar.into_inner().map_err(|e| e.to_string())?;
+                encoder.finish().map_err(|e| e.to_string())?
+            }
+        };
+        file.sync_all().map_err(|e| e.to_string())
+    }
+}
+
+fn zip_time(secs: i64) -> Option<zip::DateTime> {
+    let local = chrono::DateTime::from_timestamp(secs, 0)?.with_timezone(&chrono::Local);
+    zip::DateTime::from_date_and_time(
+        local.year().try_into().ok()?,
+        local.month() as u8,
+        local.day() as u8,
+        local.hour() as u8,
+        local.minute() as u8,
+        local.second() as u8,
+    )
+    .ok()
+}
+
+fn unix_secs(time: SystemTime) -> Option<i64> {
+    time.duration_since(UNIX_EPOCH).ok().map(|d| d.as_secs() as i64)
+}
+
+/// Calls `visit` with the name and contents of every file entry, in archive order.
+fn read_entries(
+    path: &Path,
+    format: Format,
+    mut visit: impl FnMut(&str, &mut dyn Read) -> Result<(), String>,
+) -> Result<(), String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    match format {
+        Format::Zip => {
+            let mut archive = zip::ZipArchive::new(file).map_err(|e| e.to_string())?;
+            for i in 0..archive.len() {
+                let mut entry = archive.by_index(i).map_err(|e| e.to_string())?;
+                if entry.is_dir() {
+                    continue;
+                }
+                let name = entry.name().to_string();
+                visit(&name, &mut entry)?;
+            }
+        }
+        Format::TarZst => {
+            let decoder = zstd::Decoder::new(file).map_err(|e| e.to_string())?;
+            let mut archive = tar::Archive::new(decoder);
+            for entry in archive.entries().map_err(|e| e.to_string())? {
+                let mut entry = entry.map_err(|e| e.to_string())?;
+                let name = String::from_utf8_lossy(&entry.path_bytes()).to_string();
+                match entry.header().entry_type() {
+                    tar::EntryType::Regular | tar::EntryType::Continuous => {}
+                    tar::EntryType::Directory => continue,
+                    _ => return Err(format!("Unsupported archive entry `{}`", name)),
+                }
+                visit(&name, &mut entry)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// What an archive entry is, judged by its name.
+enum Entry<'a> {
+    /// A workspace file, relative to the workspace.
+    Workspace(&'a str),
+    Config,
+    Secret(&'a str),
+}
+
+/// Rejects names that could write outside the restore target, such as `../x` or `C:x`.
+fn classify(name: &str) -> Result<Entry<'_>, String> {
+    if name == config::CONFIG_FILE {
+        return Ok(Entry::Config);
+    }
+    if name == secrets::STORE_FILE || name == secrets::KEY_FILE {
+        return Ok(Entry::Secret(name));
+    }
+    let Some(rel) = name.strip_prefix(WORKSPACE_PREFIX) else {
+        return Err(format!("Unexpected archive entry `{}`", name));
+    };
+    let safe = !rel.is_empty()
+        && rel.split('/').all(|part| {
+            let mut components = Path::new(part).components();
+            matches!((components.next(), components.next()), (Some(Component::Normal(_)), None))
+        });
+    if !safe {
+        return Err(format!("Unsafe path in archive: `{}`", name));
+    }
+    Ok(Entry::Workspace(rel))
+}
+
+fn entry_name(rel: &Path) -> String {
+    let parts: Vec<String> = rel.components().map(|c| c.as_os_str().to_string_lossy().to_string()).collect();
+    format!("{}{}", WORKSPACE_PREFIX, parts.join("/"))
+}
+
+fn default_workspace() -> Result<PathBuf, String> {
+    Ok(crate::get_xnote_root()?.join("doc"))
+}
+
+fn resolve_dir(value: &str, fallback: impl FnOnce() -> Result<PathBuf, String>) -> Result<PathBuf, String> {
+    if value.trim().is_empty() {
+        fallback()
+    } else {
+        Ok(site::lexical_normalize(Path::new(value.trim())))
+    }
+}
+
+fn backup_dir(config: &BackupConfig) -> Result<PathBuf, String> {
+    resolve_dir(&config.directory, || Ok(crate::get_xnote_root()?.join(DEFAULT_DIR)))
+}
+
+fn is_backup_name(name: &str) -> bool {
+    name.starts_with(FILE_PREFIX) && (name.ends_with(".zip") || name.ends_with(".tar.zst"))
+}
+
+/// Archives in `dir` made by this app, newest first.
+fn list(dir: &Path) -> Vec<(PathBuf, fs::Metadata)> {
+    let Ok(entries) = fs::read_dir(dir) else { return Vec::new() };
+    let mut found: Vec<(PathBuf, fs::Metadata)> = entries
+        .flatten()
+        .filter(|e| is_backup_name(&e.file_name().to_string_lossy()))
+        .filter_map(|e| Some((e.path(), e.metadata().ok().filter(|m| m.is_file())?)))
+        .collect();
+    found.sort_by(|a, b| {
+        let modified = |m: &fs::Metadata| m.modified().unwrap_or(UNIX_EPOCH);
+        modified(&b.1).cmp(&modified(&a.1)).then_with(|| b.0.cmp(&a.0))
+    });
+    found
+}
+
+/// Deletes all but the newest `keep` archives in `dir`.
+fn rotate(dir: &Path, keep: usize) -> Vec<String> {
+    let mut removed = Vec::new();
+    for (path, _) in list(dir).into_iter().skip(keep) {
+        if fs::remove_file(&path).is_ok() {
+            removed.push(path.to_string_lossy().to_string());
+        }
+    }
+    removed
+}
+
+/// Blanks credentials that config.json holds in plaintext rather than as secret store handles.
+fn scrub_secrets(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, item) in map.iter_mut() {
+                let key = key.to_lowercase();
+                match item {
+                    Value::String(text) if SECRET_KEY_WORDS.iter().any(|w| key.contains(w)) => {
+                        if secrets::handle_id(text).is_none() {
+                            text.clear();
+                        }
+                    }
+                    _ => scrub_secrets(item),
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(scrub_secrets),
+        _ => {}
+    }
+}
+
+/// Workspace files to back up, relative to `workspace`. Quarantined files, sync state, OS
+/// litter and the backup folder itself (should it live inside the workspace) are left out.
+fn collect_files(workspace: &Path, skip: &[PathBuf]) -> Vec<PathBuf> {
+    WalkDir::new(workspace)
+        .into_iter()
+        .filter_entry(|e| {
+            let app_dir = e.file_type().is_dir() && (e.file_name() == quarantine::QUARANTINE_DIR || e.file_name() == sync::STATE_DIR);
+            !app_dir && !skip.iter().any(|s| s == e.path())
+        })
+        .flatten()
+        .filter(|e| e.file_type().is_file() && !SKIPPED_FILES.contains(&e.file_name().to_string_lossy().as_ref()))
+        .filter_map(|e| e.path().strip_prefix(workspace).ok().map(Path::to_path_buf))
+        .collect()
+}
+
+fn write_archive(plan: &Plan, part: &Path, job: &dyn jobs::Progress, report: &mut BackupReport) -> Result<(), String> {
+    let xnote_root = crate::get_xnote_root()?;
+    let secret_files = [secrets::STORE_FILE, secrets::KEY_FILE].map(|name| xnote_root.join(name));
+    let mut skip = vec![plan.directory.clone()];
+    // Added separately below, should the workspace be the app folder itself.
+    skip.extend(secret_files.iter().cloned());
+    skip.push(config::config_path()?);
+    let files = collect_files(&plan.workspace, &skip);
+    let total = files.len();
+
+    let mut writer = Writer::create(part, plan.format)?;
+    let mut entries = Vec::new();
+    let mut add = |writer: &mut Writer, name: String, data: &mut dyn Read, size: u64, modified: Option<i64>| {
+        let mut hashing = Hashing::new(data);
+        writer.add(&name, &mut hashing, size, modified)?;
+        let (size, sha256) = hashing.finish();
+        entries.push(ManifestFile { path: name, size, sha256, modified });
+        Ok::<(), String>(())
+    };
+
+    for (i, rel) in files.iter().enumerate() {
+        if job.is_cancelled() {
+            return Err("Cancelled".to_string());
+        }
+        job.progress(i, total, &rel.to_string_lossy());
+        let path = plan.workspace.join(rel);
+        let mut file = File::open(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let meta = file.metadata().map_err(|e| e.to_string())?;
+        let modified = meta.modified().ok().and_then(unix_secs);
+        add(&mut writer, entry_name(rel), &mut file, meta.len(), modified)?;
+    }
+    job.progress(total, total, config::CONFIG_FILE);
+    let now = unix_secs(SystemTime::now());
+
+    if let Ok(text) = fs::read_to_string(config::config_path()?) {
+        let text = if plan.include_secrets {
+            Some(text)
+        } else {
+            match serde_json::from_str::<Value>(&text) {
+                Ok(mut value) => {
+                    scrub_secrets(&mut value);
+                    serde_json::to_string_pretty(&value).ok()
+                }
+                Err(_) => None,
+            }
+        };
+        match text {
+            Some(text) => add(&mut writer, config::CONFIG_FILE.to_string(), &mut text.as_bytes(), text.len() as u64, now)?,
+            None => report.warnings.push("config.json is not valid JSON and was left out".to_string()),
+        }
+    }
+    if plan.include_secrets {
+        for path in secret_files.iter().filter(|p| p.is_file()) {
+            let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+            let data = fs::read(path).map_err(|e| e.to_string())?;
+            add(&mut writer, name, &mut data.as_slice(), data.len() as u64, now)?;
+        }
+    }
+
+    let manifest = Manifest {
+        format: MANIFEST_FORMAT.to_string(),
+        version: MANIFEST_VERSION,
+        created: chrono::Local::now().to_rfc3339(),
+        workspace: plan.workspace.to_string_lossy().to_string(),
+        includes_secrets: plan.include_secrets,
+        files: entries,
+    };
+    let json = serde_json::to_vec_pretty(&manifest).map_err(|e| e.to_string())?;
+    writer.add(MANIFEST_FILE, &mut json.as_slice(), json.len() as u64, now)?;
+    writer.finish()?;
+    report.files = total;
+    Ok(())
+}
+
+fn run_backup(plan: &Plan, job: &dyn jobs::Progress) -> Result<BackupReport, String> {
+    if !plan.workspace.is_dir() {
+        return Err(format!("{} is not a folder", plan.workspace.display()));
+    }
+    fs::create_dir_all(&plan.directory).map_err(|e| e.to_string())?;
+
+    let stamp = chrono::Local::now().format("%Y%m%d-%H%M%S").to_string();
+    let mut target = plan.directory.join(format!("{}{}.{}", FILE_PREFIX, stamp, plan.format.name()));
+    let mut n = 1;
+    while target.exists() {
+        target = plan.directory.join(format!("{}{}-{}.{}", FILE_PREFIX, stamp, n, plan.format.name()));
+        n += 1;
+    }
+    // Written under a temporary name so an interrupted backup never looks like a complete one.
+    let part = target.with_file_name(format!("{}.part", target.file_name().unwrap_or_default().to_string_lossy()));
+
+    let mut report = BackupReport {
+        job_id: 0,
+        path: target.to_string_lossy().to_string(),
+        format: plan.format.name(),
+        files: 0,
+        size: 0,
+        includes_secrets: plan.include_secrets,
+        scheduled: plan.scheduled,
+        removed: Vec::new(),
+        warnings: Vec::new(),
+    };
+    let written = write_archive(plan, &part, job, &mut report).and_then(|_| fs::rename(&part, &target).map_err(|e| e.to_string()));
+    if let Err(err) = written {
+        let _ = fs::remove_file(&part);
+        return Err(err);
+    }
+    report.size = fs::metadata(&target).map(|m| m.len()).unwrap_or(0);
+    if plan.keep > 0 {
+        report.removed = rotate(&plan.directory, plan.keep);
+    }
+    Ok(report)
+}
+
+/// Runs a backup as a job, emitting `backup-result` when it succeeds.
+fn execute(app: &AppHandle, job: jobs::JobHandle, plan: &Plan, _guard: RunningGuard) -> bool {
+    let job_id = job.id();
+    let result = run_backup(plan, &job).map(|report| BackupReport { job_id, ..report });
+    if job.is_cancelled() {
+        job.finish(jobs::JobStatus::Cancelled, None);
+        return false;
+    }
+    if let Ok(report) = &result {
+        let _ = app.emit("backup-result", report.clone());
+    }
+    job.finish_with(&result);
+    result.is_ok()
+}
+
+/// Whether the newest archive in the backup folder is older than the configured interval.
+fn due(config: &BackupConfig) -> Result<bool, String> {
+    let interval = Duration::from_secs(config.interval_hours.max(1) * 3600);
+    let newest = list(&backup_dir(config)?).into_iter().next().and_then(|(_, meta)| meta.modified().ok());
+    Ok(match newest {
+        Some(modified) => SystemTime::now().duration_since(modified).map(|age| age >= interval).unwrap_or(false),
+        None => true,
+    })
+}
+
+/// Makes scheduled backups while `backup.enabled` is set in config.json.
+pub fn start(app: AppHandle) {
+    std::thread::spawn(move || {
+        let mut last_failure: Option<Instant> = None;
+        loop {
+            std::thread::sleep(TICK);
+            let config = load_config();
+            if !config.enabled || last_failure.is_some_and(|at| at.elapsed() < RETRY_AFTER) {
+                continue;
+            }
+            let plan = due(&config).and_then(|due| {
+                if !due {
+                    return Ok(None);
+                }
+                Ok(Some(Plan {
+                    workspace: resolve_dir(&config.workspace, default_workspace)?,
+                    directory: backup_dir(&config)?,
+                    format: Format::parse(&config.format)?,
+                    include_secrets: config.include_secrets,
+                    keep: config.keep,
+                    scheduled: true,
+                }))
+            });
+            let plan = match plan {
+                Ok(Some(plan)) => plan,
+                Ok(None) => continue,
+                Err(err) => {
+                    eprintln!("Scheduled backup skipped: {}", err);
+                    last_failure = Some(Instant::now());
+                    continue;
+                }
+            };
+            // A manual backup is running; check again on the next tick.
+            let Ok(guard) = RunningGuard::acquire() else { continue };
+            let job = jobs::start(&app, "backup");
+            let ok = execute(&app, job, &plan, guard);
+            last_failure = (!ok).then(Instant::now);
+        }
+    });
+}
+
+/// Reads the archive once, checking every entry name and checksum against the manifest,
+/// before anything is written.
+fn verify(archive: &Path, format: Format, job: &dyn jobs::Progress) -> Result<Manifest, String> {
+    let mut seen: HashMap<String, (u64, String)> = HashMap::new();
+    let mut manifest_json: Option<Vec<u8>> = None;
+    read_entries(archive, format, |name, data| {
+        if job.is_cancelled() {
+            return Err("Cancelled".to_string());
+        }
+        job.progress(seen.len(), 0, &format!("Checking {}", name));
+        if name == MANIFEST_FILE {
+            let mut json = Vec::new();
+            data.take(MAX_MANIFEST_BYTES).read_to_end(&mut json).map_err(|e| e.to_string())?;
+            manifest_json = Some(json);
+            return Ok(());
+        }
+        classify(name)?;
+        let mut hashing = Hashing::new(data);
+        io::copy(&mut hashing, &mut io::sink()).map_err(|e| format!("{}: {}", name, e))?;
+        if seen.insert(name.to_string(), hashing.finish()).is_some() {
+            return Err(format!("The archive contains `{}` twice", name));
+        }
+        Ok(())
+    })?;
+
+    let json = manifest_json.ok_or_else(|| "Not an xnote backup: manifest.json is missing".to_string())?;
+    let manifest: Manifest = serde_json::from_slice(&json).map_err(|e| format!("Invalid backup manifest: {}", e))?;
+    if manifest.format != MANIFEST_FORMAT {
+        return Err("Not an xnote backup".to_string());
+    }
+    if manifest.version > MANIFEST_VERSION {
+        return Err(format!("The backup was made by a newer version of the app (format {})", manifest.version));
+    }
+    for file in manifest.files.iter() {
+        match seen.remove(&file.path) {
+            Some((size, sha256)) if size == file.size && sha256 == file.sha256 => {}
+            Some(_) => return Err(format!("`{}` is damaged (checksum mismatch)", file.path)),
+            None => return Err(format!("`{}` is missing from the archive", file.path)),
+        }
+    }
+    if let Some(name) = seen.keys().next() {
+        return Err(format!("`{}` is not listed in the manifest", name));
+    }
+    Ok(manifest)
+}
+
+fn same_contents(path: &Path, file: &ManifestFile) -> bool {
+    let Ok(meta) = fs::metadata(path) else { return false };
+    if meta.len() != file.size {
+        return false;
+    }
+    let Ok(f) = File::open(path) else { return false };
+    let mut hashing = Hashing::new(f);
+    io::copy(&mut hashing, &mut io::sink()).is_ok() && hashing.finish().1 == file.sha256
+}
+
+/// Restores `archive` into `target`. Also returns the `config-changed` payload when
+/// config.json was restored.
+fn run_restore(
+    archive: &Path,
+    target: &Path,
+    restore_settings: bool,
+    job: &dyn jobs::Progress,
+) -> Result<(RestoreReport, Option<config::ConfigChanged>), String> {
+    let format = Format::detect(archive)?;
+    let manifest = verify(archive, format, job)?;
+    let files: HashMap<&str, &ManifestFile> = manifest.files.iter().map(|f| (f.path.as_str(), f)).collect();
+
+    let mut report = RestoreReport {
+        job_id: 0,
+        archive: archive.to_string_lossy().to_string(),
+        target: target.to_string_lossy().to_string(),
+        created: manifest.created.clone(),
+        restored: 0,
+        unchanged: 0,
+        replaced: 0,
+        quarantine: None,
+        config_restored: false,
+        secrets_restored: false,
+        warnings: Vec::new(),
+    };
+
+    // In an existing folder, identical files are skipped and differing ones are moved
+    // into a quarantine batch first, so nothing is overwritten for good.
+    let mut skip: Vec<&str> = Vec::new();
+    if target.exists() {
+        let mut batch: Option<quarantine::QuarantineBatch> = None;
+        for file in manifest.files.iter() {
+            let Ok(Entry::Workspace(rel)) = classify(&file.path) else { continue };
+            let path = target.join(rel);
+            if path.is_dir() {
+                return Err(format!("{} is a folder in the target", path.display()));
+            }
+            if !path.exists() {
+                continue;
+            }
+            if same_contents(&path, file) {
+                skip.push(&file.path);
+                report.unchanged += 1;
+                continue;
+            }
+            if batch.is_none() {
+                batch = Some(quarantine::QuarantineBatch::create(target)?);
+            }
+            if let Some(batch) = batch.as_mut() {
+                batch.add(target, &path)?;
+            }
+            report.replaced += 1;
+        }
+        if let Some(batch) = batch {
+            report.quarantine = batch.finish()?;
+        }
+    }
+    fs::create_dir_all(target).map_err(|e| e.to_string())?;
+
+    let total = manifest.files.len();
+    let mut done = 0usize;
+    let mut config_text: Option<String> = None;
+    let mut secret_files: HashMap<String, Vec<u8>> = HashMap::new();
+    read_entries(archive, format, |name, data| {
+        if job.is_cancelled() {
+            return Err("Cancelled".to_string());
+        }
+        let Some(file) = files.get(name) else { return Ok(()) };
+        done += 1;
+        job.progress(done, total, name);
+        match classify(name)? {
+            Entry::Workspace(_) if skip.contains(&name) => {}
+            Entry::Workspace(rel) => {
+                let path = target.join(rel);
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+                }
+                let mut out = File::create(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+                io::copy(data, &mut out).map_err(|e| format!("{}: {}", path.display(), e))?;
+                out.flush().map_err(|e| e.to_string())?;
+                if let Some(modified) = file.modified.and_then(|s| u64::try_from(s).ok()) {
+                    let _ = out.set_modified(UNIX_EPOCH + Duration::from_secs(modified));
+                }
+                report.restored += 1;
+            }
+            Entry::Config if restore_settings => {
+                let mut text = String::new();
+                data.read_to_string(&mut text).map_err(|e| e.to_string())?;
+                config_text = Some(text);
+            }
+            Entry::Secret(name) if restore_settings => {
+                let mut bytes = Vec::new();
+                data.read_to_end(&mut bytes).map_err(|e| e.to_string())?;
+                secret_files.insert(name.to_string(), bytes);
+            }
+            Entry::Config | Entry::Secret(_) => {}
+        }
+        Ok(())
+    })?;
+
+    // The secret store goes first so API keys in the restored config can move into it.
+    if let Some(store) = secret_files.get(secrets::STORE_FILE) {
+        match secrets::import_store(store, secret_files.get(secrets::KEY_FILE).map(|k| k.as_slice())) {
+            Ok(secrets::KeySource::Keyring) => {
+                report.secrets_restored = true;
+                report.warnings.push(
+                    "The restored secret store is encrypted with a key from the system keyring and only opens on the machine the backup was made on".to_string(),
+                );
+            }
+            Ok(_) => report.secrets_restored = true,
+            Err(err) => report.warnings.push(format!("Secrets were not restored: {}", err)),
+        }
+    }
+    let mut changed = None;
+    if let Some(text) = config_text {
+        match config::restore(&text) {
+            Ok(config) => {
+                report.config_restored = true;
+                changed = Some(config);
+            }
+            Err(err) => report.warnings.push(format!("Settings were not restored: {}", err)),
+        }
+    }
+    Ok((report, changed))
+}
+
+/// Backs up a workspace, its config.json and optionally the secret store into a zip or
+/// tar.zst archive. Arguments left out come from the `backup` section of config.json.
+/// Only archives written to the configured folder are rotated. Returns the job id.
+#[tauri::command]
+pub fn create_backup(
+    app: AppHandle,
+    root_path: Option<String>,
+    directory: Option<String>,
+    format: Option<String>,
+    include_secrets: Option<bool>,
+) -> Result<u64, String> {
+    let config = load_config();
+    let configured_dir = backup_dir(&config)?;
+    let directory = resolve_dir(directory.as_deref().unwrap_or(""), || Ok(configured_dir.clone()))?;
+    let plan = Plan {
+        workspace: resolve_dir(root_path.as_deref().unwrap_or(&config.workspace), default_workspace)?,
+        format: Format::parse(format.as_deref().unwrap_or(&config.format))?,
+        include_secrets: include_secrets.unwrap_or(config.include_secrets),
+        keep: if directory == configured_dir { config.keep } else { 0 },
+        directory,
+        scheduled: false,
+    };
+    let guard = RunningGuard::acquire()?;
+
+    let app_handle = app.clone();
+    let job = jobs::start(&app, "backup");
+    let job_id = job.id();
+    async_runtime::spawn_blocking(move || {
+        execute(&app_handle, job, &plan, guard);
+    });
+    Ok(job_id)
+}
+
+/// Archives in `directory` (default: the configured backup folder), newest first.
+#[tauri::command]
+pub fn list_backups(directory: Option<String>) -> Result<Vec<BackupInfo>, String> {
+    let dir = match directory.filter(|d| !d.trim().is_empty()) {
+        Some(dir) => site::lexical_normalize(Path::new(&dir)),
+        None => backup_dir(&load_config())?,
+    };
+    Ok(list(&dir)
+        .into_iter()
+        .map(|(path, meta)| {
+            let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+            BackupInfo {
+                format: if name.ends_with(".zip") { "zip" } else { "tar.zst" },
+                path: path.to_string_lossy().to_string(),
+                name,
+                size: meta.len(),
+                modified: meta
+                    .modified()
+                    .map(|t| chrono::DateTime::<chrono::Local>::from(t).to_rfc3339())
+                    .unwrap_or_default(),
+            }
+        })
+        .collect())
+}
+
+/// Restores a backup into `target`, which may be a new folder or an existing workspace.
+/// The archive is verified in full first. Files in the target that the backup does not
+/// contain are left alone. With `restore_settings`, config.json replaces the current one
+/// and the secret store is restored unless one already exists. Emits `restore-result`.
+#[tauri::command]
+pub fn restore_backup(
+    app: AppHandle,
+    archive: String,
+    target: String,
+    restore_settings: Option<bool>,
+) -> Result<u64, String> {
+    let archive_path = site::lexical_normalize(Path::new(&archive));
+    if !archive_path.is_file() {
+        return Err(format!("{} does not exist", archive));
+    }
+    if target.trim().is_empty() {
+        return Err("Choose a folder to restore into".to_string());
+    }
+    let target = site::lexical_normalize(Path::new(target.trim()));
+    if target.is_file() {
+        return Err(format!("{} is a file", target.display()));
+    }
+    let restore_settings = restore_settings.unwrap_or(false);
+
+    let app_handle = app.clone();
+    let job = jobs::start(&app, "restore-backup");
+    let job_id = job.id();
+    async_runtime::spawn_blocking(move || {
+        let result = run_restore(&archive_path, &target, restore_settings, &job);
+        if job.is_cancelled() {
+            job.finish(jobs::JobStatus::Cancelled, None);
+            return;
+        }
+        let result = result.map(|(report, changed)| {
+            if let Some(changed) = changed {
+                let _ = app_handle.emit("config-changed", changed);
+            }
+            RestoreReport { job_id, ..report }
+        });
+        if let Ok(report) = &result {
+            let _ = app_handle.emit("restore-result", report.clone());
+        }
+        job.finish_with(&result);
+    });
+    Ok(job_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_entry_names() {
+        let workspace = |name| match classify(name) {
+            Ok(Entry::Workspace(rel)) => Some(rel),
+            _ => None,
+        };
+        assert_eq!(workspace("workspace/a.md"), Some("a.md"));
+        assert_eq!(workspace("workspace/notes/é x.png"), Some("notes/é x.png"));
+        assert!(matches!(classify("config.json"), Ok(Entry::Config)));
+        assert!(matches!(classify("secrets.json"), Ok(Entry::Secret("secrets.json"))));
+        assert!(matches!(classify("secrets.key"), Ok(Entry::Secret("secrets.key"))));
+
+        let rejected = [
+            "workspace/../x",
+            "workspace/a/../../x",
+            "workspace//x",
+            "workspace/a//b",
+            "workspace/./x",
+            "workspace/",
+            "workspace/a/",
+            "/etc/passwd",
+            "/workspace/x",
+            "../workspace/x",
+            "C:x",
+            "notes/a.md",
+            "manifest.json",
+        ];
+        for name in rejected {
+            assert!(classify(name).is_err(), "{}", name);
+        }
+        #[cfg(windows)]
+        for name in ["workspace/C:x", "workspace/C:/x", "workspace/\\\\server\\share"] {
+            assert!(classify(name).is_err(), "{}", name);
+        }
+    }
+
+    /// Writes an archive holding `entries` and a manifest listing those in `listed`.
+    fn archive(path: &Path, format: Format, entries: &[(&str, &[u8])], listed: &[&str]) {
+        let mut writer = Writer::create(path, format).unwrap();
+        for (name, data) in entries {
+            match &mut writer {
+                // `tar` refuses to write unsafe names, so set them in the header directly.
+                Writer::TarZst(tar) => {
+                    let mut header = tar::Header::new_old();
+                    header.as_old_mut().name[..name.len()].copy_from_slice(name.as_bytes());
+                    header.set_size(data.len() as u64);
+                    header.set_entry_type(tar::EntryType::Regular);
+                    header.set_cksum();
+                    tar.append(&header, *data).unwrap();
+                }
+                Writer::Zip(_) => writer.add(name, &mut &data[..], data.len() as u64, None).unwrap(),
+            }
+        }
+        let files = listed
+            .iter()
+            .map(|name| {
+                let data = entries.iter().find(|(n, _)| n == name).map(|(_, d)| *d).unwrap_or_default();
+                let mut hashing = Hashing::new(data);
+                io::copy(&mut hashing, &mut io::sink()).unwrap();
+                let (size, sha256) = hashing.finish();
+                ManifestFile { path: name.to_string(), size, sha256, modified: None }
+            })
+            .collect();
+        let manifest = Manifest {
+            format: MANIFEST_FORMAT.to_string(),
+            version: MANIFEST_VERSION,
+            created: "2024-01-01T00:00:00+00:00".to_string(),
+            workspace: String::new(),
+            includes_secrets: false,
+            files,
+        };
+        let json = serde_json::to_vec(&manifest).unwrap();
+        writer.add(MANIFEST_FILE, &mut json.as_slice(), json.len() as u64, None).unwrap();
+        writer.finish().unwrap();
+    }
+
+    fn check(format: Format, entries: &[(&str, &[u8])], listed: &[&str]) -> Result<Manifest, String> {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("backup");
+        archive(&path, format, entries, listed);
+        verify(&path, Format::detect(&path)?, &AtomicBool::new(false))
+    }
+
+    #[test]
+    fn verify_rejects_bad_archives() {
+        for format in [Format::Zip, Format::TarZst] {
+            let a: (&str, &[u8]) = ("workspace/a.md", b"a");
+            assert_eq!(check(format, &[a], &["workspace/a.md"]).unwrap().files.len(), 1);
+
+            let extra: (&str, &[u8]) = ("workspace/extra.md", b"x");
+            assert_eq!(check(format, &[a, extra], &["workspace/a.md"]).err().unwrap(), "`workspace/extra.md` is not listed in the manifest");
+            assert_eq!(check(format, &[a], &["workspace/a.md", "workspace/gone.md"]).err().unwrap(), "`workspace/gone.md` is missing from the archive");
+
+            for name in ["workspace/../x", "workspace//x", "/tmp/x", "other/x"] {
+                let err = check(format, &[(name, b"x")], &[name]).err().unwrap();
+                assert!(err.contains("archive entry") || err.contains("Unsafe path"), "{}: {}", name, err);
+            }
+        }
+    }
+
+    fn workspace() -> tempfile::TempDir {
+        let temp = tempfile::tempdir().unwrap();
+        let files: [(&str, &[u8]); 5] = [
+            ("a.md", b"# A"),
+            ("notes/b é.md", "# B é".as_bytes()),
+            ("notes/.xnote_assets/b/pic.png", &[0x89, b'P', b'N', b'G', 0, 1, 2]),
+            (".DS_Store", b"litter"),
+            (".xnote_quarantine/old/x.png", b"quarantined"),
+        ];
+        for (rel, data) in files {
+            let path = temp.path().join(rel);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, data).unwrap();
+        }
+        File::options()
+            .write(true)
+            .open(temp.path().join("a.md"))
+            .unwrap()
+            .set_modified(UNIX_EPOCH + Duration::from_secs(1_600_000_000))
+            .unwrap();
+        temp
+    }
+
+    fn round_trip(format: Format) {
+        let source = workspace();
+        let backups = tempfile::tempdir().unwrap();
+        let plan = Plan {
+            workspace: source.path().to_path_buf(),
+            directory: backups.path().to_path_buf(),
+            format,
+            include_secrets: false,
+            keep: 0,
+            scheduled: false,
+        };
+        let job = AtomicBool::new(false);
+        let report = run_backup(&plan, &job).unwrap();
+        assert_eq!(report.files, 3);
+        let path = PathBuf::from(&report.path);
+        assert!(path.to_string_lossy().ends_with(format.name()));
+        assert!(Format::detect(&path).unwrap() == format);
+
+        let manifest = verify(&path, format, &job).unwrap();
+        let mut names: Vec<&str> = manifest.files.iter().map(|f| f.path.as_str()).filter(|p| p.starts_with(WORKSPACE_PREFIX)).collect();
+        names.sort();
+        assert_eq!(names, vec!["workspace/a.md", "workspace/notes/.xnote_assets/b/pic.png", "workspace/notes/b é.md"]);
+
+        let target = tempfile::tempdir().unwrap();
+        let target = target.path().join("restored");
+        let (restored, changed) = run_restore(&path, &target, false, &job).unwrap();
+        assert!(changed.is_none());
+        assert_eq!((restored.restored, restored.unchanged, restored.replaced), (3, 0, 0));
+        for rel in ["a.md", "notes/b é.md", "notes/.xnote_assets/b/pic.png"] {
+            assert_eq!(fs::read(target.join(rel)).unwrap(), fs::read(source.path().join(rel)).unwrap(), "{}", rel);
+        }
+        assert!(!target.join(".DS_Store").exists() && !target.join(".xnote_quarantine").exists());
+        let modified = fs::metadata(target.join("a.md")).unwrap().modified().unwrap();
+        assert_eq!(unix_secs(modified), Some(1_600_000_000));
+
+        // Into an existing workspace: changed files are quarantined, others left alone.
+        fs::write(target.join("a.md"), "edited").unwrap();
+        fs::write(target.join("new.md"), "not in the backup").unwrap();
+        let (again, _) = run_restore(&path, &target, false, &job).unwrap();
+        assert_eq!((again.restored, again.unchanged, again.replaced), (1, 2, 1));
+        assert_eq!(fs::read_to_string(target.join("a.md")).unwrap(), "# A");
+        assert_eq!(fs::read_to_string(target.join("new.md")).unwrap(), "not in the backup");
+        let quarantined = PathBuf::from(again.quarantine.unwrap()).join("a.md");
+        assert_eq!(fs::read_to_string(quarantined).unwrap(), "edited");
+    }
+
+    #[test]
+    fn zip_round_trip() {
+        round_trip(Format::Zip);
+    }
+
+    #[test]
+    fn tar_zst_round_trip() {
+        round_trip(Format::TarZst);
+    }
+}
@@ -0,0 +1,767 @@
+use base64::{engine::general_purpose, Engine as _};
+use scraper::{ElementRef, Html, Node};
+use serde::Serialize;
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use tauri::AppHandle;
+
+use crate::{export, site};
+
+const ASSETS_DIR: &str = ".xnote_assets";
+
+/// Elements whose content never ends up in the note.
+const SKIPPED: &[&str] = &[
+    "head", "script", "style", "template", "noscript", "iframe", "object", "svg", "canvas", "meta", "link", "title",
+    "button", "select", "textarea",
+];
+
+const BLOCKS: &[&str] = &[
+    "html", "body", "main", "article", "section", "header", "footer", "aside", "nav", "div", "p", "address",
+    "blockquote", "pre", "ul", "ol", "dl", "dt", "dd", "table", "figure", "figcaption", "details", "summary",
+    "center", "form", "fieldset", "hr", "h1", "h2", "h3", "h4", "h5", "h6",
+];
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PastedMarkdown {
+    markdown: String,
+    /// Which clipboard flavor was used: `files`, `html`, `image` or `text`.
+    source: String,
+    /// Workspace-relative references of the files written to `.xnote_assets`.
+    assets: Vec<String>,
+}
+
+/// Everything the clipboard offers, read in one trip to the main thread.
+#[derive(Default)]
+struct Contents {
+    files: Vec<PathBuf>,
+    html: Option<String>,
+    image: Option<(usize, usize, Vec<u8>)>,
+    text: Option<String>,
+}
+
+fn is_block(name: &str) -> bool {
+    BLOCKS.contains(&name)
+}
+
+fn escape_text(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '*' | '_' | '`' | '[' | ']' | '<') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Wraps `text` in enough backticks that none inside can close the span.
+fn code_span(text: &str) -> String {
+    let text = text.replace('\n', " ");
+    let longest = text.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat(longest + 1);
+    let pad = if text.starts_with('`') || text.ends_with('`') { " " } else { "" };
+    format!("{fence}{pad}{text}{pad}{fence}")
+}
+
+fn link_target(url: &str) -> String {
+    let url = url.trim();
+    if url.contains([' ', '(', ')']) {
+        format!("<{}>", url)
+    } else {
+        url.to_string()
+    }
+}
+
+fn style_has(el: &ElementRef, property: &str, values: &[&str]) -> bool {
+    let Some(style) = el.value().attr("style") else {
+        return false;
+    };
+    style.split(';').any(|decl| {
+        let mut parts = decl.splitn(2, ':');
+        let name = parts.next().unwrap_or("").trim().to_lowercase();
+        let value = parts.next().unwrap_or("").trim().to_lowercase();
+        name == property && values.iter().any(|v| value.starts_with(v))
+    })
+}
+
+/// Moves whitespace at the edges of `inner` outside the `marker` pair, since `** bold**`
+/// is not emphasis in markdown.
+fn wrap_emphasis(out: &mut String, inner: &str, marker: &str) {
+    let trimmed = inner.trim();
+    if trimmed.is_empty() {
+        out.push_str(inner);
+        return;
+    }
+    if inner.starts_with(char::is_whitespace) {
+        out.push(' ');
+    }
+    out.push_str(marker);
+    out.push_str(trimmed);
+    out.push_str(marker);
+    if inner.ends_with(char::is_whitespace) {
+        out.push(' ');
+    }
+}
+
+fn push_text(out: &mut String, text: &str) {
+    let mut pending_space = false;
+    for word in text.split(|c: char| c.is_ascii_whitespace()) {
+        if word.is_empty() {
+            pending_space = true;
+            continue;
+        }
+        if pending_space && !out.is_empty() && !out.ends_with([' ', '\n']) {
+            out.push(' ');
+        }
+        pending_space = true;
+        out.push_str(&escape_text(word));
+    }
+    if text.ends_with(|c: char| c.is_ascii_whitespace()) && !out.is_empty() && !out.ends_with([' ', '\n']) {
+        out.push(' ');
+    }
+}
+
+fn clean_lines(text: &str) -> String {
+    text.lines().map(str::trim).collect::<Vec<_>>().join("\n").trim().to_string()
+}
+
+/// Converts clipboard HTML (web pages, Word, Google Docs, ...) to markdown. `image` maps
+/// each `<img src>` to the reference to insert; returning `None` keeps only the alt text.
+pub fn html_to_markdown(html: &str, image: &mut impl FnMut(&str) -> Option<String>) -> String {
+    let html = fragment(html);
+    let document = Html::parse_document(html);
+    let mut converter = Converter { image };
+    let blocks = converter.blocks(document.root_element());
+    join_blocks(&blocks, false)
+}
+
+/// Windows and some browsers wrap the copied part in `<!--StartFragment-->` markers.
+fn fragment(html: &str) -> &str {
+    let start = html.find("<!--StartFragment-->").map(|i| i + "<!--StartFragment-->".len());
+    let end = html.rfind("<!--EndFragment-->");
+    match (start, end) {
+        (Some(start), Some(end)) if start <= end => &html[start..end],
+        _ => html.find('<').map(|i| &html[i..]).unwrap_or(html),
+    }
+}
+
+struct Block {
+    text: String,
+    list: bool,
+}
+
+/// Separates blocks with blank lines. Inside list items (`tight`), a nested list follows
+/// the item text directly so the list does not turn loose.
+fn join_blocks(blocks: &[Block], tight: bool) -> String {
+    let mut out = String::new();
+    for (i, block) in blocks.iter().enumerate() {
+        if i > 0 {
+            out.push_str(if tight && block.list && !blocks[i - 1].list { "\n" } else { "\n\n" });
+        }
+        out.push_str(&block.text);
+    }
+    out
+}
+
+fn indent(text: &str, width: usize) -> String {
+    let pad = " ".repeat(width);
+    text.lines()
+        .enumerate()
+        .map(|(i, line)| {
+            if i == 0 || line.is_empty() {
+                line.to_string()
+            } else {
+                format!("{}{}", pad, line)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+struct Converter<'f, F> {
+    image: &'f mut F,
+}
+
+impl<F: FnMut(&str) -> Option<String>> Converter<'_, F> {
+    /// Renders the children of a block container, grouping runs of inline content into
+    /// paragraphs.
+    fn blocks(&mut self, el: ElementRef) -> Vec<Block> {
+        let mut blocks = Vec::new();
+        let mut inline = String::new();
+        for child in el.children() {
+            match child.value() {
+                Node::Text(text) => push_text(&mut inline, text),
+                Node::Element(_) => {
+                    let Some(child) = ElementRef::wrap(child) else { continue };
+                    let name = child.value().name();
+                    if SKIPPED.contains(&name) {
+                        continue;
+                    }
+                    if is_block(name) {
+                        self.flush(&mut inline, &mut blocks);
+                        self.block(child, &mut blocks);
+                    } else if child.descendent_elements().any(|d| is_block(d.value().name())) {
+                        // Google Docs wraps the whole selection in a <b> with blocks inside.
+                        self.flush(&mut inline, &mut blocks);
+                        blocks.extend(self.blocks(child));
+                    } else {
+                        self.inline(child, &mut inline);
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.flush(&mut inline, &mut blocks);
+        blocks
+    }
+
+    fn flush(&mut self, inline: &mut String, blocks: &mut Vec<Block>) {
+        // Line breaks left in inline text come from <br>, so they become hard breaks.
+        let text = clean_lines(inline).replace('\n', "\\\n");
+        inline.clear();
+        if !text.is_empty() {
+            blocks.push(Block { text, list: false });
+        }
+    }
+
+    fn block(&mut self, el: ElementRef, blocks: &mut Vec<Block>) {
+        let name = el.value().name();
+        let text = match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                let level = name[1..].parse::<usize>().unwrap_or(1);
+                let mut inner = String::new();
+                self.inline_children(el, &mut inner);
+                let inner = inner.split_whitespace().collect::<Vec<_>>().join(" ");
+                if inner.is_empty() {
+                    return;
+                }
+                format!("{} {}", "#".repeat(level), inner)
+            }
+            "hr" => "---".to_string(),
+            "pre" => self.code_block(el),
+            "blockquote" => {
+                let inner = join_blocks(&self.blocks(el), false);
+                if inner.is_empty() {
+                    return;
+                }
+                inner
+                    .lines()
+                    .map(|line| if line.is_empty() { ">".to_string() } else { format!("> {}", line) })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }
+            "ul" | "ol" => {
+                let text = self.list(el, name == "ol");
+                if !text.is_empty() {
+                    blocks.push(Block { text, list: true });
+                }
+                return;
+            }
+            "table" => self.table(el),
+            "dt" => {
+                let mut inner = String::new();
+                self.inline_children(el, &mut inner);
+                let inner = clean_lines(&inner);
+                if inner.is_empty() {
+                    return;
+                }
+                format!("**{}**", inner)
+            }
+            _ => {
+                blocks.extend(self.blocks(el));
+                return;
+            }
+        };
+        if !text.is_empty() {
+            blocks.push(Block { text, list: false });
+        }
+    }
+
+    fn code_block(&mut self, el: ElementRef) -> String {
+        let code = el.text().collect::<String>();
+        let code = code.strip_suffix('\n').unwrap_or(&code);
+        let lang = std::iter::once(el)
+            .chain(el.child_elements().filter(|c| c.value().name() == "code"))
+            .flat_map(|e| e.value().classes().collect::<Vec<_>>())
+            .find_map(|class| class.strip_prefix("language-").or_else(|| class.strip_prefix("lang-")))
+            .unwrap_or("")
+            .to_string();
+        let longest = code
+            .lines()
+            .map(|line| line.trim_start().chars().take_while(|c| *c == '`').count())
+            .max()
+            .unwrap_or(0);
+        let fence = "`".repeat(longest.max(2) + 1);
+        format!("{fence}{lang}\n{code}\n{fence}")
+    }
+
+    fn list(&mut self, el: ElementRef, ordered: bool) -> String {
+        let mut number = el
+            .value()
+            .attr("start")
+            .and_then(|s| s.trim().parse::<u64>().ok())
+            .unwrap_or(1);
+        let mut items = Vec::new();
+        for item in el.child_elements() {
+            if item.value().name() != "li" {
+                // Browsers sometimes nest a stray list directly in its parent list.
+                if matches!(item.value().name(), "ul" | "ol") {
+                    if let Some(last) = items.last_mut() {
+                        let nested = self.list(item, item.value().name() == "ol");
+                        *last = format!("{}\n{}", last, indent(&nested, 2));
+                    }
+                }
+                continue;
+            }
+            let marker = if ordered {
+                let m = format!("{}. ", number);
+                number += 1;
+                m
+            } else {
+                "- ".to_string()
+            };
+            let task = item
+                .child_elements()
+                .flat_map(|c| std::iter::once(c).chain(c.child_elements()))
+                .find(|e| e.value().name() == "input" && e.value().attr("type") == Some("checkbox"))
+                .map(|input| if input.value().attr("checked").is_some() { "[x] " } else { "[ ] " })
+                .unwrap_or("");
+            let body = join_blocks(&self.blocks(item), true);
+            items.push(format!("{}{}{}", marker, task, indent(&body, marker.len())));
+        }
+        items.join("\n")
+    }
+
+    fn table(&mut self, el: ElementRef) -> String {
+        let mut rows: Vec<Vec<String>> = Vec::new();
+        let mut aligns: Vec<&str> = Vec::new();
+        let sections = std::iter::once(el).chain(
+            el.child_elements()
+                .filter(|c| matches!(c.value().name(), "thead" | "tbody" | "tfoot")),
+        );
+        for section in sections {
+            for row in section.child_elements().filter(|r| r.value().name() == "tr") {
+                let mut cells = Vec::new();
+                for (i, cell) in row
+                    .child_elements()
+                    .filter(|c| matches!(c.value().name(), "td" | "th"))
+                    .enumerate()
+                {
+                    if aligns.len() <= i {
+                        let align = cell.value().attr("align").map(|a| a.to_lowercase());
+                        aligns.push(match align.as_deref() {
+                            Some("center") => ":---:",
+                            Some("right") => "---:",
+                            _ if style_has(&cell, "text-align", &["center"]) => ":---:",
+                            _ if style_has(&cell, "text-align", &["right"]) => "---:",
+                            _ => "---",
+                        });
+                    }
+                    let mut text = String::new();
+                    self.inline_children(cell, &mut text);
+                    let text = clean_lines(&text).replace('\n', "<br>").replace('|', "\\|");
+                    cells.push(text);
+                    let span = cell.value().attr("colspan").and_then(|s| s.parse::<usize>().ok()).unwrap_or(1);
+                    for _ in 1..span.min(64) {
+                        cells.push(String::new());
+                    }
+                }
+                if !cells.is_empty() {
+                    rows.push(cells);
+                }
+            }
+        }
+        if rows.is_empty() {
+            return String::new();
+        }
+
+        let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+        aligns.resize(columns, "---");
+        // GFM tables need a header row, so a headerless table uses its first row.
+        let mut lines = Vec::new();
+        for (i, mut cells) in rows.into_iter().enumerate() {
+            cells.resize(columns, String::new());
+            lines.push(format!("| {} |", cells.join(" | ")));
+            if i == 0 {
+                lines.push(format!("| {} |", aligns.join(" | ")));
+            }
+        }
+        lines.join("\n")
+    }
+
+    fn inline_children(&mut self, el: ElementRef, out: &mut String) {
+        for child in el.children() {
+            match child.value() {
+                Node::Text(text) => push_text(out, text),
+                Node::Element(_) => {
+                    if let Some(child) = ElementRef::wrap(child) {
+                        self.inline(child, out);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn inline(&mut self, el: ElementRef, out: &mut String) {
+        let name = el.value().name();
+        if SKIPPED.contains(&name) {
+            return;
+        }
+        match name {
+            "br" => {
+                while out.ends_with(' ') {
+                    out.pop();
+                }
+                out.push('\n');
+            }
+            "img" => {
+                let alt = el.value().attr("alt").unwrap_or("").trim();
+                let src = el.value().attr("src").unwrap_or("").trim();
+                if src.is_empty() {
+                    return;
+                }
+                match (self.image)(src) {
+                    Some(target) => {
+                        out.push_str(&format!("![{}]({})", escape_text(alt), link_target(&target)));
+                    }
+                    None => push_text(out, alt),
+                }
+            }
+            "a" => {
+                let mut inner = String::new();
+                self.inline_children(el, &mut inner);
+                let href = el.value().attr("href").unwrap_or("").trim();
+                let text = inner.trim();
+                if href.is_empty() || href.to_lowercase().starts_with("javascript:") || text.is_empty() {
+                    out.push_str(&inner);
+                } else if text == escape_text(href) {
+                    out.push_str(&format!("<{}>", href));
+                } else {
+                    if inner.starts_with(' ') {
+                        out.push(' ');
+                    }
+                    out.push_str(&format!("[{}]({})", text, link_target(href)));
+                    if inner.ends_with(' ') {
+                        out.push(' ');
+                    }
+                }
+            }
+            "code" | "kbd" | "samp" | "tt" => {
+                let text = el.text().collect::<String>();
+                if !text.trim().is_empty() {
+                    out.push_str(&code_span(&text));
+                }
+            }
+            "input" => {}
+            _ => {
+                let mut inner = String::new();
+                self.inline_children(el, &mut inner);
+                // Google Docs wraps whole documents in <b style="font-weight:normal">.
+                let normal = style_has(&el, "font-weight", &["normal", "400"]);
+                match name {
+                    "strong" | "b" if !normal => wrap_emphasis(out, &inner, "**"),
+                    "em" | "i" | "cite" | "var" => wrap_emphasis(out, &inner, "*"),
+                    "del" | "s" | "strike" => wrap_emphasis(out, &inner, "~~"),
+                    "span" if style_has(&el, "font-weight", &["bold", "600", "700", "800", "900"]) => {
+                        wrap_emphasis(out, &inner, "**")
+                    }
+                    "span" if style_has(&el, "font-style", &["italic"]) => wrap_emphasis(out, &inner, "*"),
+                    _ if is_block(name) => {
+                        // Block content inside a table cell or heading.
+                        if !out.is_empty() && !out.ends_with(['\n', ' ']) {
+                            out.push('\n');
+                        }
+                        out.push_str(inner.trim());
+                        out.push('\n');
+                    }
+                    _ => out.push_str(&inner),
+                }
+            }
+        }
+    }
+}
+
+fn read_contents(app: &AppHandle) -> Result<Contents, String> {
+    crate::with_clipboard(app, |clipboard| {
+        let mut contents = Contents {
+            files: clipboard.get().file_list().unwrap_or_default(),
+            ..Default::default()
+        };
+        if contents.files.is_empty() {
+            contents.html = clipboard.get().html().ok().filter(|h| !h.trim().is_empty());
+        }
+        if contents.files.is_empty() && contents.html.is_none() {
+            contents.image = clipboard
+                .get()
+                .image()
+                .ok()
+                .map(|img| (img.width, img.height, img.bytes.into_owned()));
+        }
+        contents.text = clipboard.get().text().ok();
+        Ok(contents)
+    })
+}
+
+/// Saves pasted files into the note's `.xnote_assets/<stem>` folder.
+struct Assets {
+    dir: PathBuf,
+    prefix: String,
+    written: Vec<String>,
+}
+
+impl Assets {
+    fn new(root_path: &str, note_path: &str) -> Self {
+        let stem = Path::new(note_path)
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .filter(|s| !s.is_empty())
+            .unwrap_or_else(|| "note".to_string());
+        Assets {
+            dir: Path::new(root_path).join(ASSETS_DIR).join(&stem),
+            prefix: format!("/{}/{}", ASSETS_DIR, stem),
+            written: Vec::new(),
+        }
+    }
+
+    fn reference(&mut self, name: &str) -> String {
+        let reference = format!("{}/{}", self.prefix, name);
+        self.written.push(reference.clone());
+        site::encode_path(&reference)
+    }
+
+    fn save_bytes(&mut self, bytes: &[u8], ext: &str) -> Result<String, String> {
+        let name = crate::store_image(bytes, &self.dir, ext)?;
+        Ok(self.reference(&name))
+    }
+
+    fn copy_file(&mut self, source: &Path) -> Result<String, String> {
+        fs::create_dir_all(&self.dir).map_err(|e| e.to_string())?;
+        let name = source
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .ok_or_else(|| format!("{} is not a file", source.display()))?;
+        let stem = Path::new(&name).file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+        let ext = Path::new(&name).extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+        let mut target = name.clone();
+        let mut n = 1;
+        while self.dir.join(&target).exists() {
+            target = format!("{}-{}{}", stem, n, ext);
+            n += 1;
+        }
+        fs::copy(source, self.dir.join(&target)).map_err(|e| e.to_string())?;
+        Ok(self.reference(&target))
+    }
+
+    /// Saves an `<img src>` that points at data the workspace cannot otherwise reach.
+    /// Remote URLs are kept as they are.
+    fn image_src(&mut self, src: &str) -> Option<String> {
+        if let Some(data) = src.strip_prefix("data:") {
+            let (meta, payload) = data.split_once(',')?;
+            if !meta.ends_with(";base64") {
+                return None;
+            }
+            let ext = match meta.trim_end_matches(";base64") {
+                "image/jpeg" | "image/jpg" => "jpg",
+                "image/gif" => "gif",
+                "image/webp" => "webp",
+                "image/bmp" => "bmp",
+                "image/svg+xml" => "svg",
+                _ => "png",
+            };
+            let bytes = general_purpose::STANDARD.decode(payload.trim()).ok()?;
+            return self
+                .save_bytes(&bytes, ext)
+                .map_err(|err| eprintln!("Failed to save pasted image: {}", err))
+                .ok();
+        }
+        // Only local images are copied in: a page can name any file in `<img src>`.
+        if let Some(path) = file_url_path(src) {
+            if path.is_file() && export::image_mime(&path).starts_with("image/") {
+                return self
+                    .copy_file(&path)
+                    .map_err(|err| eprintln!("Failed to copy pasted image: {}", err))
+                    .ok();
+            }
+        }
+        Some(src.to_string())
+    }
+}
+
+fn file_url_path(url: &str) -> Option<PathBuf> {
+    let rest = url.strip_prefix("file://")?;
+    let decoded = percent_encoding::percent_decode_str(rest).decode_utf8_lossy().to_string();
+    // file:///C:/dir on Windows.
+    let trimmed = match decoded.as_bytes() {
+        [b'/', _, b':', ..] => decoded[1..].to_string(),
+        _ => decoded,
+    };
+    Some(PathBuf::from(trimmed))
+}
+
+fn files_markdown(files: &[PathBuf], root_path: &str, assets: &mut Assets) -> Result<String, String> {
+    let root = Path::new(root_path);
+    let mut lines = Vec::new();
+    for file in files {
+        let name = file.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        if file.is_file() && export::image_mime(file).starts_with("image/") {
+            let reference = assets.copy_file(file)?;
+            lines.push(format!("![{}]({})", escape_text(&name), reference));
+            continue;
+        }
+        // Files inside the workspace are linked in place; anything else by file URL.
+        let target = match file.strip_prefix(root) {
+            Ok(rel) => site::encode_path(&format!("/{}", rel.to_string_lossy().replace('\\', "/"))),
+            Err(_) => format!("file://{}", site::encode_path(&file.to_string_lossy().replace('\\', "/"))),
+        };
+        lines.push(format!("[{}]({})", escape_text(&name), target));
+    }
+    Ok(lines.join("\n"))
+}
+
+fn image_markdown(width: usize, height: usize, rgba: Vec<u8>, assets: &mut Assets) -> Result<String, String> {
+    let img = image::RgbaImage::from_raw(width as u32, height as u32, rgba)
+        .ok_or_else(|| "Clipboard image has an unexpected size".to_string())?;
+    let mut png = Vec::new();
+    img.write_to(&mut Cursor::new(&mut png), image::ImageOutputFormat::Png)
+        .map_err(|e| e.to_string())?;
+    let reference = assets.save_bytes(&png, "png")?;
+    Ok(format!("![Image]({})", reference))
+}
+
+/// Reads the clipboard and returns markdown ready to insert into the note at `note_path`.
+/// Copied files and images are saved into the note's `.xnote_assets` folder; HTML is
+/// converted with its embedded images saved the same way.
+#[tauri::command]
+pub fn paste_clipboard_markdown(app: AppHandle, root_path: String, note_path: String) -> Result<PastedMarkdown, String> {
+    let contents = read_contents(&app)?;
+    let mut assets = Assets::new(&root_path, &note_path);
+
+    let (source, markdown) = if !contents.files.is_empty() {
+        ("files", files_markdown(&contents.files, &root_path, &mut assets)?)
+    } else if let Some(html) = contents.html.as_deref() {
+        let markdown = html_to_markdown(html, &mut |src| assets.image_src(src));
+        if markdown.trim().is_empty() {
+            ("text", contents.text.clone().unwrap_or_default())
+        } else {
+            ("html", markdown)
+        }
+    } else if let Some((width, height, rgba)) = contents.image {
+        ("image", image_markdown(width, height, rgba, &mut assets)?)
+    } else if let Some(text) = contents.text {
+        ("text", text)
+    } else {
+        return Err("The clipboard is empty".to_string());
+    };
+
+    Ok(PastedMarkdown {
+        markdown,
+        source: source.to_string(),
+        assets: assets.written,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn convert(html: &str) -> String {
+        html_to_markdown(html, &mut |src| Some(format!("assets/{}", src)))
+    }
+
+    fn check(cases: &[(&str, &str)]) {
+        for (html, expected) in cases {
+            assert_eq!(convert(html), *expected, "converting {}", html);
+        }
+    }
+
+    #[test]
+    fn lists() {
+        check(&[
+            ("<ul><li>one</li><li>two</li></ul>", "- one\n- two"),
+            ("<ul><li>one</li><li>two<ul><li>nested</li></ul></li></ul>", "- one\n- two\n  - nested"),
+            ("<ol><li>first</li><li>second</li></ol>", "1. first\n2. second"),
+            ("<ol start=\"3\"><li>third</li></ol>", "3. third"),
+        ]);
+    }
+
+    #[test]
+    fn tables() {
+        check(&[
+            (
+                "<table><tr><th>A</th><th>B</th></tr><tr><td>1</td><td>x|y</td></tr></table>",
+                "| A | B |\n| --- | --- |\n| 1 | x\\|y |",
+            ),
+            ("<table><tr><td>1</td><td>2</td></tr></table>", "| 1 | 2 |\n| --- | --- |"),
+        ]);
+    }
+
+    #[test]
+    fn links_and_images() {
+        check(&[
+            ("<p><a href=\"https://example.com/\">site</a></p>", "[site](https://example.com/)"),
+            ("<p><a href=\"https://example.com/a b\">spaced</a></p>", "[spaced](<https://example.com/a b>)"),
+            ("<p><a href=\"#top\">top</a></p>", "[top](#top)"),
+            ("<p><img src=\"x.png\" alt=\"pic\"></p>", "![pic](assets/x.png)"),
+        ]);
+    }
+
+    #[test]
+    fn emphasis() {
+        check(&[
+            ("<p><b>bold</b> <i>it</i></p>", "**bold** *it*"),
+            ("<p><strong> spaced </strong>word</p>", "**spaced** word"),
+            ("<p><em>e</em><s>del</s></p>", "*e*~~del~~"),
+            ("<p>a *b* _c_</p>", "a \\*b\\* \\_c\\_"),
+        ]);
+    }
+
+    #[test]
+    fn code() {
+        check(&[
+            ("<p>use <code>x</code> here</p>", "use `x` here"),
+            ("<p>use <code>a`b</code> here</p>", "use ``a`b`` here"),
+            ("<pre><code class=\"language-rust\">fn main() {}\n</code></pre>", "```rust\nfn main() {}\n```"),
+        ]);
+    }
+
+    #[test]
+    fn google_docs() {
+        // Docs wraps the whole copy in a `<b>` that is not bold and styles spans instead.
+        check(&[(
+            "<meta charset=\"utf-8\"><b style=\"font-weight:normal;\" id=\"docs-internal-guid-1\">\
+             <p><span style=\"font-weight:700\">Bold</span> <span style=\"font-style:italic\">it</span> plain</p></b>",
+            "**Bold** *it* plain",
+        )]);
+    }
+
+    #[test]
+    fn file_urls_copy_only_images() {
+        let dir = tempfile::tempdir().unwrap();
+        let (root, outside) = (dir.path().join("notes"), dir.path().join("outside"));
+        fs::create_dir_all(&outside).unwrap();
+        fs::write(outside.join("pic.png"), b"png").unwrap();
+        fs::write(outside.join("id_rsa"), b"secret").unwrap();
+        let mut assets = Assets::new(&root.to_string_lossy(), "note.md");
+
+        let key = format!("file://{}", outside.join("id_rsa").display());
+        assert_eq!(assets.image_src(&key), Some(key.clone()));
+        let pic = assets.image_src(&format!("file://{}", outside.join("pic.png").display()));
+        assert_eq!(pic.as_deref(), Some("/.xnote_assets/note/pic.png"));
+        assert_eq!(assets.written, vec!["/.xnote_assets/note/pic.png"]);
+        assert!(!root.join(".xnote_assets/note/id_rsa").exists());
+    }
+
+    #[test]
+    fn fragment_markers_and_blocks() {
+        check(&[
+            (
+                "<html><body>ignored<!--StartFragment--><h2>Title</h2><p>text</p><!--EndFragment--></body></html>",
+                "## Title\n\ntext",
+            ),
+            ("<blockquote><p>q1</p><p>q2</p></blockquote>", "> q1\n>\n> q2"),
+        ]);
+    }
+}
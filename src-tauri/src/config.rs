@@ -0,0 +1,822 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeSet, HashSet, VecDeque};
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::secrets;
+use crate::terminal::TerminalProfile;
+
+/// Layout version written into config.json; bump it together with a new entry in `MIGRATIONS`.
+pub const CONFIG_VERSION: u64 = 1;
+pub const CONFIG_FILE: &str = "config.json";
+const BACKUP_DIR: &str = "config-backups";
+const MAX_BACKUPS: usize = 10;
+const MAX_REVISIONS: usize = 16;
+
+const EDITOR_MODES: &[&str] = &["edit", "split", "preview"];
+const THEMES: &[&str] = &["zinc", "midnight", "grape"];
+const LLM_PROVIDERS: &[&str] = &["openai", "ollama", "custom"];
+const LLM_TYPES: &[&str] = &["text", "image", "video"];
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Shortcuts {
+    pub search: String,
+    pub sidebar: String,
+    pub close_editor: String,
+    pub llm_panel: String,
+    pub terminal: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl Default for Shortcuts {
+    fn default() -> Self {
+        Self {
+            search: "Cmd+G".to_string(),
+            sidebar: "Cmd+1".to_string(),
+            close_editor: "Cmd+W".to_string(),
+            llm_panel: "Cmd+2".to_string(),
+            terminal: "Cmd+3".to_string(),
+            extra: Map::new(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LlmConfigEntry {
+    pub id: String,
+    pub name: String,
+    /// `openai` when left out, as in configs from before there was a choice.
+    pub provider: String,
+    pub base_url: String,
+    /// A secret store handle (`secret:llm/<id>`); plaintext keys are moved into the store on save.
+    pub api_key: String,
+    pub model_id: String,
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl Default for LlmConfigEntry {
+    fn default() -> Self {
+        Self {
+            id: String::new(),
+            name: String::new(),
+            provider: "openai".to_string(),
+            base_url: String::new(),
+            api_key: String::new(),
+            model_id: String::new(),
+            kind: None,
+            extra: Map::new(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SystemPrompt {
+    pub id: String,
+    pub name: String,
+    pub content: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LlmSection {
+    pub configs: Vec<LlmConfigEntry>,
+    pub active_id: Option<String>,
+    pub panel_width: f64,
+    pub system_prompts: Vec<SystemPrompt>,
+    pub active_system_prompt_id: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl Default for LlmSection {
+    fn default() -> Self {
+        Self {
+            configs: Vec::new(),
+            active_id: None,
+            panel_width: 300.0,
+            system_prompts: Vec::new(),
+            active_system_prompt_id: None,
+            extra: Map::new(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TerminalSection {
+    pub height: f64,
+    pub profiles: Vec<TerminalProfile>,
+    pub default_profile: Option<String>,
+    /// Backend-only settings such as `snippets` and `scrollbackBytes`.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl Default for TerminalSection {
+    fn default() -> Self {
+        Self {
+            height: 300.0,
+            profiles: Vec::new(),
+            default_profile: None,
+            extra: Map::new(),
+        }
+    }
+}
+
+/// The contents of config.json. Sections and keys this struct does not know about
+/// (e.g. `runner`) are kept in `extra` so they survive a round trip.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AppConfig {
+    pub version: u64,
+    pub sidebar_width: f64,
+    pub sidebar_open: bool,
+    pub editor_mode: String,
+    pub theme: String,
+    pub shortcuts: Shortcuts,
+    pub llm: LlmSection,
+    pub terminal: TerminalSection,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            version: CONFIG_VERSION,
+            sidebar_width: 256.0,
+            sidebar_open: true,
+            editor_mode: "split".to_string(),
+            theme: "zinc".to_string(),
+            shortcuts: Shortcuts::default(),
+            llm: LlmSection::default(),
+            terminal: TerminalSection::default(),
+            extra: Map::new(),
+        }
+    }
+}
+
+pub struct LoadedConfig {
+    pub config: AppConfig,
+    /// Problems that were worked around while loading, for showing to the user.
+    pub warnings: Vec<String>,
+}
+
+/// Payload of the `config-changed` event.
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigChanged {
+    config: AppConfig,
+    revision: String,
+    /// `external` for edits to the file, `merge` when a save picked up such edits,
+    /// `restore` when a backup's config replaced it.
+    source: &'static str,
+    warnings: Vec<String>,
+    errors: Vec<String>,
+    /// Keys changed both on disk and in the UI; the UI's values were kept.
+    conflicts: Vec<String>,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigSaved {
+    revision: String,
+    conflicts: Vec<String>,
+}
+
+/// Configs recently handed to the UI, keyed by revision; they are the common base when a
+/// save has to be merged with edits made on disk in the meantime.
+static REVISIONS: Mutex<VecDeque<(String, Value)>> = Mutex::new(VecDeque::new());
+/// config.json as last read by the watcher or written by the app. Anything else found on
+/// disk is an external edit.
+static KNOWN_TEXT: Mutex<Option<String>> = Mutex::new(None);
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigReport {
+    version: u64,
+    warnings: Vec<String>,
+    errors: Vec<String>,
+}
+
+/// v0 stored the close-editor shortcut as `shortcuts.close`.
+fn migrate_v0_to_v1(config: &mut Map<String, Value>) {
+    if let Some(Value::Object(shortcuts)) = config.get_mut("shortcuts") {
+        if let Some(close) = shortcuts.remove("close") {
+            shortcuts.entry("closeEditor").or_insert(close);
+        }
+    }
+}
+
+/// `MIGRATIONS[n]` upgrades a version `n` config to version `n + 1`.
+const MIGRATIONS: &[fn(&mut Map<String, Value>)] = &[migrate_v0_to_v1];
+
+/// Upgrades a raw config object to `CONFIG_VERSION`; returns whether anything changed.
+/// Configs written by a newer version are left alone.
+fn migrate(config: &mut Map<String, Value>) -> bool {
+    let version = config.get("version").and_then(|v| v.as_u64()).unwrap_or(0);
+    if version >= CONFIG_VERSION {
+        return false;
+    }
+    for step in MIGRATIONS.iter().skip(version as usize) {
+        step(config);
+    }
+    config.insert("version".to_string(), Value::from(CONFIG_VERSION));
+    true
+}
+
+/// Deserializes `config`, dropping top-level keys that do not fit the schema instead of
+/// failing as a whole.
+fn parse_lenient(mut config: Map<String, Value>, warnings: &mut Vec<String>) -> AppConfig {
+    if let Ok(parsed) = serde_json::from_value::<AppConfig>(Value::Object(config.clone())) {
+        return parsed;
+    }
+    let keys: Vec<String> = config.keys().cloned().collect();
+    for key in keys {
+        let single = Map::from_iter([(key.clone(), config[&key].clone())]);
+        if let Err(e) = serde_json::from_value::<AppConfig>(Value::Object(single)) {
+            warnings.push(format!("Ignored invalid `{}`: {}", key, e));
+            config.remove(&key);
+        }
+    }
+    serde_json::from_value(Value::Object(config)).unwrap_or_default()
+}
+
+fn revision_of(value: &Value) -> String {
+    let mut hasher = DefaultHasher::new();
+    value.to_string().hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+/// Records `config` as a state the UI has seen and returns its revision.
+fn remember(config: &AppConfig) -> Result<String, String> {
+    let value = serde_json::to_value(config).map_err(|e| e.to_string())?;
+    let revision = revision_of(&value);
+    let mut revisions = REVISIONS.lock().unwrap();
+    if !revisions.iter().any(|(r, _)| *r == revision) {
+        revisions.push_back((revision.clone(), value));
+        while revisions.len() > MAX_REVISIONS {
+            revisions.pop_front();
+        }
+    }
+    Ok(revision)
+}
+
+fn remembered(revision: &str) -> Option<Value> {
+    let revisions = REVISIONS.lock().unwrap();
+    revisions.iter().find(|(r, _)| r == revision).map(|(_, v)| v.clone())
+}
+
+/// Three-way merge of the UI's config (`ours`) with the file on disk (`theirs`) against the
+/// state both started from. Objects merge key by key; other values (arrays included) take
+/// whichever side changed, and `ours` when both did, recording the key in `conflicts`.
+fn merge3(base: Option<&Value>, ours: Value, theirs: Value, path: &str, conflicts: &mut Vec<String>) -> Value {
+    if ours == theirs {
+        return ours;
+    }
+    match (ours, theirs) {
+        (Value::Object(mut ours), Value::Object(mut theirs)) => {
+            let base = base.and_then(|b| b.as_object());
+            let keys: BTreeSet<String> = ours.keys().chain(theirs.keys()).cloned().collect();
+            let mut out = Map::new();
+            for key in keys {
+                let base_value = base.and_then(|b| b.get(&key));
+                let key_path = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+                match (ours.remove(&key), theirs.remove(&key)) {
+                    (Some(o), Some(t)) => {
+                        out.insert(key, merge3(base_value, o, t, &key_path, conflicts));
+                    }
+                    // Present on one side only: a deletion on the other side wins if this side
+                    // left the value untouched.
+                    (Some(v), None) | (None, Some(v)) => {
+                        if base_value != Some(&v) {
+                            out.insert(key, v);
+                        }
+                    }
+                    (None, None) => {}
+                }
+            }
+            Value::Object(out)
+        }
+        (ours, theirs) => {
+            if base == Some(&ours) {
+                theirs
+            } else {
+                if base != Some(&theirs) {
+                    conflicts.push(path.to_string());
+                }
+                ours
+            }
+        }
+    }
+}
+
+/// Objects are merged key by key; everything else in `patch` replaces what is in `base`.
+fn merge(base: &mut Value, patch: Value) {
+    match (base, patch) {
+        (Value::Object(base), Value::Object(patch)) => {
+            for (key, value) in patch {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, patch) => *base = patch,
+    }
+}
+
+fn check_unique_ids<'a>(section: &str, ids: impl Iterator<Item = &'a str>, errors: &mut Vec<String>) {
+    let mut seen = HashSet::new();
+    for (i, id) in ids.enumerate() {
+        if id.trim().is_empty() {
+            errors.push(format!("{}[{}]: id must not be empty", section, i));
+        } else if !seen.insert(id) {
+            errors.push(format!("{}[{}]: duplicate id `{}`", section, i, id));
+        }
+    }
+}
+
+fn check_size(name: &str, value: f64, errors: &mut Vec<String>) {
+    if !value.is_finite() || value <= 0.0 {
+        errors.push(format!("{}: must be a positive number", name));
+    }
+}
+
+pub fn validate(config: &AppConfig) -> Vec<String> {
+    let mut errors = Vec::new();
+
+    if !EDITOR_MODES.contains(&config.editor_mode.as_str()) {
+        errors.push(format!("editorMode: expected one of {}, got `{}`", EDITOR_MODES.join(", "), config.editor_mode));
+    }
+    if !THEMES.contains(&config.theme.as_str()) {
+        errors.push(format!("theme: expected one of {}, got `{}`", THEMES.join(", "), config.theme));
+    }
+    check_size("sidebarWidth", config.sidebar_width, &mut errors);
+    check_size("llm.panelWidth", config.llm.panel_width, &mut errors);
+    check_size("terminal.height", config.terminal.height, &mut errors);
+
+    let shortcuts = [
+        ("search", &config.shortcuts.search),
+        ("sidebar", &config.shortcuts.sidebar),
+        ("closeEditor", &config.shortcuts.close_editor),
+        ("llmPanel", &config.shortcuts.llm_panel),
+        ("terminal", &config.shortcuts.terminal),
+    ];
+    for (i, (name, keys)) in shortcuts.iter().enumerate() {
+        if keys.trim().is_empty() {
+            errors.push(format!("shortcuts.{}: must not be empty", name));
+        } else if let Some((other, _)) = shortcuts[..i].iter().find(|(_, k)| k.eq_ignore_ascii_case(keys)) {
+            errors.push(format!("shortcuts.{}: `{}` is already used by shortcuts.{}", name, keys, other));
+        }
+    }
+
+    check_unique_ids("llm.configs", config.llm.configs.iter().map(|c| c.id.as_str()), &mut errors);
+    for (i, entry) in config.llm.configs.iter().enumerate() {
+        if !LLM_PROVIDERS.contains(&entry.provider.as_str()) {
+            errors.push(format!("llm.configs[{}].provider: expected one of {}, got `{}`", i, LLM_PROVIDERS.join(", "), entry.provider));
+        }
+        if let Some(kind) = entry.kind.as_deref().filter(|k| !LLM_TYPES.contains(k)) {
+            errors.push(format!("llm.configs[{}].type: expected one of {}, got `{}`", i, LLM_TYPES.join(", "), kind));
+        }
+    }
+    check_unique_ids("llm.systemPrompts", config.llm.system_prompts.iter().map(|p| p.id.as_str()), &mut errors);
+    check_unique_ids("terminal.profiles", config.terminal.profiles.iter().map(|p| p.id.as_str()), &mut errors);
+
+    errors
+}
+
+/// Moves plaintext `llm.configs[].apiKey` values and the `sync.webdav.password` into the
+/// secret store and leaves handles in their place. Returns the plaintext values that were
+/// moved.
+fn externalize_api_keys(config: &mut AppConfig) -> Result<Vec<String>, String> {
+    let mut moved = Vec::new();
+    for entry in config.llm.configs.iter_mut() {
+        if entry.api_key.is_empty() || secrets::handle_id(&entry.api_key).is_some() {
+            continue;
+        }
+        let handle = secrets::set(&format!("llm/{}", entry.id), &entry.api_key)?;
+        moved.push(std::mem::replace(&mut entry.api_key, handle));
+    }
+    let password = config.extra.get_mut("sync").and_then(|s| s.pointer_mut("/webdav/password"));
+    if let Some(Value::String(password)) = password {
+        if !password.is_empty() && secrets::handle_id(password).is_none() {
+            let handle = secrets::set("sync/webdav", password)?;
+            moved.push(std::mem::replace(password, handle));
+        }
+    }
+    Ok(moved)
+}
+
+/// Deletes the API keys of models that were removed from the config. A locked store keeps
+/// them until a later save.
+fn remove_unused_api_keys(config: &AppConfig) {
+    let used = config.llm.configs.iter().filter_map(|c| secrets::handle_id(&c.api_key)).collect();
+    let _ = secrets::remove_unused("llm/", &used);
+}
+
+/// Deletes config backups that still contain any of `plaintexts`.
+fn scrub_backups(path: &Path, plaintexts: &[String]) {
+    let Ok(entries) = fs::read_dir(path.with_file_name(BACKUP_DIR)) else { return };
+    for entry in entries.flatten() {
+        let backup = entry.path();
+        let Ok(text) = fs::read_to_string(&backup) else { continue };
+        if plaintexts.iter().any(|secret| text.contains(secret.as_str())) {
+            let _ = fs::remove_file(&backup);
+        }
+    }
+}
+
+pub fn config_path() -> Result<PathBuf, String> {
+    Ok(crate::get_xnote_root()?.join(CONFIG_FILE))
+}
+
+/// Copies the current config into the backup folder, keeping the newest `MAX_BACKUPS`.
+/// Nothing is written when the newest backup already has the same contents.
+fn backup(path: &Path) -> Result<Option<PathBuf>, String> {
+    let Ok(current) = fs::read(path) else { return Ok(None) };
+    let dir = path.with_file_name(BACKUP_DIR);
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+
+    let mut existing: Vec<PathBuf> = fs::read_dir(&dir)
+        .map_err(|e| e.to_string())?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|p| p.extension().map(|ext| ext == "json").unwrap_or(false))
+        .collect();
+    existing.sort();
+    if let Some(newest) = existing.last() {
+        if fs::read(newest).map(|b| b == current).unwrap_or(false) {
+            return Ok(Some(newest.clone()));
+        }
+    }
+
+    let target = dir.join(format!("config-{}.json", chrono::Local::now().format("%Y%m%d-%H%M%S-%3f")));
+    fs::write(&target, &current).map_err(|e| e.to_string())?;
+    existing.retain(|p| *p != target);
+    existing.push(target.clone());
+    let excess = existing.len().saturating_sub(MAX_BACKUPS);
+    for old in existing.iter().take(excess) {
+        let _ = fs::remove_file(old);
+    }
+    Ok(Some(target))
+}
+
+fn write(path: &Path, config: &AppConfig) -> Result<(), String> {
+    let text = serde_json::to_string_pretty(config).map_err(|e| e.to_string())?;
+    let mut known = KNOWN_TEXT.lock().unwrap();
+    if fs::read_to_string(path).map(|old| old == text).unwrap_or(false) {
+        *known = Some(text);
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    backup(path)?;
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, &text).map_err(|e| e.to_string())?;
+    fs::rename(&tmp, path).map_err(|e| e.to_string())?;
+    *known = Some(text);
+    Ok(())
+}
+
+/// Reads config.json, migrating old layouts and falling back to defaults for anything
+/// unreadable. The file is only rewritten for a migration; when parts of it had to be
+/// ignored, a copy is kept in the backup folder before the next save replaces it.
+/// Plaintext API keys are moved into the secret store when it is available.
+pub fn load() -> Result<LoadedConfig, String> {
+    let path = config_path()?;
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(LoadedConfig { config: AppConfig::default(), warnings: Vec::new() });
+        }
+        Err(e) => return Err(e.to_string()),
+    };
+
+    let mut warnings = Vec::new();
+    let mut raw = match serde_json::from_str::<Value>(&text) {
+        Ok(Value::Object(map)) => map,
+        Ok(_) => {
+            warnings.push("config.json does not contain a JSON object; using defaults".to_string());
+            Map::new()
+        }
+        Err(e) => {
+            warnings.push(format!("config.json is not valid JSON ({}); using defaults", e));
+            Map::new()
+        }
+    };
+
+    let newer = raw.get("version").and_then(|v| v.as_u64()).filter(|v| *v > CONFIG_VERSION);
+    if let Some(version) = newer {
+        warnings.push(format!("config.json was written by a newer version of the app (version {})", version));
+    }
+    let migrated = migrate(&mut raw);
+    let mut config = parse_lenient(raw, &mut warnings);
+
+    // Keys from older versions or hand edits move into the secret store as soon as it is
+    // usable; a locked store just leaves them until the next save.
+    let moved = if warnings.is_empty() { externalize_api_keys(&mut config).unwrap_or_default() } else { Vec::new() };
+    if !moved.is_empty() {
+        write(&path, &config)?;
+        scrub_backups(&path, &moved);
+    } else if !warnings.is_empty() {
+        if let Some(kept) = backup(&path)? {
+            warnings.push(format!("The original file was kept as {}", kept.display()));
+        }
+    } else if migrated {
+        write(&path, &config)?;
+    }
+
+    Ok(LoadedConfig { config, warnings })
+}
+
+/// The current config as JSON for the UI, with its `revision` to pass back on save.
+pub fn load_for_ui() -> Result<String, String> {
+    let config = load()?.config;
+    let revision = remember(&config)?;
+    let mut value = serde_json::to_value(&config).map_err(|e| e.to_string())?;
+    value["revision"] = Value::from(revision);
+    Ok(value.to_string())
+}
+
+/// Saves a config sent by the frontend. It is laid over the stored config, so sections the
+/// frontend does not manage are preserved. When `base_revision` names the config the UI
+/// started from, edits made on disk since then are merged in rather than overwritten. The
+/// result is validated; problems are returned one per line and nothing is written.
+///
+/// Also returns the `config-changed` payload to send when the saved config differs from
+/// what the UI holds.
+pub fn save(text: &str, base_revision: Option<&str>) -> Result<(ConfigSaved, Option<ConfigChanged>), String> {
+    let incoming: Value = serde_json::from_str(text).map_err(|e| format!("Invalid config JSON: {}", e))?;
+    let Value::Object(mut incoming) = incoming else {
+        return Err("Config must be a JSON object".to_string());
+    };
+    incoming.remove("revision");
+    incoming.entry("version").or_insert(Value::from(CONFIG_VERSION));
+    migrate(&mut incoming);
+
+    let theirs = serde_json::to_value(load()?.config).map_err(|e| e.to_string())?;
+    let mut ours = theirs.clone();
+    merge(&mut ours, Value::Object(incoming));
+    // Normalize through the schema so e.g. `256` and `256.0` compare equal below.
+    let ours: AppConfig = serde_json::from_value(ours).map_err(|e| format!("Invalid config: {}", e))?;
+    let ours = serde_json::to_value(ours).map_err(|e| e.to_string())?;
+
+    let mut conflicts = Vec::new();
+    let merged = match base_revision.and_then(remembered) {
+        Some(base) => merge3(Some(&base), ours.clone(), theirs, "", &mut conflicts),
+        None => ours.clone(),
+    };
+    let differs_from_ui = merged != ours;
+    let mut config: AppConfig = serde_json::from_value(merged).map_err(|e| format!("Invalid config: {}", e))?;
+
+    let errors = validate(&config);
+    if !errors.is_empty() {
+        return Err(errors.join("\n"));
+    }
+    let moved = externalize_api_keys(&mut config).map_err(|e| format!("API keys and passwords were not saved: {}", e))?;
+    let path = config_path()?;
+    write(&path, &config)?;
+    scrub_backups(&path, &moved);
+    remove_unused_api_keys(&config);
+
+    let revision = remember(&config)?;
+    let changed = differs_from_ui.then(|| ConfigChanged {
+        config,
+        revision: revision.clone(),
+        source: "merge",
+        warnings: Vec::new(),
+        errors: Vec::new(),
+        conflicts: conflicts.clone(),
+    });
+    Ok((ConfigSaved { revision, conflicts }, changed))
+}
+
+/// Replaces config.json with one taken from a backup. Nothing is merged; the config being
+/// replaced is kept in the backup folder. Returns the `config-changed` payload for the UI.
+pub fn restore(text: &str) -> Result<ConfigChanged, String> {
+    let Ok(Value::Object(mut raw)) = serde_json::from_str::<Value>(text) else {
+        return Err("config.json in the backup is not a JSON object".to_string());
+    };
+    migrate(&mut raw);
+    let mut warnings = Vec::new();
+    let mut config = parse_lenient(raw, &mut warnings);
+    let errors = validate(&config);
+    if !errors.is_empty() {
+        return Err(errors.join("\n"));
+    }
+    let moved = externalize_api_keys(&mut config).unwrap_or_default();
+    let path = config_path()?;
+    write(&path, &config)?;
+    scrub_backups(&path, &moved);
+
+    let revision = remember(&config)?;
+    Ok(ConfigChanged {
+        config,
+        revision,
+        source: "restore",
+        warnings,
+        errors: Vec::new(),
+        conflicts: Vec::new(),
+    })
+}
+
+/// Notes the file contents present when the app starts watching it.
+pub fn mark_known() -> Result<(), String> {
+    let text = fs::read_to_string(config_path()?).ok();
+    *KNOWN_TEXT.lock().unwrap() = text;
+    Ok(())
+}
+
+/// Picks up an edit made to config.json outside the app. Returns `None` when the file
+/// holds what the app last read or wrote itself.
+pub fn reload_external() -> Result<Option<ConfigChanged>, String> {
+    let text = match fs::read_to_string(config_path()?) {
+        Ok(text) => text,
+        // Editors may delete and recreate the file; wait for the new one.
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.to_string()),
+    };
+    {
+        let mut known = KNOWN_TEXT.lock().unwrap();
+        if known.as_deref() == Some(text.as_str()) {
+            return Ok(None);
+        }
+        *known = Some(text);
+    }
+
+    let loaded = load()?;
+    let revision = remember(&loaded.config)?;
+    Ok(Some(ConfigChanged {
+        errors: validate(&loaded.config),
+        config: loaded.config,
+        revision,
+        source: "external",
+        warnings: loaded.warnings,
+        conflicts: Vec::new(),
+    }))
+}
+
+/// Loads and validates the stored config, reporting what the user should know about.
+#[tauri::command]
+pub fn check_config() -> Result<ConfigReport, String> {
+    let loaded = load()?;
+    Ok(ConfigReport {
+        version: loaded.config.version,
+        errors: validate(&loaded.config),
+        warnings: loaded.warnings,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn model(id: &str, api_key: &str) -> LlmConfigEntry {
+        LlmConfigEntry { id: id.to_string(), provider: "openai".to_string(), api_key: api_key.to_string(), ..Default::default() }
+    }
+
+    #[test]
+    fn models_without_a_provider_use_openai() {
+        let mut warnings = Vec::new();
+        let raw = serde_json::json!({ "llm": { "configs": [{ "id": "a", "name": "A", "modelId": "gpt-4o" }, { "id": "b", "provider": "ollama" }] } });
+        let Value::Object(raw) = raw else { unreachable!() };
+        let config = parse_lenient(raw, &mut warnings);
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        let providers: Vec<&str> = config.llm.configs.iter().map(|c| c.provider.as_str()).collect();
+        assert_eq!(providers, vec!["openai", "ollama"]);
+        assert!(validate(&config).is_empty());
+    }
+
+    #[test]
+    fn migrate_renames_v0_shortcuts() {
+        let Value::Object(mut raw) = serde_json::json!({ "shortcuts": { "close": "Ctrl+W", "search": "Ctrl+K" } }) else { unreachable!() };
+        assert!(migrate(&mut raw));
+        assert_eq!(Value::Object(raw.clone()), serde_json::json!({ "version": CONFIG_VERSION, "shortcuts": { "closeEditor": "Ctrl+W", "search": "Ctrl+K" } }));
+        assert!(!migrate(&mut raw));
+
+        // An explicit closeEditor wins over the old key.
+        let Value::Object(mut raw) = serde_json::json!({ "shortcuts": { "close": "Ctrl+W", "closeEditor": "Ctrl+Q" } }) else { unreachable!() };
+        migrate(&mut raw);
+        assert_eq!(raw["shortcuts"], serde_json::json!({ "closeEditor": "Ctrl+Q" }));
+
+        // Newer configs are left alone.
+        let Value::Object(mut raw) = serde_json::json!({ "version": CONFIG_VERSION + 1, "shortcuts": { "close": "Ctrl+W" } }) else { unreachable!() };
+        assert!(!migrate(&mut raw));
+        assert_eq!(raw["shortcuts"], serde_json::json!({ "close": "Ctrl+W" }));
+    }
+
+    #[test]
+    fn merge3_keeps_changes_from_both_sides() {
+        let base = serde_json::json!({ "theme": "zinc", "sidebarWidth": 240, "llm": { "panelWidth": 300, "configs": [1] }, "gone": 1, "kept": 2 });
+        let ours = serde_json::json!({ "theme": "grape", "sidebarWidth": 240, "llm": { "panelWidth": 300, "configs": [1, 2] }, "kept": 2 });
+        let theirs = serde_json::json!({ "theme": "zinc", "sidebarWidth": 320, "llm": { "panelWidth": 400, "configs": [1] }, "gone": 1, "new": 3 });
+        let mut conflicts = Vec::new();
+        let merged = merge3(Some(&base), ours, theirs, "", &mut conflicts);
+        assert!(conflicts.is_empty(), "{:?}", conflicts);
+        assert_eq!(
+            merged,
+            serde_json::json!({ "theme": "grape", "sidebarWidth": 320, "llm": { "panelWidth": 400, "configs": [1, 2] }, "new": 3 })
+        );
+    }
+
+    #[test]
+    fn merge3_prefers_ours_on_conflict() {
+        let base = serde_json::json!({ "theme": "zinc", "llm": { "panelWidth": 300 }, "a": 1 });
+        let ours = serde_json::json!({ "theme": "grape", "llm": { "panelWidth": 350 }, "a": 2 });
+        let theirs = serde_json::json!({ "theme": "midnight", "llm": { "panelWidth": 400 } });
+        let mut conflicts = Vec::new();
+        let merged = merge3(Some(&base), ours, theirs, "", &mut conflicts);
+        assert_eq!(merged, serde_json::json!({ "theme": "grape", "llm": { "panelWidth": 350 }, "a": 2 }));
+        assert_eq!(conflicts, vec!["llm.panelWidth", "theme"]);
+
+        // Without a base every difference is a conflict.
+        let mut conflicts = Vec::new();
+        let merged = merge3(None, serde_json::json!({ "x": 1 }), serde_json::json!({ "x": 2 }), "", &mut conflicts);
+        assert_eq!(merged, serde_json::json!({ "x": 1 }));
+        assert_eq!(conflicts, vec!["x"]);
+    }
+
+    #[test]
+    fn merge_patches_objects_and_replaces_the_rest() {
+        let mut base = serde_json::json!({ "theme": "zinc", "llm": { "panelWidth": 300, "configs": [1, 2] } });
+        merge(&mut base, serde_json::json!({ "llm": { "configs": [3] }, "editorMode": "split" }));
+        assert_eq!(base, serde_json::json!({ "theme": "zinc", "llm": { "panelWidth": 300, "configs": [3] }, "editorMode": "split" }));
+    }
+
+    #[test]
+    fn validate_reports_each_problem() {
+        assert!(validate(&AppConfig::default()).is_empty(), "{:?}", validate(&AppConfig::default()));
+
+        let mut config = AppConfig {
+            editor_mode: "wysiwyg".to_string(),
+            theme: "neon".to_string(),
+            sidebar_width: 0.0,
+            ..Default::default()
+        };
+        config.terminal.height = f64::NAN;
+        config.shortcuts.terminal = config.shortcuts.search.to_uppercase();
+        config.shortcuts.sidebar = " ".to_string();
+        config.llm.configs = vec![model("a", ""), model("a", ""), model(" ", "")];
+        config.llm.configs[1].provider = "acme".to_string();
+        config.llm.configs[2].kind = Some("audio".to_string());
+        let errors = validate(&config);
+        let expected = [
+            "editorMode: expected one of edit, split, preview, got `wysiwyg`",
+            "theme: expected one of zinc, midnight, grape, got `neon`",
+            "sidebarWidth: must be a positive number",
+            "terminal.height: must be a positive number",
+            "shortcuts.sidebar: must not be empty",
+            "shortcuts.terminal: ",
+            "llm.configs[1]: duplicate id `a`",
+            "llm.configs[2]: id must not be empty",
+            "llm.configs[1].provider: expected one of",
+            "llm.configs[2].type: expected one of",
+        ];
+        for prefix in expected {
+            assert!(errors.iter().any(|e| e.starts_with(prefix)), "{}: {:?}", prefix, errors);
+        }
+        assert_eq!(errors.len(), expected.len(), "{:?}", errors);
+    }
+
+    // Only where the secret store uses a key file, so the real keychain is never touched.
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    #[test]
+    fn api_keys_move_into_the_secret_store() {
+        let store = crate::secrets::tests::TempStore::new();
+        let mut config = AppConfig::default();
+        config.llm.configs = vec![model("gpt", "sk-plain"), model("local", ""), model("kept", "secret:llm/kept")];
+        config.extra.insert("sync".to_string(), serde_json::json!({ "webdav": { "password": "hunter2" } }));
+
+        let moved = externalize_api_keys(&mut config).unwrap();
+        assert_eq!(moved, vec!["sk-plain", "hunter2"]);
+        let keys: Vec<&str> = config.llm.configs.iter().map(|c| c.api_key.as_str()).collect();
+        assert_eq!(keys, vec!["secret:llm/gpt", "", "secret:llm/kept"]);
+        assert_eq!(config.extra["sync"]["webdav"]["password"], "secret:sync/webdav");
+        assert_eq!(secrets::get("llm/gpt").unwrap().as_deref(), Some("sk-plain"));
+        assert_eq!(secrets::get("sync/webdav").unwrap().as_deref(), Some("hunter2"));
+        assert!(!store.contents().contains("sk-plain"));
+
+        // Handles are left alone.
+        assert!(externalize_api_keys(&mut config).unwrap().is_empty());
+
+        // Removing a model removes its key; other secrets stay.
+        config.llm.configs.remove(0);
+        remove_unused_api_keys(&config);
+        assert_eq!(secrets::get("llm/gpt").unwrap(), None);
+        assert_eq!(secrets::get("sync/webdav").unwrap().as_deref(), Some("hunter2"));
+    }
+}
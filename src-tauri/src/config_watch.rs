@@ -0,0 +1,53 @@
+use notify::{RecursiveMode, Watcher};
+use std::sync::mpsc;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+
+use crate::config;
+
+/// Editors save in several steps (temp file, rename, chmod); wait for the burst to end.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Watches config.json and emits `config-changed` when it is edited outside the app.
+pub fn start(app: AppHandle) {
+    std::thread::spawn(move || {
+        if let Err(err) = watch(&app) {
+            eprintln!("Config watcher stopped: {}", err);
+        }
+    });
+}
+
+fn watch(app: &AppHandle) -> Result<(), String> {
+    let path = config::config_path()?;
+    // Watch the directory rather than the file, which editors replace on save.
+    let dir = path.parent().ok_or_else(|| "Config file has no parent directory".to_string())?;
+    std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    config::mark_known()?;
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx).map_err(|e| e.to_string())?;
+    watcher.watch(dir, RecursiveMode::NonRecursive).map_err(|e| e.to_string())?;
+
+    while let Ok(event) = rx.recv() {
+        let touches_config = match event {
+            Ok(event) => event
+                .paths
+                .iter()
+                .any(|p| p.file_name().map(|n| n == config::CONFIG_FILE).unwrap_or(false)),
+            Err(_) => false,
+        };
+        if !touches_config {
+            continue;
+        }
+        while rx.recv_timeout(DEBOUNCE).is_ok() {}
+
+        match config::reload_external() {
+            Ok(Some(changed)) => {
+                let _ = app.emit("config-changed", changed);
+            }
+            Ok(None) => {}
+            Err(err) => eprintln!("Failed to reload config: {}", err),
+        }
+    }
+    Ok(())
+}
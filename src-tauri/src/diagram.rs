@@ -0,0 +1,317 @@
+use base64::{engine::general_purpose, Engine as _};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::AppHandle;
+
+use crate::raster;
+
+const CACHE_DIR: &str = "cache/diagrams";
+const DEFAULT_TIMEOUT_SECS: u64 = 30;
+/// How long to wait for output once the renderer has exited. A process it started (e.g. the
+/// JVM behind a `plantuml` script) may keep the pipes open.
+const OUTPUT_GRACE: Duration = Duration::from_secs(2);
+
+static NEXT_TEMP: AtomicU64 = AtomicU64::new(0);
+
+/// `diagrams.plantuml` in config.json. A `jar` runs through `java -jar`; otherwise
+/// `command` (default `plantuml`) is expected on the PATH.
+#[derive(Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase", default)]
+struct PlantUmlRenderer {
+    command: String,
+    args: Vec<String>,
+    jar: String,
+    java: String,
+}
+
+/// `diagrams.mermaid` in config.json: a mermaid-cli (`mmdc`) executable.
+#[derive(Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase", default)]
+struct MermaidRenderer {
+    command: String,
+    args: Vec<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct DiagramsConfig {
+    plantuml: PlantUmlRenderer,
+    mermaid: MermaidRenderer,
+    timeout_secs: Option<u64>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct ConfigWithDiagrams {
+    diagrams: DiagramsConfig,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiagramRender {
+    /// `svg` (markup in `data`) or `png` (base64 in `data`).
+    format: String,
+    data: String,
+    /// Whether the SVG came from the cache rather than a fresh render.
+    cached: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    PlantUml,
+    Mermaid,
+}
+
+fn kind_of(lang: &str) -> Option<Kind> {
+    let lang = lang.split_whitespace().next().unwrap_or("").to_lowercase();
+    match lang.as_str() {
+        "plantuml" | "puml" | "uml" => Some(Kind::PlantUml),
+        "mermaid" | "mmd" => Some(Kind::Mermaid),
+        _ => None,
+    }
+}
+
+pub fn is_diagram_lang(lang: &str) -> bool {
+    kind_of(lang).is_some()
+}
+
+fn load_config() -> DiagramsConfig {
+    crate::get_config()
+        .ok()
+        .and_then(|text| serde_json::from_str::<ConfigWithDiagrams>(&text).ok())
+        .map(|c| c.diagrams)
+        .unwrap_or_default()
+}
+
+fn non_empty(value: &str, fallback: &str) -> String {
+    if value.trim().is_empty() {
+        fallback.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+/// A child's pipe, read to the end on its own thread.
+struct PipeReader {
+    buf: Arc<Mutex<Vec<u8>>>,
+    done: mpsc::Receiver<()>,
+}
+
+impl PipeReader {
+    fn spawn(pipe: Option<impl Read + Send + 'static>) -> Self {
+        let buf = Arc::new(Mutex::new(Vec::new()));
+        let (done_tx, done) = mpsc::channel();
+        let shared = buf.clone();
+        std::thread::spawn(move || {
+            if let Some(mut pipe) = pipe {
+                let mut chunk = [0u8; 8192];
+                while let Ok(n @ 1..) = pipe.read(&mut chunk) {
+                    shared.lock().unwrap().extend_from_slice(&chunk[..n]);
+                }
+            }
+            let _ = done_tx.send(());
+        });
+        Self { buf, done }
+    }
+
+    /// Everything read once the pipe closes, or what was read by `deadline` if it stays open.
+    fn finish(self, deadline: Instant) -> Vec<u8> {
+        let _ = self.done.recv_timeout(deadline.saturating_duration_since(Instant::now()));
+        std::mem::take(&mut *self.buf.lock().unwrap())
+    }
+}
+
+/// Runs a renderer, feeding `input` on stdin, and returns its stdout. The process is
+/// killed once `timeout` passes.
+fn run(mut cmd: Command, input: Option<&str>, timeout: Duration) -> Result<Vec<u8>, String> {
+    let program = cmd.get_program().to_string_lossy().to_string();
+    cmd.stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    let mut child = cmd.spawn().map_err(|e| format!("Failed to start `{}`: {}", program, e))?;
+
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        let input = input.to_string();
+        std::thread::spawn(move || {
+            let _ = stdin.write_all(input.as_bytes());
+        });
+    }
+    let out_reader = PipeReader::spawn(child.stdout.take());
+    let err_reader = PipeReader::spawn(child.stderr.take());
+
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait().map_err(|e| e.to_string())? {
+            break status;
+        }
+        if started.elapsed() >= timeout {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(format!("`{}` timed out after {}s", program, timeout.as_secs()));
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    };
+
+    let deadline = Instant::now() + OUTPUT_GRACE;
+    let stdout = out_reader.finish(deadline);
+    let stderr = String::from_utf8_lossy(&err_reader.finish(deadline)).to_string();
+    if !status.success() {
+        let detail = stderr.trim();
+        return Err(if detail.is_empty() {
+            format!("`{}` exited with {}", program, status)
+        } else {
+            format!("`{}` failed: {}", program, detail)
+        });
+    }
+    Ok(stdout)
+}
+
+fn render_plantuml(renderer: &PlantUmlRenderer, source: &str, timeout: Duration) -> Result<String, String> {
+    let mut cmd = if renderer.jar.trim().is_empty() {
+        let mut cmd = Command::new(non_empty(&renderer.command, "plantuml"));
+        cmd.args(&renderer.args);
+        cmd
+    } else {
+        let mut cmd = Command::new(non_empty(&renderer.java, "java"));
+        cmd.args(["-Djava.awt.headless=true", "-jar", &renderer.jar]).args(&renderer.args);
+        cmd
+    };
+    cmd.args(["-tsvg", "-pipe", "-charset", "UTF-8"]);
+    let out = run(cmd, Some(source), timeout)?;
+    String::from_utf8(out).map_err(|e| e.to_string())
+}
+
+fn render_mermaid(renderer: &MermaidRenderer, source: &str, timeout: Duration) -> Result<String, String> {
+    // mermaid-cli only reads and writes files.
+    let n = NEXT_TEMP.fetch_add(1, Ordering::Relaxed);
+    let stem = std::env::temp_dir().join(format!("xnote-mermaid-{}-{}", std::process::id(), n));
+    let input = stem.with_extension("mmd");
+    let output = stem.with_extension("svg");
+    fs::write(&input, source).map_err(|e| e.to_string())?;
+
+    let mut cmd = Command::new(non_empty(&renderer.command, "mmdc"));
+    cmd.args(&renderer.args)
+        .arg("-i")
+        .arg(&input)
+        .arg("-o")
+        .arg(&output)
+        .args(["-b", "transparent"]);
+    let result = run(cmd, None, timeout).and_then(|_| fs::read_to_string(&output).map_err(|e| e.to_string()));
+    let _ = fs::remove_file(&input);
+    let _ = fs::remove_file(&output);
+    result
+}
+
+fn cache_path(key: &str) -> Result<PathBuf, String> {
+    Ok(crate::get_xnote_root()?.join(CACHE_DIR).join(format!("{}.svg", key)))
+}
+
+/// Renders a PlantUML or Mermaid diagram to SVG with the locally configured tool.
+/// Results are cached by a hash of the source and the renderer settings, so unchanged
+/// diagrams are not rendered again. Returns the SVG and whether it came from the cache.
+pub fn render_svg(lang: &str, source: &str) -> Result<(String, bool), String> {
+    let kind = kind_of(lang).ok_or_else(|| format!("`{}` is not a diagram language", lang))?;
+    let config = load_config();
+    let timeout = Duration::from_secs(config.timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS).max(1));
+
+    let mut hasher = Sha256::new();
+    match kind {
+        Kind::PlantUml => {
+            let r = &config.plantuml;
+            hasher.update(format!("plantuml\0{}\0{}\0{}\0{:?}\0", r.command, r.jar, r.java, r.args));
+        }
+        Kind::Mermaid => {
+            let r = &config.mermaid;
+            hasher.update(format!("mermaid\0{}\0{:?}\0", r.command, r.args));
+        }
+    }
+    hasher.update(source.as_bytes());
+    let key: String = hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect();
+
+    let path = cache_path(&key)?;
+    if let Ok(svg) = fs::read_to_string(&path) {
+        return Ok((svg, true));
+    }
+
+    let svg = match kind {
+        Kind::PlantUml => render_plantuml(&config.plantuml, source, timeout)?,
+        Kind::Mermaid => render_mermaid(&config.mermaid, source, timeout)?,
+    };
+    if !svg.contains("<svg") {
+        return Err("The renderer did not produce an SVG".to_string());
+    }
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let tmp = path.with_extension("svg.tmp");
+    if fs::write(&tmp, &svg).is_ok() {
+        let _ = fs::rename(&tmp, &path);
+    }
+    Ok((svg, false))
+}
+
+/// Renders a diagram to `svg` (default) or `png` (base64, at `scale`, default 2x).
+#[tauri::command(async)]
+pub fn render_diagram(
+    lang: String,
+    source: String,
+    format: Option<String>,
+    scale: Option<f32>,
+) -> Result<DiagramRender, String> {
+    let (svg, cached) = render_svg(&lang, &source)?;
+    match format.as_deref().unwrap_or("svg") {
+        "svg" => Ok(DiagramRender { format: "svg".to_string(), data: svg, cached }),
+        "png" => {
+            let pixmap = raster::rasterize(&svg, scale.unwrap_or(raster::DEFAULT_SCALE))?;
+            let png = raster::encode_png(&pixmap)?;
+            Ok(DiagramRender {
+                format: "png".to_string(),
+                data: general_purpose::STANDARD.encode(png),
+                cached,
+            })
+        }
+        other => Err(format!("Unsupported diagram format `{}`", other)),
+    }
+}
+
+/// Renders a diagram and puts it on the clipboard as an image.
+#[tauri::command(async)]
+pub fn copy_diagram_image(app: AppHandle, lang: String, source: String, scale: Option<f32>) -> Result<(), String> {
+    let (svg, _) = render_svg(&lang, &source)?;
+    let pixmap = raster::rasterize(&svg, scale.unwrap_or(raster::DEFAULT_SCALE))?;
+    let (width, height) = (pixmap.width() as usize, pixmap.height() as usize);
+    crate::set_clipboard_rgba(&app, width, height, pixmap.take())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn sh(script: &str) -> Command {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", script]);
+        cmd
+    }
+
+    #[test]
+    fn output_left_open_by_a_grandchild_does_not_hang() {
+        let started = Instant::now();
+        let out = run(sh("cat; sleep 30 &"), Some("<svg/>"), Duration::from_secs(10)).unwrap();
+        assert_eq!(out, b"<svg/>");
+        assert!(started.elapsed() < Duration::from_secs(5), "{:?}", started.elapsed());
+    }
+
+    #[test]
+    fn failures_and_timeouts() {
+        assert_eq!(run(sh("echo broken >&2; exit 3"), None, Duration::from_secs(10)).unwrap_err(), "`sh` failed: broken");
+        assert_eq!(run(sh("sleep 30"), None, Duration::from_secs(1)).unwrap_err(), "`sh` timed out after 1s");
+    }
+}
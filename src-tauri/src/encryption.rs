@@ -0,0 +1,484 @@
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::{engine::general_purpose, Engine as _};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter};
+use walkdir::WalkDir;
+
+use crate::{secrets, site};
+
+/// At the workspace root: the key notes are encrypted with, wrapped with a key derived from
+/// the passphrase. It travels with the notes (sync, backups) so they open anywhere.
+pub const KEY_FILE: &str = ".xnote_encryption.json";
+/// Marks a folder whose notes are encrypted, including ones created later.
+pub const FOLDER_MARKER: &str = ".xnote_encrypted";
+/// Prefix of errors for notes that cannot be read or written while locked; the UI asks for
+/// the passphrase when it sees it.
+pub const LOCKED: &str = "ENCRYPTION_LOCKED";
+/// First line of an encrypted note; the rest is base64 of the nonce and the ciphertext.
+const HEADER: &[u8] = b"xnote-encrypted:1\n";
+const KEY_VERSION: u32 = 1;
+const NOTE_EXTENSIONS: &[&str] = &["md", "txt", "uml", "puml"];
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct KeyFile {
+    version: u32,
+    /// Argon2id salt for the passphrase.
+    salt: String,
+    /// The note key, sealed with AES-256-GCM under the passphrase key (nonce first).
+    wrapped_key: String,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct EncryptionStatus {
+    root: String,
+    /// Whether a passphrase was set for the workspace.
+    configured: bool,
+    unlocked: bool,
+}
+
+/// Note keys of the unlocked workspaces, by workspace root.
+static UNLOCKED: Mutex<BTreeMap<PathBuf, [u8; 32]>> = Mutex::new(BTreeMap::new());
+
+fn seal(key: &[u8; 32], plaintext: &[u8]) -> Result<Vec<u8>, String> {
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key))
+        .encrypt(&nonce, plaintext)
+        .map_err(|e| e.to_string())?;
+    Ok([nonce.as_slice(), &ciphertext].concat())
+}
+
+fn open(key: &[u8; 32], sealed: &[u8]) -> Option<Vec<u8>> {
+    if sealed.len() < 12 {
+        return None;
+    }
+    let (nonce, ciphertext) = sealed.split_at(12);
+    Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key)).decrypt(Nonce::from_slice(nonce), ciphertext).ok()
+}
+
+fn read_key_file(root: &Path) -> Result<Option<KeyFile>, String> {
+    let path = root.join(KEY_FILE);
+    match fs::read_to_string(&path) {
+        Ok(text) => serde_json::from_str(&text).map(Some).map_err(|e| format!("{} is corrupt: {}", path.display(), e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn unwrap_key(file: &KeyFile, passphrase: &str) -> Result<[u8; 32], String> {
+    let salt = general_purpose::STANDARD.decode(&file.salt).map_err(|e| e.to_string())?;
+    let wrapped = general_purpose::STANDARD.decode(&file.wrapped_key).map_err(|e| e.to_string())?;
+    let key = open(&secrets::derive_key(passphrase, &salt)?, &wrapped).ok_or("Wrong passphrase")?;
+    key.try_into().map_err(|_| format!("{} is corrupt: bad key length", KEY_FILE))
+}
+
+/// The workspace an encrypted note belongs to: the nearest folder above it with a key file.
+fn workspace_of(path: &Path) -> Option<&Path> {
+    path.ancestors().skip(1).find(|dir| dir.join(KEY_FILE).is_file())
+}
+
+fn note_key(path: &Path) -> Result<[u8; 32], String> {
+    let Some(root) = workspace_of(path) else {
+        return Err(format!("{} is encrypted, but the workspace's {} is missing", path.display(), KEY_FILE));
+    };
+    UNLOCKED
+        .lock()
+        .unwrap()
+        .get(&site::lexical_normalize(root))
+        .copied()
+        .ok_or_else(|| format!("{}: {} is encrypted; unlock encrypted notes first", LOCKED, path.display()))
+}
+
+fn unlocked_key(root: &Path) -> Result<[u8; 32], String> {
+    if let Some(key) = UNLOCKED.lock().unwrap().get(root) {
+        return Ok(*key);
+    }
+    if read_key_file(root)?.is_none() {
+        return Err("Set an encryption passphrase for this workspace first".to_string());
+    }
+    Err(format!("{}: unlock encrypted notes first", LOCKED))
+}
+
+pub fn is_encrypted(path: &Path) -> bool {
+    let mut head = [0u8; HEADER.len()];
+    fs::File::open(path).and_then(|mut f| f.read_exact(&mut head)).is_ok() && head == HEADER
+}
+
+/// Whether a folder above `path`, within the workspace, is marked as encrypted.
+fn in_encrypted_folder(path: &Path) -> bool {
+    for dir in path.ancestors().skip(1) {
+        if dir.join(FOLDER_MARKER).is_file() {
+            return true;
+        }
+        if dir.join(KEY_FILE).is_file() {
+            break;
+        }
+    }
+    false
+}
+
+/// Whether new contents of `path` have to be encrypted.
+fn should_encrypt(path: &Path) -> bool {
+    is_encrypted(path) || in_encrypted_folder(path)
+}
+
+fn decrypt_note(path: &Path, data: &[u8], key: &[u8; 32]) -> Result<String, String> {
+    let sealed = general_purpose::STANDARD
+        .decode(data[HEADER.len()..].trim_ascii())
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    let plaintext = open(key, &sealed).ok_or_else(|| format!("{} could not be decrypted; it may be damaged", path.display()))?;
+    String::from_utf8(plaintext).map_err(|e| e.to_string())
+}
+
+/// Replaces `path` through a temp file, so an interrupted write never leaves half a note.
+fn replace_file(path: &Path, data: &[u8]) -> Result<(), String> {
+    let tmp = PathBuf::from(format!("{}.tmp", path.to_string_lossy()));
+    fs::write(&tmp, data).map_err(|e| e.to_string())?;
+    fs::rename(&tmp, path).map_err(|e| {
+        let _ = fs::remove_file(&tmp);
+        e.to_string()
+    })
+}
+
+fn write_sealed(path: &Path, key: &[u8; 32], contents: &str) -> Result<(), String> {
+    let mut data = HEADER.to_vec();
+    data.extend_from_slice(general_purpose::STANDARD.encode(seal(key, contents.as_bytes())?).as_bytes());
+    data.push(b'\n');
+    replace_file(path, &data)
+}
+
+/// Reads a note, decrypting it if it is encrypted. Fails with `LOCKED` while its workspace
+/// is locked.
+pub fn read_note(path: &Path) -> Result<String, String> {
+    let data = fs::read(path).map_err(|e| e.to_string())?;
+    if !data.starts_with(HEADER) {
+        return String::from_utf8(data).map_err(|e| e.to_string());
+    }
+    decrypt_note(path, &data, &note_key(path)?)
+}
+
+/// Saves a note, encrypted if it was before or lives in an encrypted folder. Never falls
+/// back to plain text: while locked, such notes cannot be saved.
+pub fn write_note(path: &Path, contents: &str) -> Result<(), String> {
+    if !should_encrypt(path) {
+        return fs::write(path, contents).map_err(|e| e.to_string());
+    }
+    write_sealed(path, &note_key(path)?, contents)
+}
+
+/// A note's text for searching: decrypted while unlocked, `None` while locked.
+pub fn searchable_text(path: &Path) -> Option<String> {
+    let data = fs::read(path).ok()?;
+    if !data.starts_with(HEADER) {
+        return String::from_utf8(data).ok();
+    }
+    decrypt_note(path, &data, &note_key(path).ok()?).ok()
+}
+
+/// An encrypted note whose workspace is locked.
+pub fn is_locked(path: &Path) -> bool {
+    is_encrypted(path) && note_key(path).is_err()
+}
+
+fn status(root: &Path) -> Result<EncryptionStatus, String> {
+    Ok(EncryptionStatus {
+        root: root.to_string_lossy().to_string(),
+        configured: read_key_file(root)?.is_some(),
+        unlocked: UNLOCKED.lock().unwrap().contains_key(root),
+    })
+}
+
+/// Lets the UI reload what it shows when notes become readable or unreadable.
+fn emit_changed(app: &AppHandle, root: &Path) -> Result<(), String> {
+    let _ = app.emit("encryption-changed", status(root)?);
+    Ok(())
+}
+
+/// Notes under `path` (or `path` itself), skipping hidden folders.
+fn notes_under(path: &Path) -> Vec<PathBuf> {
+    WalkDir::new(path)
+        .into_iter()
+        .filter_entry(|e| e.depth() == 0 || !e.file_name().to_string_lossy().starts_with('.'))
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .map(|e| e.into_path())
+        .filter(|p| {
+            p.extension()
+                .map(|ext| NOTE_EXTENSIONS.contains(&ext.to_string_lossy().to_lowercase().as_str()))
+                .unwrap_or(false)
+        })
+        .collect()
+}
+
+fn target_in(root: &Path, path: &str) -> Result<PathBuf, String> {
+    let path = site::lexical_normalize(Path::new(path));
+    if !path.starts_with(root) || path == root {
+        return Err("Only notes and folders inside the workspace can be encrypted".to_string());
+    }
+    if !path.exists() {
+        return Err(format!("{} does not exist", path.display()));
+    }
+    Ok(path)
+}
+
+#[tauri::command]
+pub fn encryption_status(root_path: String) -> Result<EncryptionStatus, String> {
+    status(&site::lexical_normalize(Path::new(&root_path)))
+}
+
+/// Makes the workspace's encrypted notes readable for the rest of the session.
+#[tauri::command]
+pub fn unlock_encryption(app: AppHandle, root_path: String, passphrase: String) -> Result<(), String> {
+    let root = site::lexical_normalize(Path::new(&root_path));
+    unlock(&root, &passphrase)?;
+    emit_changed(&app, &root)
+}
+
+fn unlock(root: &Path, passphrase: &str) -> Result<(), String> {
+    let file = read_key_file(root)?.ok_or("Encryption is not set up for this workspace")?;
+    let key = unwrap_key(&file, passphrase)?;
+    UNLOCKED.lock().unwrap().insert(root.to_path_buf(), key);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn lock_encryption(app: AppHandle, root_path: String) -> Result<(), String> {
+    let root = site::lexical_normalize(Path::new(&root_path));
+    UNLOCKED.lock().unwrap().remove(&root);
+    emit_changed(&app, &root)
+}
+
+/// Sets up encryption for the workspace, or changes its passphrase (`current_passphrase`
+/// is then required). Notes are encrypted with a random key that is only re-wrapped here,
+/// so changing the passphrase does not rewrite any note.
+#[tauri::command]
+pub fn set_encryption_passphrase(
+    app: AppHandle,
+    root_path: String,
+    current_passphrase: Option<String>,
+    passphrase: String,
+) -> Result<(), String> {
+    let root = site::lexical_normalize(Path::new(&root_path));
+    set_passphrase(&root, current_passphrase.as_deref(), &passphrase)?;
+    emit_changed(&app, &root)
+}
+
+fn set_passphrase(root: &Path, current_passphrase: Option<&str>, passphrase: &str) -> Result<(), String> {
+    if !root.is_dir() {
+        return Err("Workspace path does not exist".to_string());
+    }
+    if passphrase.chars().count() < secrets::MIN_PASSPHRASE_LEN {
+        return Err(format!("The passphrase needs at least {} characters", secrets::MIN_PASSPHRASE_LEN));
+    }
+    let key = match read_key_file(root)? {
+        Some(file) => unwrap_key(&file, current_passphrase.unwrap_or(""))
+            .map_err(|_| "The current passphrase is wrong".to_string())?,
+        None => Aes256Gcm::generate_key(OsRng).into(),
+    };
+
+    let mut salt = [0u8; 16];
+    OsRng.fill_bytes(&mut salt);
+    let file = KeyFile {
+        version: KEY_VERSION,
+        salt: general_purpose::STANDARD.encode(salt),
+        wrapped_key: general_purpose::STANDARD.encode(seal(&secrets::derive_key(passphrase, &salt)?, &key)?),
+    };
+    let text = serde_json::to_string_pretty(&file).map_err(|e| e.to_string())?;
+    let path = root.join(KEY_FILE);
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, text).map_err(|e| e.to_string())?;
+    fs::rename(&tmp, &path).map_err(|e| e.to_string())?;
+
+    UNLOCKED.lock().unwrap().insert(root.to_path_buf(), key);
+    Ok(())
+}
+
+/// Encrypts a note, or a folder's notes along with the ones created in it later. Returns
+/// how many notes were encrypted.
+#[tauri::command]
+pub fn encrypt_path(root_path: String, path: String) -> Result<usize, String> {
+    let root = site::lexical_normalize(Path::new(&root_path));
+    let key = unlocked_key(&root)?;
+    let target = target_in(&root, &path)?;
+    if target.is_dir() {
+        fs::write(target.join(FOLDER_MARKER), "").map_err(|e| e.to_string())?;
+    }
+    let mut count = 0;
+    for note in notes_under(&target) {
+        if is_encrypted(&note) {
+            continue;
+        }
+        let text = fs::read_to_string(&note).map_err(|e| format!("{}: {}", note.display(), e))?;
+        write_sealed(&note, &key, &text)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Turns an encrypted note or folder back into plain text. Returns how many notes were
+/// decrypted. Nothing is written unless every note decrypts, and folder markers go last, so
+/// a failure never leaves plain-text notes in a folder that is no longer marked.
+#[tauri::command]
+pub fn decrypt_path(root_path: String, path: String) -> Result<usize, String> {
+    let root = site::lexical_normalize(Path::new(&root_path));
+    let key = unlocked_key(&root)?;
+    let target = target_in(&root, &path)?;
+    // Notes in an encrypted folder would be encrypted again on their next save.
+    if in_encrypted_folder(&target) {
+        return Err("This is inside an encrypted folder; decrypt the folder instead".to_string());
+    }
+    let mut decrypted = Vec::new();
+    for note in notes_under(&target) {
+        let data = fs::read(&note).map_err(|e| format!("{}: {}", note.display(), e))?;
+        if data.starts_with(HEADER) {
+            let text = decrypt_note(&note, &data, &key)?;
+            decrypted.push((note, text));
+        }
+    }
+    for (note, text) in &decrypted {
+        replace_file(note, text.as_bytes()).map_err(|e| format!("{}: {}", note.display(), e))?;
+    }
+    let markers = WalkDir::new(&target).into_iter().filter_map(|e| e.ok()).filter(|e| e.file_name() == FOLDER_MARKER);
+    for marker in markers {
+        fs::remove_file(marker.path()).map_err(|e| e.to_string())?;
+    }
+    Ok(decrypted.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PASSPHRASE: &str = "correct horse";
+
+    /// A workspace with an encrypted `secret/` folder and a plain note next to it.
+    fn workspace() -> (tempfile::TempDir, PathBuf) {
+        let temp = tempfile::tempdir().unwrap();
+        let root = site::lexical_normalize(temp.path());
+        fs::create_dir_all(root.join("secret/sub")).unwrap();
+        fs::write(root.join("secret/a.md"), "alpha needle").unwrap();
+        fs::write(root.join("secret/sub/b.txt"), "beta").unwrap();
+        fs::write(root.join("plain.md"), "plain needle").unwrap();
+        set_passphrase(&root, None, PASSPHRASE).unwrap();
+        let count = encrypt_path(root.to_string_lossy().to_string(), root.join("secret").to_string_lossy().to_string()).unwrap();
+        assert_eq!(count, 2);
+        (temp, root)
+    }
+
+    fn lock(root: &Path) {
+        UNLOCKED.lock().unwrap().remove(root);
+    }
+
+    #[test]
+    fn notes_round_trip_through_write_and_read() {
+        let (_temp, root) = workspace();
+        let note = root.join("secret/a.md");
+        assert!(is_encrypted(&note));
+        assert!(!fs::read_to_string(&note).unwrap().contains("alpha"));
+        assert_eq!(read_note(&note).unwrap(), "alpha needle");
+
+        write_note(&note, "changed").unwrap();
+        assert!(is_encrypted(&note));
+        assert_eq!(read_note(&note).unwrap(), "changed");
+
+        // New notes in an encrypted folder are encrypted; others are not.
+        let new = root.join("secret/sub/new.md");
+        write_note(&new, "fresh").unwrap();
+        assert!(is_encrypted(&new));
+        assert_eq!(read_note(&new).unwrap(), "fresh");
+        write_note(&root.join("plain.md"), "still plain").unwrap();
+        assert_eq!(fs::read_to_string(root.join("plain.md")).unwrap(), "still plain");
+    }
+
+    #[test]
+    fn locked_notes_cannot_be_read_written_or_searched() {
+        let (_temp, root) = workspace();
+        let note = root.join("secret/a.md");
+        let before = fs::read(&note).unwrap();
+        lock(&root);
+
+        assert!(is_locked(&note));
+        assert!(!is_locked(&root.join("plain.md")));
+        assert!(read_note(&note).unwrap_err().starts_with(LOCKED));
+        assert!(write_note(&note, "overwritten").unwrap_err().starts_with(LOCKED));
+        assert!(write_note(&root.join("secret/new.md"), "leak").unwrap_err().starts_with(LOCKED));
+        assert_eq!(fs::read(&note).unwrap(), before);
+        assert!(!root.join("secret/new.md").exists());
+
+        assert_eq!(searchable_text(&note), None);
+        let hits = crate::search_files(&root.to_string_lossy(), "needle", None, None).unwrap();
+        let paths: Vec<&str> = hits.iter().map(|h| h.path.as_str()).collect();
+        assert_eq!(paths, vec![root.join("plain.md").to_string_lossy()]);
+
+        unlock(&root, PASSPHRASE).unwrap();
+        assert_eq!(searchable_text(&note).as_deref(), Some("alpha needle"));
+        assert_eq!(crate::search_files(&root.to_string_lossy(), "needle", None, None).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn a_wrong_passphrase_does_not_unlock() {
+        let (_temp, root) = workspace();
+        lock(&root);
+        assert_eq!(unlock(&root, "wrong horse").unwrap_err(), "Wrong passphrase");
+        assert!(is_locked(&root.join("secret/a.md")));
+        unlock(&root, PASSPHRASE).unwrap();
+        assert!(!is_locked(&root.join("secret/a.md")));
+    }
+
+    #[test]
+    fn changing_the_passphrase_rewraps_the_key_only() {
+        let (_temp, root) = workspace();
+        let note = root.join("secret/a.md");
+        let before = fs::read(&note).unwrap();
+        let key_file = fs::read_to_string(root.join(KEY_FILE)).unwrap();
+
+        assert_eq!(set_passphrase(&root, Some("wrong horse"), "battery staple").unwrap_err(), "The current passphrase is wrong");
+        assert!(set_passphrase(&root, Some(PASSPHRASE), "short").is_err());
+        set_passphrase(&root, Some(PASSPHRASE), "battery staple").unwrap();
+
+        assert_ne!(fs::read_to_string(root.join(KEY_FILE)).unwrap(), key_file);
+        assert_eq!(fs::read(&note).unwrap(), before);
+        lock(&root);
+        assert!(unlock(&root, PASSPHRASE).is_err());
+        unlock(&root, "battery staple").unwrap();
+        assert_eq!(read_note(&note).unwrap(), "alpha needle");
+    }
+
+    #[test]
+    fn decrypting_a_folder_restores_plain_notes() {
+        let (_temp, root) = workspace();
+        let root_path = root.to_string_lossy().to_string();
+        assert!(decrypt_path(root_path.clone(), root.join("secret/a.md").to_string_lossy().to_string())
+            .unwrap_err()
+            .contains("decrypt the folder instead"));
+
+        assert_eq!(decrypt_path(root_path, root.join("secret").to_string_lossy().to_string()).unwrap(), 2);
+        assert_eq!(fs::read_to_string(root.join("secret/a.md")).unwrap(), "alpha needle");
+        assert_eq!(fs::read_to_string(root.join("secret/sub/b.txt")).unwrap(), "beta");
+        assert!(!root.join("secret").join(FOLDER_MARKER).exists());
+        write_note(&root.join("secret/new.md"), "plain again").unwrap();
+        assert!(!is_encrypted(&root.join("secret/new.md")));
+    }
+
+    #[test]
+    fn a_damaged_note_stops_decryption_before_anything_is_written() {
+        let (_temp, root) = workspace();
+        let damaged = [HEADER, b"AAAA\n"].concat();
+        fs::write(root.join("secret/sub/b.txt"), &damaged).unwrap();
+        let before = fs::read(root.join("secret/a.md")).unwrap();
+
+        let err = decrypt_path(root.to_string_lossy().to_string(), root.join("secret").to_string_lossy().to_string()).unwrap_err();
+        assert!(err.contains("could not be decrypted"), "{}", err);
+        assert_eq!(fs::read(root.join("secret/a.md")).unwrap(), before);
+        assert_eq!(fs::read(root.join("secret/sub/b.txt")).unwrap(), damaged);
+        assert!(root.join("secret").join(FOLDER_MARKER).exists());
+    }
+}
@@ -0,0 +1,515 @@
+use percent_encoding::percent_decode_str;
+use scraper::{ElementRef, Html, Node};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tauri::{async_runtime, AppHandle, Emitter};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::export::{self, escape_html as escape_xml, Heading, UrlKind};
+use crate::{jobs, markdown, site};
+
+/// Headings deeper than this stay out of the table of contents.
+const TOC_DEPTH: u8 = 3;
+/// Code highlighting palette; readers bring their own page colours, so stay light.
+const EPUB_THEME: &str = "light";
+
+const EPUB_CSS: &str = r#"
+body { font-family: serif; line-height: 1.5; margin: 0 4%; }
+h1, h2, h3, h4, h5, h6 { font-family: sans-serif; line-height: 1.25; page-break-after: avoid; }
+img { max-width: 100%; }
+pre { padding: 0.6em 0.8em; white-space: pre-wrap; word-wrap: break-word; font-size: 0.8em; }
+code { font-family: monospace; }
+blockquote { margin: 1em 0; padding-left: 1em; border-left: 3px solid #ccc; color: #555; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #ccc; padding: 0.3em 0.6em; }
+nav ol { list-style: none; padding-left: 1em; }
+"#;
+
+/// HTML void elements, which XHTML needs written as `<br/>`.
+const VOID_TAGS: &[&str] = &["area", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track", "wbr"];
+
+struct Chapter {
+    file: String,
+    title: String,
+    headings: Vec<Heading>,
+    body: String,
+}
+
+struct Image {
+    file: String,
+    media_type: &'static str,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct EpubExported {
+    job_id: u64,
+    path: String,
+    chapters: usize,
+    images: usize,
+}
+
+/// Re-serializes rendered HTML, raw HTML from notes included, as well-formed XHTML. The
+/// fragment is parsed the way a browser would, so named entities (`&copy;`, `&mdash;`)
+/// become characters, stray `<` and `&` are escaped, unclosed tags are closed and void
+/// elements are written as `<br/>`. Comments and names XML cannot express are dropped.
+fn to_xhtml(html: &str) -> String {
+    let fragment = Html::parse_fragment(html);
+    let mut out = String::with_capacity(html.len() + 64);
+    write_children(fragment.root_element(), &mut out);
+    out
+}
+
+fn write_children(parent: ElementRef, out: &mut String) {
+    for child in parent.children() {
+        match child.value() {
+            Node::Text(text) => push_escaped(out, text),
+            Node::Element(_) => write_element(ElementRef::wrap(child).unwrap(), &parent.value().name.ns, out),
+            _ => {}
+        }
+    }
+}
+
+fn write_element(el: ElementRef, parent_ns: &str, out: &mut String) {
+    let element = el.value();
+    let name = element.name();
+    // `<foo:bar>` would need a namespace declaration; keep just its content.
+    if !is_xml_name(name) {
+        write_children(el, out);
+        return;
+    }
+    out.push('<');
+    out.push_str(name);
+    // Inline SVG and MathML keep their own namespace.
+    let ns: &str = &element.name.ns;
+    if ns != parent_ns {
+        out.push_str(" xmlns=\"");
+        push_escaped(out, ns);
+        out.push('"');
+    }
+    let mut seen: Vec<&str> = Vec::new();
+    for (key, value) in element.attrs() {
+        if !is_xml_name(key) || key == "xmlns" || seen.contains(&key) {
+            continue;
+        }
+        seen.push(key);
+        out.push(' ');
+        out.push_str(key);
+        out.push_str("=\"");
+        push_escaped(out, value);
+        out.push('"');
+    }
+    if VOID_TAGS.contains(&name) {
+        out.push_str("/>");
+        return;
+    }
+    out.push('>');
+    write_children(el, out);
+    out.push_str("</");
+    out.push_str(name);
+    out.push('>');
+}
+
+/// A name XML accepts without a namespace prefix.
+fn is_xml_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().map(|c| c.is_ascii_alphabetic() || c == '_').unwrap_or(false)
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+/// Escapes text for XML, leaving out control characters XML 1.0 does not allow.
+fn push_escaped(out: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\t' | '\n' | '\r' => out.push(c),
+            c if c.is_control() && c < '\u{80}' => {}
+            c => out.push(c),
+        }
+    }
+}
+
+fn xhtml_document(title: &str, body: &str, nav: bool) -> String {
+    let (epub_type, wrapper) = if nav { ("toc", "nav") } else { ("chapter", "section") };
+    format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<!DOCTYPE html>\n<html xmlns=\"http://www.w3.org/1999/xhtml\" xmlns:epub=\"http://www.idpf.org/2007/ops\">\n<head>\n<meta charset=\"utf-8\"/>\n<title>{}</title>\n<link rel=\"stylesheet\" type=\"text/css\" href=\"style.css\"/>\n</head>\n<body>\n<{wrapper} epub:type=\"{epub_type}\">\n{}</{wrapper}>\n</body>\n</html>\n",
+        escape_xml(title),
+        body,
+    )
+}
+
+/// Nested `<ol>` table of contents: one entry per chapter with its headings below it.
+/// The chapter's own title heading is not repeated.
+fn toc_body(title: &str, chapters: &[Chapter]) -> String {
+    let mut out = format!("<h1>{}</h1>\n<ol>\n", escape_xml(title));
+    for chapter in chapters {
+        out.push_str(&format!("<li><a href=\"{}\">{}</a>", chapter.file, escape_xml(&chapter.title)));
+        let entries: Vec<&Heading> = chapter
+            .headings
+            .iter()
+            .filter(|h| h.level <= TOC_DEPTH && !h.text.is_empty())
+            .filter(|h| !(h.level == 1 && h.text == chapter.title))
+            .collect();
+
+        // Levels of the open lists. A heading shallower than everything open so far
+        // (an h2 after an h3) joins the outermost list instead of opening a second one.
+        let mut stack: Vec<u8> = Vec::new();
+        for heading in entries {
+            while stack.len() > 1 && stack.last().map(|&l| l > heading.level).unwrap_or(false) {
+                stack.pop();
+                out.push_str("</li></ol>");
+            }
+            match stack.last_mut() {
+                Some(level) if *level >= heading.level => {
+                    *level = heading.level;
+                    out.push_str("</li>");
+                }
+                _ => {
+                    stack.push(heading.level);
+                    out.push_str("<ol>");
+                }
+            }
+            out.push_str(&format!(
+                "<li><a href=\"{}#{}\">{}</a>",
+                chapter.file,
+                escape_xml(&heading.id),
+                escape_xml(&heading.text)
+            ));
+        }
+        for _ in stack {
+            out.push_str("</li></ol>");
+        }
+        out.push_str("</li>\n");
+    }
+    out.push_str("</ol>\n");
+    out
+}
+
+fn package_document(id: &str, title: &str, author: Option<&str>, chapters: &[Chapter], images: &[(PathBuf, Image)]) -> String {
+    let mut manifest = String::from(
+        "<item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>\n<item id=\"style\" href=\"style.css\" media-type=\"text/css\"/>\n",
+    );
+    let mut spine = String::new();
+    for (i, chapter) in chapters.iter().enumerate() {
+        manifest.push_str(&format!(
+            "<item id=\"chapter-{}\" href=\"{}\" media-type=\"application/xhtml+xml\"/>\n",
+            i + 1,
+            chapter.file
+        ));
+        spine.push_str(&format!("<itemref idref=\"chapter-{}\"/>\n", i + 1));
+    }
+    for (i, (_, image)) in images.iter().enumerate() {
+        manifest.push_str(&format!(
+            "<item id=\"image-{}\" href=\"{}\" media-type=\"{}\"/>\n",
+            i + 1,
+            image.file,
+            image.media_type
+        ));
+    }
+    let creator = author
+        .filter(|a| !a.trim().is_empty())
+        .map(|a| format!("<dc:creator>{}</dc:creator>\n", escape_xml(a.trim())))
+        .unwrap_or_default();
+    let modified = chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ");
+
+    format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<package xmlns=\"http://www.idpf.org/2007/opf\" version=\"3.0\" unique-identifier=\"book-id\">\n<metadata xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n<dc:identifier id=\"book-id\">{id}</dc:identifier>\n<dc:title>{title}</dc:title>\n<dc:language>en</dc:language>\n{creator}<meta property=\"dcterms:modified\">{modified}</meta>\n</metadata>\n<manifest>\n{manifest}</manifest>\n<spine>\n{spine}</spine>\n</package>\n",
+        id = escape_xml(id),
+        title = escape_xml(title),
+    )
+}
+
+const CONTAINER_XML: &str = "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<container version=\"1.0\" xmlns=\"urn:oasis:names:tc:opendocument:xmlns:container\">\n<rootfiles>\n<rootfile full-path=\"OEBPS/content.opf\" media-type=\"application/oebps-package+xml\"/>\n</rootfiles>\n</container>\n";
+
+fn write_archive(
+    output: &Path,
+    package: &str,
+    nav: &str,
+    chapters: &[Chapter],
+    images: &[(PathBuf, Image)],
+) -> Result<(), String> {
+    if let Some(parent) = output.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    // Built next to the destination and renamed into place, so a failed export
+    // leaves any previous book untouched.
+    let tmp = output.with_extension("epub.tmp");
+    let result = write_entries(&tmp, package, nav, chapters, images).and_then(|_| fs::rename(&tmp, output).map_err(|e| e.to_string()));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+fn write_entries(
+    path: &Path,
+    package: &str,
+    nav: &str,
+    chapters: &[Chapter],
+    images: &[(PathBuf, Image)],
+) -> Result<(), String> {
+    let file = File::create(path).map_err(|e| e.to_string())?;
+    let mut zip = ZipWriter::new(file);
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    // The mimetype entry must come first and be stored uncompressed.
+    zip.start_file("mimetype", stored).map_err(|e| e.to_string())?;
+    zip.write_all(b"application/epub+zip").map_err(|e| e.to_string())?;
+
+    let mut add = |name: &str, data: &[u8]| -> Result<(), String> {
+        zip.start_file(name, deflated).map_err(|e| e.to_string())?;
+        zip.write_all(data).map_err(|e| e.to_string())
+    };
+    add("META-INF/container.xml", CONTAINER_XML.as_bytes())?;
+    add("OEBPS/content.opf", package.as_bytes())?;
+    add("OEBPS/nav.xhtml", nav.as_bytes())?;
+    add("OEBPS/style.css", EPUB_CSS.as_bytes())?;
+    for chapter in chapters {
+        add(&format!("OEBPS/{}", chapter.file), xhtml_document(&chapter.title, &chapter.body, false).as_bytes())?;
+    }
+    for (source, image) in images {
+        let data = fs::read(source).map_err(|e| format!("{}: {}", source.display(), e))?;
+        add(&format!("OEBPS/{}", image.file), &data)?;
+    }
+    zip.finish().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn build(
+    notes: &[PathBuf],
+    root: &Path,
+    output: &Path,
+    title: &str,
+    author: Option<&str>,
+    job: &jobs::JobHandle,
+) -> Result<EpubExported, String> {
+    if notes.is_empty() {
+        return Err("No notes to export".to_string());
+    }
+    let by_source: HashMap<PathBuf, String> = notes
+        .iter()
+        .enumerate()
+        .map(|(i, p)| (p.clone(), format!("chapter-{}.xhtml", i + 1)))
+        .collect();
+    let mut images: Vec<(PathBuf, Image)> = Vec::new();
+    let mut image_files: HashMap<PathBuf, String> = HashMap::new();
+    let mut chapters: Vec<Chapter> = Vec::with_capacity(notes.len());
+
+    for (i, source) in notes.iter().enumerate() {
+        if job.is_cancelled() {
+            return Err("Cancelled".to_string());
+        }
+        let label = source
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| format!("Chapter {}", i + 1));
+        job.progress(i, notes.len(), &label);
+        let text = site::note_source(source)?;
+
+        let rendered = export::render_note(&text, EPUB_THEME, |kind, url| {
+            let url = markdown::trim_wrapping(url);
+            if url.is_empty() || url.starts_with('#') {
+                return None;
+            }
+            let (path, fragment) = match url.find('#') {
+                Some(pos) => (&url[..pos], &url[pos..]),
+                None => (url, ""),
+            };
+            let path = path.split('?').next().unwrap_or(path);
+            let decoded = percent_decode_str(path).decode_utf8_lossy();
+            let target = site::lexical_normalize(&crate::normalize_ref_path(root, source, &decoded)?);
+            match kind {
+                UrlKind::Link => by_source.get(&target).map(|file| format!("{}{}", file, fragment)),
+                UrlKind::Image => {
+                    if let Some(file) = image_files.get(&target) {
+                        return Some(file.clone());
+                    }
+                    let media_type = export::image_mime(&target);
+                    if !media_type.starts_with("image/") || !site::is_file_inside(root, &target) {
+                        return None;
+                    }
+                    let ext = target.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+                    let file = format!("images/image-{}.{}", images.len() + 1, ext);
+                    image_files.insert(target.clone(), file.clone());
+                    images.push((target, Image { file: file.clone(), media_type }));
+                    Some(file)
+                }
+            }
+        });
+
+        chapters.push(Chapter {
+            file: by_source[source].clone(),
+            title: rendered.title.unwrap_or(label),
+            headings: rendered.headings,
+            body: to_xhtml(&rendered.body),
+        });
+    }
+
+    job.progress(notes.len(), notes.len(), "Writing archive");
+    let id = format!("urn:uuid:{}", uuid::Uuid::new_v4());
+    let package = package_document(&id, title, author, &chapters, &images);
+    let nav = xhtml_document(title, &toc_body(title, &chapters), true);
+    write_archive(output, &package, &nav, &chapters, &images)?;
+
+    Ok(EpubExported {
+        job_id: job.id(),
+        path: output.to_string_lossy().to_string(),
+        chapters: chapters.len(),
+        images: images.len(),
+    })
+}
+
+/// Compiles notes into an EPUB 3 book at `output_path`, one chapter per note. Pass the
+/// notes in reading order as `notes`, or a `folder` to take every note under it in
+/// sidebar order. Referenced local images are bundled, links between included notes
+/// point at their chapters, and the table of contents lists each chapter with its
+/// headings. Runs as an `export-epub` job and reports through `epub-export-result`;
+/// returns the job id.
+#[allow(clippy::too_many_arguments)]
+#[tauri::command]
+pub fn export_epub(
+    app: AppHandle,
+    notes: Option<Vec<String>>,
+    folder: Option<String>,
+    root_path: String,
+    output_path: String,
+    title: Option<String>,
+    author: Option<String>,
+) -> Result<u64, String> {
+    let root = site::lexical_normalize(Path::new(&root_path));
+    let (notes, default_title) = match (notes, folder) {
+        (Some(notes), _) if !notes.is_empty() => {
+            (notes.iter().map(|p| site::lexical_normalize(Path::new(p))).collect::<Vec<_>>(), None)
+        }
+        (_, Some(folder)) => {
+            let folder = site::lexical_normalize(Path::new(&folder));
+            let name = folder.file_name().map(|n| n.to_string_lossy().to_string());
+            (site::note_paths(&folder), name)
+        }
+        _ => return Err("Choose notes or a folder to export".to_string()),
+    };
+    let title = title
+        .filter(|t| !t.trim().is_empty())
+        .or(default_title)
+        .unwrap_or_else(|| "Notes".to_string());
+    let output = PathBuf::from(output_path);
+
+    let app_handle = app.clone();
+    let job = jobs::start(&app, "export-epub");
+    let job_id = job.id();
+    async_runtime::spawn_blocking(move || {
+        let result = build(&notes, &root, &output, &title, author.as_deref(), &job);
+        if job.is_cancelled() {
+            job.finish(jobs::JobStatus::Cancelled, None);
+            return;
+        }
+        if let Ok(exported) = &result {
+            let _ = app_handle.emit("epub-export-result", exported.clone());
+        }
+        job.finish_with(&result);
+    });
+    Ok(job_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses `body` as a chapter the way a strict XML reader would.
+    fn assert_well_formed(body: &str) {
+        let document = xhtml_document("Title", body, false);
+        let options = roxmltree::ParsingOptions { allow_dtd: true, ..Default::default() };
+        if let Err(e) = roxmltree::Document::parse_with_options(&document, options) {
+            panic!("{}: {}", e, body);
+        }
+    }
+
+    #[test]
+    fn to_xhtml_writes_well_formed_markup() {
+        let cases = [
+            ("<p>a<br>b</p>", "<p>a<br/>b</p>"),
+            ("<img src=\"a.png\" alt='x > y'>", "<img alt=\"x &gt; y\" src=\"a.png\"/>"),
+            ("<hr/><input type=checkbox checked disabled>", "<hr/><input checked=\"\" disabled=\"\" type=\"checkbox\"/>"),
+            ("&copy; 2024 &mdash; &nbsp;&hellip; &amp; &#x1F600;", "© 2024 — \u{a0}… &amp; 😀"),
+            ("<p title=\"&eacute;t&eacute;\">AT&T & co</p>", "<p title=\"été\">AT&amp;T &amp; co</p>"),
+            ("<div><p>unclosed<b>bold</div>", "<div><p>unclosed<b>bold</b></p></div>"),
+            ("<p>a < b</p><!-- note -->", "<p>a &lt; b</p>"),
+            ("<details open><summary>S</summary>x</details>", "<details open=\"\"><summary>S</summary>x</details>"),
+            ("<x:tag a:b=\"1\" c=\"2\">kept</x:tag>", "kept"),
+            ("<span @click=\"go\" data-x=\"1\">s</span>", "<span data-x=\"1\">s</span>"),
+            ("<p>bell\u{7}</p>", "<p>bell</p>"),
+        ];
+        for (html, expected) in cases {
+            let xhtml = to_xhtml(html);
+            assert_eq!(xhtml, expected, "{}", html);
+            assert_well_formed(&xhtml);
+        }
+    }
+
+    #[test]
+    fn inline_svg_keeps_its_namespace() {
+        let xhtml = to_xhtml("<svg viewBox=\"0 0 1 1\"><foreignObject><p>x</p></foreignObject><rect width=\"1\"/></svg>");
+        assert_eq!(
+            xhtml,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 1 1\"><foreignObject><p xmlns=\"http://www.w3.org/1999/xhtml\">x</p></foreignObject><rect width=\"1\"></rect></svg>"
+        );
+        assert_well_formed(&xhtml);
+    }
+
+    #[test]
+    fn rendered_notes_become_valid_chapters() {
+        let source = "# Title &copy;\n\n| a | b |\n|---|---|\n| 1 | 2 |\n\n- [x] done\n\n<center>raw &mdash; <br> html</center>\n\n```rust\nfn main() {}\n```\n\nfootnote[^1]\n\n[^1]: here";
+        let body = to_xhtml(&export::render_note(source, EPUB_THEME, |_, _| None).body);
+        assert!(body.contains("<input checked=\"\" disabled=\"\" type=\"checkbox\"/>"), "{}", body);
+        assert!(body.contains("raw — <br/> html"), "{}", body);
+        assert_well_formed(&body);
+    }
+
+    fn heading(level: u8, text: &str) -> Heading {
+        Heading { level, text: text.to_string(), id: text.to_lowercase().replace(' ', "-") }
+    }
+
+    fn chapter(file: &str, title: &str, headings: Vec<Heading>) -> Chapter {
+        Chapter { file: file.to_string(), title: title.to_string(), headings, body: String::new() }
+    }
+
+    #[test]
+    fn toc_nests_headings_under_their_chapter() {
+        let chapters = [
+            chapter(
+                "chapter-1.xhtml",
+                "One",
+                vec![heading(1, "One"), heading(2, "A"), heading(3, "A1"), heading(4, "Deep"), heading(3, "A2"), heading(2, "B")],
+            ),
+            chapter("chapter-2.xhtml", "Two & more", vec![heading(3, "Starts deep"), heading(2, "Then shallower"), heading(1, "Other h1")]),
+            chapter("chapter-3.xhtml", "Three", vec![heading(2, "")]),
+        ];
+        let toc = toc_body("Book", &chapters);
+        assert_eq!(
+            toc,
+            "<h1>Book</h1>\n<ol>\n\
+             <li><a href=\"chapter-1.xhtml\">One</a><ol>\
+             <li><a href=\"chapter-1.xhtml#a\">A</a><ol>\
+             <li><a href=\"chapter-1.xhtml#a1\">A1</a></li>\
+             <li><a href=\"chapter-1.xhtml#a2\">A2</a></li></ol></li>\
+             <li><a href=\"chapter-1.xhtml#b\">B</a></li></ol></li>\n\
+             <li><a href=\"chapter-2.xhtml\">Two &amp; more</a><ol>\
+             <li><a href=\"chapter-2.xhtml#starts-deep\">Starts deep</a></li>\
+             <li><a href=\"chapter-2.xhtml#then-shallower\">Then shallower</a></li>\
+             <li><a href=\"chapter-2.xhtml#other-h1\">Other h1</a></li></ol></li>\n\
+             <li><a href=\"chapter-3.xhtml\">Three</a></li>\n\
+             </ol>\n"
+        );
+        let nav = xhtml_document("Book", &toc, true);
+        let options = roxmltree::ParsingOptions { allow_dtd: true, ..Default::default() };
+        roxmltree::Document::parse_with_options(&nav, options).unwrap();
+    }
+}
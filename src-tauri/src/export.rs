@@ -0,0 +1,497 @@
+use base64::{engine::general_purpose, Engine as _};
+use pulldown_cmark::{html, CodeBlockKind, CowStr, Event, Parser, Tag, TagEnd};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use syntect::highlighting::ThemeSet;
+use syntect::html::highlighted_html_for_string;
+use syntect::parsing::SyntaxSet;
+use tauri::AppHandle;
+
+use crate::{diagram, encryption, markdown, site};
+
+/// Shared by every theme; colours come from the `--color-*` variables set per theme.
+const BASE_CSS: &str = r#"
+* { box-sizing: border-box; }
+body {
+  margin: 0;
+  background: rgb(var(--color-bg));
+  color: rgb(var(--color-text));
+  font: 16px/1.65 -apple-system, BlinkMacSystemFont, "Segoe UI", Helvetica, Arial, sans-serif;
+}
+main { max-width: 820px; margin: 0 auto; padding: 48px 24px 96px; }
+h1, h2, h3, h4, h5, h6 { line-height: 1.25; margin: 1.6em 0 0.6em; }
+h1 { font-size: 2em; border-bottom: 1px solid rgb(var(--color-border)); padding-bottom: 0.3em; }
+h2 { font-size: 1.5em; border-bottom: 1px solid rgb(var(--color-border)); padding-bottom: 0.3em; }
+a { color: rgb(var(--color-accent)); }
+img { max-width: 100%; }
+hr { border: 0; border-top: 1px solid rgb(var(--color-border)); margin: 2em 0; }
+blockquote { margin: 1em 0; padding: 0 1em; color: rgb(var(--color-muted)); border-left: 4px solid rgb(var(--color-border)); }
+code { font-family: ui-monospace, SFMono-Regular, Menlo, Consolas, monospace; font-size: 0.9em; }
+:not(pre) > code { background: rgb(var(--color-surface)); padding: 0.15em 0.35em; border-radius: 4px; }
+pre { padding: 14px 16px; border-radius: 8px; overflow-x: auto; line-height: 1.45; border: 1px solid rgb(var(--color-border)); }
+pre code { font-size: 0.85em; }
+table { border-collapse: collapse; margin: 1em 0; display: block; overflow-x: auto; }
+th, td { border: 1px solid rgb(var(--color-border)); padding: 6px 12px; }
+th { background: rgb(var(--color-surface)); }
+li:has(> input[type=checkbox]) { list-style: none; margin-left: -1.4em; }
+input[type=checkbox] { margin-right: 0.5em; }
+.footnote-definition { color: rgb(var(--color-muted)); font-size: 0.9em; }
+"#;
+
+struct Theme {
+    bg: &'static str,
+    surface: &'static str,
+    border: &'static str,
+    text: &'static str,
+    muted: &'static str,
+    accent: &'static str,
+    scheme: &'static str,
+    /// Syntect theme used for code blocks.
+    code: &'static str,
+}
+
+/// Mirrors the theme palettes in `layout.css`, plus a light palette for documents
+/// that are read outside the app (e-books, print).
+fn theme_palette(name: &str) -> Theme {
+    match name {
+        "light" => Theme {
+            bg: "255 255 255",
+            surface: "246 248 250",
+            border: "208 215 222",
+            text: "31 35 40",
+            muted: "89 99 110",
+            accent: "9 105 218",
+            scheme: "light",
+            code: "InspiredGitHub",
+        },
+        "midnight" => Theme {
+            bg: "5 8 22",
+            surface: "11 18 32",
+            border: "42 53 81",
+            text: "230 234 242",
+            muted: "154 164 191",
+            accent: "56 189 248",
+            scheme: "dark",
+            code: "base16-ocean.dark",
+        },
+        "grape" => Theme {
+            bg: "8 6 11",
+            surface: "20 16 24",
+            border: "60 47 70",
+            text: "242 238 247",
+            muted: "184 173 196",
+            accent: "168 85 247",
+            scheme: "dark",
+            code: "base16-mocha.dark",
+        },
+        _ => Theme {
+            bg: "9 9 11",
+            surface: "24 24 27",
+            border: "63 63 70",
+            text: "228 228 231",
+            muted: "161 161 170",
+            accent: "59 130 246",
+            scheme: "dark",
+            code: "base16-eighties.dark",
+        },
+    }
+}
+
+pub fn theme_css(name: &str) -> String {
+    let t = theme_palette(name);
+    format!(
+        ":root {{ --color-bg: {}; --color-surface: {}; --color-border: {}; --color-text: {}; --color-muted: {}; --color-accent: {}; color-scheme: {}; }}{}",
+        t.bg, t.surface, t.border, t.text, t.muted, t.accent, t.scheme, BASE_CSS
+    )
+}
+
+fn syntax_set() -> &'static SyntaxSet {
+    static SYNTAXES: OnceLock<SyntaxSet> = OnceLock::new();
+    SYNTAXES.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+fn theme_set() -> &'static ThemeSet {
+    static THEMES: OnceLock<ThemeSet> = OnceLock::new();
+    THEMES.get_or_init(ThemeSet::load_defaults)
+}
+
+pub fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn highlight_code(lang: &str, code: &str, code_theme: &str) -> String {
+    let ss = syntax_set();
+    let token = lang.split_whitespace().next().unwrap_or("");
+    let syntax = ss
+        .find_syntax_by_token(token)
+        .unwrap_or_else(|| ss.find_syntax_plain_text());
+    let theme = &theme_set().themes[code_theme];
+    highlighted_html_for_string(code, ss, syntax, theme)
+        .unwrap_or_else(|_| format!("<pre><code>{}</code></pre>\n", escape_html(code)))
+}
+
+/// PlantUML and Mermaid blocks as an embedded SVG image, when a local renderer is
+/// configured; otherwise they are shown as code.
+fn diagram_html(lang: &str, code: &str) -> Option<String> {
+    if !diagram::is_diagram_lang(lang) {
+        return None;
+    }
+    let (svg, _) = diagram::render_svg(lang, code).ok()?;
+    Some(format!(
+        "<p class=\"diagram\"><img alt=\"diagram\" src=\"data:image/svg+xml;base64,{}\" /></p>\n",
+        general_purpose::STANDARD.encode(svg)
+    ))
+}
+
+pub fn image_mime(path: &Path) -> &'static str {
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase())
+        .unwrap_or_default();
+    match ext.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "bmp" => "image/bmp",
+        "svg" => "image/svg+xml",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Reads a local image referenced from `note_path` as a `data:` URI. Only images inside the
+/// workspace are read; anything else, such as `../../.ssh/id_rsa`, is left as a link.
+fn inline_image(root: &Path, note_path: &Path, src: &str) -> Option<String> {
+    let decoded = percent_encoding::percent_decode_str(src).decode_utf8_lossy();
+    let path = site::lexical_normalize(&crate::normalize_ref_path(root, note_path, &decoded)?);
+    if !image_mime(&path).starts_with("image/") || !site::is_file_inside(&site::lexical_normalize(root), &path) {
+        return None;
+    }
+    let data = crate::file_base64(&path).ok()?;
+    Some(format!("data:{};base64,{}", image_mime(&path), data))
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum UrlKind {
+    Image,
+    Link,
+}
+
+pub struct Heading {
+    pub level: u8,
+    pub text: String,
+    /// Anchor id, either given in the source or derived from the text.
+    pub id: String,
+}
+
+pub struct RenderedNote {
+    /// Text of the first level-one heading, if any.
+    pub title: Option<String>,
+    pub headings: Vec<Heading>,
+    pub body: String,
+}
+
+/// Anchor id for a heading: lowercase words joined by `-`, made unique within the note.
+/// Ids never start with a digit so they stay valid in XHTML.
+pub fn heading_id(text: &str, used: &mut HashSet<String>) -> String {
+    let mut slug = String::new();
+    for c in text.trim().chars() {
+        if c.is_alphanumeric() {
+            slug.extend(c.to_lowercase());
+        } else if (c.is_whitespace() || c == '-' || c == '_') && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let mut slug = slug.trim_matches('-').to_string();
+    if slug.is_empty() {
+        slug = "section".to_string();
+    } else if slug.starts_with(|c: char| c.is_ascii_digit()) {
+        slug.insert_str(0, "h-");
+    }
+    let mut id = slug.clone();
+    let mut n = 1;
+    while !used.insert(id.clone()) {
+        id = format!("{}-{}", slug, n);
+        n += 1;
+    }
+    id
+}
+
+/// Renders markdown to an HTML fragment with the editor's extensions (tables, task
+/// lists, footnotes, strikethrough), syntax-highlighted code blocks and rendered
+/// diagrams. Image sources
+/// and link targets are passed through `rewrite_url`, which may replace them.
+pub fn render_note(
+    source: &str,
+    theme: &str,
+    mut rewrite_url: impl FnMut(UrlKind, &str) -> Option<String>,
+) -> RenderedNote {
+    let code_theme = theme_palette(theme).code;
+    let mut headings: Vec<Heading> = Vec::new();
+    let mut used_ids: HashSet<String> = HashSet::new();
+    // Index of the open heading's start event and its text so far.
+    let mut heading: Option<(usize, String)> = None;
+    let mut code: Option<(String, String)> = None;
+    let mut events: Vec<Event> = Vec::new();
+
+    for event in Parser::new_ext(source, markdown::markdown_options()) {
+        match event {
+            Event::Start(Tag::CodeBlock(kind)) => {
+                let lang = match kind {
+                    CodeBlockKind::Fenced(lang) => lang.to_string(),
+                    CodeBlockKind::Indented => String::new(),
+                };
+                code = Some((lang, String::new()));
+            }
+            Event::Text(text) if code.is_some() => {
+                if let Some((_, body)) = code.as_mut() {
+                    body.push_str(&text);
+                }
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                if let Some((lang, body)) = code.take() {
+                    let html = diagram_html(&lang, &body).unwrap_or_else(|| highlight_code(&lang, &body, code_theme));
+                    events.push(Event::Html(html.into()));
+                }
+            }
+            Event::Start(Tag::Heading { .. }) => {
+                heading = Some((events.len(), String::new()));
+                events.push(event);
+            }
+            Event::End(TagEnd::Heading(level)) => {
+                if let Some((start, text)) = heading.take() {
+                    if let Event::Start(Tag::Heading { id, .. }) = &mut events[start] {
+                        let anchor = match id {
+                            Some(id) => {
+                                used_ids.insert(id.to_string());
+                                id.to_string()
+                            }
+                            None => {
+                                let anchor = heading_id(&text, &mut used_ids);
+                                *id = Some(anchor.clone().into());
+                                anchor
+                            }
+                        };
+                        headings.push(Heading { level: level as u8, text: text.trim().to_string(), id: anchor });
+                    }
+                }
+                events.push(event);
+            }
+            Event::Text(ref text) | Event::Code(ref text) if heading.is_some() => {
+                if let Some((_, heading_text)) = heading.as_mut() {
+                    heading_text.push_str(text);
+                }
+                events.push(event);
+            }
+            Event::Start(Tag::Image { link_type, dest_url, title: image_title, id }) => {
+                let dest_url = rewrite_url(UrlKind::Image, &dest_url).map(CowStr::from).unwrap_or(dest_url);
+                events.push(Event::Start(Tag::Image { link_type, dest_url, title: image_title, id }));
+            }
+            Event::Start(Tag::Link { link_type, dest_url, title: link_title, id }) => {
+                let dest_url = rewrite_url(UrlKind::Link, &dest_url).map(CowStr::from).unwrap_or(dest_url);
+                events.push(Event::Start(Tag::Link { link_type, dest_url, title: link_title, id }));
+            }
+            other => events.push(other),
+        }
+    }
+
+    let mut body = String::with_capacity(source.len() * 3 / 2);
+    html::push_html(&mut body, events.into_iter());
+    let title = headings
+        .iter()
+        .find(|h| h.level == 1 && !h.text.is_empty())
+        .map(|h| h.text.clone());
+    RenderedNote { title, headings, body }
+}
+
+pub fn html_document(title: &str, theme: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n<meta name=\"generator\" content=\"XNote\">\n<title>{}</title>\n<style>{}</style>\n</head>\n<body>\n<main>\n{}</main>\n</body>\n</html>\n",
+        escape_html(title),
+        theme_css(theme),
+        body
+    )
+}
+
+pub fn configured_theme() -> String {
+    crate::config::load()
+        .map(|loaded| loaded.config.theme)
+        .unwrap_or_else(|_| "zinc".to_string())
+}
+
+/// Renders the note at `path` to a single self-contained HTML file. Local images
+/// (including `/.xnote_assets/...`) are embedded as data URIs; remote ones are left
+/// as links. Writes next to the note as `<stem>.html` unless `output_path` is given,
+/// and returns the path written. Encrypted notes are only exported to an `output_path`
+/// outside the workspace, so their plaintext never lands next to them. A single note
+/// renders in well under a second, so this runs directly rather than as a job.
+#[tauri::command]
+pub fn export_note_html(
+    path: String,
+    root_path: String,
+    output_path: Option<String>,
+    theme: Option<String>,
+) -> Result<String, String> {
+    let note_path = Path::new(&path);
+    let root = Path::new(&root_path);
+    let output = match output_path {
+        Some(p) => PathBuf::from(p),
+        None => note_path.with_extension("html"),
+    };
+    if encryption::is_encrypted(note_path) && site::lexical_normalize(&output).starts_with(site::lexical_normalize(root)) {
+        return Err("Encrypted notes can only be exported to a folder outside the workspace".to_string());
+    }
+    let source = encryption::read_note(note_path)?;
+    let theme = theme.unwrap_or_else(configured_theme);
+
+    let rendered = render_note(&source, &theme, |kind, url| match kind {
+        UrlKind::Image => inline_image(root, note_path, url),
+        UrlKind::Link => None,
+    });
+    let title = rendered.title.unwrap_or_else(|| {
+        note_path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| "Untitled".to_string())
+    });
+
+    if let Some(parent) = output.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    fs::write(&output, html_document(&title, &theme, &rendered.body)).map_err(|e| e.to_string())?;
+    Ok(output.to_string_lossy().to_string())
+}
+
+/// Wraps rendered HTML for pasting into mail and chat apps, which ignore stylesheets:
+/// the few styles that matter are inlined.
+fn clipboard_html(body: &str) -> String {
+    let body = body
+        .replace("<table>", "<table style=\"border-collapse:collapse\">")
+        .replace("<th>", "<th style=\"border:1px solid #d0d7de;padding:4px 10px\">")
+        .replace("<td>", "<td style=\"border:1px solid #d0d7de;padding:4px 10px\">")
+        .replace("<pre style=\"", "<pre style=\"padding:10px 12px;border-radius:6px;")
+        .replace("<img ", "<img style=\"max-width:100%\" ");
+    format!(
+        "<div style=\"font-family:-apple-system,BlinkMacSystemFont,'Segoe UI',Helvetica,Arial,sans-serif;line-height:1.5\">\n{}</div>",
+        body
+    )
+}
+
+/// Copies a note to the clipboard as rendered HTML, with local images embedded as data
+/// URIs, plus its markdown source as the plain-text flavour. Pass `content` to copy
+/// unsaved editor text instead of the file on disk.
+#[tauri::command]
+pub fn copy_note_as_rich_text(
+    app: AppHandle,
+    path: String,
+    root_path: String,
+    content: Option<String>,
+) -> Result<(), String> {
+    let note_path = Path::new(&path);
+    let root = Path::new(&root_path);
+    let source = match content {
+        Some(content) => content,
+        None => encryption::read_note(note_path)?,
+    };
+    let rendered = render_note(&source, "light", |kind, url| match kind {
+        UrlKind::Image => inline_image(root, note_path, url),
+        UrlKind::Link => None,
+    });
+    crate::set_clipboard_html(&app, clipboard_html(&rendered.body), source)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(source: &str) -> String {
+        render_note(source, "light", |_, _| None).body
+    }
+
+    #[test]
+    fn renders_tables_and_task_lists() {
+        let body = render("| a | b |\n|---|:-:|\n| 1 | 2 |\n\n- [x] done\n- [ ] todo\n\n~~gone~~");
+        assert!(body.contains("<table>"), "{}", body);
+        assert!(body.contains("<th>a</th>"), "{}", body);
+        assert!(body.contains("<td style=\"text-align: center\">2</td>"), "{}", body);
+        assert!(body.contains("<input disabled=\"\" type=\"checkbox\" checked=\"\"/>\ndone"), "{}", body);
+        assert!(body.contains("<input disabled=\"\" type=\"checkbox\"/>\ntodo"), "{}", body);
+        assert!(body.contains("<del>gone</del>"), "{}", body);
+    }
+
+    #[test]
+    fn highlights_code_blocks() {
+        let body = render("```rust\nfn main() {}\n```\n\n```\n<plain> & text\n```");
+        assert!(body.contains("<pre style=\"background-color:"), "{}", body);
+        assert!(body.contains("<span style=\"color:"), "{}", body);
+        assert!(body.contains("&lt;plain&gt; &amp; text"), "{}", body);
+        assert!(!body.contains("<code class=\"language-rust\">"), "{}", body);
+    }
+
+    #[test]
+    fn headings_get_unique_ids_and_the_title() {
+        let rendered = render_note("# Hello World\n\n## Intro\n\n## Intro\n\n## 2024", "light", |_, _| None);
+        assert_eq!(rendered.title.as_deref(), Some("Hello World"));
+        let ids: Vec<&str> = rendered.headings.iter().map(|h| h.id.as_str()).collect();
+        assert_eq!(ids, vec!["hello-world", "intro", "intro-1", "h-2024"]);
+        assert!(rendered.body.contains("<h2 id=\"intro-1\">Intro</h2>"), "{}", rendered.body);
+    }
+
+    #[test]
+    fn inlines_only_images_inside_the_workspace() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path().join("ws");
+        let note = root.join("notes/a.md");
+        fs::create_dir_all(root.join("notes/img")).unwrap();
+        fs::create_dir_all(root.join(".xnote_assets/a")).unwrap();
+        fs::write(root.join("notes/img/p.png"), [1, 2, 3]).unwrap();
+        fs::write(root.join(".xnote_assets/a/q.gif"), [4]).unwrap();
+        fs::write(root.join("notes/secret.txt"), "inside, but not an image").unwrap();
+        fs::write(temp.path().join("outside.png"), [5]).unwrap();
+        fs::write(temp.path().join("id_rsa"), "key").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(temp.path().join("outside.png"), root.join("notes/link.png")).unwrap();
+
+        let source = "![p](img/p.png) ![q](/.xnote_assets/a/q.gif) ![s](secret.txt) ![o](../../outside.png) \
+                      ![k](../../id_rsa) ![l](link.png) ![m](img/missing.png) ![r](https://example.com/r.png)";
+        let body = render_note(source, "light", |kind, url| match kind {
+            UrlKind::Image => inline_image(&root, &note, url),
+            UrlKind::Link => None,
+        })
+        .body;
+        assert!(body.contains("src=\"data:image/png;base64,AQID\""), "{}", body);
+        assert!(body.contains("src=\"data:image/gif;base64,BA==\""), "{}", body);
+        for kept in ["secret.txt", "../../outside.png", "../../id_rsa", "link.png", "img/missing.png", "https://example.com/r.png"] {
+            assert!(body.contains(&format!("src=\"{}\"", kept)), "{}: {}", kept, body);
+        }
+    }
+
+    #[test]
+    fn encrypted_notes_are_not_exported_into_the_workspace() {
+        let temp = tempfile::tempdir().unwrap();
+        let note = temp.path().join("a.md");
+        fs::write(&note, b"xnote-encrypted:1\nsealed").unwrap();
+        if !encryption::is_encrypted(&note) {
+            return;
+        }
+        let root = temp.path().to_string_lossy().to_string();
+        let export = |output: Option<String>| export_note_html(note.to_string_lossy().to_string(), root.clone(), output, None);
+        for output in [None, Some(temp.path().join("out/a.html").to_string_lossy().to_string())] {
+            assert_eq!(export(output).unwrap_err(), "Encrypted notes can only be exported to a folder outside the workspace");
+        }
+        assert!(!temp.path().join("a.html").exists());
+    }
+}
@@ -0,0 +1,1144 @@
+use base64::{engine::general_purpose, Engine as _};
+use md5::{Digest, Md5};
+use percent_encoding::percent_decode_str;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::SystemTime;
+use tauri::{async_runtime, AppHandle, Emitter};
+use walkdir::WalkDir;
+
+use crate::{clipboard, export, jobs, markdown, site};
+
+const ASSETS_DIR: &str = ".xnote_assets";
+/// Placeholder scheme for ENEX resources until their asset paths are known.
+const ENEX_RESOURCE: &str = "enex-resource:";
+/// Nested zips inside a Notion export are unpacked up to this depth.
+const MAX_ZIP_DEPTH: usize = 3;
+
+static NEXT_TEMP: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum Format {
+    Obsidian,
+    Notion,
+    Enex,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct PlannedFile {
+    /// Workspace-relative destination.
+    path: String,
+    /// Where it comes from: a path inside the export, or an Evernote note title.
+    from: String,
+    attachment: bool,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct ImportReport {
+    job_id: u64,
+    format: Format,
+    source: String,
+    target_dir: String,
+    dry_run: bool,
+    notes: usize,
+    attachments: usize,
+    files: Vec<PlannedFile>,
+    warnings: Vec<String>,
+}
+
+enum Payload {
+    Text(String),
+    Copy(PathBuf),
+    Bytes(Vec<u8>),
+}
+
+struct Output {
+    dest: PathBuf,
+    from: String,
+    payload: Payload,
+    attachment: bool,
+    modified: Option<SystemTime>,
+}
+
+/// Everything an import will write. A dry run reports the plan; a real run writes it,
+/// so both name files the same way. Existing files are never overwritten.
+struct Plan {
+    root: PathBuf,
+    outputs: Vec<Output>,
+    /// Lowercased destinations already claimed, for case-insensitive file systems.
+    taken: HashSet<String>,
+    /// `(asset folder, source key)` → reference, so repeated embeds share one copy.
+    assets: HashMap<(PathBuf, String), String>,
+    warnings: Vec<String>,
+    unpacked: Option<TempDir>,
+}
+
+impl Plan {
+    fn new(root: &Path) -> Self {
+        Plan {
+            root: root.to_path_buf(),
+            outputs: Vec::new(),
+            taken: HashSet::new(),
+            assets: HashMap::new(),
+            warnings: Vec::new(),
+            unpacked: None,
+        }
+    }
+
+    /// Claims `path`, or `name-1.ext`, `name-2.ext`, ... if it exists or is planned.
+    fn reserve(&mut self, path: PathBuf) -> PathBuf {
+        let stem = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+        let ext = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+        let mut candidate = path.clone();
+        let mut n = 1;
+        while candidate.exists() || self.taken.contains(&candidate.to_string_lossy().to_lowercase()) {
+            candidate = path.with_file_name(format!("{}-{}{}", stem, n, ext));
+            n += 1;
+        }
+        self.taken.insert(candidate.to_string_lossy().to_lowercase());
+        candidate
+    }
+
+    fn add_note(&mut self, dest: PathBuf, from: String, text: String, modified: Option<SystemTime>) {
+        self.outputs.push(Output { dest, from, payload: Payload::Text(text), attachment: false, modified });
+    }
+
+    /// Plans an attachment in the `.xnote_assets/<stem>` folder of the note at `note` and
+    /// returns the reference to write in that note.
+    fn asset(&mut self, note: &Path, name: &str, key: &str, payload: impl FnOnce() -> Payload) -> String {
+        let stem = note
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .filter(|s| !s.is_empty())
+            .unwrap_or_else(|| "note".to_string());
+        let dir = self.root.join(ASSETS_DIR).join(&stem);
+        if let Some(reference) = self.assets.get(&(dir.clone(), key.to_string())) {
+            return reference.clone();
+        }
+        let dest = self.reserve(dir.join(sanitize_name(name)));
+        let file_name = dest.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        let reference = site::encode_path(&format!("/{}/{}/{}", ASSETS_DIR, stem, file_name));
+        self.outputs.push(Output {
+            dest,
+            from: key.to_string(),
+            payload: payload(),
+            attachment: true,
+            modified: None,
+        });
+        self.assets.insert((dir, key.to_string()), reference.clone());
+        reference
+    }
+
+    /// Link from one planned note to another, relative to the linking note.
+    fn note_link(&self, from: &Path, to: &Path) -> String {
+        site::relative_url(&self.relative(from), &self.relative(to))
+    }
+
+    fn relative(&self, path: &Path) -> String {
+        path.strip_prefix(&self.root).unwrap_or(path).to_string_lossy().replace('\\', "/")
+    }
+
+    fn report(&self, job_id: u64, format: Format, source: &str, target_dir: &Path, dry_run: bool) -> ImportReport {
+        let files: Vec<PlannedFile> = self
+            .outputs
+            .iter()
+            .map(|o| PlannedFile { path: self.relative(&o.dest), from: o.from.clone(), attachment: o.attachment })
+            .collect();
+        let attachments = files.iter().filter(|f| f.attachment).count();
+        ImportReport {
+            job_id,
+            format,
+            source: source.to_string(),
+            target_dir: target_dir.to_string_lossy().to_string(),
+            dry_run,
+            notes: files.len() - attachments,
+            attachments,
+            files,
+            warnings: self.warnings.clone(),
+        }
+    }
+
+    fn write(&self, job: &dyn jobs::Progress) -> Result<(), String> {
+        let total = self.outputs.len();
+        for (i, output) in self.outputs.iter().enumerate() {
+            if job.is_cancelled() {
+                return Err("Cancelled".to_string());
+            }
+            job.progress(i, total, &self.relative(&output.dest));
+            if let Some(parent) = output.dest.parent() {
+                fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+            }
+            let mut file = File::options()
+                .write(true)
+                .create_new(true)
+                .open(&output.dest)
+                .map_err(|e| format!("{}: {}", output.dest.display(), e))?;
+            match &output.payload {
+                Payload::Text(text) => io::Write::write_all(&mut file, text.as_bytes()),
+                Payload::Bytes(bytes) => io::Write::write_all(&mut file, bytes),
+                Payload::Copy(source) => File::open(source).and_then(|mut src| io::copy(&mut src, &mut file).map(|_| ())),
+            }
+            .map_err(|e| format!("{}: {}", output.dest.display(), e))?;
+            if let Some(modified) = output.modified {
+                let _ = file.set_modified(modified);
+            }
+        }
+        job.progress(total, total, "Done");
+        Ok(())
+    }
+}
+
+/// Replaces characters file systems reject and trims what Finder and Explorer hide.
+fn sanitize_name(name: &str) -> String {
+    let cleaned: String = name
+        .chars()
+        .map(|c| if matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|') || c.is_control() { '-' } else { c })
+        .collect();
+    let cleaned = cleaned.trim().trim_matches('.').trim();
+    let cleaned: String = cleaned.chars().take(120).collect();
+    if cleaned.is_empty() {
+        "Untitled".to_string()
+    } else {
+        cleaned
+    }
+}
+
+fn has_scheme(url: &str) -> bool {
+    match url.find(':') {
+        Some(i) => url[..i].chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.')) && i > 1,
+        None => false,
+    }
+}
+
+/// Splits a local link into its decoded path and `#fragment`.
+fn split_local(url: &str) -> Option<(String, Option<String>)> {
+    let url = url.trim();
+    if url.is_empty() || url.starts_with('#') || has_scheme(url) {
+        return None;
+    }
+    let (path, fragment) = match url.split_once('#') {
+        Some((p, f)) => (p, Some(f.to_string())),
+        None => (url, None),
+    };
+    let path = path.split('?').next().unwrap_or(path);
+    Some((percent_decode_str(path).decode_utf8_lossy().to_string(), fragment))
+}
+
+fn anchor(heading: &str) -> String {
+    format!("#{}", export::heading_id(heading, &mut HashSet::new()))
+}
+
+fn is_markdown(path: &Path) -> bool {
+    path.extension()
+        .map(|e| e.eq_ignore_ascii_case("md") || e.eq_ignore_ascii_case("markdown"))
+        .unwrap_or(false)
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default()
+}
+
+/// Files below `dir`, relative to it, skipping hidden entries such as `.obsidian`.
+fn walk(dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = WalkDir::new(dir)
+        .into_iter()
+        .filter_entry(|e| e.depth() == 0 || !e.file_name().to_string_lossy().starts_with('.'))
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|e| e.path().strip_prefix(dir).ok().map(Path::to_path_buf))
+        .collect();
+    files.sort();
+    files
+}
+
+// ---------------------------------------------------------------------------
+// Obsidian
+// ---------------------------------------------------------------------------
+
+enum Resolved {
+    Note(PathBuf),
+    File(PathBuf),
+}
+
+struct Vault {
+    dir: PathBuf,
+    attachment_dir: Option<String>,
+    notes_by_path: HashMap<String, PathBuf>,
+    notes_by_name: HashMap<String, Vec<PathBuf>>,
+    files_by_path: HashMap<String, PathBuf>,
+    files_by_name: HashMap<String, Vec<PathBuf>>,
+}
+
+fn key(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/").to_lowercase()
+}
+
+impl Vault {
+    fn scan(dir: &Path) -> (Self, Vec<PathBuf>, Vec<PathBuf>) {
+        // The folder new attachments go to, from Settings → Files and links.
+        let attachment_dir = fs::read_to_string(dir.join(".obsidian").join("app.json"))
+            .ok()
+            .and_then(|text| serde_json::from_str::<serde_json::Value>(&text).ok())
+            .and_then(|v| v.get("attachmentFolderPath").and_then(|p| p.as_str()).map(str::to_string))
+            .map(|p| p.trim_matches('/').to_lowercase())
+            .filter(|p| !p.is_empty() && !p.starts_with('.'));
+
+        let mut vault = Vault {
+            dir: dir.to_path_buf(),
+            attachment_dir,
+            notes_by_path: HashMap::new(),
+            notes_by_name: HashMap::new(),
+            files_by_path: HashMap::new(),
+            files_by_name: HashMap::new(),
+        };
+        let (mut notes, mut files) = (Vec::new(), Vec::new());
+        for rel in walk(dir) {
+            if is_markdown(&rel) {
+                vault.notes_by_path.insert(key(&rel.with_extension("")), rel.clone());
+                let stem = rel.file_stem().map(|s| s.to_string_lossy().to_lowercase()).unwrap_or_default();
+                vault.notes_by_name.entry(stem).or_default().push(rel.clone());
+                notes.push(rel);
+            } else {
+                vault.files_by_path.insert(key(&rel), rel.clone());
+                vault.files_by_name.entry(file_name(&rel).to_lowercase()).or_default().push(rel.clone());
+                files.push(rel);
+            }
+        }
+        (vault, notes, files)
+    }
+
+    /// Obsidian prefers a match next to the linking note, then the attachment folder,
+    /// then the shortest path.
+    fn pick(&self, candidates: &[PathBuf], from: &Path) -> Option<PathBuf> {
+        let from_dir = from.parent().unwrap_or(Path::new(""));
+        candidates
+            .iter()
+            .min_by_key(|c| {
+                let same_dir = c.parent().unwrap_or(Path::new("")) == from_dir;
+                let in_attachments = self.attachment_dir.as_deref().map(|a| key(c).starts_with(a)).unwrap_or(false);
+                (!same_dir, !in_attachments, c.components().count())
+            })
+            .cloned()
+    }
+
+    fn resolve_wikilink(&self, target: &str, from: &Path) -> Option<Resolved> {
+        let target = target.trim().replace('\\', "/");
+        let lower = target.trim_start_matches('/').to_lowercase();
+        let note_key = lower.strip_suffix(".md").unwrap_or(&lower);
+        if let Some(note) = self.notes_by_path.get(note_key) {
+            return Some(Resolved::Note(note.clone()));
+        }
+        if !note_key.contains('/') {
+            if let Some(note) = self.notes_by_name.get(note_key).and_then(|c| self.pick(c, from)) {
+                return Some(Resolved::Note(note));
+            }
+        } else if let Some(name) = note_key.rsplit('/').next() {
+            // `folder/Note` written relative to a subfolder.
+            let suffix = format!("/{}", note_key);
+            if let Some(candidates) = self.notes_by_name.get(name) {
+                let matching: Vec<PathBuf> =
+                    candidates.iter().filter(|c| format!("/{}", key(&c.with_extension(""))).ends_with(&suffix)).cloned().collect();
+                if let Some(note) = self.pick(&matching, from) {
+                    return Some(Resolved::Note(note));
+                }
+            }
+        }
+        if let Some(file) = self.files_by_path.get(&lower) {
+            return Some(Resolved::File(file.clone()));
+        }
+        let name = lower.rsplit('/').next().unwrap_or(&lower);
+        self.files_by_name.get(name).and_then(|c| self.pick(c, from)).map(Resolved::File)
+    }
+
+    /// A markdown link: relative to the note first, then by name like a wikilink.
+    fn resolve_path(&self, path: &str, from: &Path) -> Option<Resolved> {
+        let base = from.parent().unwrap_or(Path::new(""));
+        let joined = if let Some(abs) = path.strip_prefix('/') {
+            PathBuf::from(abs)
+        } else {
+            base.join(path)
+        };
+        let rel = site::lexical_normalize(&joined);
+        if is_markdown(&rel) {
+            if let Some(note) = self.notes_by_path.get(&key(&rel.with_extension(""))) {
+                return Some(Resolved::Note(note.clone()));
+            }
+        } else if let Some(file) = self.files_by_path.get(&key(&rel)) {
+            return Some(Resolved::File(file.clone()));
+        }
+        self.resolve_wikilink(path, from)
+    }
+}
+
+/// Replaces `[[...]]` and `![[...]]` outside code with what `convert` returns for the
+/// inner text.
+fn replace_wikilinks(content: &str, mut convert: impl FnMut(&str, bool) -> String) -> String {
+    let code = markdown::code_ranges(content);
+    let mut out = String::with_capacity(content.len());
+    let mut i = 0;
+    while let Some(pos) = content[i..].find("[[") {
+        let start = i + pos;
+        let in_code = code.iter().any(|r| r.contains(&start));
+        let end = content[start + 2..].find("]]").map(|e| start + 2 + e);
+        let Some(end) = end.filter(|e| !in_code && !content[start + 2..*e].contains('\n') && *e > start + 2) else {
+            out.push_str(&content[i..start + 2]);
+            i = start + 2;
+            continue;
+        };
+        let embed = start > i && content.as_bytes()[start - 1] == b'!';
+        out.push_str(&content[i..if embed { start - 1 } else { start }]);
+        out.push_str(&convert(&content[start + 2..end], embed));
+        i = end + 2;
+    }
+    out.push_str(&content[i..]);
+    out
+}
+
+/// `![[image.png|300]]` sets a size rather than alt text.
+fn is_size(alias: &str) -> bool {
+    let alias = alias.trim();
+    !alias.is_empty() && alias.split('x').all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()))
+}
+
+fn plan_obsidian(source: &Path, target: &Path, plan: &mut Plan, job: &dyn jobs::Progress) -> Result<(), String> {
+    let (vault, notes, files) = Vault::scan(source);
+    if notes.is_empty() {
+        return Err(format!("No markdown notes found in {}", source.display()));
+    }
+    let dests: HashMap<PathBuf, PathBuf> = notes.iter().map(|rel| (rel.clone(), plan.reserve(target.join(rel)))).collect();
+    let mut used_files: HashSet<PathBuf> = HashSet::new();
+
+    for (i, rel) in notes.iter().enumerate() {
+        if job.is_cancelled() {
+            return Err("Cancelled".to_string());
+        }
+        job.progress(i, notes.len(), &rel.to_string_lossy());
+        let dest = &dests[rel];
+        let text = fs::read_to_string(vault.dir.join(rel)).map_err(|e| format!("{}: {}", rel.display(), e))?;
+
+        let mut attach = |plan: &mut Plan, file: &Path| {
+            used_files.insert(file.to_path_buf());
+            let from = file.to_string_lossy().to_string();
+            plan.asset(dest, &file_name(file), &from, || Payload::Copy(vault.dir.join(file)))
+        };
+
+        let text = markdown::rewrite_link_targets(&text, |url| {
+            let (path, fragment) = split_local(url)?;
+            let fragment = fragment.map(|f| format!("#{}", f)).unwrap_or_default();
+            match vault.resolve_path(&path, rel)? {
+                Resolved::Note(note) => Some(format!("{}{}", plan.note_link(dest, &dests[&note]), fragment)),
+                Resolved::File(file) => Some(attach(plan, &file)),
+            }
+        });
+
+        let mut unresolved = Vec::new();
+        let text = replace_wikilinks(&text, |inner, embed| {
+            let (link, alias) = match inner.split_once('|') {
+                Some((l, a)) => (l, Some(a.trim())),
+                None => (inner, None),
+            };
+            let (target, heading) = match link.split_once('#') {
+                Some((t, h)) => (t.trim(), Some(h.trim_start_matches('^').trim())),
+                None => (link.trim(), None),
+            };
+            if target.is_empty() {
+                let heading = heading.unwrap_or("");
+                return format!("[{}]({})", alias.unwrap_or(heading), anchor(heading));
+            }
+            match vault.resolve_wikilink(target, rel) {
+                Some(Resolved::Note(note)) => {
+                    let name = target.rsplit('/').next().unwrap_or(target);
+                    let label = match (alias, heading) {
+                        (Some(alias), _) => alias.to_string(),
+                        (None, Some(heading)) => format!("{} > {}", name, heading),
+                        (None, None) => name.to_string(),
+                    };
+                    let fragment = heading.map(anchor).unwrap_or_default();
+                    format!("[{}]({}{})", label, plan.note_link(dest, &dests[&note]), fragment)
+                }
+                Some(Resolved::File(file)) => {
+                    let reference = attach(plan, &file);
+                    let image = export::image_mime(&file).starts_with("image/");
+                    let label = alias.filter(|a| !is_size(a)).map(str::to_string).unwrap_or_else(|| file_name(&file));
+                    if embed && image {
+                        format!("![{}]({})", alias.filter(|a| !is_size(a)).unwrap_or(""), reference)
+                    } else {
+                        format!("[{}]({})", label, reference)
+                    }
+                }
+                None => {
+                    unresolved.push(target.to_string());
+                    alias.unwrap_or(target).to_string()
+                }
+            }
+        });
+        for target in unresolved {
+            plan.warnings.push(format!("{}: no note or file named \"{}\"", rel.display(), target));
+        }
+
+        let modified = fs::metadata(vault.dir.join(rel)).and_then(|m| m.modified()).ok();
+        plan.add_note(dest.clone(), rel.to_string_lossy().to_string(), text, modified);
+    }
+
+    let skipped = files.iter().filter(|f| !used_files.contains(*f)).count();
+    if skipped > 0 {
+        plan.warnings.push(format!("{} attachment(s) are not referenced by any note and were left out", skipped));
+    }
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// Notion
+// ---------------------------------------------------------------------------
+
+/// A directory that is deleted when dropped.
+struct TempDir(PathBuf);
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Unpacks a Notion export, including the `Part-N.zip` files large exports are split into.
+fn unpack(zip_path: &Path) -> Result<TempDir, String> {
+    let n = NEXT_TEMP.fetch_add(1, Ordering::Relaxed);
+    let dir = TempDir(std::env::temp_dir().join(format!("xnote-import-{}-{}", std::process::id(), n)));
+    fs::create_dir_all(&dir.0).map_err(|e| e.to_string())?;
+    let open = |path: &Path, into: &Path| -> Result<(), String> {
+        let file = File::open(path).map_err(|e| e.to_string())?;
+        let mut archive = zip::ZipArchive::new(file).map_err(|e| format!("{}: {}", path.display(), e))?;
+        archive.extract(into).map_err(|e| format!("{}: {}", path.display(), e))
+    };
+    open(zip_path, &dir.0)?;
+    for _ in 0..MAX_ZIP_DEPTH {
+        let nested: Vec<PathBuf> = walk(&dir.0)
+            .into_iter()
+            .filter(|p| p.extension().map(|e| e.eq_ignore_ascii_case("zip")).unwrap_or(false))
+            .collect();
+        if nested.is_empty() {
+            break;
+        }
+        for rel in nested {
+            let path = dir.0.join(&rel);
+            open(&path, path.parent().unwrap_or(&dir.0))?;
+            fs::remove_file(&path).map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(dir)
+}
+
+/// Drops the ` 0123456789abcdef0123456789abcdef` page id Notion appends to names.
+fn strip_notion_id(name: &str) -> String {
+    let (stem, ext) = match name.rfind('.') {
+        Some(i) if i > 0 => (&name[..i], &name[i..]),
+        _ => (name, ""),
+    };
+    let (stem, all) = match stem.strip_suffix("_all") {
+        Some(s) => (s, "_all"),
+        None => (stem, ""),
+    };
+    let stripped = match stem.len().checked_sub(32) {
+        Some(at) if stem.is_char_boundary(at) && stem[at..].chars().all(|c| c.is_ascii_hexdigit()) => {
+            stem[..at].trim_end()
+        }
+        _ => stem,
+    };
+    let stripped = if stripped.is_empty() { stem } else { stripped };
+    format!("{}{}{}", stripped, all, ext)
+}
+
+fn notion_path(rel: &Path) -> PathBuf {
+    rel.components()
+        .map(|c| sanitize_name(&strip_notion_id(&c.as_os_str().to_string_lossy())))
+        .collect()
+}
+
+/// Minimal RFC 4180 reader: quoted fields, doubled quotes and line breaks in quotes.
+fn parse_csv(text: &str) -> Vec<Vec<String>> {
+    let text = text.trim_start_matches('\u{feff}');
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' if quoted => quoted = false,
+            '"' if field.is_empty() => quoted = true,
+            ',' if !quoted => row.push(std::mem::take(&mut field)),
+            '\r' if !quoted => {}
+            '\n' if !quoted => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            c => field.push(c),
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    rows.retain(|r| r.iter().any(|f| !f.trim().is_empty()));
+    rows
+}
+
+fn csv_table(title: &str, text: &str) -> String {
+    let rows = parse_csv(text);
+    let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let mut out = format!("# {}\n", title);
+    if columns == 0 {
+        return out;
+    }
+    out.push('\n');
+    for (i, row) in rows.iter().enumerate() {
+        let cells: Vec<String> = (0..columns)
+            .map(|c| {
+                let cell = row.get(c).map(String::as_str).unwrap_or("");
+                cell.trim().replace('|', "\\|").replace("\r\n", "<br>").replace('\n', "<br>")
+            })
+            .collect();
+        out.push_str(&format!("| {} |\n", cells.join(" | ")));
+        if i == 0 {
+            out.push_str(&format!("|{}\n", " --- |".repeat(columns)));
+        }
+    }
+    out
+}
+
+fn plan_notion(source: &Path, target: &Path, plan: &mut Plan, job: &dyn jobs::Progress) -> Result<(), String> {
+    let unpacked = if source.is_dir() { None } else { Some(unpack(source)?) };
+    let dir = unpacked.as_ref().map(|t| t.0.as_path()).unwrap_or(source);
+    let files = walk(dir);
+
+    // Databases come as `Name <id>.csv` (the current view) and `Name <id>_all.csv`
+    // (every row); keep the complete one.
+    let csv_all: HashSet<PathBuf> = files
+        .iter()
+        .filter(|f| f.to_string_lossy().to_lowercase().ends_with("_all.csv"))
+        .cloned()
+        .collect();
+    let mut pages: Vec<PathBuf> = Vec::new();
+    // Links to a dropped view point at the complete table instead.
+    let mut views: HashMap<PathBuf, PathBuf> = HashMap::new();
+    for rel in &files {
+        let lower = rel.to_string_lossy().to_lowercase();
+        if is_markdown(rel) {
+            pages.push(rel.clone());
+        } else if lower.ends_with(".csv") {
+            let all = PathBuf::from(format!("{}_all.csv", &rel.to_string_lossy()[..lower.len() - 4]));
+            if !lower.ends_with("_all.csv") && csv_all.contains(&all) {
+                views.insert(rel.clone(), all);
+            } else {
+                pages.push(rel.clone());
+            }
+        }
+    }
+    if pages.is_empty() {
+        return Err(format!("No Notion pages found in {}", source.display()));
+    }
+
+    let mut dests: HashMap<PathBuf, PathBuf> = HashMap::new();
+    for rel in &pages {
+        let mut dest = target.join(notion_path(rel));
+        if !is_markdown(rel) {
+            let stem = strip_notion_id(&file_name(rel));
+            let stem = stem.trim_end_matches(".csv").trim_end_matches(".CSV").trim_end_matches("_all");
+            dest = dest.with_file_name(format!("{}.md", sanitize_name(stem)));
+        }
+        dests.insert(rel.clone(), plan.reserve(dest));
+    }
+
+    for (i, rel) in pages.iter().enumerate() {
+        if job.is_cancelled() {
+            return Err("Cancelled".to_string());
+        }
+        job.progress(i, pages.len(), &rel.to_string_lossy());
+        let dest = &dests[rel];
+        let text = fs::read_to_string(dir.join(rel)).map_err(|e| format!("{}: {}", rel.display(), e))?;
+        let from = rel.to_string_lossy().to_string();
+
+        if !is_markdown(rel) {
+            let title = dest.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+            plan.add_note(dest.clone(), from, csv_table(&title, &text), None);
+            continue;
+        }
+
+        let base = rel.parent().unwrap_or(Path::new(""));
+        let mut missing = Vec::new();
+        let text = markdown::rewrite_link_targets(&text, |url| {
+            let (path, fragment) = split_local(url)?;
+            let fragment = fragment.map(|f| format!("#{}", f)).unwrap_or_default();
+            let linked = site::lexical_normalize(&base.join(&path));
+            if let Some(note) = dests.get(views.get(&linked).unwrap_or(&linked)) {
+                return Some(format!("{}{}", plan.note_link(dest, note), fragment));
+            }
+            // Only files of the export itself: `../` and absolute links must not pull in
+            // anything from outside it.
+            let inside = linked.is_relative() && !linked.starts_with("..");
+            if inside && dir.join(&linked).is_file() && !linked.to_string_lossy().to_lowercase().ends_with(".csv") {
+                let key = linked.to_string_lossy().to_string();
+                return Some(plan.asset(dest, &file_name(&linked), &key, || Payload::Copy(dir.join(&linked))));
+            }
+            missing.push(path);
+            None
+        });
+        for path in missing {
+            plan.warnings.push(format!("{}: link to missing \"{}\" left unchanged", notion_path(rel).display(), path));
+        }
+        plan.add_note(dest.clone(), from, text, None);
+    }
+
+    // Attachments are copied from the unpacked tree, so it has to outlive the plan.
+    plan.unpacked = unpacked;
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// Evernote
+// ---------------------------------------------------------------------------
+
+struct Resource {
+    data: Vec<u8>,
+    mime: String,
+    name: String,
+}
+
+fn child<'a, 'input>(node: roxmltree::Node<'a, 'input>, name: &str) -> Option<roxmltree::Node<'a, 'input>> {
+    node.children().find(|c| c.has_tag_name(name))
+}
+
+fn child_text(node: roxmltree::Node, name: &str) -> Option<String> {
+    child(node, name).map(|c| c.descendants().filter(|d| d.is_text()).filter_map(|d| d.text()).collect())
+}
+
+/// ENEX timestamps look like `20230415T093000Z`.
+fn enex_time(value: &str) -> Option<SystemTime> {
+    let time = chrono::NaiveDateTime::parse_from_str(value.trim(), "%Y%m%dT%H%M%SZ").ok()?;
+    Some(SystemTime::from(time.and_utc()))
+}
+
+fn extension_for(mime: &str) -> &'static str {
+    match mime {
+        "image/png" => "png",
+        "image/jpeg" => "jpg",
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        "image/svg+xml" => "svg",
+        "application/pdf" => "pdf",
+        "audio/mpeg" => "mp3",
+        "audio/wav" | "audio/x-wav" => "wav",
+        _ => "bin",
+    }
+}
+
+fn tag_attr(tag: &str, name: &str) -> Option<String> {
+    let mut from = 0;
+    while let Some(pos) = tag[from..].find(name) {
+        let start = from + pos;
+        from = start + name.len();
+        if !tag[..start].ends_with(char::is_whitespace) {
+            continue;
+        }
+        let rest = tag[from..].trim_start().strip_prefix('=')?.trim_start();
+        let quote = rest.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+        let end = rest[1..].find(quote)?;
+        return Some(rest[1..1 + end].to_string());
+    }
+    None
+}
+
+/// Turns ENML's `<en-media>` into images or links with placeholder URLs and
+/// `<en-todo>` checkboxes into ☐ / ☑, leaving plain HTML for the converter.
+fn rewrite_enml(enml: &str, resources: &HashMap<String, Resource>) -> String {
+    let mut out = String::with_capacity(enml.len());
+    let mut rest = enml;
+    while let Some(pos) = [rest.find("<en-"), rest.find("</en-")].into_iter().flatten().min() {
+        out.push_str(&rest[..pos]);
+        let tail = &rest[pos..];
+        let Some(end) = tail.find('>') else {
+            rest = tail;
+            break;
+        };
+        let tag = &tail[..=end];
+        rest = &tail[end + 1..];
+        if tag.starts_with("<en-media") {
+            let hash = tag_attr(tag, "hash").unwrap_or_default().to_lowercase();
+            let Some(resource) = resources.get(&hash) else { continue };
+            if resource.mime.starts_with("image/") {
+                out.push_str(&format!("<img src=\"{}{}\" alt=\"\">", ENEX_RESOURCE, hash));
+            } else {
+                out.push_str(&format!(
+                    "<a href=\"{}{}\">{}</a>",
+                    ENEX_RESOURCE,
+                    hash,
+                    export::escape_html(&resource.name)
+                ));
+            }
+        } else if tag.starts_with("<en-todo") {
+            let checked = tag_attr(tag, "checked").map(|v| v == "true").unwrap_or(false);
+            out.push_str(if checked { "☑ " } else { "☐ " });
+        } else if !tag.starts_with("</en-media") && !tag.starts_with("</en-todo") {
+            out.push_str(tag);
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn plan_enex(source: &Path, target: &Path, plan: &mut Plan, job: &dyn jobs::Progress) -> Result<(), String> {
+    let text = fs::read_to_string(source).map_err(|e| format!("{}: {}", source.display(), e))?;
+    let options = roxmltree::ParsingOptions { allow_dtd: true, ..Default::default() };
+    let doc = roxmltree::Document::parse_with_options(&text, options).map_err(|e| format!("{}: {}", source.display(), e))?;
+    let notes: Vec<roxmltree::Node> = doc.root_element().children().filter(|n| n.has_tag_name("note")).collect();
+    if notes.is_empty() {
+        return Err(format!("No notes found in {}", source.display()));
+    }
+
+    for (i, note) in notes.iter().enumerate() {
+        if job.is_cancelled() {
+            return Err("Cancelled".to_string());
+        }
+        let title = child_text(*note, "title")
+            .map(|t| t.trim().to_string())
+            .filter(|t| !t.is_empty())
+            .unwrap_or_else(|| "Untitled".to_string());
+        job.progress(i, notes.len(), &title);
+
+        let mut resources: HashMap<String, Resource> = HashMap::new();
+        let mut order: Vec<String> = Vec::new();
+        for res in note.children().filter(|c| c.has_tag_name("resource")) {
+            let data: String = child_text(res, "data").unwrap_or_default().split_whitespace().collect();
+            let Ok(data) = general_purpose::STANDARD.decode(data) else {
+                plan.warnings.push(format!("{}: skipped an attachment that could not be decoded", title));
+                continue;
+            };
+            let hash: String = Md5::digest(&data).iter().map(|b| format!("{:02x}", b)).collect();
+            if resources.contains_key(&hash) {
+                continue;
+            }
+            let mime = child_text(res, "mime").unwrap_or_default().trim().to_string();
+            let name = child(res, "resource-attributes")
+                .and_then(|attrs| child_text(attrs, "file-name"))
+                .map(|n| n.trim().to_string())
+                .filter(|n| !n.is_empty())
+                .unwrap_or_else(|| format!("{}.{}", &hash[..8], extension_for(&mime)));
+            order.push(hash.clone());
+            resources.insert(hash, Resource { data, mime, name });
+        }
+
+        let dest = plan.reserve(target.join(format!("{}.md", sanitize_name(&title))));
+        let html = rewrite_enml(&child_text(*note, "content").unwrap_or_default(), &resources);
+        let mut body = clipboard::html_to_markdown(&html, &mut |src| Some(src.to_string()));
+        let mut unreferenced = Vec::new();
+        for hash in &order {
+            let resource = &resources[hash];
+            let reference = plan.asset(&dest, &resource.name, hash, || Payload::Bytes(resource.data.clone()));
+            let placeholder = format!("{}{}", ENEX_RESOURCE, hash);
+            if body.contains(&placeholder) {
+                body = body.replace(&placeholder, &reference);
+            } else {
+                unreferenced.push(format!("- [{}]({})", resource.name, reference));
+            }
+        }
+
+        let mut text = format!("# {}\n\n", title);
+        let tags: Vec<String> = note
+            .children()
+            .filter(|c| c.has_tag_name("tag"))
+            .filter_map(|c| c.text())
+            .map(|t| format!("#{}", t.trim().replace(char::is_whitespace, "-")))
+            .collect();
+        if !tags.is_empty() {
+            text.push_str(&format!("Tags: {}\n\n", tags.join(" ")));
+        }
+        text.push_str(body.trim());
+        if !unreferenced.is_empty() {
+            text.push_str("\n\n## Attachments\n\n");
+            text.push_str(&unreferenced.join("\n"));
+        }
+        text.push('\n');
+
+        let modified = child_text(*note, "updated")
+            .or_else(|| child_text(*note, "created"))
+            .and_then(|t| enex_time(&t));
+        plan.add_note(dest, title, text, modified);
+    }
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// Command
+// ---------------------------------------------------------------------------
+
+fn detect_format(source: &Path) -> Format {
+    let ext = source.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+    if ext == "enex" {
+        return Format::Enex;
+    }
+    if ext == "zip" {
+        return Format::Notion;
+    }
+    if source.join(".obsidian").is_dir() {
+        return Format::Obsidian;
+    }
+    let notion = walk(source).iter().any(|rel| {
+        let name = file_name(rel);
+        strip_notion_id(&name) != name
+    });
+    if notion {
+        Format::Notion
+    } else {
+        Format::Obsidian
+    }
+}
+
+fn plan_import(
+    format: Format,
+    source: &Path,
+    root: &Path,
+    target: &Path,
+    dry_run: bool,
+    job: &dyn jobs::Progress,
+) -> Result<Plan, String> {
+    let mut plan = Plan::new(root);
+    match format {
+        Format::Obsidian => plan_obsidian(source, target, &mut plan, job)?,
+        Format::Notion => plan_notion(source, target, &mut plan, job)?,
+        Format::Enex => plan_enex(source, target, &mut plan, job)?,
+    }
+    if !dry_run {
+        plan.write(job)?;
+    }
+    Ok(plan)
+}
+
+/// Imports an Obsidian vault, a Notion markdown/CSV export (zip or unpacked folder) or
+/// an Evernote `.enex` file into `target_dir`, which defaults to a folder named after
+/// the source at the workspace root. Links between notes are rewritten to relative
+/// markdown links and attachments are copied into `.xnote_assets`. With `dry_run` the
+/// report lists what would be written without touching the workspace. Runs as an
+/// `import-notes` job and reports through `import-result`; returns the job id.
+#[tauri::command]
+pub fn import_notes(
+    app: AppHandle,
+    source: String,
+    root_path: String,
+    target_dir: Option<String>,
+    format: Option<String>,
+    dry_run: Option<bool>,
+) -> Result<u64, String> {
+    let source_path = site::lexical_normalize(Path::new(&source));
+    if !source_path.exists() {
+        return Err(format!("{} does not exist", source));
+    }
+    let format = match format.as_deref() {
+        Some("obsidian") => Format::Obsidian,
+        Some("notion") => Format::Notion,
+        Some("enex") | Some("evernote") => Format::Enex,
+        Some(other) => return Err(format!("Unknown import format `{}`", other)),
+        None => detect_format(&source_path),
+    };
+    let root = site::lexical_normalize(Path::new(&root_path));
+    let target = match target_dir.filter(|t| !t.trim().is_empty()) {
+        Some(dir) => site::lexical_normalize(Path::new(&dir)),
+        None => {
+            let name = source_path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+            root.join(sanitize_name(&name))
+        }
+    };
+    if !target.starts_with(&root) {
+        return Err("The import folder must be inside the workspace".to_string());
+    }
+    if source_path.is_dir() && target.starts_with(&source_path) {
+        return Err("Cannot import a folder into itself".to_string());
+    }
+    let dry_run = dry_run.unwrap_or(false);
+
+    let app_handle = app.clone();
+    let job = jobs::start(&app, "import-notes");
+    let job_id = job.id();
+    async_runtime::spawn_blocking(move || {
+        let result = plan_import(format, &source_path, &root, &target, dry_run, &job)
+            .map(|plan| plan.report(job.id(), format, &source, &target, dry_run));
+        if job.is_cancelled() {
+            job.finish(jobs::JobStatus::Cancelled, None);
+            return;
+        }
+        if let Ok(report) = &result {
+            let _ = app_handle.emit("import-result", report.clone());
+        }
+        job.finish_with(&result);
+    });
+    Ok(job_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicBool;
+
+    const ID: &str = "0123456789abcdef0123456789ABCDEF";
+
+    #[test]
+    fn notion_ids_are_stripped() {
+        let cases = [
+            (format!("Page {}.md", ID), "Page.md"),
+            (format!("Tasks {}_all.csv", ID), "Tasks_all.csv"),
+            (format!("Folder {}", ID), "Folder"),
+            (format!("Café {}.png", ID), "Café.png"),
+            (format!("{}.md", ID), &*format!("{}.md", ID)),
+            ("Page 0123.md".to_string(), "Page 0123.md"),
+            ("Notes.md".to_string(), "Notes.md"),
+            (".hidden".to_string(), ".hidden"),
+        ];
+        for (name, expected) in &cases {
+            assert_eq!(strip_notion_id(name), *expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn csv_fields_can_hold_quotes_commas_and_line_breaks() {
+        let text = "\u{feff}Name,Notes,Tags\r\n\"Smith, J\",\"said \"\"hi\"\"\r\nthen left\",a\r\n\r\nplain,,\"\"\nlast,row";
+        let rows = parse_csv(text);
+        assert_eq!(
+            rows,
+            vec![
+                vec!["Name", "Notes", "Tags"],
+                vec!["Smith, J", "said \"hi\"\r\nthen left", "a"],
+                vec!["plain", "", ""],
+                vec!["last", "row"],
+            ]
+        );
+        assert_eq!(
+            csv_table("Tasks", text),
+            "# Tasks\n\n| Name | Notes | Tags |\n| --- | --- | --- |\n| Smith, J | said \"hi\"<br>then left | a |\n| plain |  |  |\n| last | row |  |\n"
+        );
+    }
+
+    #[test]
+    fn wikilinks_outside_code_are_replaced() {
+        let convert = |inner: &str, embed: bool| format!("<{}{}>", if embed { "!" } else { "" }, inner);
+        let cases = [
+            ("see [[Note]] and [[Other|alias]]", "see <Note> and <Other|alias>"),
+            ("![[pic.png|300]] ![[pic.png|200x100]]", "<!pic.png|300> <!pic.png|200x100>"),
+            ("`[[code]]` and ``[[more]]``", "`[[code]]` and ``[[more]]``"),
+            ("```\n[[fenced]]\n```\n[[after]]", "```\n[[fenced]]\n```\n<after>"),
+            ("[[]] [[broken\nlink]] [[open", "[[]] [[broken\nlink]] [[open"),
+            ("[[#Heading]]", "<#Heading>"),
+        ];
+        for (content, expected) in cases {
+            assert_eq!(replace_wikilinks(content, convert), expected, "{}", content);
+        }
+        for (alias, size) in [("300", true), ("200x100", true), ("x100", false), ("A cat", false), ("", false)] {
+            assert_eq!(is_size(alias), size, "{}", alias);
+        }
+    }
+
+    #[test]
+    fn enml_media_and_todos_are_rewritten() {
+        let mut resources = HashMap::new();
+        resources.insert("aa11".to_string(), Resource { data: vec![1], mime: "image/png".to_string(), name: "a.png".to_string() });
+        resources.insert("bb22".to_string(), Resource { data: vec![2], mime: "application/pdf".to_string(), name: "R&D.pdf".to_string() });
+        let enml = "<en-note><div><en-todo checked=\"true\"/>done</div><div><en-todo/>open</div>\
+                    <en-media type=\"image/png\" hash=\"AA11\"></en-media><en-media hash='bb22'/>\
+                    <en-media hash=\"ffff\"/><p>kept</p></en-note>";
+        assert_eq!(
+            rewrite_enml(enml, &resources),
+            "<en-note><div>☑ done</div><div>☐ open</div>\
+             <img src=\"enex-resource:aa11\" alt=\"\"><a href=\"enex-resource:bb22\">R&amp;D.pdf</a>\
+             <p>kept</p></en-note>"
+        );
+    }
+
+    #[test]
+    fn notion_links_cannot_reach_outside_the_export() {
+        let temp = tempfile::tempdir().unwrap();
+        let source = temp.path().join("Export");
+        let root = temp.path().join("ws");
+        fs::create_dir_all(source.join(format!("Page {}", ID))).unwrap();
+        fs::create_dir_all(&root).unwrap();
+        fs::write(temp.path().join("secret.txt"), "secret").unwrap();
+        fs::write(source.join(format!("Page {}/a.png", ID)), [1]).unwrap();
+        let page = format!("# Page\n\n![a](Page%20{}/a.png) [s](../secret.txt) [t]({}/secret.txt)\n", ID, temp.path().display());
+        fs::write(source.join(format!("Page {}.md", ID)), page).unwrap();
+
+        let plan = plan_import(Format::Notion, &source, &root, &root.join("Export"), true, &AtomicBool::new(false)).unwrap();
+        let report = plan.report(0, Format::Notion, "Export", &root.join("Export"), true);
+        let paths: Vec<&str> = report.files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(paths, vec![".xnote_assets/Page/a.png", "Export/Page.md"]);
+        assert_eq!(report.warnings.len(), 2, "{:?}", report.warnings);
+    }
+
+    /// An Obsidian vault with an attachment folder, a note in a subfolder and a note name
+    /// that is already taken in the workspace.
+    fn vault(temp: &Path) -> (PathBuf, PathBuf) {
+        let source = temp.join("Vault");
+        let root = temp.join("ws");
+        fs::create_dir_all(source.join(".obsidian")).unwrap();
+        fs::create_dir_all(source.join("attachments")).unwrap();
+        fs::create_dir_all(source.join("Sub")).unwrap();
+        fs::create_dir_all(root.join("Vault")).unwrap();
+        fs::write(source.join(".obsidian/app.json"), r#"{"attachmentFolderPath": "attachments"}"#).unwrap();
+        fs::write(
+            source.join("Index.md"),
+            "See [[Sub/Page#Intro|the page]] and [[Page]].\n\n![[pic.png|300]] ![[pic.png|A cat]] [[doc.pdf|200x100]]\n\n\
+             [md link](Sub/Page.md) `[[Page]]` [[Missing]]\n",
+        )
+        .unwrap();
+        fs::write(source.join("Sub/Page.md"), "## Intro\n\nBack to [[Index]].\n").unwrap();
+        fs::write(source.join("attachments/pic.png"), [1, 2, 3]).unwrap();
+        fs::write(source.join("attachments/doc.pdf"), "pdf").unwrap();
+        fs::write(source.join("attachments/unused.txt"), "unused").unwrap();
+        fs::write(root.join("Vault/Index.md"), "already here").unwrap();
+        (source, root)
+    }
+
+    #[test]
+    fn a_dry_run_reports_without_writing() {
+        let temp = tempfile::tempdir().unwrap();
+        let (source, root) = vault(temp.path());
+        let target = root.join("Vault");
+        let plan = plan_import(Format::Obsidian, &source, &root, &target, true, &AtomicBool::new(false)).unwrap();
+        let report = plan.report(7, Format::Obsidian, "Vault", &target, true);
+
+        let files: Vec<(&str, &str, bool)> = report.files.iter().map(|f| (f.path.as_str(), f.from.as_str(), f.attachment)).collect();
+        assert_eq!(
+            files,
+            vec![
+                (".xnote_assets/Index-1/pic.png", "attachments/pic.png", true),
+                (".xnote_assets/Index-1/doc.pdf", "attachments/doc.pdf", true),
+                ("Vault/Index-1.md", "Index.md", false),
+                ("Vault/Sub/Page.md", "Sub/Page.md", false),
+            ]
+        );
+        let text = |dest: &str| match plan.outputs.iter().find(|o| o.dest == target.join(dest)).map(|o| &o.payload) {
+            Some(Payload::Text(text)) => text.clone(),
+            _ => panic!("no note planned at {}", dest),
+        };
+        assert_eq!(
+            text("Index-1.md"),
+            "See [the page](Sub/Page.md#intro) and [Page](Sub/Page.md).\n\n\
+             ![](/.xnote_assets/Index-1/pic.png) ![A cat](/.xnote_assets/Index-1/pic.png) [doc.pdf](/.xnote_assets/Index-1/doc.pdf)\n\n\
+             [md link](Sub/Page.md) `[[Page]]` Missing\n"
+        );
+        assert_eq!(text("Sub/Page.md"), "## Intro\n\nBack to [Index](../Index-1.md).\n");
+        assert_eq!(
+            report.warnings,
+            vec!["Index.md: no note or file named \"Missing\"", "1 attachment(s) are not referenced by any note and were left out"]
+        );
+        assert_eq!((report.notes, report.attachments, report.dry_run), (2, 2, true));
+        assert_eq!(walk(&root), vec![PathBuf::from("Vault/Index.md")]);
+    }
+}
@@ -0,0 +1,314 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use tauri::{AppHandle, Emitter};
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Running,
+    Completed,
+    Cancelled,
+    Failed,
+}
+
+#[derive(Serialize, Clone)]
+pub struct JobInfo {
+    pub id: u64,
+    pub kind: String,
+    pub status: JobStatus,
+    pub current: usize,
+    pub total: usize,
+    pub message: String,
+}
+
+#[derive(Serialize, Clone)]
+struct JobFinished {
+    id: u64,
+    kind: String,
+    status: JobStatus,
+    error: Option<String>,
+}
+
+struct JobEntry {
+    info: JobInfo,
+    cancel: Arc<AtomicBool>,
+}
+
+struct JobManager {
+    next_id: AtomicU64,
+    jobs: Mutex<HashMap<u64, JobEntry>>,
+}
+
+static JOBS: OnceLock<JobManager> = OnceLock::new();
+
+fn manager() -> &'static JobManager {
+    JOBS.get_or_init(|| JobManager {
+        next_id: AtomicU64::new(1),
+        jobs: Mutex::new(HashMap::new()),
+    })
+}
+
+/// A running background job. Progress is mirrored into the job list and emitted as
+/// `job-progress`; dropping the handle without calling `finish` reports the job as failed.
+pub struct JobHandle {
+    id: u64,
+    kind: String,
+    cancel: Arc<AtomicBool>,
+    app: AppHandle,
+    finished: bool,
+}
+
+pub fn start(app: &AppHandle, kind: &str) -> JobHandle {
+    let (info, cancel) = register(kind);
+    let id = info.id;
+    let _ = app.emit("job-started", info);
+
+    JobHandle {
+        id,
+        kind: kind.to_string(),
+        cancel,
+        app: app.clone(),
+        finished: false,
+    }
+}
+
+/// Adds a running job to the list, returning it with its cancellation flag.
+fn register(kind: &str) -> (JobInfo, Arc<AtomicBool>) {
+    let mgr = manager();
+    let id = mgr.next_id.fetch_add(1, Ordering::Relaxed);
+    let cancel = Arc::new(AtomicBool::new(false));
+    let info = JobInfo {
+        id,
+        kind: kind.to_string(),
+        status: JobStatus::Running,
+        current: 0,
+        total: 0,
+        message: String::new(),
+    };
+    mgr.jobs.lock().unwrap().insert(id, JobEntry { info: info.clone(), cancel: cancel.clone() });
+    (info, cancel)
+}
+
+/// Records progress of a listed job; `None` once it has finished.
+fn update(id: u64, current: usize, total: usize, message: &str) -> Option<JobInfo> {
+    let mut jobs = manager().jobs.lock().unwrap();
+    let entry = jobs.get_mut(&id)?;
+    entry.info.current = current;
+    entry.info.total = total;
+    entry.info.message = message.to_string();
+    Some(entry.info.clone())
+}
+
+fn unregister(id: u64) {
+    manager().jobs.lock().unwrap().remove(&id);
+}
+
+/// The status a job ends with for its result; a successful run that was asked to stop
+/// counts as cancelled.
+fn final_status<T>(result: &Result<T, String>, cancelled: bool) -> (JobStatus, Option<String>) {
+    match result {
+        Ok(_) if cancelled => (JobStatus::Cancelled, None),
+        Ok(_) => (JobStatus::Completed, None),
+        Err(err) => (JobStatus::Failed, Some(err.clone())),
+    }
+}
+
+impl JobHandle {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn app(&self) -> &AppHandle {
+        &self.app
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.load(Ordering::Relaxed)
+    }
+
+    pub fn progress(&self, current: usize, total: usize, message: &str) {
+        if let Some(info) = update(self.id, current, total, message) {
+            let _ = self.app.emit("job-progress", info);
+        }
+    }
+
+    pub fn finish(mut self, status: JobStatus, error: Option<String>) {
+        self.report(status, error);
+    }
+
+    /// Finishes the job from a result, marking it cancelled if cancellation was requested.
+    pub fn finish_with<T>(self, result: &Result<T, String>) {
+        let (status, error) = final_status(result, self.is_cancelled());
+        self.finish(status, error);
+    }
+
+    fn report(&mut self, status: JobStatus, error: Option<String>) {
+        self.finished = true;
+        unregister(self.id);
+        let _ = self.app.emit(
+            "job-finished",
+            JobFinished {
+                id: self.id,
+                kind: self.kind.clone(),
+                status,
+                error,
+            },
+        );
+    }
+}
+
+/// What long-running work needs from its job, so that it can also run without an app.
+pub trait Progress {
+    fn is_cancelled(&self) -> bool;
+    fn progress(&self, current: usize, total: usize, message: &str);
+}
+
+impl Progress for JobHandle {
+    fn is_cancelled(&self) -> bool {
+        JobHandle::is_cancelled(self)
+    }
+
+    fn progress(&self, current: usize, total: usize, message: &str) {
+        JobHandle::progress(self, current, total, message)
+    }
+}
+
+/// A bare cancellation flag, for running work in tests; progress goes nowhere.
+#[cfg(test)]
+impl Progress for AtomicBool {
+    fn is_cancelled(&self) -> bool {
+        self.load(Ordering::Relaxed)
+    }
+
+    fn progress(&self, _current: usize, _total: usize, _message: &str) {}
+}
+
+impl Drop for JobHandle {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.report(JobStatus::Failed, Some("job ended unexpectedly".to_string()));
+        }
+    }
+}
+
+pub fn cancel(id: u64) -> bool {
+    let jobs = manager().jobs.lock().unwrap();
+    match jobs.get(&id) {
+        Some(entry) => {
+            entry.cancel.store(true, Ordering::Relaxed);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Requests cancellation of every active job of the given kinds; returns whether any was found.
+pub fn cancel_kinds(kinds: &[&str]) -> bool {
+    let jobs = manager().jobs.lock().unwrap();
+    let mut found = false;
+    for entry in jobs.values() {
+        if kinds.contains(&entry.info.kind.as_str()) {
+            entry.cancel.store(true, Ordering::Relaxed);
+            found = true;
+        }
+    }
+    found
+}
+
+pub fn list() -> Vec<JobInfo> {
+    let jobs = manager().jobs.lock().unwrap();
+    let mut out: Vec<JobInfo> = jobs.values().map(|e| e.info.clone()).collect();
+    out.sort_by_key(|j| j.id);
+    out
+}
+
+#[tauri::command]
+pub fn list_jobs() -> Result<Vec<JobInfo>, String> {
+    Ok(list())
+}
+
+#[tauri::command]
+pub fn cancel_job(id: u64) -> Result<bool, String> {
+    Ok(cancel(id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The registry is shared by tests running in parallel, so each test uses its own kinds
+    // and only looks at the jobs it registered.
+    fn listed(id: u64) -> Option<JobInfo> {
+        list().into_iter().find(|job| job.id == id)
+    }
+
+    #[test]
+    fn started_jobs_are_listed_in_order() {
+        let (first, _) = register("test-list");
+        let (second, _) = register("test-list");
+        assert!(second.id > first.id);
+        let ids: Vec<u64> = list().iter().map(|job| job.id).filter(|id| [first.id, second.id].contains(id)).collect();
+        assert_eq!(ids, vec![first.id, second.id]);
+        let job = listed(first.id).unwrap();
+        assert_eq!((job.kind.as_str(), job.status, job.current, job.total), ("test-list", JobStatus::Running, 0, 0));
+        unregister(first.id);
+        unregister(second.id);
+    }
+
+    #[test]
+    fn progress_is_mirrored_until_finished() {
+        let (info, _) = register("test-progress");
+        let updated = update(info.id, 3, 10, "copying").unwrap();
+        assert_eq!((updated.current, updated.total, updated.message.as_str()), (3, 10, "copying"));
+        assert_eq!(listed(info.id).unwrap().message, "copying");
+
+        unregister(info.id);
+        assert!(listed(info.id).is_none());
+        assert!(update(info.id, 4, 10, "late").is_none());
+    }
+
+    #[test]
+    fn cancel_flags_only_the_requested_job() {
+        let (a, a_flag) = register("test-cancel");
+        let (b, b_flag) = register("test-cancel");
+        assert!(cancel(a.id));
+        assert!(a_flag.load(Ordering::Relaxed));
+        assert!(!b_flag.load(Ordering::Relaxed));
+
+        unregister(a.id);
+        unregister(b.id);
+        assert!(!cancel(a.id), "finished jobs cannot be cancelled");
+    }
+
+    #[test]
+    fn cancel_kinds_flags_matching_jobs() {
+        let (scan, scan_flag) = register("test-kinds-scan");
+        let (delete, delete_flag) = register("test-kinds-delete");
+        let (other, other_flag) = register("test-kinds-other");
+        assert!(cancel_kinds(&["test-kinds-scan", "test-kinds-delete"]));
+        assert!(scan_flag.load(Ordering::Relaxed));
+        assert!(delete_flag.load(Ordering::Relaxed));
+        assert!(!other_flag.load(Ordering::Relaxed));
+        for id in [scan.id, delete.id, other.id] {
+            unregister(id);
+        }
+        assert!(!cancel_kinds(&["test-kinds-scan"]));
+    }
+
+    #[test]
+    fn final_status_follows_result_and_cancellation() {
+        let ok: Result<(), String> = Ok(());
+        let err: Result<(), String> = Err("disk full".to_string());
+        let cases = [
+            (&ok, false, (JobStatus::Completed, None)),
+            (&ok, true, (JobStatus::Cancelled, None)),
+            (&err, false, (JobStatus::Failed, Some("disk full".to_string()))),
+            (&err, true, (JobStatus::Failed, Some("disk full".to_string()))),
+        ];
+        for (result, cancelled, expected) in cases {
+            assert_eq!(final_status(result, cancelled), expected, "{result:?} cancelled={cancelled}");
+        }
+    }
+}
@@ -8,28 +8,33 @@ use std::io::Write;
 use walkdir::WalkDir;
 use std::collections::HashSet;
 use tauri::async_runtime;
-use std::sync::{mpsc, Arc, Mutex, OnceLock};
+use std::sync::mpsc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use tauri::Manager;
-use portable_pty::{CommandBuilder, NativePtySystem, PtySize, PtySystem};
-use std::collections::HashMap;
-use std::io::Read;
-
-struct TerminalSession {
-    pty_master: Box<dyn portable_pty::MasterPty + Send>,
-    writer: Box<dyn std::io::Write + Send>,
-}
-
-struct TerminalState {
-    sessions: Arc<Mutex<HashMap<String, TerminalSession>>>,
-}
-
-impl TerminalState {
-    fn new() -> Self {
-        Self {
-            sessions: Arc::new(Mutex::new(HashMap::new())),
-        }
-    }
-}
+use markdown::trim_wrapping;
+
+mod backup;
+mod clipboard;
+mod config;
+mod config_watch;
+mod diagram;
+mod encryption;
+mod epub;
+mod export;
+mod import;
+mod jobs;
+mod llm;
+mod markdown;
+mod quarantine;
+mod raster;
+mod runner;
+mod secrets;
+mod site;
+mod sync;
+mod terminal;
+mod terminal_output;
+mod terminal_recording;
+mod webdav;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct FileNode {
@@ -38,17 +43,13 @@ pub struct FileNode {
     is_dir: bool,
     children: Option<Vec<FileNode>>,
     last_modified: Option<String>,
+    /// An encrypted note, or a folder whose notes are encrypted.
+    encrypted: bool,
 }
 
 use tauri::{AppHandle, Emitter};
 use tauri::menu::{Menu, Submenu, MenuItem, PredefinedMenuItem};
 
-#[derive(Serialize, Clone)]
-struct TerminalOutputChunk {
-    encoding: &'static str,
-    data: String,
-}
-
 #[tauri::command]
 fn get_default_workspace(_app: AppHandle) -> Result<String, String> {
     println!("Backend: get_default_workspace called");
@@ -106,140 +107,16 @@ fn get_xnote_root() -> Result<PathBuf, String> {
 
 #[tauri::command]
 fn get_config() -> Result<String, String> {
-    let root = get_xnote_root()?;
-    let config_path = root.join("config.json");
-    
-    if config_path.exists() {
-        fs::read_to_string(config_path).map_err(|e| e.to_string())
-    } else {
-        Ok("{}".to_string())
-    }
+    config::load_for_ui()
 }
 
 #[tauri::command]
-fn save_config(config: String) -> Result<(), String> {
-    let root = get_xnote_root()?;
-    let config_path = root.join("config.json");
-    
-    // Ensure parent dir exists
-    if let Some(parent) = config_path.parent() {
-        if !parent.exists() {
-             fs::create_dir_all(parent).map_err(|e| e.to_string())?;
-        }
+fn save_config(app: AppHandle, config: String, base_revision: Option<String>) -> Result<config::ConfigSaved, String> {
+    let (saved, changed) = config::save(&config, base_revision.as_deref())?;
+    if let Some(changed) = changed {
+        let _ = app.emit("config-changed", changed);
     }
-
-    fs::write(config_path, config).map_err(|e| e.to_string())
-}
-
-#[tauri::command]
-fn create_terminal(
-    app: AppHandle,
-    state: tauri::State<'_, TerminalState>,
-    id: String,
-    cwd: Option<String>,
-) -> Result<(), String> {
-    let pty_system = NativePtySystem::default();
-    let size = PtySize {
-        rows: 24,
-        cols: 80,
-        pixel_width: 0,
-        pixel_height: 0,
-    };
-
-    let pair = pty_system.openpty(size).map_err(|e| e.to_string())?;
-
-    let mut cmd = CommandBuilder::new("zsh");
-    if let Some(path) = cwd {
-        if Path::new(&path).exists() {
-             cmd.cwd(path);
-        }
-    }
-    // Set some env vars if needed
-    cmd.env("TERM", "xterm-256color");
-    cmd.env("LANG", "en_US.UTF-8");
-    cmd.env("LC_ALL", "en_US.UTF-8");
-    cmd.env("LC_CTYPE", "en_US.UTF-8");
-
-    let _child = pair.slave.spawn_command(cmd).map_err(|e| e.to_string())?;
-
-    let mut reader = pair.master.try_clone_reader().map_err(|e| e.to_string())?;
-    let writer = pair.master.take_writer().map_err(|e| e.to_string())?;
-
-    // Store session
-    {
-        let mut sessions = state.sessions.lock().unwrap();
-        sessions.insert(id.clone(), TerminalSession {
-             pty_master: pair.master,
-             writer,
-        });
-    }
-
-    let app_handle = app.clone();
-    let session_id = id.clone();
-    
-    // Spawn reader thread
-    std::thread::spawn(move || {
-        let mut buffer = [0u8; 1024];
-        loop {
-            match reader.read(&mut buffer) {
-                Ok(n) if n > 0 => {
-                     let payload = TerminalOutputChunk {
-                         encoding: "base64",
-                         data: general_purpose::STANDARD.encode(&buffer[..n]),
-                     };
-                     let _ = app_handle.emit(&format!("terminal-output:{}", session_id), payload);
-                }
-                Ok(_) => break, // EOF
-                Err(_) => break,
-            }
-        }
-        // Emit exit?
-        let _ = app_handle.emit(&format!("terminal-exit:{}", session_id), ());
-    });
-
-    Ok(())
-}
-
-#[tauri::command]
-fn write_to_terminal(
-    state: tauri::State<'_, TerminalState>,
-    id: String,
-    data: String,
-) -> Result<(), String> {
-    let mut sessions = state.sessions.lock().unwrap();
-    if let Some(session) = sessions.get_mut(&id) {
-        session.writer.write_all(data.as_bytes()).map_err(|e| e.to_string())?;
-    }
-    Ok(())
-}
-
-#[tauri::command]
-fn resize_terminal(
-    state: tauri::State<'_, TerminalState>,
-    id: String,
-    rows: u16,
-    cols: u16,
-) -> Result<(), String> {
-    let mut sessions = state.sessions.lock().unwrap();
-    if let Some(session) = sessions.get_mut(&id) {
-        session.pty_master.resize(PtySize {
-            rows,
-            cols,
-            pixel_width: 0,
-            pixel_height: 0,
-        }).map_err(|e| e.to_string())?;
-    }
-    Ok(())
-}
-
-#[tauri::command]
-fn close_terminal(
-    state: tauri::State<'_, TerminalState>,
-    id: String,
-) -> Result<(), String> {
-    let mut sessions = state.sessions.lock().unwrap();
-    sessions.remove(&id);
-    Ok(())
+    Ok(saved)
 }
 
 #[tauri::command]
@@ -257,7 +134,7 @@ fn get_files(path: String) -> Result<Vec<FileNode>, String> {
         name.ends_with(".md") || name.ends_with(".uml") || name.ends_with(".puml")
     }
 
-    fn sort_nodes(nodes: &mut Vec<FileNode>) {
+    fn sort_nodes(nodes: &mut [FileNode]) {
         nodes.sort_by(|a, b| {
             if a.is_dir == b.is_dir {
                 a.name.cmp(&b.name)
@@ -283,12 +160,18 @@ fn get_files(path: String) -> Result<Vec<FileNode>, String> {
                 continue;
             }
 
+            let encrypted = if is_dir {
+                path_buf.join(encryption::FOLDER_MARKER).is_file()
+            } else {
+                encryption::is_encrypted(&path_buf)
+            };
             let mut node = FileNode {
                 name: name.clone(),
                 path: path_buf.to_string_lossy().to_string(),
                 is_dir,
                 children: None,
                 last_modified: None,
+                encrypted,
             };
 
             if is_dir {
@@ -325,12 +208,12 @@ fn get_files(path: String) -> Result<Vec<FileNode>, String> {
 
 #[tauri::command]
 fn read_file(path: String) -> Result<String, String> {
-    fs::read_to_string(path).map_err(|e| e.to_string())
+    encryption::read_note(Path::new(&path))
 }
 
 #[tauri::command]
 fn save_file(path: String, content: String) -> Result<(), String> {
-    fs::write(path, content).map_err(|e| e.to_string())
+    encryption::write_note(Path::new(&path), &content)
 }
 
 #[tauri::command]
@@ -346,7 +229,8 @@ fn create_note(dir_path: String, filename: String) -> Result<String, String> {
         return Err("File already exists".to_string());
     }
 
-    match fs::write(&full_path, "") {
+    // Encrypted from the start when created in an encrypted folder.
+    match encryption::write_note(&full_path, "") {
         Ok(_) => {
             println!("Backend: File created successfully: {:?}", full_path);
             Ok(full_path.to_string_lossy().to_string())
@@ -388,53 +272,49 @@ fn save_image(img_data_base64: String, save_dir: String) -> Result<String, Strin
         .decode(raw_data)
         .map_err(|e| e.to_string())?;
 
-    let filename = format!("img_{}.png", chrono::Local::now().format("%Y%m%d%H%M%S%f"));
-    let path = Path::new(&save_dir).join(&filename);
-    
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    store_image(&bytes, Path::new(&save_dir), "png")
+}
+
+/// Writes image bytes into `save_dir` as `img_<timestamp>.<ext>` and returns the file name.
+fn store_image(bytes: &[u8], save_dir: &Path, ext: &str) -> Result<String, String> {
+    fs::create_dir_all(save_dir).map_err(|e| e.to_string())?;
+
+    let stamp = chrono::Local::now().format("%Y%m%d%H%M%S%f").to_string();
+    let mut filename = format!("img_{}.{}", stamp, ext);
+    let mut n = 1;
+    while save_dir.join(&filename).exists() {
+        filename = format!("img_{}_{}.{}", stamp, n, ext);
+        n += 1;
     }
 
-    let mut file = fs::File::create(&path).map_err(|e| e.to_string())?;
-    file.write_all(&bytes).map_err(|e| e.to_string())?;
+    let mut file = fs::File::create(save_dir.join(&filename)).map_err(|e| e.to_string())?;
+    file.write_all(bytes).map_err(|e| e.to_string())?;
 
     Ok(filename)
 }
 
-#[tauri::command]
-fn read_file_base64(path: String) -> Result<String, String> {
+fn file_base64(path: &Path) -> Result<String, String> {
     let bytes = fs::read(path).map_err(|e| e.to_string())?;
     Ok(general_purpose::STANDARD.encode(bytes))
 }
 
 #[tauri::command]
-fn set_clipboard_image(app: AppHandle, png_data_base64: String) -> Result<(), String> {
-    let data_start = png_data_base64.find(",").map(|i| i + 1).unwrap_or(0);
-    let raw_data = &png_data_base64[data_start..];
-    let bytes = general_purpose::STANDARD
-        .decode(raw_data)
-        .map_err(|e| format!("base64 decode failed: {}", e))?;
-
-    let img = image::load_from_memory(&bytes).map_err(|e| format!("image decode failed: {}", e))?;
-    let rgba = img.to_rgba8();
-    let (width, height) = rgba.dimensions();
-    let data = rgba.into_raw();
+fn read_file_base64(path: String) -> Result<String, String> {
+    file_base64(Path::new(&path))
+}
 
-    let (tx, rx) = mpsc::channel::<Result<(), String>>();
+/// Runs `f` against the system clipboard on the main thread, which macOS requires.
+fn with_clipboard<T, F>(app: &AppHandle, f: F) -> Result<T, String>
+where
+    T: Send + 'static,
+    F: FnOnce(&mut arboard::Clipboard) -> Result<T, String> + Send + 'static,
+{
+    let (tx, rx) = mpsc::channel::<Result<T, String>>();
 
     app.run_on_main_thread(move || {
-        let result = (|| -> Result<(), String> {
-            let mut clipboard =
-                arboard::Clipboard::new().map_err(|e| format!("clipboard init failed: {}", e))?;
-            clipboard
-                .set_image(arboard::ImageData {
-                    width: width as usize,
-                    height: height as usize,
-                    bytes: Cow::Owned(data),
-                })
-                .map_err(|e| format!("clipboard set_image failed: {}", e))?;
-            Ok(())
-        })();
+        let result = arboard::Clipboard::new()
+            .map_err(|e| format!("clipboard init failed: {}", e))
+            .and_then(|mut clipboard| f(&mut clipboard));
         let _ = tx.send(result);
     })
     .map_err(|e| format!("run_on_main_thread failed: {}", e))?;
@@ -443,58 +323,48 @@ fn set_clipboard_image(app: AppHandle, png_data_base64: String) -> Result<(), St
         .map_err(|e| format!("clipboard result receive failed: {}", e))?
 }
 
-#[tauri::command]
-fn set_clipboard_image_from_svg(app: AppHandle, svg_text: String) -> Result<(), String> {
-    let mut fontdb = resvg::usvg::fontdb::Database::new();
-    fontdb.load_system_fonts();
-    let mut opt = resvg::usvg::Options::default();
-    opt.fontdb = Arc::new(fontdb);
-
-    let tree = resvg::usvg::Tree::from_str(&svg_text, &opt)
-        .map_err(|e| format!("svg parse failed: {}", e))?;
-
-    let size = tree.size();
-    let width = size.width().ceil() as u32;
-    let height = size.height().ceil() as u32;
-    if width == 0 || height == 0 {
-        return Err("svg size is zero".to_string());
-    }
-
-    let dpr: f32 = 2.0;
-    let out_width = ((width as f32) * dpr).ceil() as u32;
-    let out_height = ((height as f32) * dpr).ceil() as u32;
+fn set_clipboard_rgba(app: &AppHandle, width: usize, height: usize, data: Vec<u8>) -> Result<(), String> {
+    with_clipboard(app, move |clipboard| {
+        clipboard
+            .set_image(arboard::ImageData {
+                width,
+                height,
+                bytes: Cow::Owned(data),
+            })
+            .map_err(|e| format!("clipboard set_image failed: {}", e))
+    })
+}
 
-    let mut pixmap = resvg::tiny_skia::Pixmap::new(out_width, out_height)
-        .ok_or_else(|| "pixmap alloc failed".to_string())?;
-    let transform = resvg::usvg::Transform::from_scale(dpr, dpr);
-    let mut pixmap_mut = pixmap.as_mut();
-    resvg::render(&tree, transform, &mut pixmap_mut);
+fn set_clipboard_html(app: &AppHandle, html: String, text: String) -> Result<(), String> {
+    with_clipboard(app, move |clipboard| {
+        clipboard
+            .set_html(html, Some(text))
+            .map_err(|e| format!("clipboard set_html failed: {}", e))
+    })
+}
 
-    let data = pixmap.data().to_vec();
+#[tauri::command]
+fn set_clipboard_image(app: AppHandle, png_data_base64: String) -> Result<(), String> {
+    let data_start = png_data_base64.find(",").map(|i| i + 1).unwrap_or(0);
+    let raw_data = &png_data_base64[data_start..];
+    let bytes = general_purpose::STANDARD
+        .decode(raw_data)
+        .map_err(|e| format!("base64 decode failed: {}", e))?;
 
-    let (tx, rx) = mpsc::channel::<Result<(), String>>();
-    app.run_on_main_thread(move || {
-        let result = (|| -> Result<(), String> {
-            let mut clipboard =
-                arboard::Clipboard::new().map_err(|e| format!("clipboard init failed: {}", e))?;
-            clipboard
-                .set_image(arboard::ImageData {
-                    width: out_width as usize,
-                    height: out_height as usize,
-                    bytes: Cow::Owned(data),
-                })
-                .map_err(|e| format!("clipboard set_image failed: {}", e))?;
-            Ok(())
-        })();
-        let _ = tx.send(result);
-    })
-    .map_err(|e| format!("run_on_main_thread failed: {}", e))?;
+    let img = image::load_from_memory(&bytes).map_err(|e| format!("image decode failed: {}", e))?;
+    let rgba = img.to_rgba8();
+    let (width, height) = rgba.dimensions();
+    set_clipboard_rgba(&app, width as usize, height as usize, rgba.into_raw())
+}
 
-    rx.recv()
-        .map_err(|e| format!("clipboard result receive failed: {}", e))?
+#[tauri::command]
+fn set_clipboard_image_from_svg(app: AppHandle, svg_text: String) -> Result<(), String> {
+    let pixmap = raster::rasterize(&svg_text, raster::DEFAULT_SCALE)?;
+    let (width, height) = (pixmap.width() as usize, pixmap.height() as usize);
+    set_clipboard_rgba(&app, width, height, pixmap.take())
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Clone)]
 struct SearchHit {
     path: String,
     name: String,
@@ -502,8 +372,33 @@ struct SearchHit {
     preview: String,
 }
 
+#[derive(Serialize, Clone)]
+struct SearchTextResult {
+    job_id: u64,
+    hits: Vec<SearchHit>,
+}
+
 #[tauri::command]
 fn search_text(root_path: String, query: String, limit: Option<usize>) -> Result<Vec<SearchHit>, String> {
+    search_files(&root_path, &query, limit, None)
+}
+
+#[tauri::command]
+fn start_search_text(app: AppHandle, root_path: String, query: String, limit: Option<usize>) -> Result<u64, String> {
+    let app_handle = app.clone();
+    let job = jobs::start(&app, "search");
+    let job_id = job.id();
+    async_runtime::spawn_blocking(move || {
+        let result = search_files(&root_path, &query, limit, Some(&job));
+        if let Ok(hits) = &result {
+            let _ = app_handle.emit("search-text-result", SearchTextResult { job_id, hits: hits.clone() });
+        }
+        job.finish_with(&result);
+    });
+    Ok(job_id)
+}
+
+fn search_files(root_path: &str, query: &str, limit: Option<usize>, job: Option<&jobs::JobHandle>) -> Result<Vec<SearchHit>, String> {
     let q = query.trim();
     if q.is_empty() {
         return Ok(vec![]);
@@ -513,10 +408,13 @@ fn search_text(root_path: String, query: String, limit: Option<usize>) -> Result
     let max_hits = limit.unwrap_or(50).min(200);
 
     let mut results: Vec<SearchHit> = Vec::new();
-    for entry in WalkDir::new(&root_path).into_iter().filter_map(|e| e.ok()) {
+    for entry in WalkDir::new(root_path).into_iter().filter_map(|e| e.ok()) {
         if results.len() >= max_hits {
             break;
         }
+        if job.map(|j| j.is_cancelled()).unwrap_or(false) {
+            break;
+        }
 
         if entry.file_type().is_dir() {
             let name = entry.file_name().to_string_lossy();
@@ -542,9 +440,10 @@ fn search_text(root_path: String, query: String, limit: Option<usize>) -> Result
             .unwrap_or("")
             .to_string();
 
-        let content = match fs::read_to_string(path) {
-            Ok(c) => c,
-            Err(_) => continue,
+        // Encrypted notes are only searched while unlocked.
+        let content = match encryption::searchable_text(path) {
+            Some(c) => c,
+            None => continue,
         };
 
         for (idx, line) in content.lines().enumerate() {
@@ -587,45 +486,16 @@ struct CleanResult {
 struct DeleteResult {
     deleted: usize,
     total: usize,
-}
-
-struct CleanTaskController {
-    job_id: u64,
-    cancel: bool,
-}
-static CLEAN_TASK: OnceLock<Mutex<CleanTaskController>> = OnceLock::new();
-
-fn clean_task() -> &'static Mutex<CleanTaskController> {
-    CLEAN_TASK.get_or_init(|| Mutex::new(CleanTaskController { job_id: 0, cancel: false }))
-}
-
-fn start_new_job() -> u64 {
-    let mut guard = clean_task().lock().unwrap();
-    guard.job_id += 1;
-    guard.cancel = false;
-    guard.job_id
-}
-
-fn request_cancel() -> bool {
-    let mut guard = clean_task().lock().unwrap();
-    if guard.job_id == 0 {
-        return false;
-    }
-    guard.cancel = true;
-    true
-}
-
-fn is_cancelled(job_id: u64) -> bool {
-    let guard = clean_task().lock().unwrap();
-    guard.job_id == job_id && guard.cancel
+    quarantine: Option<String>,
 }
 
 fn emit_clean_log(app: &AppHandle, message: &str) {
     let _ = app.emit("clean-unused-images-log", message.to_string());
 }
 
-fn emit_clean_progress(app: &AppHandle, phase: &str, current: usize, total: usize, message: String) {
-    let _ = app.emit(
+fn emit_clean_progress(job: &jobs::JobHandle, phase: &str, current: usize, total: usize, message: String) {
+    job.progress(current, total, &message);
+    let _ = job.app().emit(
         "clean-unused-images-progress",
         CleanProgress {
             phase: phase.to_string(),
@@ -644,69 +514,6 @@ fn is_text_ext(ext: &str) -> bool {
     matches!(ext, "md" | "txt" | "uml" | "puml")
 }
 
-fn trim_wrapping(s: &str) -> &str {
-    let mut out = s.trim();
-    if (out.starts_with('<') && out.ends_with('>')) || (out.starts_with('"') && out.ends_with('"')) || (out.starts_with('\'') && out.ends_with('\'')) {
-        out = &out[1..out.len().saturating_sub(1)];
-    }
-    out.trim()
-}
-
-fn extract_candidate_paths(content: &str) -> Vec<String> {
-    let mut out: Vec<String> = Vec::new();
-    let bytes = content.as_bytes();
-
-    let mut i = 0usize;
-    while i + 2 < bytes.len() {
-        if bytes[i] == b']' && bytes[i + 1] == b'(' {
-            let start = i + 2;
-            if let Some(end_rel) = content[start..].find(')') {
-                let raw = &content[start..start + end_rel];
-                let raw = raw.split_whitespace().next().unwrap_or(raw);
-                let raw = trim_wrapping(raw);
-                if !raw.is_empty() {
-                    out.push(raw.to_string());
-                }
-                i = start + end_rel + 1;
-                continue;
-            }
-        }
-        i += 1;
-    }
-
-    let mut j = 0usize;
-    let lower = content.to_lowercase();
-    while let Some(pos) = lower[j..].find("src=") {
-        let at = j + pos + 4;
-        if at >= content.len() {
-            break;
-        }
-        let rest = &content[at..];
-        let rest = rest.trim_start();
-        let quote = rest.chars().next().unwrap_or('"');
-        if quote == '"' || quote == '\'' {
-            if let Some(end) = rest[1..].find(quote) {
-                let raw = &rest[1..1 + end];
-                let raw = trim_wrapping(raw);
-                if !raw.is_empty() {
-                    out.push(raw.to_string());
-                }
-                j = at + 1 + end + 1;
-                continue;
-            }
-        } else {
-            let raw = rest.split_whitespace().next().unwrap_or("");
-            let raw = trim_wrapping(raw);
-            if !raw.is_empty() {
-                out.push(raw.to_string());
-            }
-        }
-        j = at + 4;
-    }
-
-    out
-}
-
 fn normalize_ref_path(root_path: &Path, file_path: &Path, raw: &str) -> Option<PathBuf> {
     let r = raw.trim();
     if r.is_empty() {
@@ -737,17 +544,59 @@ fn normalize_ref_path(root_path: &Path, file_path: &Path, raw: &str) -> Option<P
     Some(p)
 }
 
-fn compute_unused_images(app: &AppHandle, root_path: &str, job_id: u64) -> Result<Vec<String>, String> {
+fn looks_like_image_ref(raw: &str) -> bool {
+    let raw_lower = raw.to_lowercase();
+    if raw_lower.contains("://") {
+        return false;
+    }
+    raw_lower.contains("/.xnote_assets/")
+        || raw_lower.ends_with(".png")
+        || raw_lower.ends_with(".jpg")
+        || raw_lower.ends_with(".jpeg")
+        || raw_lower.ends_with(".gif")
+        || raw_lower.ends_with(".webp")
+        || raw_lower.ends_with(".bmp")
+        || raw_lower.ends_with(".svg")
+}
+
+fn collect_file_refs(root: &Path, file_path: &Path, referenced: &mut HashSet<String>) {
+    let content = match encryption::searchable_text(file_path) {
+        Some(c) => c,
+        None => return,
+    };
+    let ext = file_path
+        .extension()
+        .and_then(|s| s.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+    let is_plantuml = ext == "puml" || ext == "uml";
+    for raw in markdown::extract_candidate_paths(&content, is_plantuml) {
+        if !looks_like_image_ref(&raw) {
+            continue;
+        }
+        if let Some(p) = normalize_ref_path(root, file_path, &raw) {
+            let canon = fs::canonicalize(&p).unwrap_or(p);
+            referenced.insert(canon.to_string_lossy().to_string());
+        }
+    }
+}
+
+const MAX_SCAN_WORKERS: usize = 8;
+
+fn compute_unused_images(app: &AppHandle, root_path: &str, job: &jobs::JobHandle) -> Result<Vec<String>, String> {
     let root = PathBuf::from(root_path);
     if !root.exists() {
         return Err("Workspace path does not exist".to_string());
     }
 
     emit_clean_log(app, &format!("Clean: scanning images under {}", root_path));
-    emit_clean_progress(app, "collect_images", 0, 0, "Collecting images…".to_string());
+    emit_clean_progress(job, "collect_images", 0, 0, "Collecting images…".to_string());
 
+    // Single walk: images are collected everywhere (including .xnote_assets),
+    // text files everywhere except inside .xnote_assets.
     let mut images: Vec<String> = Vec::new();
     let mut image_set: HashSet<String> = HashSet::new();
+    let mut text_files: Vec<PathBuf> = Vec::new();
 
     let mut scanned_entries = 0usize;
     for entry in WalkDir::new(&root)
@@ -769,15 +618,15 @@ fn compute_unused_images(app: &AppHandle, root_path: &str, job_id: u64) -> Resul
         })
         .filter_map(|e| e.ok())
     {
-        if is_cancelled(job_id) {
+        if job.is_cancelled() {
             emit_clean_log(app, "Clean: cancelled");
-            emit_clean_progress(app, "cancelled", scanned_entries, 0, "Cancelled".to_string());
+            emit_clean_progress(job, "cancelled", scanned_entries, 0, "Cancelled".to_string());
             return Ok(vec![]);
         }
         scanned_entries += 1;
-        if scanned_entries % 100 == 0 {
+        if scanned_entries.is_multiple_of(100) {
             emit_clean_progress(
-                app,
+                job,
                 "collect_images",
                 scanned_entries,
                 0,
@@ -795,109 +644,106 @@ fn compute_unused_images(app: &AppHandle, root_path: &str, job_id: u64) -> Resul
             .and_then(|s| s.to_str())
             .unwrap_or("")
             .to_lowercase();
-        if !is_image_ext(&ext) {
-            continue;
-        }
 
-        let canonical = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
-        let s = canonical.to_string_lossy().to_string();
-        if image_set.insert(s.clone()) {
-            images.push(s);
+        if is_image_ext(&ext) {
+            let canonical = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+            let s = canonical.to_string_lossy().to_string();
+            if image_set.insert(s.clone()) {
+                images.push(s);
+            }
+        } else if is_text_ext(&ext) {
+            let in_assets = path
+                .strip_prefix(&root)
+                .map(|rel| rel.components().any(|c| c.as_os_str() == ".xnote_assets"))
+                .unwrap_or(false);
+            if !in_assets {
+                text_files.push(path.to_path_buf());
+            }
         }
     }
 
     emit_clean_log(app, &format!("Clean: collected {} images", image_set.len()));
-    emit_clean_progress(app, "scan_refs", 0, 0, "Scanning references…".to_string());
-
-    let mut text_files: Vec<PathBuf> = Vec::new();
-    for entry in WalkDir::new(&root)
-        .into_iter()
-        .filter_entry(|e| {
-            if e.depth() == 0 {
-                return true;
-            }
-            if e.file_type().is_dir() {
-                let name = e.file_name().to_string_lossy();
-                if name == ".xnote_assets" {
-                    return false;
-                }
-                if name.starts_with('.') {
-                    return false;
-                }
-            }
-            true
-        })
-        .filter_map(|e| e.ok())
-    {
-        if is_cancelled(job_id) {
-            emit_clean_log(app, "Clean: cancelled");
-            emit_clean_progress(app, "cancelled", 0, 0, "Cancelled".to_string());
-            return Ok(vec![]);
-        }
-        if entry.file_type().is_dir() {
-            continue;
-        }
-        let path = entry.path();
-        let ext = path
-            .extension()
-            .and_then(|s| s.to_str())
-            .unwrap_or("")
-            .to_lowercase();
-        if !is_text_ext(&ext) {
-            continue;
-        }
-        text_files.push(path.to_path_buf());
+    // Images used only by a locked note would look unused.
+    if text_files.iter().any(|p| encryption::is_locked(p)) {
+        return Err("Unlock encrypted notes first; their images cannot be checked while locked".to_string());
     }
+    emit_clean_progress(job, "scan_refs", 0, 0, "Scanning references…".to_string());
 
     let total_files = text_files.len().max(1);
-    emit_clean_log(app, &format!("Clean: scanning {} text files for references", text_files.len()));
-
-    let mut referenced: HashSet<String> = HashSet::new();
+    let workers = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+        .clamp(1, MAX_SCAN_WORKERS)
+        .min(text_files.len().max(1));
+    emit_clean_log(
+        app,
+        &format!("Clean: scanning {} text files for references ({} workers)", text_files.len(), workers),
+    );
 
-    for (idx, file_path) in text_files.iter().enumerate() {
-        if is_cancelled(job_id) {
-            emit_clean_log(app, "Clean: cancelled");
-            emit_clean_progress(app, "cancelled", idx, total_files, "Cancelled".to_string());
-            return Ok(vec![]);
+    // Workers pull the next file index from a shared counter so slow (e.g. network-mounted)
+    // files don't hold up a whole pre-assigned chunk.
+    let next_file = AtomicUsize::new(0);
+    let done_files = AtomicUsize::new(0);
+    let cancelled = AtomicBool::new(false);
+
+    let referenced: Result<HashSet<String>, String> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut local: HashSet<String> = HashSet::new();
+                    loop {
+                        if cancelled.load(Ordering::Relaxed) {
+                            break;
+                        }
+                        if job.is_cancelled() {
+                            cancelled.store(true, Ordering::Relaxed);
+                            break;
+                        }
+                        let idx = next_file.fetch_add(1, Ordering::Relaxed);
+                        let Some(file_path) = text_files.get(idx) else { break };
+
+                        collect_file_refs(&root, file_path, &mut local);
+
+                        let done = done_files.fetch_add(1, Ordering::Relaxed) + 1;
+                        if done.is_multiple_of(10) {
+                            emit_clean_progress(
+                                job,
+                                "scan_refs",
+                                done,
+                                total_files,
+                                format!("Scanning references… ({}/{})", done, total_files),
+                            );
+                        }
+                    }
+                    local
+                })
+            })
+            .collect();
+
+        // A worker that panicked took its references with it; going on would report the
+        // images only it had seen as unused.
+        let mut merged: HashSet<String> = HashSet::new();
+        let mut failed = false;
+        for handle in handles {
+            match handle.join() {
+                Ok(local) => merged.extend(local),
+                Err(_) => failed = true,
+            }
         }
-        if idx % 10 == 0 {
-            emit_clean_progress(
-                app,
-                "scan_refs",
-                idx,
-                total_files,
-                format!("Scanning references… ({}/{})", idx, total_files),
-            );
+        if failed {
+            return Err("Scanning notes for image references failed; nothing was marked as unused".to_string());
         }
+        Ok(merged)
+    });
+    let referenced = referenced?;
 
-        let content = match fs::read_to_string(file_path) {
-            Ok(c) => c,
-            Err(_) => continue,
-        };
-        for raw in extract_candidate_paths(&content) {
-            let raw_lower = raw.to_lowercase();
-            if raw_lower.contains("://") {
-                continue;
-            }
-            if !(raw_lower.contains("/.xnote_assets/")
-                || raw_lower.ends_with(".png")
-                || raw_lower.ends_with(".jpg")
-                || raw_lower.ends_with(".jpeg")
-                || raw_lower.ends_with(".gif")
-                || raw_lower.ends_with(".webp")
-                || raw_lower.ends_with(".bmp")
-                || raw_lower.ends_with(".svg"))
-            {
-                continue;
-            }
-            if let Some(p) = normalize_ref_path(&root, file_path, &raw) {
-                let canon = fs::canonicalize(&p).unwrap_or(p);
-                referenced.insert(canon.to_string_lossy().to_string());
-            }
-        }
+    if cancelled.load(Ordering::Relaxed) {
+        emit_clean_log(app, "Clean: cancelled");
+        emit_clean_progress(job, "cancelled", done_files.load(Ordering::Relaxed), total_files, "Cancelled".to_string());
+        return Ok(vec![]);
     }
 
-    emit_clean_progress(app, "compute", 1, 1, "Computing unused images…".to_string());
+    emit_clean_progress(job, "compute", 1, 1, "Computing unused images…".to_string());
 
     let unused: Vec<String> = images
         .into_iter()
@@ -905,16 +751,17 @@ fn compute_unused_images(app: &AppHandle, root_path: &str, job_id: u64) -> Resul
         .collect();
 
     emit_clean_log(app, &format!("Clean: found {} unused images", unused.len()));
-    emit_clean_progress(app, "done", 1, 1, "Done".to_string());
+    emit_clean_progress(job, "done", 1, 1, "Done".to_string());
 
     Ok(unused)
 }
 
 #[tauri::command]
 fn find_unused_images(app: AppHandle, root_path: String) -> Result<UnusedImageResult, String> {
-    let job_id = start_new_job();
-    let images = compute_unused_images(&app, &root_path, job_id)?;
-    Ok(UnusedImageResult { images })
+    let job = jobs::start(&app, "clean-scan");
+    let result = compute_unused_images(&app, &root_path, &job);
+    job.finish_with(&result);
+    Ok(UnusedImageResult { images: result? })
 }
 
 #[tauri::command]
@@ -932,94 +779,111 @@ fn delete_path(path: String) -> Result<(), String> {
     }
 }
 
-#[tauri::command]
-fn delete_files(app: AppHandle, root_path: String, paths: Vec<String>) -> Result<usize, String> {
-    let root = fs::canonicalize(&root_path).unwrap_or_else(|_| PathBuf::from(&root_path));
+fn quarantine_files(app: &AppHandle, root_path: &str, paths: &[String], job: &jobs::JobHandle) -> Result<DeleteResult, String> {
+    let root = fs::canonicalize(root_path).unwrap_or_else(|_| PathBuf::from(root_path));
     let total = paths.len().max(1);
     let mut deleted = 0usize;
-    let job_id = start_new_job();
+    let mut batch = quarantine::QuarantineBatch::create(&root)?;
+
+    emit_clean_log(app, &format!("Clean: moving images to {}", batch.dir().display()));
 
     for (idx, p) in paths.iter().enumerate() {
-        if is_cancelled(job_id) {
-            emit_clean_log(&app, "Clean: cancelled");
-            emit_clean_progress(&app, "cancelled", idx, total, "Cancelled".to_string());
-            return Ok(deleted);
+        if job.is_cancelled() {
+            emit_clean_log(app, "Clean: cancelled");
+            emit_clean_progress(job, "cancelled", idx, total, "Cancelled".to_string());
+            let quarantine = batch.finish()?;
+            return Ok(DeleteResult { deleted, total: paths.len(), quarantine });
         }
         if idx % 5 == 0 {
-            let _ = app.emit(
-                "clean-unused-images-progress",
-                CleanProgress {
-                    phase: "delete".to_string(),
-                    current: idx,
-                    total,
-                    message: format!("Deleting… ({}/{})", idx, total),
-                },
-            );
+            emit_clean_progress(job, "delete", idx, total, format!("Deleting… ({}/{})", idx, total));
         }
 
         let candidate = fs::canonicalize(p).unwrap_or_else(|_| PathBuf::from(p));
-        if !candidate.starts_with(&root) {
+        if !candidate.starts_with(&root) || candidate.starts_with(root.join(quarantine::QUARANTINE_DIR)) {
             continue;
         }
         if candidate.is_file() {
-            if fs::remove_file(&candidate).is_ok() {
-                deleted += 1;
-            }
-            if let Some(parent) = candidate.parent() {
-                if parent.read_dir().map(|mut it| it.next().is_none()).unwrap_or(false) {
-                    let _ = fs::remove_dir(parent);
-                }
+            match batch.add(&root, &candidate) {
+                Ok(()) => deleted += 1,
+                Err(err) => emit_clean_log(app, &format!("Clean: failed to move {}: {}", candidate.display(), err)),
             }
         }
     }
 
-    let _ = app.emit(
-        "clean-unused-images-progress",
-        CleanProgress {
-            phase: "done".to_string(),
-            current: 1,
-            total: 1,
-            message: "Done".to_string(),
-        },
+    let quarantine = batch.finish()?;
+    emit_clean_progress(job, "done", 1, 1, "Done".to_string());
+
+    Ok(DeleteResult { deleted, total: paths.len(), quarantine })
+}
+
+#[tauri::command]
+fn delete_files(app: AppHandle, root_path: String, paths: Vec<String>) -> Result<usize, String> {
+    let job = jobs::start(&app, "clean-delete");
+    let result = quarantine_files(&app, &root_path, &paths, &job);
+    job.finish_with(&result);
+    result.map(|r| r.deleted)
+}
+
+#[tauri::command]
+fn undo_last_clean(app: AppHandle, root_path: String) -> Result<quarantine::UndoResult, String> {
+    let root = fs::canonicalize(&root_path).unwrap_or_else(|_| PathBuf::from(&root_path));
+    let result = quarantine::undo_last(&root)?;
+    emit_clean_log(
+        &app,
+        &format!("Clean: restored {} images, {} skipped", result.restored, result.skipped.len()),
     );
+    Ok(result)
+}
 
-    Ok(deleted)
+#[tauri::command]
+fn purge_quarantine(root_path: String, older_than_days: Option<i64>) -> Result<usize, String> {
+    let root = fs::canonicalize(&root_path).unwrap_or_else(|_| PathBuf::from(&root_path));
+    quarantine::purge(&root, older_than_days)
 }
 
 #[tauri::command]
-fn start_find_unused_images_scan(app: AppHandle, root_path: String) -> Result<(), String> {
+fn start_find_unused_images_scan(app: AppHandle, root_path: String) -> Result<u64, String> {
     let app_handle = app.clone();
-    let job_id = start_new_job();
+    let job = jobs::start(&app, "clean-scan");
+    let job_id = job.id();
     async_runtime::spawn_blocking(move || {
-        match compute_unused_images(&app_handle, &root_path, job_id) {
+        let result = compute_unused_images(&app_handle, &root_path, &job);
+        match &result {
             Ok(images) => {
-                let _ = app_handle.emit("clean-unused-images-result", CleanResult { images });
+                let _ = app_handle.emit("clean-unused-images-result", CleanResult { images: images.clone() });
             }
             Err(err) => {
                 emit_clean_log(&app_handle, &format!("Clean: error: {}", err));
-                emit_clean_progress(&app_handle, "error", 1, 1, "Error".to_string());
+                emit_clean_progress(&job, "error", 1, 1, "Error".to_string());
             }
         }
+        job.finish_with(&result);
     });
-    Ok(())
+    Ok(job_id)
 }
 
 #[tauri::command]
-fn start_delete_unused_images(app: AppHandle, root_path: String, paths: Vec<String>) -> Result<(), String> {
+fn start_delete_unused_images(app: AppHandle, root_path: String, paths: Vec<String>) -> Result<u64, String> {
     let app_handle = app.clone();
+    let job = jobs::start(&app, "clean-delete");
+    let job_id = job.id();
     async_runtime::spawn_blocking(move || {
         emit_clean_log(&app_handle, "Clean: deleting unused images…");
-        let total = paths.len();
-        let deleted = delete_files(app_handle.clone(), root_path, paths).unwrap_or(0);
-        emit_clean_log(&app_handle, &format!("Clean: deleted {} / {}", deleted, total));
-        let _ = app_handle.emit("clean-unused-images-delete-result", DeleteResult { deleted, total });
+        let result = quarantine_files(&app_handle, &root_path, &paths, &job);
+        let summary = result.clone().unwrap_or_else(|err| {
+            emit_clean_log(&app_handle, &format!("Clean: error: {}", err));
+            DeleteResult { deleted: 0, total: paths.len(), quarantine: None }
+        });
+        emit_clean_log(&app_handle, &format!("Clean: deleted {} / {}", summary.deleted, summary.total));
+        let _ = app_handle.emit("clean-unused-images-delete-result", summary);
+        job.finish_with(&result);
     });
-    Ok(())
+    Ok(job_id)
 }
 
 #[tauri::command]
 fn cancel_clean_unused_images() -> Result<bool, String> {
-    Ok(request_cancel())
+    Ok(jobs::cancel_kinds(&["clean-scan", "clean-delete"]))
 }
 
 #[tauri::command]
@@ -1090,7 +954,9 @@ pub fn run() {
 
             app.set_menu(menu)?;
 
-            app.manage(TerminalState::new());
+            app.manage(terminal::TerminalState::new());
+            config_watch::start(handle.clone());
+            backup::start(handle.clone());
 
             Ok(())
         })
@@ -1115,9 +981,23 @@ pub fn run() {
             create_folder,
             save_image,
             read_file_base64,
+            export::export_note_html,
+            export::copy_note_as_rich_text,
+            epub::export_epub,
+            site::build_site,
+            import::import_notes,
+            backup::create_backup,
+            backup::list_backups,
+            backup::restore_backup,
+            sync::sync_workspace,
             set_clipboard_image,
             set_clipboard_image_from_svg,
+            clipboard::paste_clipboard_markdown,
+            raster::render_svg,
+            diagram::render_diagram,
+            diagram::copy_diagram_image,
             search_text,
+            start_search_text,
             find_unused_images,
             get_default_workspace,
             move_path,
@@ -1126,14 +1006,50 @@ pub fn run() {
             start_find_unused_images_scan,
             start_delete_unused_images,
             cancel_clean_unused_images,
+            undo_last_clean,
+            purge_quarantine,
+            config::check_config,
+            jobs::list_jobs,
+            jobs::cancel_job,
+            llm::chat_stream,
             copy_file,
             get_config,
             save_config,
-            create_terminal,
-            write_to_terminal,
-            resize_terminal,
-            close_terminal
+            runner::run_code_block,
+            secrets::secret_store_status,
+            secrets::unlock_secret_store,
+            secrets::lock_secret_store,
+            secrets::set_secret_store_passphrase,
+            secrets::list_secrets,
+            secrets::set_secret,
+            secrets::get_secret,
+            secrets::delete_secret,
+            encryption::encryption_status,
+            encryption::unlock_encryption,
+            encryption::lock_encryption,
+            encryption::set_encryption_passphrase,
+            encryption::encrypt_path,
+            encryption::decrypt_path,
+            terminal::list_terminal_profiles,
+            terminal::list_terminals,
+            terminal::attach_terminal,
+            terminal::ack_terminal_output,
+            terminal::create_terminal,
+            terminal::write_to_terminal,
+            terminal::resize_terminal,
+            terminal::close_terminal,
+            terminal::start_terminal_recording,
+            terminal::stop_terminal_recording,
+            terminal::terminal_recording_to_markdown,
+            terminal::insert_terminal_recording,
+            terminal::list_terminal_snippets,
+            terminal::send_to_terminal
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app_handle, event| {
+            if let tauri::RunEvent::Exit = event {
+                app_handle.state::<terminal::TerminalState>().shutdown();
+            }
+        });
 }
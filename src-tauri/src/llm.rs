@@ -0,0 +1,707 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::time::Duration;
+use tauri::{async_runtime, AppHandle, Emitter};
+use tauri_plugin_http::reqwest::{self, header, RequestBuilder, Response, StatusCode, Url};
+
+use crate::config::{self, LlmConfigEntry};
+use crate::jobs::{self, JobStatus, Progress};
+use crate::secrets;
+
+const OPENAI_BASE_URL: &str = "https://api.openai.com/v1";
+const OLLAMA_BASE_URL: &str = "http://localhost:11434";
+/// Attempts per request; later ones are only made for errors that may go away by themselves.
+const MAX_ATTEMPTS: u32 = 3;
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+/// Upper bound for waiting between attempts, including what a `Retry-After` header asks for.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(20);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(15);
+/// How long a response may go without sending anything. Models that think before answering
+/// can be quiet for a while, so this is generous.
+const READ_TIMEOUT: Duration = Duration::from_secs(120);
+/// How often a request that is waiting on the server checks whether it was cancelled.
+const CANCEL_POLL: Duration = Duration::from_millis(100);
+/// Longest server error text included in an error message.
+const MAX_ERROR_TEXT: usize = 500;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ChatMessage {
+    pub role: String,
+    pub content: String,
+}
+
+/// Payload of the `llm-delta` event: text to append to the reply of job `job_id`.
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct LlmDelta {
+    job_id: u64,
+    delta: String,
+}
+
+/// Payload of the `llm-result` event, sent once a reply is complete.
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct LlmResult {
+    job_id: u64,
+    content: String,
+}
+
+/// What one line of a streamed response contributed.
+#[derive(Default)]
+struct StreamLine {
+    delta: String,
+    /// The server marked the reply as complete.
+    done: bool,
+}
+
+/// The wire format of a chat API. Requests are always streamed; the response is read
+/// line by line.
+trait Provider {
+    fn chat_request(&self, client: &reqwest::Client, messages: &[ChatMessage]) -> RequestBuilder;
+    fn parse_line(&self, line: &str) -> Result<StreamLine, String>;
+}
+
+/// The `/chat/completions` API of OpenAI and the many services that copy it, streamed
+/// as server-sent events.
+struct OpenAiCompatible {
+    base_url: String,
+    api_key: String,
+    model: String,
+}
+
+impl OpenAiCompatible {
+    /// Appends `/chat/completions` to the base URL unless it already names an endpoint
+    /// (a path with `/chat/`, ending in `/completions`, or a `/api/coding` endpoint).
+    /// Query parameters, as Azure OpenAI uses, are kept.
+    fn chat_url(&self) -> String {
+        let (path, query) = match self.base_url.split_once('?') {
+            Some((path, query)) => (path, Some(query)),
+            None => (self.base_url.as_str(), None),
+        };
+        let mut path = path.trim_end_matches('/').to_string();
+        if !path.contains("/chat/") && !path.ends_with("/completions") && !path.contains("/api/coding") {
+            path.push_str("/chat/completions");
+        }
+        match query {
+            Some(query) => format!("{}?{}", path, query),
+            None => path,
+        }
+    }
+
+    fn image_url(&self) -> String {
+        let url = self.base_url.trim_end_matches('/');
+        if url.ends_with("/images/generations") {
+            return url.to_string();
+        }
+        let url = url.strip_suffix("/chat/completions").unwrap_or(url).trim_end_matches('/');
+        format!("{}/images/generations", url)
+    }
+
+    fn authorize(&self, request: RequestBuilder) -> RequestBuilder {
+        if self.api_key.is_empty() {
+            request
+        } else {
+            request.bearer_auth(&self.api_key)
+        }
+    }
+
+    fn image_request(&self, client: &reqwest::Client, prompt: &str) -> RequestBuilder {
+        let url = self.image_url();
+        let body = if url.contains("volces.com") {
+            // Volcengine takes its own options and rejects OpenAI's `n`.
+            json!({
+                "model": self.model,
+                "prompt": prompt,
+                "sequential_image_generation": "disabled",
+                "response_format": "url",
+                "size": "2K",
+                "stream": false,
+                "watermark": false,
+            })
+        } else {
+            json!({
+                "model": self.model,
+                "prompt": prompt,
+                "n": 1,
+                "size": "1024x1024",
+                "response_format": "url",
+            })
+        };
+        self.authorize(client.post(url)).json(&body)
+    }
+}
+
+impl Provider for OpenAiCompatible {
+    fn chat_request(&self, client: &reqwest::Client, messages: &[ChatMessage]) -> RequestBuilder {
+        let body = json!({ "model": self.model, "messages": messages, "stream": true });
+        self.authorize(client.post(self.chat_url())).json(&body)
+    }
+
+    fn parse_line(&self, line: &str) -> Result<StreamLine, String> {
+        // Comments (`: keep-alive`), `event:` and `id:` lines carry nothing we use.
+        let Some(data) = line.strip_prefix("data:").map(str::trim) else {
+            return Ok(StreamLine::default());
+        };
+        if data == "[DONE]" {
+            return Ok(StreamLine { delta: String::new(), done: true });
+        }
+        let value: Value = serde_json::from_str(data).map_err(|e| format!("Invalid response from the model: {}", e))?;
+        if let Some(error) = value.get("error") {
+            return Err(format!("The model reported an error: {}", error_message(error)));
+        }
+        let delta = value
+            .pointer("/choices/0/delta/content")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        Ok(StreamLine { delta: delta.to_string(), done: false })
+    }
+}
+
+/// Ollama's native `/api/chat`, streamed as one JSON object per line.
+struct Ollama {
+    base_url: String,
+    model: String,
+}
+
+impl Ollama {
+    /// Accepts the server address with or without `/api` or `/api/chat`, and the `/v1`
+    /// address of Ollama's OpenAI-compatible API.
+    fn chat_url(&self) -> String {
+        let url = self.base_url.trim_end_matches('/');
+        if url.ends_with("/api/chat") {
+            return url.to_string();
+        }
+        let url = url.strip_suffix("/api").or_else(|| url.strip_suffix("/v1")).unwrap_or(url);
+        format!("{}/api/chat", url)
+    }
+}
+
+impl Provider for Ollama {
+    fn chat_request(&self, client: &reqwest::Client, messages: &[ChatMessage]) -> RequestBuilder {
+        let body = json!({ "model": self.model, "messages": messages, "stream": true });
+        client.post(self.chat_url()).json(&body)
+    }
+
+    fn parse_line(&self, line: &str) -> Result<StreamLine, String> {
+        let value: Value = serde_json::from_str(line).map_err(|e| format!("Invalid response from the model: {}", e))?;
+        if let Some(error) = value.get("error") {
+            return Err(format!("The model reported an error: {}", error_message(error)));
+        }
+        let delta = value
+            .pointer("/message/content")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        Ok(StreamLine {
+            delta: delta.to_string(),
+            done: value.get("done").and_then(Value::as_bool).unwrap_or(false),
+        })
+    }
+}
+
+/// The `error` of an API response, which is a string or an object with a `message`.
+fn error_message(error: &Value) -> String {
+    error
+        .as_str()
+        .or_else(|| error.get("message").and_then(Value::as_str))
+        .map(str::to_string)
+        .unwrap_or_else(|| error.to_string())
+}
+
+fn resolve_api_key(entry: &LlmConfigEntry) -> Result<String, String> {
+    match secrets::handle_id(&entry.api_key) {
+        Some(id) => secrets::get(id)?
+            .ok_or_else(|| format!("The API key of {} is missing from the secret store; enter it again", entry.name)),
+        None => Ok(entry.api_key.trim().to_string()),
+    }
+}
+
+fn base_url(entry: &LlmConfigEntry, default: &str) -> Result<String, String> {
+    let url = entry.base_url.trim();
+    let url = if url.is_empty() { default } else { url };
+    if url.is_empty() {
+        return Err(format!("{} has no base URL", entry.name));
+    }
+    let parsed = Url::parse(url).map_err(|e| format!("Invalid base URL `{}`: {}", url, e))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(format!("Invalid base URL `{}`: expected http or https", url));
+    }
+    Ok(url.to_string())
+}
+
+fn openai_compatible(entry: &LlmConfigEntry) -> Result<OpenAiCompatible, String> {
+    let default = if entry.provider == "openai" { OPENAI_BASE_URL } else { "" };
+    Ok(OpenAiCompatible {
+        base_url: base_url(entry, default)?,
+        api_key: resolve_api_key(entry)?,
+        model: entry.model_id.trim().to_string(),
+    })
+}
+
+/// The provider for a configured model.
+fn provider_for(entry: &LlmConfigEntry) -> Result<Box<dyn Provider + Send + Sync>, String> {
+    match entry.provider.as_str() {
+        "openai" | "custom" => Ok(Box::new(openai_compatible(entry)?)),
+        "ollama" => Ok(Box::new(Ollama {
+            base_url: base_url(entry, OLLAMA_BASE_URL)?,
+            model: entry.model_id.trim().to_string(),
+        })),
+        other => Err(format!("Provider {} is not supported", other)),
+    }
+}
+
+fn client() -> Result<reqwest::Client, String> {
+    reqwest::Client::builder()
+        .connect_timeout(CONNECT_TIMEOUT)
+        .read_timeout(READ_TIMEOUT)
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+/// Waits for `future`, giving up early when the job is cancelled. `None` means cancelled.
+async fn unless_cancelled<F: std::future::Future>(job: &impl Progress, future: F) -> Option<F::Output> {
+    let mut future = std::pin::pin!(future);
+    loop {
+        if job.is_cancelled() {
+            return None;
+        }
+        if let Ok(output) = tokio::time::timeout(CANCEL_POLL, &mut future).await {
+            return Some(output);
+        }
+    }
+}
+
+fn retryable(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// The wait a `Retry-After: <seconds>` header asks for.
+fn retry_after(response: &Response) -> Option<Duration> {
+    let seconds = response.headers().get(header::RETRY_AFTER)?.to_str().ok()?.trim().parse::<u64>().ok()?;
+    Some(Duration::from_secs(seconds))
+}
+
+async fn status_error(response: Response) -> String {
+    let status = response.status();
+    let text = response.text().await.unwrap_or_default();
+    let message = serde_json::from_str::<Value>(&text)
+        .ok()
+        .and_then(|v| v.get("error").map(error_message))
+        .unwrap_or_else(|| text.trim().chars().take(MAX_ERROR_TEXT).collect());
+    if status == StatusCode::UNAUTHORIZED || status == StatusCode::FORBIDDEN {
+        return format!("The API key was rejected ({}): {}", status, message);
+    }
+    format!("API error {}: {}", status, message)
+}
+
+/// A message for a failed request that names the server but not the full URL, whose query
+/// may hold a key.
+fn request_error(error: reqwest::Error) -> String {
+    let server = error.url().map(|u| u.origin().ascii_serialization()).unwrap_or_default();
+    if error.is_connect() {
+        format!("Could not connect to {}", server)
+    } else if error.is_timeout() {
+        format!("{} did not respond in time", server)
+    } else {
+        format!("Request to {} failed: {}", server, error.without_url())
+    }
+}
+
+/// Sends a request, trying again after connection failures, timeouts, rate limiting and
+/// server errors. Nothing has been shown to the user at that point, so a retry is invisible.
+/// `Ok(None)` means the job was cancelled.
+async fn send(job: &impl Progress, build: impl Fn() -> RequestBuilder) -> Result<Option<Response>, String> {
+    let mut delay = RETRY_DELAY;
+    let mut attempt = 1;
+    loop {
+        let last = attempt == MAX_ATTEMPTS;
+        let Some(result) = unless_cancelled(job, build().send()).await else { return Ok(None) };
+        let wait = match result {
+            Ok(response) if response.status().is_success() => return Ok(Some(response)),
+            Ok(response) if retryable(response.status()) && !last => retry_after(&response).unwrap_or(delay),
+            Ok(response) => return Err(status_error(response).await),
+            Err(e) if (e.is_connect() || e.is_timeout()) && !last => delay,
+            Err(e) => return Err(request_error(e)),
+        };
+        if unless_cancelled(job, tokio::time::sleep(wait.min(MAX_RETRY_DELAY))).await.is_none() {
+            return Ok(None);
+        }
+        delay *= 2;
+        attempt += 1;
+    }
+}
+
+/// Streams a chat reply, passing each piece to `on_delta`. Returns the whole reply, or
+/// `None` if the job was cancelled.
+async fn stream_chat(
+    job: &impl Progress,
+    client: &reqwest::Client,
+    provider: &(dyn Provider + Send + Sync),
+    messages: &[ChatMessage],
+    on_delta: impl Fn(String),
+) -> Result<Option<String>, String> {
+    let Some(mut response) = send(job, || provider.chat_request(client, messages)).await? else {
+        return Ok(None);
+    };
+    let mut content = String::new();
+    // Bytes of a line that has not ended yet; a chunk may also end inside a UTF-8 sequence.
+    let mut pending: Vec<u8> = Vec::new();
+    loop {
+        let Some(chunk) = unless_cancelled(job, response.chunk()).await else { return Ok(None) };
+        let chunk = chunk.map_err(|e| {
+            if e.is_timeout() {
+                request_error(e)
+            } else {
+                format!("Reading the reply failed: {}", e.without_url())
+            }
+        })?;
+        let finished = chunk.is_none();
+        match chunk {
+            Some(bytes) => pending.extend_from_slice(&bytes),
+            None => pending.push(b'\n'),
+        }
+        let mut delta = String::new();
+        let mut done = false;
+        while let Some(end) = pending.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = pending.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let parsed = provider.parse_line(line)?;
+            delta.push_str(&parsed.delta);
+            if parsed.done {
+                done = true;
+                break;
+            }
+        }
+        if !delta.is_empty() {
+            content.push_str(&delta);
+            on_delta(delta);
+        }
+        if done || finished {
+            return Ok(Some(content));
+        }
+    }
+}
+
+/// Generates an image from the last user message; the reply is Markdown showing it.
+async fn generate_image(
+    job: &impl Progress,
+    client: &reqwest::Client,
+    entry: &LlmConfigEntry,
+    messages: &[ChatMessage],
+    on_delta: impl Fn(String),
+) -> Result<Option<String>, String> {
+    if entry.provider == "ollama" {
+        return Err("Image generation is not supported with Ollama".to_string());
+    }
+    let prompt = messages
+        .iter()
+        .rev()
+        .find(|m| m.role == "user")
+        .map(|m| m.content.clone())
+        .ok_or("No user message to use as the image prompt")?;
+    let provider = openai_compatible(entry)?;
+    let Some(response) = send(job, || provider.image_request(client, &prompt)).await? else {
+        return Ok(None);
+    };
+    let Some(body) = unless_cancelled(job, response.json::<Value>()).await else { return Ok(None) };
+    let body = body.map_err(|e| format!("Invalid response from the image API: {}", e))?;
+    let url = body
+        .pointer("/data/0/url")
+        .and_then(Value::as_str)
+        .ok_or("The image API returned no image URL")?;
+    let markdown = format!("![Generated Image]({})", url);
+    on_delta(markdown.clone());
+    Ok(Some(markdown))
+}
+
+fn find_config(config_id: &str) -> Result<LlmConfigEntry, String> {
+    config::load()?
+        .config
+        .llm
+        .configs
+        .into_iter()
+        .find(|c| c.id == config_id)
+        .ok_or_else(|| "This model is no longer configured; choose another one in Settings".to_string())
+}
+
+/// Sends a conversation to the configured model `config_id` and streams the reply as
+/// `llm-delta` events, ending with `llm-result`. API keys are read from the secret store
+/// here and never reach the webview. Cancel with `cancel_job`. Returns the job id.
+#[tauri::command]
+pub fn chat_stream(app: AppHandle, config_id: String, messages: Vec<ChatMessage>) -> Result<u64, String> {
+    let entry = find_config(&config_id)?;
+    if messages.is_empty() {
+        return Err("Nothing to send".to_string());
+    }
+    let image = entry.kind.as_deref() == Some("image");
+    let provider = if image { None } else { Some(provider_for(&entry)?) };
+    let client = client()?;
+
+    let job = jobs::start(&app, "llm");
+    let job_id = job.id();
+    async_runtime::spawn(async move {
+        let on_delta = |delta| {
+            let _ = app.emit("llm-delta", LlmDelta { job_id, delta });
+        };
+        let result = match &provider {
+            Some(provider) => stream_chat(&job, &client, provider.as_ref(), &messages, on_delta).await,
+            None => generate_image(&job, &client, &entry, &messages, on_delta).await,
+        };
+        match result {
+            Ok(None) => job.finish(JobStatus::Cancelled, None),
+            Ok(Some(content)) => {
+                let _ = app.emit("llm-result", LlmResult { job_id, content });
+                job.finish(JobStatus::Completed, None);
+            }
+            Err(err) => job.finish(JobStatus::Failed, Some(err)),
+        }
+    });
+    Ok(job_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Instant;
+
+    /// One scripted response; the body is sent as separate chunks with a pause between them.
+    struct Reply {
+        status: &'static str,
+        headers: Vec<(&'static str, &'static str)>,
+        chunks: Vec<Vec<u8>>,
+        pause: Duration,
+    }
+
+    fn reply(status: &'static str, chunks: &[&[u8]]) -> Reply {
+        Reply { status, headers: Vec::new(), chunks: chunks.iter().map(|c| c.to_vec()).collect(), pause: Duration::from_millis(5) }
+    }
+
+    /// A server on localhost that answers requests with `replies` in turn and records each
+    /// request's path and `Authorization` header.
+    struct Server {
+        url: String,
+        requests: Arc<Mutex<Vec<(String, String)>>>,
+    }
+
+    impl Server {
+        fn start(replies: Vec<Reply>) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let url = format!("http://{}", listener.local_addr().unwrap());
+            let requests = Arc::new(Mutex::new(Vec::new()));
+            let seen = requests.clone();
+            std::thread::spawn(move || {
+                let mut replies = replies.into_iter();
+                for stream in listener.incoming() {
+                    let (Ok(stream), Some(reply)) = (stream, replies.next()) else { break };
+                    let seen = seen.clone();
+                    std::thread::spawn(move || answer(stream, reply, &seen));
+                }
+            });
+            Server { url, requests }
+        }
+
+        fn requests(&self) -> Vec<(String, String)> {
+            self.requests.lock().unwrap().clone()
+        }
+    }
+
+    fn answer(mut stream: std::net::TcpStream, reply: Reply, seen: &Mutex<Vec<(String, String)>>) {
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        let path = line.split_whitespace().nth(1).unwrap_or("").to_string();
+        let (mut length, mut auth) = (0, String::new());
+        loop {
+            let mut header = String::new();
+            reader.read_line(&mut header).unwrap();
+            let Some((name, value)) = header.trim_end().split_once(':') else { break };
+            match name.to_lowercase().as_str() {
+                "content-length" => length = value.trim().parse().unwrap(),
+                "authorization" => auth = value.trim().to_string(),
+                _ => {}
+            }
+        }
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).unwrap();
+        seen.lock().unwrap().push((path, auth));
+
+        let mut head = format!("HTTP/1.1 {}\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n", reply.status);
+        for (name, value) in &reply.headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        head.push_str("\r\n");
+        if stream.write_all(head.as_bytes()).is_err() {
+            return;
+        }
+        for (i, chunk) in reply.chunks.iter().enumerate() {
+            if i > 0 {
+                std::thread::sleep(reply.pause);
+            }
+            let sent = stream
+                .write_all(format!("{:x}\r\n", chunk.len()).as_bytes())
+                .and_then(|_| stream.write_all(chunk))
+                .and_then(|_| stream.write_all(b"\r\n"))
+                .and_then(|_| stream.flush());
+            if sent.is_err() {
+                return;
+            }
+        }
+        let _ = stream.write_all(b"0\r\n\r\n");
+    }
+
+    fn openai(url: &str) -> OpenAiCompatible {
+        OpenAiCompatible { base_url: format!("{}/v1", url), api_key: "sk-test".to_string(), model: "gpt".to_string() }
+    }
+
+    fn ollama(url: &str) -> Ollama {
+        Ollama { base_url: url.to_string(), model: "llama".to_string() }
+    }
+
+    /// Streams a reply; returns the result and the pieces passed on as they arrived.
+    fn chat(provider: &(dyn Provider + Send + Sync), job: &AtomicBool) -> (Result<Option<String>, String>, Vec<String>) {
+        let deltas = Mutex::new(Vec::new());
+        let messages = [ChatMessage { role: "user".to_string(), content: "hi".to_string() }];
+        let result = async_runtime::block_on(stream_chat(job, &client().unwrap(), provider, &messages, |delta| {
+            deltas.lock().unwrap().push(delta)
+        }));
+        (result, deltas.into_inner().unwrap())
+    }
+
+    #[test]
+    fn server_sent_events_split_anywhere() {
+        // "é" is 0xC3 0xA9; the second chunk ends between the two.
+        let server = Server::start(vec![reply(
+            "200 OK",
+            &[
+                b": keep-alive\n\ndata: {\"choices\":[{\"delta\":{\"content\":\"Caf\"}}]}\n\nda",
+                b"ta: {\"choices\":[{\"delta\":{\"content\":\"\xC3",
+                b"\xA9\"}}]}\n\nevent: x\ndata: {\"choices\":[{\"delta\":{}}]}\n\n",
+                b"data: {\"choices\":[{\"delta\":{\"content\":\"!\"}}]}",
+            ],
+        )]);
+        let (result, deltas) = chat(&openai(&server.url), &AtomicBool::new(false));
+        assert_eq!(result.unwrap().as_deref(), Some("Café!"));
+        assert_eq!(deltas, vec!["Caf", "é", "!"]);
+        assert_eq!(server.requests(), vec![("/v1/chat/completions".to_string(), "Bearer sk-test".to_string())]);
+    }
+
+    #[test]
+    fn done_ends_the_reply() {
+        let server = Server::start(vec![reply(
+            "200 OK",
+            &[b"data: {\"choices\":[{\"delta\":{\"content\":\"one\"}}]}\n\ndata: [DONE]\n\ndata: {\"choices\":[{\"delta\":{\"content\":\"two\"}}]}\n\n"],
+        )]);
+        let (result, _) = chat(&openai(&server.url), &AtomicBool::new(false));
+        assert_eq!(result.unwrap().as_deref(), Some("one"));
+    }
+
+    #[test]
+    fn ollama_streams_json_lines() {
+        let server = Server::start(vec![reply(
+            "200 OK",
+            &[
+                b"{\"message\":{\"role\":\"assistant\",\"content\":\"Hel\"},\"done\":false}\n{\"message\":{\"content\":\"lo\"},",
+                b"\"done\":false}\n{\"message\":{\"content\":\"\"},\"done\":true}\n",
+            ],
+        )]);
+        let (result, deltas) = chat(&ollama(&server.url), &AtomicBool::new(false));
+        assert_eq!(result.unwrap().as_deref(), Some("Hello"));
+        assert_eq!(deltas, vec!["Hel", "lo"]);
+        assert_eq!(server.requests()[0].0, "/api/chat");
+
+        let server = Server::start(vec![reply("200 OK", &[b"{\"error\":\"model \\\"llama\\\" not found\"}\n"])]);
+        let (result, _) = chat(&ollama(&server.url), &AtomicBool::new(false));
+        assert_eq!(result.unwrap_err(), "The model reported an error: model \"llama\" not found");
+    }
+
+    #[test]
+    fn rate_limits_are_retried_after_the_requested_wait() {
+        let mut limited = reply("429 Too Many Requests", &[b"{\"error\":{\"message\":\"slow down\"}}"]);
+        limited.headers.push(("Retry-After", "0"));
+        let server = Server::start(vec![limited, reply("200 OK", &[b"data: {\"choices\":[{\"delta\":{\"content\":\"ok\"}}]}\n\n"])]);
+        let started = Instant::now();
+        let (result, _) = chat(&openai(&server.url), &AtomicBool::new(false));
+        assert_eq!(result.unwrap().as_deref(), Some("ok"));
+        assert_eq!(server.requests().len(), 2);
+        // Without the header the wait would have been RETRY_DELAY.
+        assert!(started.elapsed() < RETRY_DELAY, "{:?}", started.elapsed());
+    }
+
+    #[test]
+    fn a_rejected_key_is_reported_without_retrying() {
+        let server = Server::start(vec![
+            reply("401 Unauthorized", &[b"{\"error\":{\"message\":\"Incorrect API key provided\"}}"]),
+            reply("200 OK", &[]),
+        ]);
+        let (result, _) = chat(&openai(&server.url), &AtomicBool::new(false));
+        assert_eq!(result.unwrap_err(), "The API key was rejected (401 Unauthorized): Incorrect API key provided");
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[test]
+    fn cancelling_stops_the_stream() {
+        let mut slow = reply(
+            "200 OK",
+            &[b"data: {\"choices\":[{\"delta\":{\"content\":\"first\"}}]}\n\n", b"data: {\"choices\":[{\"delta\":{\"content\":\"late\"}}]}\n\n"],
+        );
+        slow.pause = Duration::from_secs(3);
+        let server = Server::start(vec![slow]);
+        let provider = openai(&server.url);
+        let job = AtomicBool::new(false);
+        let messages = [ChatMessage { role: "user".to_string(), content: "hi".to_string() }];
+        let started = Instant::now();
+        let result = async_runtime::block_on(stream_chat(&job, &client().unwrap(), &provider, &messages, |delta| {
+            assert_eq!(delta, "first");
+            job.store(true, Ordering::Relaxed);
+        }));
+        assert_eq!(result, Ok(None));
+        assert!(started.elapsed() < Duration::from_secs(2), "{:?}", started.elapsed());
+    }
+
+    #[test]
+    fn openai_compatible_chat_urls() {
+        let cases = [
+            ("https://api.openai.com/v1", "https://api.openai.com/v1/chat/completions"),
+            ("https://api.openai.com/v1/", "https://api.openai.com/v1/chat/completions"),
+            ("https://api.openai.com/v1/chat/completions", "https://api.openai.com/v1/chat/completions"),
+            ("https://api.deepseek.com", "https://api.deepseek.com/chat/completions"),
+            ("https://host/openai/v1/completions", "https://host/openai/v1/completions"),
+            ("https://ark.cn-beijing.volces.com/api/coding/v3", "https://ark.cn-beijing.volces.com/api/coding/v3"),
+            (
+                "https://x.openai.azure.com/openai/deployments/gpt?api-version=2024-06-01",
+                "https://x.openai.azure.com/openai/deployments/gpt/chat/completions?api-version=2024-06-01",
+            ),
+            (
+                "https://x.openai.azure.com/openai/deployments/gpt/chat/completions/?api-version=1",
+                "https://x.openai.azure.com/openai/deployments/gpt/chat/completions?api-version=1",
+            ),
+        ];
+        for (base_url, expected) in cases {
+            let provider = OpenAiCompatible { base_url: base_url.to_string(), api_key: String::new(), model: String::new() };
+            assert_eq!(provider.chat_url(), expected, "{}", base_url);
+        }
+    }
+
+    #[test]
+    fn ollama_chat_urls() {
+        let cases = [
+            ("http://localhost:11434", "http://localhost:11434/api/chat"),
+            ("http://localhost:11434/", "http://localhost:11434/api/chat"),
+            ("http://localhost:11434/api", "http://localhost:11434/api/chat"),
+            ("http://localhost:11434/api/", "http://localhost:11434/api/chat"),
+            ("http://localhost:11434/api/chat", "http://localhost:11434/api/chat"),
+            ("http://localhost:11434/v1", "http://localhost:11434/api/chat"),
+            ("https://gpu.lan/ollama", "https://gpu.lan/ollama/api/chat"),
+        ];
+        for (base_url, expected) in cases {
+            assert_eq!(ollama(base_url).chat_url(), expected, "{}", base_url);
+        }
+    }
+}
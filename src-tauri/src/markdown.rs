@@ -0,0 +1,387 @@
+use percent_encoding::percent_decode_str;
+use pulldown_cmark::{CodeBlockKind, Event, LinkType, Options, Parser, Tag, TagEnd};
+use std::ops::Range;
+
+pub fn markdown_options() -> Options {
+    let mut opts = Options::empty();
+    opts.insert(Options::ENABLE_TABLES);
+    opts.insert(Options::ENABLE_FOOTNOTES);
+    opts.insert(Options::ENABLE_STRIKETHROUGH);
+    opts.insert(Options::ENABLE_TASKLISTS);
+    opts
+}
+
+pub fn trim_wrapping(s: &str) -> &str {
+    let mut out = s.trim();
+    if (out.starts_with('<') && out.ends_with('>')) || (out.starts_with('"') && out.ends_with('"')) || (out.starts_with('\'') && out.ends_with('\'')) {
+        out = &out[1..out.len().saturating_sub(1)];
+    }
+    out.trim()
+}
+
+fn is_plantuml_lang(lang: &str) -> bool {
+    let lang = lang.split_whitespace().next().unwrap_or("").to_lowercase();
+    matches!(lang.as_str(), "plantuml" | "puml" | "uml")
+}
+
+// Pushes the reference as written and, when it contains percent escapes
+// (e.g. `my%20image.png`), the decoded form too.
+fn push_ref(out: &mut Vec<String>, raw: &str) {
+    let raw = trim_wrapping(raw);
+    if raw.is_empty() {
+        return;
+    }
+    let decoded = percent_decode_str(raw).decode_utf8_lossy();
+    if decoded != raw {
+        out.push(decoded.to_string());
+    }
+    out.push(raw.to_string());
+}
+
+/// Collects `src`, `href` and `srcset` attribute values from an HTML fragment.
+pub fn extract_html_refs(html: &str, out: &mut Vec<String>) {
+    let lower = html.to_ascii_lowercase();
+    let bytes = lower.as_bytes();
+
+    for attr in ["src", "href", "srcset"] {
+        let mut from = 0usize;
+        while let Some(pos) = lower[from..].find(attr) {
+            let start = from + pos;
+            from = start + attr.len();
+
+            // Attribute names must stand on their own: `data-src` or `srcset` must not match `src`.
+            let boundary_before = start == 0 || bytes[start - 1].is_ascii_whitespace();
+            let boundary_after = bytes
+                .get(from)
+                .map(|b| *b == b'=' || b.is_ascii_whitespace())
+                .unwrap_or(false);
+            if !boundary_before || !boundary_after {
+                continue;
+            }
+
+            let rest = html[from..].trim_start();
+            let Some(rest) = rest.strip_prefix('=') else { continue };
+            let rest = rest.trim_start();
+
+            let value = match rest.chars().next() {
+                Some(quote @ ('"' | '\'')) => match rest[1..].find(quote) {
+                    Some(end) => &rest[1..1 + end],
+                    None => continue,
+                },
+                Some(_) => rest
+                    .split(|c: char| c.is_whitespace() || c == '>')
+                    .next()
+                    .unwrap_or(""),
+                None => continue,
+            };
+
+            if attr == "srcset" {
+                for candidate in value.split(',') {
+                    if let Some(url) = candidate.split_whitespace().next() {
+                        push_ref(out, url);
+                    }
+                }
+            } else {
+                push_ref(out, value);
+            }
+        }
+    }
+}
+
+/// Collects PlantUML creole image references: `<img:path>` and `<img:path{scale=0.5}>`.
+pub fn extract_plantuml_refs(source: &str, out: &mut Vec<String>) {
+    let lower = source.to_ascii_lowercase();
+    let mut from = 0usize;
+    while let Some(pos) = lower[from..].find("<img:") {
+        let start = from + pos + "<img:".len();
+        let Some(end_rel) = source[start..].find('>') else { break };
+        let raw = &source[start..start + end_rel];
+        let raw = raw.split('{').next().unwrap_or(raw);
+        push_ref(out, raw);
+        from = start + end_rel + 1;
+    }
+}
+
+/// Extracts every local path a note may reference: inline, reference-style and
+/// autolinked markdown links and images, `src`/`href` attributes of HTML tags,
+/// and PlantUML `<img:...>` sprites. Code blocks and code spans are ignored,
+/// except for fenced PlantUML blocks which are scanned for sprites.
+///
+/// `.puml`/`.uml` sources are not markdown, so pass `is_plantuml` to scan them
+/// as a whole.
+pub fn extract_candidate_paths(content: &str, is_plantuml: bool) -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+
+    if is_plantuml {
+        extract_plantuml_refs(content, &mut out);
+        return out;
+    }
+
+    let parser = Parser::new_ext(content, markdown_options());
+    for (_, def) in parser.reference_definitions().iter() {
+        push_ref(&mut out, &def.dest);
+    }
+
+    let mut plantuml_block: Option<String> = None;
+    for event in parser {
+        match event {
+            Event::Start(Tag::Image { dest_url, .. }) | Event::Start(Tag::Link { dest_url, .. }) => {
+                push_ref(&mut out, &dest_url);
+            }
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(lang))) if is_plantuml_lang(&lang) => {
+                plantuml_block = Some(String::new());
+            }
+            Event::Text(text) => {
+                if let Some(block) = plantuml_block.as_mut() {
+                    block.push_str(&text);
+                }
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                if let Some(block) = plantuml_block.take() {
+                    extract_plantuml_refs(&block, &mut out);
+                }
+            }
+            Event::Html(html) | Event::InlineHtml(html) => {
+                extract_html_refs(&html, &mut out);
+            }
+            _ => {}
+        }
+    }
+
+    out
+}
+
+/// A backtick fence for a code block holding `body`, longer than any backtick run in it.
+pub fn code_fence(body: &str) -> String {
+    let mut longest = 0usize;
+    let mut run = 0usize;
+    for c in body.chars() {
+        if c == '`' {
+            run += 1;
+            longest = longest.max(run);
+        } else {
+            run = 0;
+        }
+    }
+    "`".repeat(longest.max(2) + 1)
+}
+
+/// A fenced code block and its byte range (fences included) within the note.
+pub struct FencedBlock {
+    pub lang: String,
+    pub code: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+pub fn fenced_code_blocks(content: &str) -> Vec<FencedBlock> {
+    let mut blocks: Vec<FencedBlock> = Vec::new();
+    let mut current: Option<FencedBlock> = None;
+
+    for (event, range) in Parser::new_ext(content, markdown_options()).into_offset_iter() {
+        match event {
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(lang))) => {
+                current = Some(FencedBlock {
+                    lang: lang.split_whitespace().next().unwrap_or("").to_string(),
+                    code: String::new(),
+                    start: range.start,
+                    end: range.end,
+                });
+            }
+            Event::Text(text) => {
+                if let Some(block) = current.as_mut() {
+                    block.code.push_str(&text);
+                }
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                if let Some(block) = current.take() {
+                    blocks.push(block);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    blocks
+}
+
+/// Byte ranges of code blocks and code spans, where link-like text must be left alone.
+pub fn code_ranges(content: &str) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    for (event, range) in Parser::new_ext(content, markdown_options()).into_offset_iter() {
+        match event {
+            Event::Start(Tag::CodeBlock(_)) | Event::Code(_) => ranges.push(range),
+            _ => {}
+        }
+    }
+    ranges
+}
+
+/// Byte range of the destination in an inline link or image written as `[text](dest "title")`.
+fn inline_dest(content: &str, range: &Range<usize>) -> Option<Range<usize>> {
+    let slice = &content[range.clone()];
+    let open = slice.rfind("](")? + 2;
+    let rest = &slice[open..];
+    let lead = rest.len() - rest.trim_start().len();
+    let rest = &rest[lead..];
+    let len = if rest.starts_with('<') {
+        rest.find('>')? + 1
+    } else {
+        // Balanced parentheses are allowed in a bare destination.
+        let mut depth = 0usize;
+        let mut end = rest.len();
+        for (i, c) in rest.char_indices() {
+            match c {
+                '(' => depth += 1,
+                ')' if depth == 0 => {
+                    end = i;
+                    break;
+                }
+                ')' => depth -= 1,
+                c if c.is_whitespace() => {
+                    end = i;
+                    break;
+                }
+                _ => {}
+            }
+        }
+        end
+    };
+    let start = range.start + open + lead;
+    Some(start..start + len)
+}
+
+/// Rewrites the destinations of inline links and images. `rewrite` gets the destination
+/// as parsed (`<...>` unwrapped) and returns the replacement as it should be written, or
+/// `None` to leave it unchanged.
+pub fn rewrite_link_targets(content: &str, mut rewrite: impl FnMut(&str) -> Option<String>) -> String {
+    let mut edits: Vec<(Range<usize>, String)> = Vec::new();
+    for (event, range) in Parser::new_ext(content, markdown_options()).into_offset_iter() {
+        let dest_url = match event {
+            Event::Start(Tag::Image { link_type: LinkType::Inline, dest_url, .. })
+            | Event::Start(Tag::Link { link_type: LinkType::Inline, dest_url, .. }) => dest_url,
+            _ => continue,
+        };
+        let Some(dest) = inline_dest(content, &range) else { continue };
+        if let Some(replacement) = rewrite(&dest_url) {
+            edits.push((dest, replacement));
+        }
+    }
+
+    edits.sort_by_key(|(range, _)| range.start);
+    let mut out = String::with_capacity(content.len());
+    let mut last = 0;
+    for (range, replacement) in edits {
+        if range.start < last {
+            continue;
+        }
+        out.push_str(&content[last..range.start]);
+        out.push_str(&replacement);
+        last = range.end;
+    }
+    out.push_str(&content[last..]);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(content: &str, is_plantuml: bool) -> Vec<String> {
+        let mut out = extract_candidate_paths(content, is_plantuml);
+        out.sort();
+        out.dedup();
+        out
+    }
+
+    fn html(fragment: &str) -> Vec<String> {
+        let mut out = Vec::new();
+        extract_html_refs(fragment, &mut out);
+        out.sort();
+        out
+    }
+
+    #[test]
+    fn markdown_links() {
+        let cases: &[(&str, &[&str])] = &[
+            ("![a](img/a.png)", &["img/a.png"]),
+            ("![a](img/a.png \"Title\")", &["img/a.png"]),
+            ("[doc](notes/doc.pdf)", &["notes/doc.pdf"]),
+            ("![a][logo]\n\n[logo]: assets/logo.png", &["assets/logo.png"]),
+            ("[unused]: <assets/with space.png> \"t\"", &["assets/with space.png"]),
+            ("![a][]\n\n[a]: a.png", &["a.png"]),
+            ("<https://example.com/x.png>", &["https://example.com/x.png"]),
+            ("![a](<my image.png>)", &["my image.png"]),
+            ("![a](my%20image.png)", &["my image.png", "my%20image.png"]),
+            ("![a](caf%C3%A9.png)", &["café.png", "caf%C3%A9.png"]),
+            ("![a](a\\(1\\).png)", &["a(1).png"]),
+            ("no links here", &[]),
+        ];
+        for (content, expected) in cases {
+            let mut expected: Vec<String> = expected.iter().map(|s| s.to_string()).collect();
+            expected.sort();
+            assert_eq!(paths(content, false), expected, "{}", content);
+        }
+    }
+
+    #[test]
+    fn html_attributes() {
+        let cases: &[(&str, &[&str])] = &[
+            (r#"<img src="a.png">"#, &["a.png"]),
+            (r#"<IMG SRC='b.png' alt="x">"#, &["b.png"]),
+            ("<img src=c.png>", &["c.png"]),
+            (r#"<a href = "doc.pdf">d</a>"#, &["doc.pdf"]),
+            (r#"<img srcset="s1.png 1x, s2.png 2x" src="s.png">"#, &["s.png", "s1.png", "s2.png"]),
+            (r#"<img data-src="lazy.png" datasrc="x.png">"#, &[]),
+            (r#"<img src="my%20pic.png">"#, &["my pic.png", "my%20pic.png"]),
+            (r#"<img src="unterminated.png>"#, &[]),
+        ];
+        for (fragment, expected) in cases {
+            let mut expected: Vec<String> = expected.iter().map(|s| s.to_string()).collect();
+            expected.sort();
+            assert_eq!(html(fragment), expected, "{}", fragment);
+        }
+        assert_eq!(paths("Inline <img src=\"inline.png\"> html\n\n<div><img src=\"block.png\"></div>", false), vec!["block.png", "inline.png"]);
+    }
+
+    #[test]
+    fn plantuml_sprites() {
+        let mut out = Vec::new();
+        extract_plantuml_refs("A -> B : <img:icons/a.png>\nnote: <IMG:b.png{scale=0.5}> and <img:c d.png>", &mut out);
+        assert_eq!(out, vec!["icons/a.png", "b.png", "c d.png"]);
+
+        assert_eq!(paths("@startuml\nA -> B : <img:sprite.png>\n@enduml", true), vec!["sprite.png"]);
+        let fenced = "```plantuml\nA -> B : <img:fenced.png>\n```\n\n```puml\n<img:puml.png>\n```\n\n```text\n<img:plain.png>\n```";
+        assert_eq!(paths(fenced, false), vec!["fenced.png", "puml.png"]);
+    }
+
+    #[test]
+    fn non_ascii_text_keeps_offsets() {
+        // `İ` lowercases to two chars, which would shift every offset after it.
+        assert_eq!(html(r#"<p title="İİİ">İ</p><img src="after.png"><a HREF="ünï.md">"#), vec!["after.png", "ünï.md"]);
+        let mut out = Vec::new();
+        extract_plantuml_refs("İstanbul -> B : <img:İcon.png>\nB -> C : <IMG:c.png>", &mut out);
+        assert_eq!(out, vec!["İcon.png", "c.png"]);
+    }
+
+    #[test]
+    fn code_is_ignored() {
+        let content = "\
+![kept](kept.png)
+
+```markdown
+![fenced](fenced.png)
+<img src=\"fenced-html.png\">
+```
+
+~~~
+[tilde](tilde.png)
+~~~
+
+    ![indented](indented.png)
+
+Inline `![span](span.png)` and ``<img src=\"span-html.png\">``.
+";
+        assert_eq!(paths(content, false), vec!["kept.png"]);
+    }
+}
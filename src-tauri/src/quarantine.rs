@@ -0,0 +1,324 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const QUARANTINE_DIR: &str = ".xnote_quarantine";
+const MANIFEST_FILE: &str = "manifest.json";
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct QuarantineEntry {
+    pub original: String,
+    pub quarantined: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct QuarantineManifest {
+    pub created_at: String,
+    pub root: String,
+    pub entries: Vec<QuarantineEntry>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct UndoResult {
+    pub restored: usize,
+    pub skipped: Vec<String>,
+}
+
+/// A batch folder under `<workspace>/.xnote_quarantine/<timestamp>` that files are
+/// moved into instead of being deleted. The manifest records where each file came from.
+pub struct QuarantineBatch {
+    dir: PathBuf,
+    manifest: QuarantineManifest,
+}
+
+impl QuarantineBatch {
+    pub fn create(root: &Path) -> Result<Self, String> {
+        let now = chrono::Local::now();
+        let base = root.join(QUARANTINE_DIR);
+        let stamp = now.format("%Y%m%d-%H%M%S").to_string();
+
+        let mut dir = base.join(&stamp);
+        let mut n = 1;
+        while dir.exists() {
+            dir = base.join(format!("{}-{}", stamp, n));
+            n += 1;
+        }
+        fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            dir,
+            manifest: QuarantineManifest {
+                created_at: now.to_rfc3339(),
+                root: root.to_string_lossy().to_string(),
+                entries: Vec::new(),
+            },
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Moves `path` (which must live under `root`) into the batch, keeping its relative layout.
+    /// The manifest is saved with the entry before the move, so a file is never in the batch
+    /// without a record of where it came from; an entry whose move never happened is dropped
+    /// on restore.
+    pub fn add(&mut self, root: &Path, path: &Path) -> Result<(), String> {
+        let rel = path
+            .strip_prefix(root)
+            .map_err(|_| format!("{} is outside the workspace", path.display()))?;
+        let target = self.dir.join(rel);
+        self.manifest.entries.push(QuarantineEntry {
+            original: path.to_string_lossy().to_string(),
+            quarantined: target.to_string_lossy().to_string(),
+        });
+        self.save()?;
+        if let Err(err) = move_file(path, &target) {
+            self.manifest.entries.pop();
+            let _ = self.save();
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(&self.manifest).map_err(|e| e.to_string())?;
+        fs::write(self.dir.join(MANIFEST_FILE), json).map_err(|e| e.to_string())
+    }
+
+    /// Saves the manifest, or removes the batch folder again if nothing was moved into it.
+    pub fn finish(self) -> Result<Option<String>, String> {
+        if self.manifest.entries.is_empty() {
+            let _ = fs::remove_dir_all(&self.dir);
+            return Ok(None);
+        }
+        self.save()?;
+        Ok(Some(self.dir.to_string_lossy().to_string()))
+    }
+}
+
+fn move_file(source: &Path, target: &Path) -> Result<(), String> {
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    if fs::rename(source, target).is_ok() {
+        return Ok(());
+    }
+    // rename fails across filesystems (e.g. a mounted workspace); fall back to copy + remove.
+    fs::copy(source, target).map_err(|e| e.to_string())?;
+    fs::remove_file(source).map_err(|e| e.to_string())
+}
+
+/// Batches with a manifest, oldest first by the time they were created.
+fn list_batches(root: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(root.join(QUARANTINE_DIR)) else { return Vec::new() };
+    let mut batches: Vec<(Option<chrono::DateTime<chrono::FixedOffset>>, PathBuf)> = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.join(MANIFEST_FILE).is_file())
+        .map(|p| (read_manifest(&p).ok().and_then(|m| chrono::DateTime::parse_from_rfc3339(&m.created_at).ok()), p))
+        .collect();
+    batches.sort();
+    batches.into_iter().map(|(_, p)| p).collect()
+}
+
+fn read_manifest(batch: &Path) -> Result<QuarantineManifest, String> {
+    let text = fs::read_to_string(batch.join(MANIFEST_FILE)).map_err(|e| e.to_string())?;
+    serde_json::from_str(&text).map_err(|e| format!("invalid quarantine manifest: {}", e))
+}
+
+/// Moves the files of `batch` back; returns how many were restored and the entries left in
+/// quarantine. Entries whose quarantined file is gone are dropped, and the batch is removed
+/// once nothing is left in it.
+fn restore_batch(batch: &Path, manifest: QuarantineManifest) -> Result<(usize, Vec<QuarantineEntry>), String> {
+    let mut restored = 0usize;
+    let mut remaining: Vec<QuarantineEntry> = Vec::new();
+    for entry in manifest.entries.iter() {
+        let original = Path::new(&entry.original);
+        let quarantined = Path::new(&entry.quarantined);
+        if !quarantined.exists() {
+            continue;
+        }
+        if original.exists() || move_file(quarantined, original).is_err() {
+            remaining.push(entry.clone());
+            continue;
+        }
+        restored += 1;
+    }
+
+    if remaining.is_empty() {
+        fs::remove_dir_all(batch).map_err(|e| e.to_string())?;
+    } else if remaining.len() != manifest.entries.len() {
+        let manifest = QuarantineManifest { entries: remaining.clone(), ..manifest };
+        let json = serde_json::to_string_pretty(&manifest).map_err(|e| e.to_string())?;
+        fs::write(batch.join(MANIFEST_FILE), json).map_err(|e| e.to_string())?;
+    }
+    Ok((restored, remaining))
+}
+
+/// Moves every file of the most recent batch back to its original location.
+/// Files whose original path has been taken again in the meantime are left in
+/// quarantine and reported as skipped. Only the most recent batch is ever undone: when
+/// none of its files can be restored, the reason is returned as an error and older
+/// batches are left alone.
+pub fn undo_last(root: &Path) -> Result<UndoResult, String> {
+    let Some(batch) = list_batches(root).pop() else {
+        return Err("Nothing to undo".to_string());
+    };
+    let manifest = read_manifest(&batch)?;
+    let (restored, remaining) = restore_batch(&batch, manifest)?;
+    let skipped: Vec<String> = remaining.into_iter().map(|e| e.original).collect();
+    if restored > 0 {
+        return Ok(UndoResult { restored, skipped });
+    }
+    if skipped.is_empty() {
+        return Err("The files of the last clean-up are no longer in quarantine".to_string());
+    }
+    Err(format!(
+        "The last clean-up cannot be undone: {} exist again at their original paths ({})",
+        skipped.len(),
+        skipped.join(", ")
+    ))
+}
+
+/// Permanently removes quarantine batches, optionally only those older than `older_than_days`.
+pub fn purge(root: &Path, older_than_days: Option<i64>) -> Result<usize, String> {
+    let cutoff = older_than_days.map(|days| chrono::Local::now() - chrono::Duration::days(days));
+    let mut purged = 0usize;
+
+    for batch in list_batches(root) {
+        if let Some(cutoff) = cutoff {
+            let created = read_manifest(&batch)
+                .ok()
+                .and_then(|m| chrono::DateTime::parse_from_rfc3339(&m.created_at).ok());
+            match created {
+                Some(created) if created < cutoff => {}
+                _ => continue,
+            }
+        }
+        fs::remove_dir_all(&batch).map_err(|e| e.to_string())?;
+        purged += 1;
+    }
+
+    let base = root.join(QUARANTINE_DIR);
+    if base.read_dir().map(|mut it| it.next().is_none()).unwrap_or(false) {
+        let _ = fs::remove_dir(base);
+    }
+
+    Ok(purged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes a batch folder by hand, as `create` would have at `created_at`.
+    fn batch(root: &Path, name: &str, created_at: &str, files: &[&str]) {
+        let dir = root.join(QUARANTINE_DIR).join(name);
+        let entries = files
+            .iter()
+            .map(|file| {
+                let quarantined = dir.join(file);
+                fs::create_dir_all(quarantined.parent().unwrap()).unwrap();
+                fs::write(&quarantined, name).unwrap();
+                QuarantineEntry {
+                    original: root.join(file).to_string_lossy().to_string(),
+                    quarantined: quarantined.to_string_lossy().to_string(),
+                }
+            })
+            .collect();
+        let manifest = QuarantineManifest { created_at: created_at.to_string(), root: root.to_string_lossy().to_string(), entries };
+        fs::write(dir.join(MANIFEST_FILE), serde_json::to_string(&manifest).unwrap()).unwrap();
+    }
+
+    fn names(root: &Path) -> Vec<String> {
+        list_batches(root).iter().map(|p| p.file_name().unwrap().to_string_lossy().to_string()).collect()
+    }
+
+    #[test]
+    fn batches_are_ordered_by_creation_time() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+        batch(root, "20240101-120000-10", "2024-01-01T12:00:00.900+00:00", &["c.png"]);
+        batch(root, "20240101-120000-2", "2024-01-01T12:00:00.200+00:00", &["b.png"]);
+        batch(root, "20240101-120000", "2024-01-01T12:00:00.100+00:00", &["a.png"]);
+        batch(root, "20231231-230000", "2024-01-01T01:00:00+02:00", &["z.png"]);
+        assert_eq!(names(root), vec!["20231231-230000", "20240101-120000", "20240101-120000-2", "20240101-120000-10"]);
+
+        assert_eq!(undo_last(root).unwrap().restored, 1);
+        assert_eq!(fs::read_to_string(root.join("c.png")).unwrap(), "20240101-120000-10");
+    }
+
+    #[test]
+    fn undo_never_reaches_past_a_blocked_batch() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+        batch(root, "old", "2024-01-01T10:00:00+00:00", &["img/a.png", "img/b.png"]);
+        batch(root, "new", "2024-01-01T11:00:00+00:00", &["img/taken.png"]);
+        fs::create_dir_all(root.join("img")).unwrap();
+        fs::write(root.join("img/taken.png"), "new file at the old path").unwrap();
+
+        let err = undo_last(root).err().unwrap();
+        assert!(err.starts_with("The last clean-up cannot be undone: 1 exist again"), "{}", err);
+        assert!(err.contains(&root.join("img/taken.png").to_string_lossy().to_string()), "{}", err);
+        assert!(!root.join("img/a.png").exists());
+        assert_eq!(fs::read_to_string(root.join("img/taken.png")).unwrap(), "new file at the old path");
+        assert_eq!(names(root), vec!["old", "new"]);
+
+        fs::remove_file(root.join("img/taken.png")).unwrap();
+        assert_eq!(undo_last(root).unwrap().restored, 1);
+        assert_eq!(fs::read_to_string(root.join("img/taken.png")).unwrap(), "new");
+        assert_eq!(names(root), vec!["old"]);
+        assert_eq!(undo_last(root).unwrap().restored, 2);
+        assert_eq!(undo_last(root).err().as_deref(), Some("Nothing to undo"));
+    }
+
+    #[test]
+    fn partly_blocked_batches_restore_the_rest() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+        batch(root, "new", "2024-01-01T11:00:00+00:00", &["a.png", "taken.png"]);
+        fs::write(root.join("taken.png"), "new file at the old path").unwrap();
+
+        let result = undo_last(root).unwrap();
+        assert_eq!(result.restored, 1);
+        assert_eq!(result.skipped, vec![root.join("taken.png").to_string_lossy().to_string()]);
+        assert_eq!(read_manifest(&root.join(QUARANTINE_DIR).join("new")).unwrap().entries.len(), 1);
+    }
+
+    #[test]
+    fn batches_whose_files_are_gone_are_dropped() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+        batch(root, "old", "2024-01-01T10:00:00+00:00", &["a.png"]);
+        batch(root, "new", "2024-01-01T11:00:00+00:00", &["b.png"]);
+        fs::remove_file(root.join(QUARANTINE_DIR).join("new/b.png")).unwrap();
+
+        assert_eq!(undo_last(root).err().as_deref(), Some("The files of the last clean-up are no longer in quarantine"));
+        assert!(!root.join("a.png").exists());
+        assert_eq!(names(root), vec!["old"]);
+    }
+
+    #[test]
+    fn the_manifest_is_saved_with_every_file() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+        fs::create_dir_all(root.join("img")).unwrap();
+        fs::write(root.join("img/a.png"), "a").unwrap();
+        fs::write(root.join("b.png"), "b").unwrap();
+
+        let mut batch = QuarantineBatch::create(root).unwrap();
+        batch.add(root, &root.join("img/a.png")).unwrap();
+        assert_eq!(read_manifest(batch.dir()).unwrap().entries.len(), 1);
+        batch.add(root, &root.join("b.png")).unwrap();
+        assert!(batch.add(root, &root.join("missing.png")).is_err());
+        let manifest = read_manifest(batch.dir()).unwrap();
+        let originals: Vec<&str> = manifest.entries.iter().map(|e| e.original.as_str()).collect();
+        assert_eq!(originals, vec![root.join("img/a.png").to_string_lossy(), root.join("b.png").to_string_lossy()]);
+
+        // Without `finish`, as after a crash, the files can still be restored.
+        drop(batch);
+        assert_eq!(undo_last(root).unwrap().restored, 2);
+        assert_eq!(fs::read_to_string(root.join("img/a.png")).unwrap(), "a");
+    }
+}
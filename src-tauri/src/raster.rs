@@ -0,0 +1,279 @@
+use base64::{engine::general_purpose, Engine as _};
+use resvg::tiny_skia::{Color, Pixmap};
+use resvg::usvg;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+use tauri::AppHandle;
+
+/// CSS pixels per inch, the unit SVG sizes are in.
+const CSS_DPI: f32 = 96.0;
+pub const DEFAULT_SCALE: f32 = 2.0;
+/// Upper bound on output pixels (about 8192 x 8192) unless the caller sets `max_pixels`.
+const DEFAULT_MAX_PIXELS: u64 = 64 * 1024 * 1024;
+const ASSETS_DIR: &str = ".xnote_assets";
+
+/// System fonts are scanned once; loading them takes longer than rendering most diagrams.
+fn fontdb() -> Arc<usvg::fontdb::Database> {
+    static FONTS: OnceLock<Arc<usvg::fontdb::Database>> = OnceLock::new();
+    FONTS
+        .get_or_init(|| {
+            let mut fontdb = usvg::fontdb::Database::new();
+            fontdb.load_system_fonts();
+            Arc::new(fontdb)
+        })
+        .clone()
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RasterOptions {
+    /// Device pixels per SVG unit. Ignored when `dpi` is set.
+    pub scale: Option<f32>,
+    /// Print resolution; 96 DPI is 1x, 300 DPI is 3.125x.
+    pub dpi: Option<f32>,
+    /// CSS hex colour (`#fff`, `#ffffff`, `#ffffffcc`) or `transparent` (the default).
+    pub background: Option<String>,
+    pub max_pixels: Option<u64>,
+}
+
+/// Where `render_svg` sends the PNG.
+#[derive(Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase", rename_all_fields = "camelCase")]
+pub enum RasterOutput {
+    Clipboard,
+    /// `.xnote_assets/<note stem>/` under `root_path`, like pasted images.
+    File {
+        root_path: String,
+        note_path: String,
+        file_name: Option<String>,
+    },
+    Base64,
+}
+
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct RasterResult {
+    width: u32,
+    height: u32,
+    /// Workspace-relative markdown path of the written file (`/.xnote_assets/...`).
+    path: Option<String>,
+    /// Base64 PNG, for the `base64` output.
+    data: Option<String>,
+}
+
+fn parse_color(value: &str) -> Result<Option<Color>, String> {
+    let value = value.trim();
+    match value.to_lowercase().as_str() {
+        "" | "transparent" | "none" => return Ok(None),
+        "white" => return Ok(Some(Color::WHITE)),
+        "black" => return Ok(Some(Color::BLACK)),
+        _ => {}
+    }
+    let hex = value
+        .strip_prefix('#')
+        .filter(|h| h.chars().all(|c| c.is_ascii_hexdigit()))
+        .ok_or_else(|| format!("Invalid background colour `{}`", value))?;
+    let expanded: String = match hex.len() {
+        3 | 4 => hex.chars().flat_map(|c| [c, c]).collect(),
+        6 | 8 => hex.to_string(),
+        _ => return Err(format!("Invalid background colour `{}`", value)),
+    };
+    let channel = |i: usize| u8::from_str_radix(&expanded[i..i + 2], 16).unwrap_or(255);
+    let alpha = if expanded.len() == 8 { channel(6) } else { 255 };
+    Ok(Some(Color::from_rgba8(channel(0), channel(2), channel(4), alpha)))
+}
+
+/// Renders SVG markup to a premultiplied RGBA pixmap at `scale` device pixels per SVG unit.
+pub fn rasterize(svg_text: &str, scale: f32) -> Result<Pixmap, String> {
+    rasterize_with(svg_text, &RasterOptions { scale: Some(scale), ..Default::default() })
+}
+
+pub fn rasterize_with(svg_text: &str, options: &RasterOptions) -> Result<Pixmap, String> {
+    let scale = match (options.dpi, options.scale) {
+        (Some(dpi), _) => dpi / CSS_DPI,
+        (None, Some(scale)) => scale,
+        (None, None) => DEFAULT_SCALE,
+    };
+    if !scale.is_finite() || scale <= 0.0 {
+        return Err("Scale must be a positive number".to_string());
+    }
+    let background = parse_color(options.background.as_deref().unwrap_or(""))?;
+
+    let opt = usvg::Options {
+        fontdb: fontdb(),
+        ..Default::default()
+    };
+    let tree = usvg::Tree::from_str(svg_text, &opt).map_err(|e| format!("svg parse failed: {}", e))?;
+
+    let size = tree.size();
+    let width = size.width().ceil() as u32;
+    let height = size.height().ceil() as u32;
+    if width == 0 || height == 0 {
+        return Err("svg size is zero".to_string());
+    }
+
+    let out_width = ((width as f32) * scale).ceil() as u32;
+    let out_height = ((height as f32) * scale).ceil() as u32;
+    let max_pixels = options.max_pixels.unwrap_or(DEFAULT_MAX_PIXELS);
+    if out_width as u64 * out_height as u64 > max_pixels {
+        let fits = ((max_pixels as f64) / (width as f64 * height as f64)).sqrt();
+        return Err(format!(
+            "{}x{} px exceeds the limit of {} pixels; use a scale of at most {:.2}",
+            out_width, out_height, max_pixels, fits
+        ));
+    }
+
+    let mut pixmap = Pixmap::new(out_width, out_height).ok_or_else(|| "pixmap alloc failed".to_string())?;
+    if let Some(color) = background {
+        pixmap.fill(color);
+    }
+    let transform = usvg::Transform::from_scale(scale, scale);
+    resvg::render(&tree, transform, &mut pixmap.as_mut());
+    Ok(pixmap)
+}
+
+pub fn encode_png(pixmap: &Pixmap) -> Result<Vec<u8>, String> {
+    pixmap.encode_png().map_err(|e| format!("png encode failed: {}", e))
+}
+
+fn write_asset(root_path: &str, note_path: &str, file_name: Option<String>, png: &[u8]) -> Result<String, String> {
+    let stem = Path::new(note_path)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .filter(|s| !s.is_empty())
+        .unwrap_or_else(|| "note".to_string());
+    let mut name = file_name
+        .map(|n| n.trim().replace(['/', '\\'], "_"))
+        .filter(|n| !n.is_empty())
+        .unwrap_or_else(|| format!("img_{}", chrono::Local::now().format("%Y%m%d%H%M%S%f")));
+    if !name.to_lowercase().ends_with(".png") {
+        name.push_str(".png");
+    }
+
+    let dir = Path::new(root_path).join(ASSETS_DIR).join(&stem);
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    // An existing image keeps its name; the new one gets a `-1`, `-2`, ... suffix.
+    let base = name[..name.len() - ".png".len()].to_string();
+    let mut n = 1;
+    let mut file = loop {
+        match fs::OpenOptions::new().write(true).create_new(true).open(dir.join(&name)) {
+            Ok(file) => break file,
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                name = format!("{}-{}.png", base, n);
+                n += 1;
+            }
+            Err(e) => return Err(e.to_string()),
+        }
+    };
+    file.write_all(png).map_err(|e| e.to_string())?;
+    Ok(format!("/{}/{}/{}", ASSETS_DIR, stem, name))
+}
+
+/// Rasterizes SVG markup to PNG and sends it to the clipboard, to a file in the note's
+/// `.xnote_assets` folder, or back to the caller as base64.
+#[tauri::command(async)]
+pub fn render_svg(
+    app: AppHandle,
+    svg_text: String,
+    options: Option<RasterOptions>,
+    output: RasterOutput,
+) -> Result<RasterResult, String> {
+    let pixmap = rasterize_with(&svg_text, &options.unwrap_or_default())?;
+    let (width, height) = (pixmap.width(), pixmap.height());
+    let mut result = RasterResult { width, height, ..Default::default() };
+    match output {
+        RasterOutput::Clipboard => {
+            crate::set_clipboard_rgba(&app, width as usize, height as usize, pixmap.take())?;
+        }
+        RasterOutput::File { root_path, note_path, file_name } => {
+            let png = encode_png(&pixmap)?;
+            result.path = Some(write_asset(&root_path, &note_path, file_name, &png)?);
+        }
+        RasterOutput::Base64 => {
+            let png = encode_png(&pixmap)?;
+            result.data = Some(general_purpose::STANDARD.encode(png));
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SQUARE: &str = r#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="20"><rect width="10" height="20" fill="red"/></svg>"#;
+
+    #[test]
+    fn parses_background_colours() {
+        let cases = [
+            ("", None),
+            ("transparent", None),
+            (" None ", None),
+            ("white", Some((255, 255, 255, 255))),
+            ("BLACK", Some((0, 0, 0, 255))),
+            ("#fff", Some((255, 255, 255, 255))),
+            ("#1234", Some((0x11, 0x22, 0x33, 0x44))),
+            ("#336699", Some((0x33, 0x66, 0x99, 255))),
+            ("#33669980", Some((0x33, 0x66, 0x99, 0x80))),
+            ("#AbCdEf", Some((0xab, 0xcd, 0xef, 255))),
+        ];
+        for (value, expected) in cases {
+            let color = parse_color(value).unwrap().map(|c| {
+                let c = c.to_color_u8();
+                (c.red(), c.green(), c.blue(), c.alpha())
+            });
+            assert_eq!(color, expected, "{value:?}");
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_colours() {
+        for value in ["red", "#12", "#12345", "#ggg", "336699", "#1234567"] {
+            assert!(parse_color(value).is_err(), "{value:?}");
+        }
+    }
+
+    #[test]
+    fn scale_and_dpi_set_the_output_size() {
+        let pixmap = rasterize(SQUARE, 3.0).unwrap();
+        assert_eq!((pixmap.width(), pixmap.height()), (30, 60));
+        let options = RasterOptions { scale: Some(5.0), dpi: Some(192.0), ..Default::default() };
+        let pixmap = rasterize_with(SQUARE, &options).unwrap();
+        assert_eq!((pixmap.width(), pixmap.height()), (20, 40), "dpi wins over scale");
+        assert!(rasterize(SQUARE, 0.0).is_err());
+        assert!(rasterize(SQUARE, f32::NAN).is_err());
+    }
+
+    #[test]
+    fn refuses_images_over_the_pixel_limit() {
+        // 20 x 40 = 800 pixels at 2x.
+        let at_limit = RasterOptions { scale: Some(2.0), max_pixels: Some(800), ..Default::default() };
+        assert!(rasterize_with(SQUARE, &at_limit).is_ok());
+        let over = RasterOptions { scale: Some(2.0), max_pixels: Some(799), ..Default::default() };
+        let err = rasterize_with(SQUARE, &over).unwrap_err();
+        assert!(err.contains("20x40 px exceeds the limit of 799 pixels"), "{err}");
+
+        let huge = format!(r#"<svg xmlns="http://www.w3.org/2000/svg" width="{0}" height="{0}"/>"#, 100_000);
+        assert!(rasterize(&huge, 1.0).unwrap_err().contains("exceeds the limit"));
+    }
+
+    #[test]
+    fn assets_are_never_overwritten() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().to_str().unwrap();
+        let write = |name: &str, png: &[u8]| write_asset(root, "notes/Diagram.md", Some(name.to_string()), png).unwrap();
+
+        assert_eq!(write("chart", b"one"), "/.xnote_assets/Diagram/chart.png");
+        assert_eq!(write("chart.png", b"two"), "/.xnote_assets/Diagram/chart-1.png");
+        assert_eq!(write("chart", b"three"), "/.xnote_assets/Diagram/chart-2.png");
+        assert_eq!(write("a/b", b"four"), "/.xnote_assets/Diagram/a_b.png");
+
+        let assets = dir.path().join(ASSETS_DIR).join("Diagram");
+        assert_eq!(fs::read(assets.join("chart.png")).unwrap(), b"one");
+        assert_eq!(fs::read(assets.join("chart-1.png")).unwrap(), b"two");
+        assert_eq!(fs::read(assets.join("chart-2.png")).unwrap(), b"three");
+    }
+}
@@ -0,0 +1,462 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufRead, Write};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{async_runtime, AppHandle, Emitter};
+
+use crate::{encryption, jobs, markdown};
+
+const DEFAULT_TIMEOUT_SECS: u64 = 300;
+/// Output kept for the written-back result block; streaming events are not capped.
+const MAX_CAPTURED_BYTES: usize = 256 * 1024;
+const RESULT_LANG: &str = "output";
+/// How long output may keep arriving after the interpreter exits, from processes it left
+/// running in the background.
+const OUTPUT_GRACE: Duration = Duration::from_secs(2);
+
+/// How to run a code block of one language, from `runner.interpreters` in config.json.
+/// The block is written to a temp file whose path is appended to `args`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Interpreter {
+    pub command: String,
+    pub args: Vec<String>,
+    pub extension: String,
+    pub env: HashMap<String, String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct RunnerConfig {
+    interpreters: HashMap<String, Interpreter>,
+    timeout_secs: Option<u64>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct ConfigWithRunner {
+    runner: RunnerConfig,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct CodeRunOutput {
+    job_id: u64,
+    stream: &'static str,
+    data: String,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct CodeRunExit {
+    job_id: u64,
+    code: Option<i32>,
+    timed_out: bool,
+    cancelled: bool,
+    duration_ms: u128,
+    /// Whether a result block was written back into the note.
+    written_back: bool,
+    error: Option<String>,
+}
+
+fn builtin_interpreter(lang: &str) -> Option<Interpreter> {
+    let (command, extension) = match lang {
+        "bash" => ("bash", "sh"),
+        "sh" | "shell" => ("sh", "sh"),
+        "zsh" => ("zsh", "zsh"),
+        "fish" => ("fish", "fish"),
+        "python" | "python3" | "py" => ("python3", "py"),
+        "node" | "js" | "javascript" => ("node", "js"),
+        "ruby" | "rb" => ("ruby", "rb"),
+        "perl" => ("perl", "pl"),
+        _ => return None,
+    };
+    Some(Interpreter {
+        command: command.to_string(),
+        extension: extension.to_string(),
+        ..Default::default()
+    })
+}
+
+fn load_runner_config() -> RunnerConfig {
+    crate::get_config()
+        .ok()
+        .and_then(|text| serde_json::from_str::<ConfigWithRunner>(&text).ok())
+        .map(|c| c.runner)
+        .unwrap_or_default()
+}
+
+fn resolve_interpreter(config: &RunnerConfig, lang: &str) -> Result<Interpreter, String> {
+    let lang = lang.to_lowercase();
+    config
+        .interpreters
+        .get(&lang)
+        .cloned()
+        .filter(|i| !i.command.trim().is_empty())
+        .or_else(|| builtin_interpreter(&lang))
+        .ok_or_else(|| format!("No interpreter configured for `{}`", lang))
+}
+
+#[cfg(unix)]
+fn kill_process_tree(child: &mut Child) {
+    // The interpreter leads its own process group, so this also reaches anything it started.
+    unsafe {
+        libc::kill(-(child.id() as i32), libc::SIGKILL);
+    }
+    let _ = child.kill();
+}
+
+#[cfg(not(unix))]
+fn kill_process_tree(child: &mut Child) {
+    let _ = child.kill();
+}
+
+/// Waits up to `timeout` for the output readers; returns whether they all finished.
+fn wait_for_readers(readers: &[std::thread::JoinHandle<()>], timeout: Duration) -> bool {
+    let started = Instant::now();
+    while !readers.iter().all(|r| r.is_finished()) {
+        if started.elapsed() >= timeout {
+            return false;
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    true
+}
+
+fn spawn_stream_reader(
+    app: AppHandle,
+    job_id: u64,
+    stream: &'static str,
+    source: impl std::io::Read + Send + 'static,
+    captured: Arc<Mutex<String>>,
+) -> std::thread::JoinHandle<()> {
+    std::thread::spawn(move || {
+        let mut reader = std::io::BufReader::new(source);
+        let mut line: Vec<u8> = Vec::new();
+        loop {
+            line.clear();
+            match reader.read_until(b'\n', &mut line) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {
+                    let text = String::from_utf8_lossy(&line).to_string();
+                    {
+                        let mut captured = captured.lock().unwrap();
+                        if captured.len() < MAX_CAPTURED_BYTES {
+                            captured.push_str(&text);
+                        }
+                    }
+                    let _ = app.emit("code-run-output", CodeRunOutput { job_id, stream, data: text });
+                }
+            }
+        }
+    })
+}
+
+/// Inserts (or replaces) an ```output block directly beneath code block `block_index`.
+fn write_result_block(path: &Path, block_index: usize, expected_code: &str, output: &str) -> Result<(), String> {
+    let content = encryption::read_note(path)?;
+    let blocks = markdown::fenced_code_blocks(&content);
+    let block = blocks
+        .get(block_index)
+        .filter(|b| b.code == expected_code)
+        .ok_or_else(|| "The code block changed while it was running; result not written".to_string())?;
+
+    let existing = blocks.get(block_index + 1).filter(|next| {
+        next.lang == RESULT_LANG && content[block.end..next.start].trim().is_empty()
+    });
+
+    let body = output.trim_end_matches('\n');
+    let fence = markdown::code_fence(body);
+    let result = format!("{fence}{RESULT_LANG}\n{body}\n{fence}");
+
+    let (start, end) = match existing {
+        Some(next) => (next.start, next.end),
+        None => (block.end, block.end),
+    };
+    let mut updated = String::with_capacity(content.len() + result.len() + 2);
+    updated.push_str(&content[..start]);
+    if existing.is_none() {
+        if !updated.ends_with('\n') {
+            updated.push('\n');
+        }
+        updated.push('\n');
+    }
+    updated.push_str(&result);
+    let rest = &content[end..];
+    if !rest.starts_with('\n') {
+        updated.push('\n');
+    }
+    updated.push_str(rest);
+
+    encryption::write_note(path, &updated)
+}
+
+/// Writes the block to a new file only the user can read. The name is predictable, so a
+/// leftover is removed first and anything recreated in its place is refused, not followed.
+fn write_script(path: &Path, code: &str) -> Result<(), String> {
+    let _ = fs::remove_file(path);
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path).map_err(|e| e.to_string())?;
+    file.write_all(code.as_bytes()).map_err(|e| e.to_string())
+}
+
+struct RunOutcome {
+    code: Option<i32>,
+    timed_out: bool,
+    cancelled: bool,
+    output: String,
+}
+
+fn run_block(
+    app: &AppHandle,
+    job: &jobs::JobHandle,
+    note_dir: &Path,
+    interpreter: &Interpreter,
+    code: &str,
+    timeout: Duration,
+) -> Result<RunOutcome, String> {
+    let extension = if interpreter.extension.is_empty() { "txt" } else { &interpreter.extension };
+    let script = std::env::temp_dir().join(format!("xnote-run-{}-{}.{}", std::process::id(), job.id(), extension));
+    write_script(&script, code)?;
+
+    let mut cmd = Command::new(&interpreter.command);
+    cmd.args(&interpreter.args)
+        .arg(&script)
+        .current_dir(note_dir)
+        .envs(&interpreter.env)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        cmd.process_group(0);
+    }
+
+    let mut child = match cmd.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            let _ = fs::remove_file(&script);
+            return Err(format!("Failed to start `{}`: {}", interpreter.command, e));
+        }
+    };
+
+    let captured = Arc::new(Mutex::new(String::new()));
+    let mut readers = Vec::new();
+    if let Some(stdout) = child.stdout.take() {
+        readers.push(spawn_stream_reader(app.clone(), job.id(), "stdout", stdout, captured.clone()));
+    }
+    if let Some(stderr) = child.stderr.take() {
+        readers.push(spawn_stream_reader(app.clone(), job.id(), "stderr", stderr, captured.clone()));
+    }
+
+    let started = Instant::now();
+    let mut timed_out = false;
+    let mut cancelled = false;
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break Some(status),
+            Ok(None) => {}
+            Err(_) => break None,
+        }
+        if job.is_cancelled() {
+            cancelled = true;
+            kill_process_tree(&mut child);
+            break child.wait().ok();
+        }
+        if started.elapsed() >= timeout {
+            timed_out = true;
+            kill_process_tree(&mut child);
+            break child.wait().ok();
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    };
+
+    // A background process the code started keeps the output pipes open. Give it a moment,
+    // then end the whole group so the run finishes; readers still stuck are left behind.
+    if !wait_for_readers(&readers, OUTPUT_GRACE) {
+        kill_process_tree(&mut child);
+        wait_for_readers(&readers, OUTPUT_GRACE);
+    }
+    let _ = fs::remove_file(&script);
+
+    let output = captured.lock().unwrap().clone();
+    Ok(RunOutcome {
+        code: status.and_then(|s| s.code()),
+        timed_out,
+        cancelled,
+        output,
+    })
+}
+
+/// Runs fenced code block `block_index` of the note at `path` in the note's directory.
+/// Output is streamed as `code-run-output` events and the run ends with `code-run-exit`;
+/// cancel it with `cancel_job`. Returns the job id.
+#[tauri::command]
+pub fn run_code_block(
+    app: AppHandle,
+    path: String,
+    block_index: usize,
+    timeout_secs: Option<u64>,
+    write_back: Option<bool>,
+) -> Result<u64, String> {
+    let note_path = Path::new(&path).to_path_buf();
+    let content = encryption::read_note(&note_path)?;
+    let block = markdown::fenced_code_blocks(&content)
+        .into_iter()
+        .nth(block_index)
+        .ok_or_else(|| format!("Code block {} not found", block_index))?;
+
+    let config = load_runner_config();
+    let interpreter = resolve_interpreter(&config, &block.lang)?;
+    let timeout = Duration::from_secs(
+        timeout_secs
+            .or(config.timeout_secs)
+            .unwrap_or(DEFAULT_TIMEOUT_SECS)
+            .max(1),
+    );
+    let note_dir = note_path
+        .parent()
+        .map(|p| p.to_path_buf())
+        .unwrap_or_else(std::env::temp_dir);
+
+    let job = jobs::start(&app, "run-code");
+    let job_id = job.id();
+    let app_handle = app.clone();
+    async_runtime::spawn_blocking(move || {
+        let started = Instant::now();
+        let result = run_block(&app_handle, &job, &note_dir, &interpreter, &block.code, timeout);
+
+        let (exit, job_result) = match result {
+            Ok(outcome) => {
+                let mut error = None;
+                let mut written_back = false;
+                if write_back.unwrap_or(false) && !outcome.cancelled {
+                    match write_result_block(&note_path, block_index, &block.code, &outcome.output) {
+                        Ok(()) => written_back = true,
+                        Err(e) => error = Some(e),
+                    }
+                }
+                let job_result = if outcome.timed_out {
+                    Err(format!("Timed out after {}s", timeout.as_secs()))
+                } else {
+                    Ok(())
+                };
+                (
+                    CodeRunExit {
+                        job_id,
+                        code: outcome.code,
+                        timed_out: outcome.timed_out,
+                        cancelled: outcome.cancelled,
+                        duration_ms: started.elapsed().as_millis(),
+                        written_back,
+                        error,
+                    },
+                    job_result,
+                )
+            }
+            Err(err) => (
+                CodeRunExit {
+                    job_id,
+                    code: None,
+                    timed_out: false,
+                    cancelled: false,
+                    duration_ms: started.elapsed().as_millis(),
+                    written_back: false,
+                    error: Some(err.clone()),
+                },
+                Err(err),
+            ),
+        };
+
+        let _ = app_handle.emit("code-run-exit", exit);
+        job.finish_with(&job_result);
+    });
+
+    Ok(job_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(content: &str) -> (tempfile::TempDir, std::path::PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("note.md");
+        fs::write(&path, content).unwrap();
+        (dir, path)
+    }
+
+    #[test]
+    fn result_block_is_inserted_below_the_code() {
+        let (_dir, path) = note("Intro\n\n```sh\necho hi\n```\nAfter\n");
+        write_result_block(&path, 0, "echo hi\n", "hi\n").unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "Intro\n\n```sh\necho hi\n```\n\n```output\nhi\n```\nAfter\n"
+        );
+    }
+
+    #[test]
+    fn existing_result_block_is_replaced() {
+        let (_dir, path) = note("```sh\necho hi\n```\n\n```output\nold\n```\nAfter\n");
+        write_result_block(&path, 0, "echo hi\n", "new\n").unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "```sh\necho hi\n```\n\n```output\nnew\n```\nAfter\n"
+        );
+        // Running again keeps a single result block.
+        write_result_block(&path, 0, "echo hi\n", "newer\n").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap().matches("```output").count(), 1);
+    }
+
+    #[test]
+    fn output_block_of_another_language_is_not_replaced() {
+        let (_dir, path) = note("```sh\necho hi\n```\n\n```text\nkeep\n```\n");
+        write_result_block(&path, 0, "echo hi\n", "hi").unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(content.contains("```output\nhi\n```"), "{content}");
+        assert!(content.contains("```text\nkeep\n```"), "{content}");
+    }
+
+    #[test]
+    fn fences_in_the_output_are_escaped() {
+        let (_dir, path) = note("```sh\ncat README.md\n```\n");
+        write_result_block(&path, 0, "cat README.md\n", "```rust\nfn main() {}\n```\n").unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        let blocks = markdown::fenced_code_blocks(&content);
+        assert_eq!(blocks.len(), 2, "{content}");
+        assert_eq!(blocks[1].lang, RESULT_LANG);
+        assert_eq!(blocks[1].code, "```rust\nfn main() {}\n```\n");
+    }
+
+    #[test]
+    fn changed_code_is_not_overwritten() {
+        let (_dir, path) = note("```sh\necho bye\n```\n");
+        assert!(write_result_block(&path, 0, "echo hi\n", "hi").is_err());
+        assert!(write_result_block(&path, 1, "echo bye\n", "bye").is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "```sh\necho bye\n```\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn scripts_are_private_and_replace_leftovers() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("run.sh");
+        fs::write(&script, "stale").unwrap();
+        write_script(&script, "echo hi\n").unwrap();
+        assert_eq!(fs::read_to_string(&script).unwrap(), "echo hi\n");
+        assert_eq!(fs::metadata(&script).unwrap().permissions().mode() & 0o777, 0o600);
+    }
+}
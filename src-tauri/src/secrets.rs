@@ -0,0 +1,489 @@
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use argon2::Argon2;
+use base64::{engine::general_purpose, Engine as _};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Config values of this form refer to a secret store entry instead of holding the secret.
+pub const HANDLE_PREFIX: &str = "secret:";
+pub const STORE_FILE: &str = "secrets.json";
+pub const KEY_FILE: &str = "secrets.key";
+const STORE_VERSION: u32 = 1;
+pub const MIN_PASSPHRASE_LEN: usize = 8;
+const LOCKED: &str = "The secret store is locked; unlock it with your passphrase first";
+
+/// Where the store's encryption key comes from.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum KeySource {
+    /// A random key kept in the macOS Keychain or Windows Credential Manager.
+    Keyring,
+    /// A random key in `secrets.key` next to the store, readable only by the user.
+    /// Used where no OS keyring is available.
+    KeyFile,
+    /// Derived from a passphrase with Argon2id; the store has to be unlocked after each start.
+    Passphrase,
+}
+
+/// On-disk layout of secrets.json; the secrets are a JSON object encrypted with AES-256-GCM.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StoreFile {
+    version: u32,
+    key_source: KeySource,
+    /// Argon2id salt, for passphrase-derived keys.
+    salt: Option<String>,
+    nonce: String,
+    ciphertext: String,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SecretStoreStatus {
+    exists: bool,
+    key_source: Option<KeySource>,
+    unlocked: bool,
+    keyring_available: bool,
+}
+
+/// Key of the store in use, once it has been read from the keyring/key file or unlocked.
+static UNLOCKED_KEY: Mutex<Option<[u8; 32]>> = Mutex::new(None);
+
+/// Folder of the store for tests, which must not touch the real one.
+#[cfg(test)]
+static TEST_ROOT: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+fn store_root() -> Result<PathBuf, String> {
+    #[cfg(test)]
+    if let Some(root) = TEST_ROOT.lock().unwrap().clone() {
+        return Ok(root);
+    }
+    crate::get_xnote_root()
+}
+
+fn store_path() -> Result<PathBuf, String> {
+    Ok(store_root()?.join(STORE_FILE))
+}
+
+fn key_file_path() -> Result<PathBuf, String> {
+    Ok(store_root()?.join(KEY_FILE))
+}
+
+fn keyring_available() -> bool {
+    cfg!(any(target_os = "macos", target_os = "windows"))
+}
+
+fn default_key_source() -> KeySource {
+    if keyring_available() {
+        KeySource::Keyring
+    } else {
+        KeySource::KeyFile
+    }
+}
+
+fn decode_key(encoded: &str) -> Result<[u8; 32], String> {
+    let bytes = general_purpose::STANDARD
+        .decode(encoded.trim())
+        .map_err(|e| e.to_string())?;
+    bytes
+        .try_into()
+        .map_err(|_| "Stored secret key has the wrong length".to_string())
+}
+
+fn new_key() -> [u8; 32] {
+    Aes256Gcm::generate_key(OsRng).into()
+}
+
+/// Writes `data` via a temp file, readable only by the current user.
+fn write_private(path: &Path, data: &[u8]) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, data).map_err(|e| e.to_string())?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&tmp, fs::Permissions::from_mode(0o600)).map_err(|e| e.to_string())?;
+    }
+    fs::rename(&tmp, path).map_err(|e| e.to_string())
+}
+
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+fn keyring_key(create: bool) -> Result<[u8; 32], String> {
+    let entry = keyring::Entry::new("xnote", "secret-store-key").map_err(|e| e.to_string())?;
+    match entry.get_password() {
+        Ok(encoded) => decode_key(&encoded),
+        Err(keyring::Error::NoEntry) if create => {
+            let key = new_key();
+            entry
+                .set_password(&general_purpose::STANDARD.encode(key))
+                .map_err(|e| e.to_string())?;
+            Ok(key)
+        }
+        Err(keyring::Error::NoEntry) => Err("The secret store key is missing from the keyring".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn keyring_key(_create: bool) -> Result<[u8; 32], String> {
+    Err("No OS keyring is available on this platform".to_string())
+}
+
+fn key_file_key(create: bool) -> Result<[u8; 32], String> {
+    let path = key_file_path()?;
+    match fs::read_to_string(&path) {
+        Ok(encoded) => decode_key(&encoded),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound && create => {
+            let key = new_key();
+            write_private(&path, general_purpose::STANDARD.encode(key).as_bytes())?;
+            Ok(key)
+        }
+        Err(e) => Err(format!("Cannot read {}: {}", path.display(), e)),
+    }
+}
+
+/// A 256-bit key from `passphrase` with Argon2id.
+pub fn derive_key(passphrase: &str, salt: &[u8]) -> Result<[u8; 32], String> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| e.to_string())?;
+    Ok(key)
+}
+
+fn read_store_file() -> Result<Option<StoreFile>, String> {
+    match fs::read_to_string(store_path()?) {
+        Ok(text) => serde_json::from_str(&text)
+            .map(Some)
+            .map_err(|e| format!("Secret store is corrupt: {}", e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn decrypt(key: &[u8; 32], file: &StoreFile) -> Result<BTreeMap<String, String>, String> {
+    let nonce = general_purpose::STANDARD.decode(&file.nonce).map_err(|e| e.to_string())?;
+    let ciphertext = general_purpose::STANDARD.decode(&file.ciphertext).map_err(|e| e.to_string())?;
+    if nonce.len() != 12 {
+        return Err("Secret store is corrupt: bad nonce".to_string());
+    }
+    let plaintext = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key))
+        .decrypt(Nonce::from_slice(&nonce), ciphertext.as_ref())
+        .map_err(|_| "Could not decrypt the secret store (wrong key or passphrase)".to_string())?;
+    serde_json::from_slice(&plaintext).map_err(|e| e.to_string())
+}
+
+/// The decrypted store, ready to be read or modified and saved again.
+struct OpenStore {
+    key: [u8; 32],
+    key_source: KeySource,
+    salt: Option<String>,
+    secrets: BTreeMap<String, String>,
+}
+
+impl OpenStore {
+    fn open() -> Result<Self, String> {
+        let file = read_store_file()?;
+        let key_source = file.as_ref().map(|f| f.key_source).unwrap_or_else(default_key_source);
+
+        // A cached key only ever belongs to the store file it was read for.
+        let cached = UNLOCKED_KEY.lock().unwrap().filter(|_| file.is_some());
+        let key = match (cached, key_source) {
+            (Some(key), _) => key,
+            (None, KeySource::Keyring) => keyring_key(file.is_none())?,
+            (None, KeySource::KeyFile) => key_file_key(file.is_none())?,
+            (None, KeySource::Passphrase) => return Err(LOCKED.to_string()),
+        };
+        let secrets = match file.as_ref() {
+            Some(f) => decrypt(&key, f)?,
+            None => BTreeMap::new(),
+        };
+        *UNLOCKED_KEY.lock().unwrap() = Some(key);
+
+        Ok(Self {
+            key,
+            key_source,
+            salt: file.and_then(|f| f.salt),
+            secrets,
+        })
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let plaintext = serde_json::to_vec(&self.secrets).map_err(|e| e.to_string())?;
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&self.key))
+            .encrypt(&nonce, plaintext.as_ref())
+            .map_err(|e| e.to_string())?;
+        let file = StoreFile {
+            version: STORE_VERSION,
+            key_source: self.key_source,
+            salt: self.salt.clone(),
+            nonce: general_purpose::STANDARD.encode(nonce),
+            ciphertext: general_purpose::STANDARD.encode(ciphertext),
+        };
+        let text = serde_json::to_string_pretty(&file).map_err(|e| e.to_string())?;
+        write_private(&store_path()?, text.as_bytes())
+    }
+}
+
+pub fn handle_for(id: &str) -> String {
+    format!("{}{}", HANDLE_PREFIX, id)
+}
+
+/// The secret id a config value refers to, if it is a handle.
+pub fn handle_id(value: &str) -> Option<&str> {
+    value.strip_prefix(HANDLE_PREFIX).filter(|id| !id.is_empty())
+}
+
+/// Stores a secret and returns the handle to put into config.
+pub fn set(id: &str, value: &str) -> Result<String, String> {
+    if id.trim().is_empty() {
+        return Err("Secret id must not be empty".to_string());
+    }
+    let mut store = OpenStore::open()?;
+    if store.secrets.get(id).map(|v| v != value).unwrap_or(true) {
+        store.secrets.insert(id.to_string(), value.to_string());
+        store.save()?;
+    }
+    Ok(handle_for(id))
+}
+
+pub fn get(id: &str) -> Result<Option<String>, String> {
+    let id = handle_id(id).unwrap_or(id);
+    Ok(OpenStore::open()?.secrets.get(id).cloned())
+}
+
+/// Deletes the secrets under `prefix` (e.g. `llm/`) whose ids are not in `used`.
+pub fn remove_unused(prefix: &str, used: &HashSet<&str>) -> Result<(), String> {
+    if read_store_file()?.is_none() {
+        return Ok(());
+    }
+    let mut store = OpenStore::open()?;
+    let before = store.secrets.len();
+    store.secrets.retain(|id, _| !id.starts_with(prefix) || used.contains(id.as_str()));
+    if store.secrets.len() != before {
+        store.save()?;
+    }
+    Ok(())
+}
+
+/// Installs a secret store taken from a backup, along with its key file if it had one.
+/// An existing store is never replaced, as its secrets may be newer than the backup's.
+pub fn import_store(store: &[u8], key_file: Option<&[u8]>) -> Result<KeySource, String> {
+    let path = store_path()?;
+    if path.exists() {
+        return Err("a secret store already exists".to_string());
+    }
+    let file: StoreFile = serde_json::from_slice(store).map_err(|e| format!("invalid secret store: {}", e))?;
+    if let Some(key) = key_file {
+        write_private(&key_file_path()?, key)?;
+    }
+    write_private(&path, store)?;
+    *UNLOCKED_KEY.lock().unwrap() = None;
+    Ok(file.key_source)
+}
+
+#[tauri::command]
+pub fn secret_store_status() -> Result<SecretStoreStatus, String> {
+    let file = read_store_file()?;
+    let key_source = file.as_ref().map(|f| f.key_source);
+    let unlocked = UNLOCKED_KEY.lock().unwrap().is_some() || key_source != Some(KeySource::Passphrase);
+    Ok(SecretStoreStatus {
+        exists: file.is_some(),
+        key_source,
+        unlocked,
+        keyring_available: keyring_available(),
+    })
+}
+
+/// Unlocks a passphrase-protected store for the rest of the session.
+#[tauri::command]
+pub fn unlock_secret_store(passphrase: String) -> Result<(), String> {
+    let Some(file) = read_store_file()? else {
+        return Err("There is no secret store to unlock".to_string());
+    };
+    if file.key_source != KeySource::Passphrase {
+        return Ok(());
+    }
+    let salt = file.salt.as_deref().ok_or_else(|| "Secret store is corrupt: missing salt".to_string())?;
+    let salt = general_purpose::STANDARD.decode(salt).map_err(|e| e.to_string())?;
+    let key = derive_key(&passphrase, &salt)?;
+    decrypt(&key, &file).map_err(|_| "Wrong passphrase".to_string())?;
+    *UNLOCKED_KEY.lock().unwrap() = Some(key);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn lock_secret_store() -> Result<(), String> {
+    *UNLOCKED_KEY.lock().unwrap() = None;
+    Ok(())
+}
+
+/// Protects the store with `passphrase`, or moves it back to the keyring (or key file)
+/// when `passphrase` is empty. The store must be unlocked.
+#[tauri::command]
+pub fn set_secret_store_passphrase(passphrase: Option<String>) -> Result<(), String> {
+    let mut store = OpenStore::open()?;
+    let passphrase = passphrase.filter(|p| !p.is_empty());
+
+    match passphrase {
+        Some(passphrase) => {
+            if passphrase.chars().count() < MIN_PASSPHRASE_LEN {
+                return Err(format!("The passphrase needs at least {} characters", MIN_PASSPHRASE_LEN));
+            }
+            let mut salt = [0u8; 16];
+            OsRng.fill_bytes(&mut salt);
+            store.key = derive_key(&passphrase, &salt)?;
+            store.key_source = KeySource::Passphrase;
+            store.salt = Some(general_purpose::STANDARD.encode(salt));
+        }
+        None => {
+            store.key_source = default_key_source();
+            store.key = match store.key_source {
+                KeySource::Keyring => keyring_key(true)?,
+                _ => key_file_key(true)?,
+            };
+            store.salt = None;
+        }
+    }
+
+    store.save()?;
+    *UNLOCKED_KEY.lock().unwrap() = Some(store.key);
+    if store.key_source != KeySource::KeyFile {
+        // The old random key no longer protects anything.
+        let _ = fs::remove_file(key_file_path()?);
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn list_secrets() -> Result<Vec<String>, String> {
+    Ok(OpenStore::open()?.secrets.into_keys().collect())
+}
+
+/// Stores a secret under `id`; returns the handle (`secret:<id>`) to reference it from config.
+#[tauri::command]
+pub fn set_secret(id: String, value: String) -> Result<String, String> {
+    set(&id, &value)
+}
+
+/// Looks up a secret by id or handle.
+#[tauri::command]
+pub fn get_secret(id: String) -> Result<Option<String>, String> {
+    get(&id)
+}
+
+#[tauri::command]
+pub fn delete_secret(id: String) -> Result<bool, String> {
+    let id = handle_id(&id).unwrap_or(&id).to_string();
+    let mut store = OpenStore::open()?;
+    if store.secrets.remove(&id).is_none() {
+        return Ok(false);
+    }
+    store.save()?;
+    Ok(true)
+}
+
+// Only where the default is a key file, so tests never touch the real keychain.
+#[cfg(all(test, not(any(target_os = "macos", target_os = "windows"))))]
+pub(crate) mod tests {
+    use super::*;
+    use std::sync::MutexGuard;
+
+    /// Tests share the unlocked key, so they take turns.
+    static SERIAL: Mutex<()> = Mutex::new(());
+
+    /// A store in a temporary folder for the duration of a test.
+    pub(crate) struct TempStore {
+        pub dir: tempfile::TempDir,
+        _serial: MutexGuard<'static, ()>,
+    }
+
+    impl TempStore {
+        pub fn new() -> Self {
+            let serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+            let dir = tempfile::tempdir().unwrap();
+            *TEST_ROOT.lock().unwrap() = Some(dir.path().to_path_buf());
+            *UNLOCKED_KEY.lock().unwrap() = None;
+            TempStore { dir, _serial: serial }
+        }
+
+        pub fn contents(&self) -> String {
+            fs::read_to_string(self.dir.path().join(STORE_FILE)).unwrap_or_default()
+        }
+    }
+
+    impl Drop for TempStore {
+        fn drop(&mut self) {
+            *TEST_ROOT.lock().unwrap() = None;
+            *UNLOCKED_KEY.lock().unwrap() = None;
+        }
+    }
+
+    #[test]
+    fn key_file_round_trip() {
+        let store = TempStore::new();
+        assert!(!secret_store_status().unwrap().exists);
+        assert_eq!(set("llm/a", "sk-plain").unwrap(), "secret:llm/a");
+        assert!(store.dir.path().join(KEY_FILE).is_file());
+        assert!(!store.contents().contains("sk-plain"));
+
+        // A fresh start reads the key file again.
+        lock_secret_store().unwrap();
+        assert_eq!(get("secret:llm/a").unwrap().as_deref(), Some("sk-plain"));
+        assert_eq!(get("llm/missing").unwrap(), None);
+        assert_eq!(list_secrets().unwrap(), vec!["llm/a"]);
+        assert!(delete_secret("secret:llm/a".to_string()).unwrap());
+        assert!(!delete_secret("llm/a".to_string()).unwrap());
+
+        let status = secret_store_status().unwrap();
+        assert_eq!((status.exists, status.key_source, status.unlocked), (true, Some(KeySource::KeyFile), true));
+    }
+
+    #[test]
+    fn passphrase_protected_store() {
+        let store = TempStore::new();
+        set("llm/a", "sk-plain").unwrap();
+        assert!(set_secret_store_passphrase(Some("short".to_string())).unwrap_err().contains("at least"));
+        set_secret_store_passphrase(Some("correct horse".to_string())).unwrap();
+        assert!(!store.dir.path().join(KEY_FILE).exists());
+        assert_eq!(get("llm/a").unwrap().as_deref(), Some("sk-plain"));
+
+        lock_secret_store().unwrap();
+        let status = secret_store_status().unwrap();
+        assert_eq!((status.key_source, status.unlocked), (Some(KeySource::Passphrase), false));
+        assert_eq!(get("llm/a").unwrap_err(), LOCKED);
+        assert_eq!(set("llm/b", "x").unwrap_err(), LOCKED);
+        assert_eq!(unlock_secret_store("wrong horse".to_string()).unwrap_err(), "Wrong passphrase");
+        assert_eq!(get("llm/a").unwrap_err(), LOCKED);
+
+        unlock_secret_store("correct horse".to_string()).unwrap();
+        assert_eq!(get("llm/a").unwrap().as_deref(), Some("sk-plain"));
+
+        // Back to a key file.
+        set_secret_store_passphrase(None).unwrap();
+        lock_secret_store().unwrap();
+        assert_eq!(secret_store_status().unwrap().key_source, Some(KeySource::KeyFile));
+        assert_eq!(get("llm/a").unwrap().as_deref(), Some("sk-plain"));
+    }
+
+    #[test]
+    fn removes_only_unused_secrets_under_the_prefix() {
+        let _store = TempStore::new();
+        remove_unused("llm/", &HashSet::new()).unwrap();
+        assert_eq!(read_store_file().unwrap().map(|f| f.key_source), None);
+
+        for id in ["llm/a", "llm/b", "sync/webdav"] {
+            set(id, "v").unwrap();
+        }
+        remove_unused("llm/", &HashSet::from(["llm/b"])).unwrap();
+        assert_eq!(list_secrets().unwrap(), vec!["llm/b", "sync/webdav"]);
+    }
+}
@@ -0,0 +1,546 @@
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+use pulldown_cmark::{Event, Parser};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use tauri::{async_runtime, AppHandle, Emitter};
+
+use crate::export::{self, UrlKind};
+use crate::{encryption, jobs, markdown};
+
+const ASSET_DIR: &str = "assets";
+const SEARCH_INDEX: &str = "search-index.json";
+/// Text kept per page in the search index; enough to match on without bloating the file.
+const MAX_INDEX_CHARS: usize = 8 * 1024;
+
+/// Characters escaped in a URL path segment.
+const SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+const SITE_CSS: &str = r#"
+.site { display: flex; min-height: 100vh; }
+.site-nav { width: 280px; flex-shrink: 0; padding: 24px 16px; border-right: 1px solid rgb(var(--color-border)); background: rgb(var(--color-surface)); position: sticky; top: 0; height: 100vh; overflow-y: auto; font-size: 14px; }
+.site-nav .site-title { display: block; font-weight: 600; color: rgb(var(--color-text)); text-decoration: none; margin-bottom: 12px; }
+.site-nav ul { list-style: none; margin: 0; padding-left: 12px; }
+.site-nav > ul { padding-left: 0; }
+.site-nav li { margin: 2px 0; }
+.site-nav a { color: rgb(var(--color-muted)); text-decoration: none; }
+.site-nav a:hover, .site-nav a[aria-current=page] { color: rgb(var(--color-text)); }
+.site-nav summary { cursor: pointer; color: rgb(var(--color-text)); }
+.site-search { width: 100%; margin-bottom: 12px; padding: 6px 8px; border-radius: 6px; border: 1px solid rgb(var(--color-border)); background: rgb(var(--color-bg)); color: rgb(var(--color-text)); }
+.site-results { margin-bottom: 12px; }
+.site-results:empty { display: none; }
+.site main { flex: 1; min-width: 0; }
+"#;
+
+const SEARCH_SCRIPT: &str = r#"
+(function () {
+  var root = document.body.dataset.root || '';
+  var input = document.querySelector('.site-search');
+  var results = document.querySelector('.site-results');
+  var index = null;
+  function load() {
+    if (index) return Promise.resolve(index);
+    return fetch(root + 'search-index.json').then(function (r) { return r.json(); }).then(function (data) { index = data; return data; });
+  }
+  function render(query) {
+    var terms = query.toLowerCase().split(/\s+/).filter(Boolean);
+    results.innerHTML = '';
+    if (!terms.length) return;
+    load().then(function (pages) {
+      pages.filter(function (p) {
+        var hay = (p.title + ' ' + p.text).toLowerCase();
+        return terms.every(function (t) { return hay.indexOf(t) !== -1; });
+      }).slice(0, 20).forEach(function (p) {
+        var li = document.createElement('li');
+        var a = document.createElement('a');
+        a.href = root + p.url;
+        a.textContent = p.title;
+        li.appendChild(a);
+        results.appendChild(li);
+      });
+    }).catch(function () {});
+  }
+  if (input) input.addEventListener('input', function () { render(input.value); });
+})();
+"#;
+
+struct Page {
+    source: PathBuf,
+    /// Output path relative to the site root, with `/` separators.
+    url: String,
+    label: String,
+}
+
+enum NavNode {
+    Dir { name: String, children: Vec<NavNode> },
+    Page(usize),
+}
+
+#[derive(Serialize)]
+struct SearchEntry {
+    title: String,
+    url: String,
+    text: String,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct SiteBuilt {
+    job_id: u64,
+    out_dir: String,
+    pages: usize,
+    assets: usize,
+}
+
+/// Resolves `.` and `..` without touching the filesystem, so paths can be compared.
+/// A `..` that would climb above the start of a relative path is kept; above `/` it is
+/// dropped, as the filesystem does. The result is not confined to anything: callers
+/// check that it stays inside the folder they expect.
+pub fn lexical_normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match out.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    out.pop();
+                }
+                Some(Component::RootDir | Component::Prefix(_)) => {}
+                _ => out.push(".."),
+            },
+            other => out.push(other.as_os_str()),
+        }
+    }
+    out
+}
+
+/// Whether `path` is a file inside `root`, also once symlinks are resolved. Both paths are
+/// expected to be lexically normalized.
+pub fn is_file_inside(root: &Path, path: &Path) -> bool {
+    if !path.starts_with(root) || !path.is_file() {
+        return false;
+    }
+    match (fs::canonicalize(root), fs::canonicalize(path)) {
+        (Ok(real_root), Ok(real)) => real.starts_with(real_root),
+        _ => false,
+    }
+}
+
+fn is_note(name: &str) -> bool {
+    name.ends_with(".md") || name.ends_with(".uml") || name.ends_with(".puml")
+}
+
+fn has_scheme(url: &str) -> bool {
+    match url.find(':') {
+        Some(pos) => !url[..pos].contains('/'),
+        None => false,
+    }
+}
+
+/// Walks `dir` the way `get_files` does (hidden entries skipped, folders first, then by
+/// name), without its depth limit. Folders without notes are left out, and encrypted notes
+/// are never published.
+fn collect(dir: &Path, folder: &Path, skip: &Path, pages: &mut Vec<Page>) -> Vec<NavNode> {
+    let Ok(entries) = fs::read_dir(dir) else { return Vec::new() };
+    let mut entries: Vec<(String, PathBuf, bool)> = entries
+        .flatten()
+        .map(|e| (e.file_name().to_string_lossy().to_string(), e.path(), e.path().is_dir()))
+        .filter(|(name, path, is_dir)| {
+            !name.starts_with('.')
+                && (if *is_dir { lexical_normalize(path) != skip } else { is_note(name) && !encryption::is_encrypted(path) })
+        })
+        .collect();
+    entries.sort_by(|a, b| if a.2 == b.2 { a.0.cmp(&b.0) } else { b.2.cmp(&a.2) });
+
+    let mut nodes = Vec::new();
+    for (name, path, is_dir) in entries {
+        if is_dir {
+            let children = collect(&path, folder, skip, pages);
+            if !children.is_empty() {
+                nodes.push(NavNode::Dir { name, children });
+            }
+            continue;
+        }
+        let rel = path.strip_prefix(folder).unwrap_or(&path).with_extension("html");
+        let url = rel
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().to_string())
+            .collect::<Vec<_>>()
+            .join("/");
+        let label = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or(name);
+        nodes.push(NavNode::Page(pages.len()));
+        pages.push(Page { source: lexical_normalize(&path), url, label });
+    }
+    nodes
+}
+
+/// Notes under `folder` in sidebar order.
+pub fn note_paths(folder: &Path) -> Vec<PathBuf> {
+    let mut pages = Vec::new();
+    collect(folder, folder, Path::new(""), &mut pages);
+    pages.into_iter().map(|p| p.source).collect()
+}
+
+pub fn encode_path(path: &str) -> String {
+    path.split('/')
+        .map(|segment| utf8_percent_encode(segment, SEGMENT).to_string())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Relative link from the page at `from` to `to`, both site-root-relative.
+pub fn relative_url(from: &str, to: &str) -> String {
+    let from_dir: Vec<&str> = from.split('/').collect::<Vec<_>>();
+    let from_dir = &from_dir[..from_dir.len() - 1];
+    let to_parts: Vec<&str> = to.split('/').collect();
+    let common = from_dir
+        .iter()
+        .zip(to_parts.iter())
+        .take_while(|(a, b)| a == b)
+        .count()
+        .min(to_parts.len() - 1);
+    let mut parts: Vec<&str> = vec![".."; from_dir.len() - common];
+    parts.extend_from_slice(&to_parts[common..]);
+    encode_path(&parts.join("/"))
+}
+
+fn root_prefix(url: &str) -> String {
+    "../".repeat(url.matches('/').count())
+}
+
+/// Where a referenced file is copied, given its path below the workspace root: under
+/// `assets/`, keeping that path with leading dots dropped so static hosts don't hide it.
+fn asset_url(rel: &Path) -> String {
+    let rel: Vec<String> = rel
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().trim_start_matches('.').to_string())
+        .filter(|c| !c.is_empty())
+        .collect();
+    format!("{}/{}", ASSET_DIR, rel.join("/"))
+}
+
+fn render_nav(nodes: &[NavNode], pages: &[Page], current: Option<usize>, out: &mut String) -> bool {
+    let mut contains_current = false;
+    out.push_str("<ul>");
+    for node in nodes {
+        match node {
+            NavNode::Dir { name, children } => {
+                let mut inner = String::new();
+                let open = render_nav(children, pages, current, &mut inner);
+                contains_current |= open;
+                out.push_str(&format!(
+                    "<li><details{}><summary>{}</summary>{}</details></li>",
+                    if open { " open" } else { "" },
+                    export::escape_html(name),
+                    inner
+                ));
+            }
+            NavNode::Page(i) => {
+                let page = &pages[*i];
+                let is_current = current == Some(*i);
+                contains_current |= is_current;
+                let href = match current {
+                    Some(c) => relative_url(&pages[c].url, &page.url),
+                    None => encode_path(&page.url),
+                };
+                out.push_str(&format!(
+                    "<li><a href=\"{}\"{}>{}</a></li>",
+                    href,
+                    if is_current { " aria-current=\"page\"" } else { "" },
+                    export::escape_html(&page.label)
+                ));
+            }
+        }
+    }
+    out.push_str("</ul>");
+    contains_current
+}
+
+fn site_document(site_title: &str, title: &str, theme: &str, root: &str, nav: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n<meta name=\"generator\" content=\"XNote\">\n<title>{title} - {site}</title>\n<style>{css}{site_css}</style>\n</head>\n<body data-root=\"{root}\">\n<div class=\"site\">\n<nav class=\"site-nav\">\n<a class=\"site-title\" href=\"{root}index.html\">{site}</a>\n<input class=\"site-search\" type=\"search\" placeholder=\"Search\">\n<ul class=\"site-results\"></ul>\n{nav}\n</nav>\n<main>\n{body}</main>\n</div>\n<script>{script}</script>\n</body>\n</html>\n",
+        title = export::escape_html(title),
+        site = export::escape_html(site_title),
+        css = export::theme_css(theme),
+        site_css = SITE_CSS,
+        root = root,
+        nav = nav,
+        body = body,
+        script = SEARCH_SCRIPT,
+    )
+}
+
+fn plain_text(source: &str) -> String {
+    let mut text = String::new();
+    for event in Parser::new_ext(source, markdown::markdown_options()) {
+        match event {
+            Event::Text(t) | Event::Code(t) => {
+                text.push_str(&t);
+                text.push(' ');
+            }
+            Event::SoftBreak | Event::HardBreak => text.push(' '),
+            _ => {}
+        }
+    }
+    let mut out = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if out.len() > MAX_INDEX_CHARS {
+        let mut end = MAX_INDEX_CHARS;
+        while !out.is_char_boundary(end) {
+            end -= 1;
+        }
+        out.truncate(end);
+    }
+    out
+}
+
+/// Markdown for a note; PlantUML sources are wrapped in a fenced block.
+pub fn note_source(path: &Path) -> Result<String, String> {
+    let text = encryption::read_note(path)?;
+    let is_plantuml = path
+        .extension()
+        .map(|e| e == "uml" || e == "puml")
+        .unwrap_or(false);
+    if is_plantuml {
+        let fence = markdown::code_fence(&text);
+        return Ok(format!("{fence}plantuml\n{}\n{fence}\n", text.trim_end_matches('\n')));
+    }
+    Ok(text)
+}
+
+fn write_file(path: &Path, contents: &[u8]) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    fs::write(path, contents).map_err(|e| e.to_string())
+}
+
+fn build(folder: &Path, out_dir: &Path, root: &Path, theme: &str, job: &jobs::JobHandle) -> Result<SiteBuilt, String> {
+    if !folder.is_dir() {
+        return Err("Folder does not exist".to_string());
+    }
+    if out_dir == folder {
+        return Err("The output folder must differ from the source folder".to_string());
+    }
+    let site_title = folder
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "Notes".to_string());
+
+    let mut pages = Vec::new();
+    let nav = collect(folder, folder, out_dir, &mut pages);
+    if pages.is_empty() {
+        return Err("The folder contains no notes".to_string());
+    }
+    let by_source: HashMap<PathBuf, usize> = pages.iter().enumerate().map(|(i, p)| (p.source.clone(), i)).collect();
+    let mut assets: HashMap<PathBuf, String> = HashMap::new();
+    let mut index: Vec<SearchEntry> = Vec::with_capacity(pages.len());
+    let total = pages.len();
+
+    for (i, page) in pages.iter().enumerate() {
+        if job.is_cancelled() {
+            return Ok(SiteBuilt { job_id: job.id(), out_dir: out_dir.to_string_lossy().to_string(), pages: i, assets: 0 });
+        }
+        job.progress(i, total, &page.url);
+        let source = note_source(&page.source)?;
+
+        let rendered = export::render_note(&source, theme, |kind, url| {
+            let url = markdown::trim_wrapping(url);
+            if url.is_empty() || url.starts_with('#') || has_scheme(url) {
+                return None;
+            }
+            let (path, fragment) = match url.find('#') {
+                Some(pos) => (&url[..pos], &url[pos..]),
+                None => (url, ""),
+            };
+            let path = path.split('?').next().unwrap_or(path);
+            let decoded = percent_decode_str(path).decode_utf8_lossy();
+            let target = lexical_normalize(&crate::normalize_ref_path(root, &page.source, &decoded)?);
+
+            if kind == UrlKind::Link {
+                if let Some(&j) = by_source.get(&target) {
+                    return Some(format!("{}{}", relative_url(&page.url, &pages[j].url), fragment));
+                }
+            }
+            // Only files inside the workspace are published; a link such as
+            // `../../.ssh/id_rsa`, or a symlink leading out, is left as written.
+            let Ok(rel) = target.strip_prefix(root) else { return None };
+            if !is_file_inside(root, &target) {
+                return None;
+            }
+            let asset = assets.entry(target.clone()).or_insert_with(|| asset_url(rel));
+            Some(format!("{}{}", relative_url(&page.url, asset), fragment))
+        });
+
+        let title = rendered.title.unwrap_or_else(|| page.label.clone());
+        let mut nav_html = String::new();
+        render_nav(&nav, &pages, Some(i), &mut nav_html);
+        let html = site_document(&site_title, &title, theme, &root_prefix(&page.url), &nav_html, &rendered.body);
+        write_file(&out_dir.join(&page.url), html.as_bytes())?;
+
+        index.push(SearchEntry { title, url: encode_path(&page.url), text: plain_text(&source) });
+    }
+
+    if !pages.iter().any(|p| p.url == "index.html") {
+        let mut nav_html = String::new();
+        render_nav(&nav, &pages, None, &mut nav_html);
+        let body = format!("<h1>{}</h1>\n{}\n", export::escape_html(&site_title), nav_html);
+        let html = site_document(&site_title, &site_title, theme, "", &nav_html, &body);
+        write_file(&out_dir.join("index.html"), html.as_bytes())?;
+    }
+
+    job.progress(total, total, "Copying assets");
+    for (source, url) in &assets {
+        if job.is_cancelled() {
+            break;
+        }
+        let dest = out_dir.join(url);
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        fs::copy(source, &dest).map_err(|e| format!("{}: {}", source.display(), e))?;
+    }
+
+    let index_json = serde_json::to_vec(&index).map_err(|e| e.to_string())?;
+    write_file(&out_dir.join(SEARCH_INDEX), &index_json)?;
+
+    Ok(SiteBuilt {
+        job_id: job.id(),
+        out_dir: out_dir.to_string_lossy().to_string(),
+        pages: pages.len(),
+        assets: assets.len(),
+    })
+}
+
+/// Publishes the notes under `folder` as a static site in `out_dir`: one page per note
+/// with a navigation tree mirroring the folder, links between notes pointing at the
+/// generated pages, only the files notes reference copied into `assets/`, and a
+/// `search-index.json` used by the built-in search box. `root_path` is the workspace
+/// root that `/`-prefixed references resolve against (defaults to `folder`).
+/// Runs as a `build-site` job and reports through `site-build-result`; returns the job id.
+#[tauri::command]
+pub fn build_site(
+    app: AppHandle,
+    folder: String,
+    out_dir: String,
+    root_path: Option<String>,
+    theme: Option<String>,
+) -> Result<u64, String> {
+    let folder = lexical_normalize(Path::new(&folder));
+    let out_dir = lexical_normalize(Path::new(&out_dir));
+    let root = root_path
+        .map(|p| lexical_normalize(Path::new(&p)))
+        .unwrap_or_else(|| folder.clone());
+    let theme = theme.unwrap_or_else(export::configured_theme);
+
+    let app_handle = app.clone();
+    let job = jobs::start(&app, "build-site");
+    let job_id = job.id();
+    async_runtime::spawn_blocking(move || {
+        let result = build(&folder, &out_dir, &root, &theme, &job);
+        if let Ok(built) = &result {
+            let _ = app_handle.emit("site-build-result", built.clone());
+        }
+        job.finish_with(&result);
+    });
+    Ok(job_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lexical_normalize_keeps_leading_parent_dirs() {
+        let cases = [
+            ("/w/notes/../img/./a.png", "/w/img/a.png"),
+            ("/w/../../etc/passwd", "/etc/passwd"),
+            ("/..", "/"),
+            ("a/b/../../c", "c"),
+            ("../a", "../a"),
+            ("a/../../b", "../b"),
+            ("./../../a/./b/..", "../../a"),
+            ("a/b/..", "a"),
+            ("", ""),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(lexical_normalize(Path::new(path)), PathBuf::from(expected), "{}", path);
+        }
+    }
+
+    #[test]
+    fn files_outside_the_root_are_not_inside() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path().join("ws");
+        fs::create_dir_all(root.join("img")).unwrap();
+        fs::write(root.join("img/a.png"), "a").unwrap();
+        fs::write(temp.path().join("secret"), "s").unwrap();
+
+        assert!(is_file_inside(&root, &root.join("img/a.png")));
+        assert!(!is_file_inside(&root, &root.join("img")));
+        assert!(!is_file_inside(&root, &root.join("img/missing.png")));
+        assert!(!is_file_inside(&root, &lexical_normalize(&root.join("img/../../secret"))));
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(temp.path().join("secret"), root.join("img/link.png")).unwrap();
+            assert!(!is_file_inside(&root, &root.join("img/link.png")));
+        }
+    }
+
+    #[test]
+    fn relative_urls_between_pages() {
+        let cases = [
+            ("index.html", "a/b.html", "a/b.html"),
+            ("a/b.html", "c.html", "../c.html"),
+            ("a/b.html", "a/c.html", "c.html"),
+            ("a/b.html", "a/b.html", "b.html"),
+            ("a/index.html", "a.html", "../a.html"),
+            ("a/x/b.html", "a/y/c d.html", "../y/c%20d.html"),
+            ("a/b/c.html", "assets/a/#1.png", "../../assets/a/%231.png"),
+        ];
+        for (from, to, expected) in cases {
+            assert_eq!(relative_url(from, to), expected, "{} -> {}", from, to);
+        }
+    }
+
+    #[test]
+    fn asset_urls_drop_leading_dots() {
+        let cases = [
+            ("img/a.png", "assets/img/a.png"),
+            (".xnote_assets/note/a.png", "assets/xnote_assets/note/a.png"),
+            ("docs/.hidden/..b.pdf", "assets/docs/hidden/b.pdf"),
+        ];
+        for (rel, expected) in cases {
+            assert_eq!(asset_url(Path::new(rel)), expected, "{}", rel);
+        }
+    }
+
+    #[test]
+    fn schemes_are_recognized() {
+        let cases = [
+            ("https://example.com/a.png", true),
+            ("mailto:me@example.com", true),
+            ("data:image/png;base64,AA", true),
+            ("img/a.png", false),
+            ("img/a:b.png", false),
+            ("../a.md", false),
+            ("#heading", false),
+        ];
+        for (url, expected) in cases {
+            assert_eq!(has_scheme(url), expected, "{}", url);
+        }
+    }
+}
@@ -0,0 +1,836 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, UNIX_EPOCH};
+use tauri::{async_runtime, AppHandle, Emitter};
+use walkdir::WalkDir;
+
+use crate::webdav::WebDav;
+use crate::{jobs, quarantine, secrets, site};
+
+/// Per-remote sync state inside the workspace; never synced itself.
+pub const STATE_DIR: &str = ".xnote_sync";
+const STATE_VERSION: u32 = 1;
+const TEMP_SUFFIX: &str = ".xnote-sync-tmp";
+const SKIPPED_DIRS: &[&str] = &[quarantine::QUARANTINE_DIR, STATE_DIR];
+const SKIPPED_FILES: &[&str] = &[".DS_Store", "Thumbs.db", "desktop.ini"];
+/// Error for a file that changed between listing it and writing or removing it.
+pub const CHANGED_DURING_SYNC: &str = "changed while syncing; sync again";
+
+/// Set while a sync runs; two syncs of the same files would trip over each other.
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// `sync` in config.json.
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct SyncConfig {
+    /// `folder` or `webdav`.
+    provider: String,
+    /// Folder the workspace syncs with, e.g. on a NAS mount or USB drive.
+    folder: String,
+    webdav: WebDavConfig,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct WebDavConfig {
+    /// Folder URL, e.g. `https://cloud.example.com/remote.php/dav/files/me/Notes`.
+    url: String,
+    username: String,
+    /// Normally a secret store handle.
+    password: String,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct ConfigWithSync {
+    sync: SyncConfig,
+}
+
+fn load_config() -> SyncConfig {
+    crate::get_config()
+        .ok()
+        .and_then(|text| serde_json::from_str::<ConfigWithSync>(&text).ok())
+        .map(|c| c.sync)
+        .unwrap_or_default()
+}
+
+/// What a provider reports about one file. Two infos with equal fields are taken to
+/// mean the file has not changed.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct FileInfo {
+    pub size: u64,
+    /// Milliseconds since the Unix epoch.
+    pub modified: i64,
+    /// An extra change marker, such as an HTTP ETag; empty where there is none.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub version: String,
+}
+
+/// One side of a sync: the workspace, or the place it syncs with. Paths are relative
+/// to the provider's root and separated by `/`.
+pub trait Provider {
+    /// Where the files live, for reports and to tell sync states apart.
+    fn location(&self) -> String;
+    /// Every file that takes part in syncing.
+    fn list(&self) -> Result<HashMap<String, FileInfo>, String>;
+    fn read(&self, path: &str) -> Result<Vec<u8>, String>;
+    /// Writes a file, creating its folder, and returns its state afterwards. `replaces` is
+    /// the file as listed, or `None` for a new file; if the file no longer matches it, the
+    /// write fails with `CHANGED_DURING_SYNC` instead of losing the other change.
+    fn write(&self, path: &str, data: &[u8], modified: i64, replaces: Option<&FileInfo>) -> Result<FileInfo, String>;
+    /// Removes a file, failing like `write` if it no longer matches `listed`.
+    fn remove(&self, path: &str, listed: &FileInfo) -> Result<(), String>;
+    /// Called once all changes are applied. Returns where removed files can be found, if
+    /// the provider keeps them.
+    fn finish(&self) -> Result<Option<String>, String> {
+        Ok(None)
+    }
+}
+
+/// A local folder, or a remote one mounted into the file system. Removed files go into
+/// a quarantine batch in the folder rather than being deleted.
+pub struct Folder {
+    root: PathBuf,
+    trash: Mutex<Option<quarantine::QuarantineBatch>>,
+}
+
+impl Folder {
+    pub fn new(root: &Path) -> Self {
+        Self { root: root.to_path_buf(), trash: Mutex::new(None) }
+    }
+
+    fn path(&self, rel: &str) -> Result<PathBuf, String> {
+        check_path(rel)?;
+        Ok(self.root.join(rel))
+    }
+
+    fn check_unchanged(path: &Path, listed: Option<&FileInfo>) -> Result<(), String> {
+        let current = fs::metadata(path).ok().filter(|m| m.is_file()).map(|m| Self::info(&m));
+        if current.as_ref() != listed {
+            return Err(CHANGED_DURING_SYNC.to_string());
+        }
+        Ok(())
+    }
+
+    fn info(meta: &fs::Metadata) -> FileInfo {
+        let modified = meta
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_millis() as i64)
+            .unwrap_or(0);
+        FileInfo { size: meta.len(), modified, version: String::new() }
+    }
+}
+
+impl Provider for Folder {
+    fn location(&self) -> String {
+        self.root.to_string_lossy().to_string()
+    }
+
+    fn list(&self) -> Result<HashMap<String, FileInfo>, String> {
+        if !self.root.is_dir() {
+            return Err(format!("{} does not exist; is the drive connected?", self.root.display()));
+        }
+        let mut files = HashMap::new();
+        let walker = WalkDir::new(&self.root)
+            .into_iter()
+            .filter_entry(|e| !(e.file_type().is_dir() && is_skipped(&e.file_name().to_string_lossy(), true)));
+        for entry in walker {
+            let entry = entry.map_err(|e| e.to_string())?;
+            if !entry.file_type().is_file() || is_skipped(&entry.file_name().to_string_lossy(), false) {
+                continue;
+            }
+            let Ok(rel) = entry.path().strip_prefix(&self.root) else { continue };
+            let rel: Vec<String> = rel.components().map(|c| c.as_os_str().to_string_lossy().to_string()).collect();
+            let meta = entry.metadata().map_err(|e| e.to_string())?;
+            files.insert(rel.join("/"), Self::info(&meta));
+        }
+        Ok(files)
+    }
+
+    fn read(&self, path: &str) -> Result<Vec<u8>, String> {
+        let path = self.path(path)?;
+        fs::read(&path).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    fn write(&self, path: &str, data: &[u8], modified: i64, replaces: Option<&FileInfo>) -> Result<FileInfo, String> {
+        let path = self.path(path)?;
+        Self::check_unchanged(&path, replaces)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        // Written next to the target and renamed, so an interrupted sync leaves no half file.
+        let tmp = PathBuf::from(format!("{}{}", path.to_string_lossy(), TEMP_SUFFIX));
+        fs::write(&tmp, data).map_err(|e| format!("{}: {}", path.display(), e))?;
+        if let Ok(millis) = u64::try_from(modified) {
+            if let Ok(file) = File::options().write(true).open(&tmp) {
+                let _ = file.set_modified(UNIX_EPOCH + Duration::from_millis(millis));
+            }
+        }
+        if let Err(e) = fs::rename(&tmp, &path) {
+            let _ = fs::remove_file(&tmp);
+            return Err(format!("{}: {}", path.display(), e));
+        }
+        let meta = fs::metadata(&path).map_err(|e| e.to_string())?;
+        Ok(Self::info(&meta))
+    }
+
+    fn remove(&self, path: &str, listed: &FileInfo) -> Result<(), String> {
+        let path = self.path(path)?;
+        Self::check_unchanged(&path, Some(listed))?;
+        let mut trash = self.trash.lock().unwrap();
+        if trash.is_none() {
+            *trash = Some(quarantine::QuarantineBatch::create(&self.root)?);
+        }
+        if let Some(batch) = trash.as_mut() {
+            batch.add(&self.root, &path)?;
+        }
+        // Drop folders the removal left empty, up to the root.
+        let mut dir = path.parent();
+        while let Some(d) = dir.filter(|d| *d != self.root) {
+            if fs::remove_dir(d).is_err() {
+                break;
+            }
+            dir = d.parent();
+        }
+        Ok(())
+    }
+
+    fn finish(&self) -> Result<Option<String>, String> {
+        match self.trash.lock().unwrap().take() {
+            Some(batch) => batch.finish(),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Rejects provider paths that are empty or could step outside the provider's root.
+pub fn check_path(rel: &str) -> Result<(), String> {
+    let safe = !rel.is_empty() && rel.split('/').all(|part| !part.is_empty() && part != "." && part != "..");
+    if !safe {
+        return Err(format!("Unsafe sync path `{}`", rel));
+    }
+    Ok(())
+}
+
+/// Whether a file or folder of this name stays out of syncing: app state, OS litter and
+/// files still being written.
+pub fn is_skipped(name: &str, dir: bool) -> bool {
+    if dir {
+        SKIPPED_DIRS.contains(&name)
+    } else {
+        SKIPPED_FILES.contains(&name) || name.ends_with(TEMP_SUFFIX)
+    }
+}
+
+/// Both sides of a file as of the last sync, when they had the same contents.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct FileState {
+    sha256: String,
+    local: FileInfo,
+    remote: FileInfo,
+}
+
+/// `.xnote_sync/<id>.json`: what both sides looked like after the last sync.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SyncState {
+    version: u32,
+    remote: String,
+    last_sync: Option<String>,
+    files: BTreeMap<String, FileState>,
+}
+
+impl SyncState {
+    fn path(root: &Path, remote: &str) -> PathBuf {
+        let id: String = Sha256::digest(remote.as_bytes()).iter().take(8).map(|b| format!("{:02x}", b)).collect();
+        root.join(STATE_DIR).join(format!("{}.json", id))
+    }
+
+    fn load(path: &Path, remote: &str) -> Result<Self, String> {
+        match fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text).map_err(|e| format!("Invalid sync state {}: {}", path.display(), e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self {
+                version: STATE_VERSION,
+                remote: remote.to_string(),
+                last_sync: None,
+                files: BTreeMap::new(),
+            }),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, json).map_err(|e| e.to_string())?;
+        fs::rename(&tmp, path).map_err(|e| e.to_string())
+    }
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub enum Action {
+    Upload,
+    Download,
+    DeleteLocal,
+    DeleteRemote,
+    Conflict,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Change {
+    path: String,
+    action: Action,
+    /// For conflicts: where the older of the two versions was saved.
+    conflict_copy: Option<String>,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncReport {
+    /// Set by the caller; `run_sync` does not know about jobs.
+    job_id: u64,
+    root: String,
+    remote: String,
+    dry_run: bool,
+    changes: Vec<Change>,
+    uploaded: usize,
+    downloaded: usize,
+    deleted: usize,
+    conflicts: usize,
+    /// Where files removed from the workspace or the remote were moved.
+    local_quarantine: Option<String>,
+    remote_quarantine: Option<String>,
+}
+
+fn sha256(data: &[u8]) -> String {
+    Sha256::digest(data).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// `notes/a.md` becomes `notes/a (conflict 2026-10-17).md`, with a counter when taken.
+fn conflict_name(path: &str, taken: impl Fn(&str) -> bool) -> String {
+    let (dir, file) = match path.rfind('/') {
+        Some(i) => (&path[..=i], &path[i + 1..]),
+        None => ("", path),
+    };
+    let (stem, ext) = match file.rfind('.') {
+        Some(i) if i > 0 => (&file[..i], &file[i..]),
+        _ => (file, ""),
+    };
+    let date = chrono::Local::now().format("%Y-%m-%d");
+    let mut n = 1;
+    loop {
+        let counter = if n == 1 { String::new() } else { format!(" {}", n) };
+        let candidate = format!("{}{} (conflict {}{}){}", dir, stem, date, counter, ext);
+        if !taken(&candidate) {
+            return candidate;
+        }
+        n += 1;
+    }
+}
+
+/// The two providers being synced, with what they listed at the start.
+struct Sides<'a> {
+    local: &'a dyn Provider,
+    remote: &'a dyn Provider,
+    local_files: HashMap<String, FileInfo>,
+    remote_files: HashMap<String, FileInfo>,
+}
+
+impl Sides<'_> {
+    /// Reads a file that changed since the last sync; an unchanged hash means it was only
+    /// touched, which is recorded in `state` so it is not seen as changed again.
+    fn changed_contents(&self, local: bool, path: &str, state: &mut Option<FileState>) -> Result<Option<Vec<u8>>, String> {
+        let (provider, info) = if local {
+            (self.local, self.local_files.get(path))
+        } else {
+            (self.remote, self.remote_files.get(path))
+        };
+        let Some(info) = info else { return Ok(None) };
+        let data = provider.read(path)?;
+        if let Some(s) = state.as_mut().filter(|s| s.sha256 == sha256(&data)) {
+            if local {
+                s.local = info.clone();
+            } else {
+                s.remote = info.clone();
+            }
+        }
+        Ok(Some(data))
+    }
+}
+
+fn is_changed(info: Option<&FileInfo>, recorded: Option<&FileInfo>) -> bool {
+    match (info, recorded) {
+        (Some(info), Some(recorded)) => info != recorded,
+        (None, None) => false,
+        _ => true,
+    }
+}
+
+/// Two-way sync of `local` with `remote`. Each side's changes since the last sync (per the
+/// state file in `root`) are applied to the other. When both sides changed a file, the
+/// newer version keeps the name and the older one is saved beside it as a conflict copy
+/// on both sides. With `dry_run`, the changes are only reported.
+pub fn run_sync(
+    root: &Path,
+    local: &dyn Provider,
+    remote: &dyn Provider,
+    dry_run: bool,
+    job: &dyn jobs::Progress,
+) -> Result<SyncReport, String> {
+    let location = remote.location();
+    let state_path = SyncState::path(root, &location);
+    let mut state = SyncState::load(&state_path, &location)?;
+
+    job.progress(0, 0, "Listing files");
+    let sides = Sides {
+        local,
+        remote,
+        local_files: local.list()?,
+        remote_files: remote.list()?,
+    };
+    // An unmounted drive looks like an empty folder; syncing with it would delete everything.
+    if !state.files.is_empty() {
+        if sides.remote_files.is_empty() {
+            return Err(format!("{} is empty; not removing {} files from the workspace", location, state.files.len()));
+        }
+        if sides.local_files.is_empty() {
+            return Err(format!("The workspace is empty; not removing {} files from {}", state.files.len(), location));
+        }
+    }
+
+    let paths: BTreeSet<String> = sides
+        .local_files
+        .keys()
+        .chain(sides.remote_files.keys())
+        .chain(state.files.keys())
+        .cloned()
+        .collect();
+    let mut report = SyncReport {
+        job_id: 0,
+        root: root.to_string_lossy().to_string(),
+        remote: location,
+        dry_run,
+        changes: Vec::new(),
+        uploaded: 0,
+        downloaded: 0,
+        deleted: 0,
+        conflicts: 0,
+        local_quarantine: None,
+        remote_quarantine: None,
+    };
+
+    let total = paths.len();
+    let mut result = Ok(());
+    for (i, path) in paths.iter().enumerate() {
+        if job.is_cancelled() {
+            result = Err("Cancelled".to_string());
+            break;
+        }
+        job.progress(i, total, path);
+        if let Err(err) = sync_path(&sides, path, &mut state, dry_run, &mut report) {
+            result = Err(format!("{}: {}", path, err));
+            break;
+        }
+    }
+
+    // Whatever was applied before an error is recorded, so the next run does not redo it.
+    if !dry_run {
+        report.local_quarantine = local.finish()?;
+        report.remote_quarantine = remote.finish()?;
+        state.last_sync = Some(chrono::Local::now().to_rfc3339());
+        state.save(&state_path)?;
+    }
+    result.map(|_| report)
+}
+
+fn upload(sides: &Sides, path: &str, l: &FileInfo, data: Option<Vec<u8>>, state: &mut SyncState) -> Result<(), String> {
+    let data = data.ok_or("missing local contents")?;
+    let r = sides.remote.write(path, &data, l.modified, sides.remote_files.get(path))?;
+    state.files.insert(path.to_string(), FileState { sha256: sha256(&data), local: l.clone(), remote: r });
+    Ok(())
+}
+
+fn download(sides: &Sides, path: &str, r: &FileInfo, data: Option<Vec<u8>>, state: &mut SyncState) -> Result<(), String> {
+    let data = data.ok_or("missing remote contents")?;
+    let l = sides.local.write(path, &data, r.modified, sides.local_files.get(path))?;
+    state.files.insert(path.to_string(), FileState { sha256: sha256(&data), local: l, remote: r.clone() });
+    Ok(())
+}
+
+fn sync_path(sides: &Sides, path: &str, state: &mut SyncState, dry_run: bool, report: &mut SyncReport) -> Result<(), String> {
+    let mut recorded = state.files.get(path).cloned();
+    let local = sides.local_files.get(path);
+    let remote = sides.remote_files.get(path);
+
+    let mut local_data = None;
+    let mut remote_data = None;
+    if is_changed(local, recorded.as_ref().map(|s| &s.local)) {
+        local_data = sides.changed_contents(true, path, &mut recorded)?;
+    }
+    if is_changed(remote, recorded.as_ref().map(|s| &s.remote)) {
+        remote_data = sides.changed_contents(false, path, &mut recorded)?;
+    }
+    let local_changed = is_changed(local, recorded.as_ref().map(|s| &s.local));
+    let remote_changed = is_changed(remote, recorded.as_ref().map(|s| &s.remote));
+
+    let mut change = |action: Action, conflict_copy: Option<String>| {
+        match action {
+            Action::Upload => report.uploaded += 1,
+            Action::Download => report.downloaded += 1,
+            Action::DeleteLocal | Action::DeleteRemote => report.deleted += 1,
+            Action::Conflict => report.conflicts += 1,
+        }
+        report.changes.push(Change { path: path.to_string(), action, conflict_copy });
+    };
+
+    match (local, remote) {
+        (None, None) => {
+            state.files.remove(path);
+        }
+        _ if !local_changed && !remote_changed => {
+            if let Some(recorded) = recorded {
+                state.files.insert(path.to_string(), recorded);
+            }
+        }
+        // One side deleted a file the other did not touch.
+        (None, Some(r)) if !remote_changed => {
+            change(Action::DeleteRemote, None);
+            if !dry_run {
+                sides.remote.remove(path, r)?;
+                state.files.remove(path);
+            }
+        }
+        (Some(l), None) if !local_changed => {
+            change(Action::DeleteLocal, None);
+            if !dry_run {
+                sides.local.remove(path, l)?;
+                state.files.remove(path);
+            }
+        }
+        // Only one side changed, or a change meets a deletion on the other side.
+        (Some(l), None) => {
+            change(Action::Upload, None);
+            if !dry_run {
+                upload(sides, path, l, local_data, state)?;
+            }
+        }
+        (None, Some(r)) => {
+            change(Action::Download, None);
+            if !dry_run {
+                download(sides, path, r, remote_data, state)?;
+            }
+        }
+        (Some(l), Some(_)) if !remote_changed => {
+            change(Action::Upload, None);
+            if !dry_run {
+                upload(sides, path, l, local_data, state)?;
+            }
+        }
+        (Some(_), Some(r)) if !local_changed => {
+            change(Action::Download, None);
+            if !dry_run {
+                download(sides, path, r, remote_data, state)?;
+            }
+        }
+        (Some(l), Some(r)) => {
+            let local_data = match local_data {
+                Some(data) => data,
+                None => sides.local.read(path)?,
+            };
+            let remote_data = match remote_data {
+                Some(data) => data,
+                None => sides.remote.read(path)?,
+            };
+            let hash = sha256(&local_data);
+            if hash == sha256(&remote_data) {
+                // Both sides made the same edit, or the file was added to both.
+                state.files.insert(path.to_string(), FileState { sha256: hash, local: l.clone(), remote: r.clone() });
+                return Ok(());
+            }
+
+            let taken = |p: &str| {
+                sides.local_files.contains_key(p) || sides.remote_files.contains_key(p) || state.files.contains_key(p)
+            };
+            let copy = conflict_name(path, taken);
+            change(Action::Conflict, Some(copy.clone()));
+            if dry_run {
+                return Ok(());
+            }
+            if l.modified >= r.modified {
+                let cl = sides.local.write(&copy, &remote_data, r.modified, None)?;
+                let cr = sides.remote.write(&copy, &remote_data, r.modified, None)?;
+                state.files.insert(copy, FileState { sha256: sha256(&remote_data), local: cl, remote: cr });
+                let nr = sides.remote.write(path, &local_data, l.modified, Some(r))?;
+                state.files.insert(path.to_string(), FileState { sha256: hash, local: l.clone(), remote: nr });
+            } else {
+                let cl = sides.local.write(&copy, &local_data, l.modified, None)?;
+                let cr = sides.remote.write(&copy, &local_data, l.modified, None)?;
+                state.files.insert(copy, FileState { sha256: hash, local: cl, remote: cr });
+                let nl = sides.local.write(path, &remote_data, r.modified, Some(l))?;
+                state.files.insert(path.to_string(), FileState { sha256: sha256(&remote_data), local: nl, remote: r.clone() });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The provider for `remote`: a WebDAV server for http(s) URLs, signing in with the
+/// credentials from config.json, or else a folder.
+fn remote_provider(root: &Path, remote: &str, config: &SyncConfig) -> Result<Box<dyn Provider + Send>, String> {
+    if remote.starts_with("http://") || remote.starts_with("https://") {
+        // The stored sign-in belongs to the configured server; never hand it to another URL.
+        if remote != config.webdav.url.trim() {
+            return Ok(Box::new(WebDav::new(remote, "", "")?));
+        }
+        let password = match secrets::handle_id(&config.webdav.password) {
+            Some(id) => secrets::get(id)?.ok_or("The WebDAV password is missing from the secret store; enter it again")?,
+            None => config.webdav.password.clone(),
+        };
+        return Ok(Box::new(WebDav::new(remote, &config.webdav.username, &password)?));
+    }
+    let folder = site::lexical_normalize(Path::new(remote));
+    if folder.starts_with(root) || root.starts_with(&folder) {
+        return Err("The sync folder must not contain or be inside the workspace".to_string());
+    }
+    Ok(Box::new(Folder::new(&folder)))
+}
+
+/// Two-way sync of a workspace with a folder or WebDAV URL (default: the one configured
+/// under `sync` in config.json), emitting `sync-result` with what was (or, for a dry run,
+/// would be) changed. Returns the job id.
+#[tauri::command]
+pub fn sync_workspace(app: AppHandle, root_path: String, remote: Option<String>, dry_run: Option<bool>) -> Result<u64, String> {
+    let root = site::lexical_normalize(Path::new(&root_path));
+    let config = load_config();
+    let remote = match remote.filter(|r| !r.trim().is_empty()) {
+        Some(remote) => remote,
+        None if config.provider == "webdav" => config.webdav.url.clone(),
+        None => config.folder.clone(),
+    };
+    if remote.trim().is_empty() {
+        return Err("Choose a folder or server to sync with first".to_string());
+    }
+    let remote = remote_provider(&root, remote.trim(), &config)?;
+    if RUNNING.swap(true, Ordering::AcqRel) {
+        return Err("A sync is already running".to_string());
+    }
+    let dry_run = dry_run.unwrap_or(false);
+
+    let app_handle = app.clone();
+    let job = jobs::start(&app, "sync");
+    let job_id = job.id();
+    async_runtime::spawn_blocking(move || {
+        let result = run_sync(&root, &Folder::new(&root), remote.as_ref(), dry_run, &job)
+            .map(|report| SyncReport { job_id, ..report });
+        RUNNING.store(false, Ordering::Release);
+        if job.is_cancelled() {
+            job.finish(jobs::JobStatus::Cancelled, None);
+            return;
+        }
+        if let Ok(report) = &result {
+            let _ = app_handle.emit("sync-result", report.clone());
+        }
+        job.finish_with(&result);
+    });
+    Ok(job_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Dirs {
+        _temp: tempfile::TempDir,
+        local: PathBuf,
+        remote: PathBuf,
+    }
+
+    fn dirs() -> Dirs {
+        let temp = tempfile::tempdir().unwrap();
+        let (local, remote) = (temp.path().join("local"), temp.path().join("remote"));
+        fs::create_dir_all(&local).unwrap();
+        fs::create_dir_all(&remote).unwrap();
+        Dirs { _temp: temp, local, remote }
+    }
+
+    /// Writes a file with a fixed modification time, so changes never hinge on the clock.
+    fn put(dir: &Path, rel: &str, text: &str, modified_secs: u64) {
+        let path = dir.join(rel);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, text).unwrap();
+        touch(dir, rel, modified_secs);
+    }
+
+    fn touch(dir: &Path, rel: &str, modified_secs: u64) {
+        let file = File::options().write(true).open(dir.join(rel)).unwrap();
+        file.set_modified(UNIX_EPOCH + Duration::from_secs(modified_secs)).unwrap();
+    }
+
+    fn get(dir: &Path, rel: &str) -> Option<String> {
+        fs::read_to_string(dir.join(rel)).ok()
+    }
+
+    fn sync(dirs: &Dirs, dry_run: bool) -> Result<SyncReport, String> {
+        let cancelled = AtomicBool::new(false);
+        run_sync(&dirs.local, &Folder::new(&dirs.local), &Folder::new(&dirs.remote), dry_run, &cancelled)
+    }
+
+    fn actions(report: &SyncReport) -> Vec<(&str, Action)> {
+        report.changes.iter().map(|c| (c.path.as_str(), c.action)).collect()
+    }
+
+    #[test]
+    fn uploads_downloads_and_deletes_on_each_side() {
+        let dirs = dirs();
+        put(&dirs.local, "a.md", "local note", 1_000);
+        put(&dirs.remote, "sub/b.md", "remote note", 1_000);
+
+        let report = sync(&dirs, false).unwrap();
+        assert_eq!(actions(&report), vec![("a.md", Action::Upload), ("sub/b.md", Action::Download)]);
+        assert_eq!(get(&dirs.remote, "a.md").as_deref(), Some("local note"));
+        assert_eq!(get(&dirs.local, "sub/b.md").as_deref(), Some("remote note"));
+        assert!(sync(&dirs, false).unwrap().changes.is_empty());
+
+        put(&dirs.local, "a.md", "edited locally", 2_000);
+        put(&dirs.remote, "sub/b.md", "edited remotely", 2_000);
+        let report = sync(&dirs, false).unwrap();
+        assert_eq!(actions(&report), vec![("a.md", Action::Upload), ("sub/b.md", Action::Download)]);
+        assert_eq!(get(&dirs.remote, "a.md").as_deref(), Some("edited locally"));
+        assert_eq!(get(&dirs.local, "sub/b.md").as_deref(), Some("edited remotely"));
+
+        put(&dirs.local, "c.md", "keep", 1_000);
+        sync(&dirs, false).unwrap();
+        fs::remove_file(dirs.local.join("a.md")).unwrap();
+        fs::remove_file(dirs.remote.join("sub/b.md")).unwrap();
+        let report = sync(&dirs, false).unwrap();
+        assert_eq!(actions(&report), vec![("a.md", Action::DeleteRemote), ("sub/b.md", Action::DeleteLocal)]);
+        assert_eq!(get(&dirs.remote, "a.md"), None);
+        assert_eq!(get(&dirs.local, "sub/b.md"), None);
+        // Removed files are quarantined rather than deleted.
+        let kept = PathBuf::from(report.remote_quarantine.unwrap()).join("a.md");
+        assert_eq!(fs::read_to_string(kept).unwrap(), "edited locally");
+        assert!(report.local_quarantine.is_some());
+    }
+
+    #[test]
+    fn touched_but_unchanged_files_are_not_synced() {
+        let dirs = dirs();
+        put(&dirs.local, "a.md", "same", 1_000);
+        sync(&dirs, false).unwrap();
+
+        touch(&dirs.local, "a.md", 5_000);
+        touch(&dirs.remote, "a.md", 6_000);
+        assert!(sync(&dirs, false).unwrap().changes.is_empty());
+        // The new times were recorded, so the files are not read again next time either.
+        assert!(sync(&dirs, false).unwrap().changes.is_empty());
+        let remote = fs::metadata(dirs.remote.join("a.md")).unwrap().modified().unwrap();
+        assert_eq!(remote, UNIX_EPOCH + Duration::from_secs(6_000));
+    }
+
+    #[test]
+    fn edits_on_both_sides_keep_a_conflict_copy() {
+        let dirs = dirs();
+        put(&dirs.local, "notes/x.md", "base", 1_000);
+        sync(&dirs, false).unwrap();
+
+        put(&dirs.local, "notes/x.md", "older local edit", 2_000);
+        put(&dirs.remote, "notes/x.md", "newer remote edit", 3_000);
+        let report = sync(&dirs, false).unwrap();
+
+        let copy = format!("notes/x (conflict {}).md", chrono::Local::now().format("%Y-%m-%d"));
+        assert_eq!(actions(&report), vec![("notes/x.md", Action::Conflict)]);
+        assert_eq!(report.changes[0].conflict_copy.as_deref(), Some(copy.as_str()));
+        for dir in [&dirs.local, &dirs.remote] {
+            assert_eq!(get(dir, "notes/x.md").as_deref(), Some("newer remote edit"));
+            assert_eq!(get(dir, &copy).as_deref(), Some("older local edit"));
+        }
+        assert!(sync(&dirs, false).unwrap().changes.is_empty());
+    }
+
+    #[test]
+    fn dry_run_writes_nothing() {
+        let dirs = dirs();
+        put(&dirs.local, "a.md", "local", 1_000);
+        put(&dirs.remote, "b.md", "remote", 1_000);
+
+        let report = sync(&dirs, true).unwrap();
+        assert!(report.dry_run);
+        assert_eq!(actions(&report), vec![("a.md", Action::Upload), ("b.md", Action::Download)]);
+        assert_eq!(get(&dirs.remote, "a.md"), None);
+        assert_eq!(get(&dirs.local, "b.md"), None);
+        assert!(!dirs.local.join(STATE_DIR).exists());
+    }
+
+    #[test]
+    fn refuses_an_empty_remote_after_syncing() {
+        let dirs = dirs();
+        put(&dirs.local, "a.md", "note", 1_000);
+        sync(&dirs, false).unwrap();
+
+        // What an unmounted drive looks like.
+        fs::remove_file(dirs.remote.join("a.md")).unwrap();
+        let err = sync(&dirs, false).err().expect("an empty remote is refused");
+        assert!(err.contains("is empty"), "{}", err);
+        assert_eq!(get(&dirs.local, "a.md").as_deref(), Some("note"));
+    }
+
+    /// Answers every request with 401 and records its `Authorization` header.
+    fn sign_in_recorder() -> (String, std::sync::mpsc::Receiver<Option<String>>) {
+        use std::io::{BufRead, BufReader, Write};
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/dav/", listener.local_addr().unwrap());
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let mut auth = None;
+                for line in BufReader::new(stream.try_clone().unwrap()).lines() {
+                    let line = line.unwrap();
+                    if line.is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':') {
+                        if name.eq_ignore_ascii_case("authorization") {
+                            auth = Some(value.trim().to_string());
+                        }
+                    }
+                }
+                let _ = stream.write_all(b"HTTP/1.1 401 Unauthorized\r\nContent-Length: 0\r\nConnection: close\r\n\r\n");
+                let _ = sender.send(auth);
+            }
+        });
+        (url, receiver)
+    }
+
+    #[test]
+    fn stored_sign_in_goes_only_to_the_configured_server() {
+        let (url, requests) = sign_in_recorder();
+        let temp = tempfile::tempdir().unwrap();
+        let config = SyncConfig {
+            provider: "webdav".to_string(),
+            folder: String::new(),
+            webdav: WebDavConfig { url: url.clone(), username: "me".to_string(), password: "pw".to_string() },
+        };
+
+        let configured = remote_provider(temp.path(), &url, &config).unwrap();
+        assert!(configured.list().is_err());
+        assert_eq!(requests.recv().unwrap().as_deref(), Some("Basic bWU6cHc="));
+
+        let other = remote_provider(temp.path(), &format!("{}elsewhere/", url), &config).unwrap();
+        assert!(other.list().is_err());
+        assert_eq!(requests.recv().unwrap(), None);
+    }
+}
@@ -0,0 +1,1021 @@
+use base64::{engine::general_purpose, Engine as _};
+use portable_pty::{ChildKiller, CommandBuilder, NativePtySystem, PtySize, PtySystem};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::ffi::OsStr;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::ipc::{Channel, InvokeResponseBody};
+use tauri::{AppHandle, Emitter};
+
+use crate::terminal_output::{self, BatchConfig, FlowControl};
+use crate::terminal_recording::{self, Recorder};
+
+/// How long a shell gets to exit after SIGHUP before it is killed outright.
+const CLOSE_GRACE: Duration = Duration::from_secs(2);
+const SHUTDOWN_GRACE: Duration = Duration::from_millis(500);
+const DEFAULT_SCROLLBACK_BYTES: usize = 512 * 1024;
+const READ_BUFFER_BYTES: usize = 64 * 1024;
+const READ_QUEUE_DEPTH: usize = 32;
+const RECORDING_DIR: &str = "terminal";
+const RECORDING_LANG: &str = "console";
+const PASTE_MODE_ON: &[u8] = b"\x1b[?2004h";
+const PASTE_MODE_OFF: &[u8] = b"\x1b[?2004l";
+const PASTE_START: &str = "\x1b[200~";
+const PASTE_END: &str = "\x1b[201~";
+
+/// The most recent PTY output of a session, kept so a reloaded webview can replay it.
+struct Scrollback {
+    data: VecDeque<u8>,
+    capacity: usize,
+    /// Total bytes ever written; the stream offset just past the newest byte in `data`.
+    end_offset: u64,
+}
+
+impl Scrollback {
+    fn new(capacity: usize) -> Self {
+        Self {
+            data: VecDeque::new(),
+            capacity: capacity.max(1),
+            end_offset: 0,
+        }
+    }
+
+    /// Appends output and returns the stream offset of its first byte.
+    fn push(&mut self, bytes: &[u8]) -> u64 {
+        let offset = self.end_offset;
+        self.end_offset += bytes.len() as u64;
+        let bytes = &bytes[bytes.len().saturating_sub(self.capacity)..];
+        let overflow = (self.data.len() + bytes.len()).saturating_sub(self.capacity);
+        self.data.drain(..overflow);
+        self.data.extend(bytes);
+        offset
+    }
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TerminalInfo {
+    id: String,
+    title: String,
+    profile: String,
+    cwd: Option<String>,
+    pid: Option<u32>,
+    rows: u16,
+    cols: u16,
+    created_at: String,
+    /// Path of the asciicast file while the session is being recorded.
+    recording: Option<String>,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TerminalAttach {
+    info: TerminalInfo,
+    encoding: &'static str,
+    data: String,
+    /// Output events with an `offset` below this value are already contained in `data`.
+    offset: u64,
+}
+
+pub struct TerminalSession {
+    pty_master: Box<dyn portable_pty::MasterPty + Send>,
+    writer: Box<dyn std::io::Write + Send>,
+    killer: Box<dyn ChildKiller + Send + Sync>,
+    pid: Option<u32>,
+    exited: Arc<AtomicBool>,
+    info: TerminalInfo,
+    scrollback: Arc<Mutex<Scrollback>>,
+    flow: Arc<FlowControl>,
+    /// Binary output channel of the attached webview; output goes out as events when unset.
+    output_channel: Arc<Mutex<Option<Channel>>>,
+    recorder: Arc<Mutex<Option<Recorder>>>,
+    /// Whether the shell has enabled bracketed paste mode.
+    bracketed_paste: Arc<AtomicBool>,
+}
+
+impl TerminalSession {
+    /// Sends SIGHUP (or terminates, on Windows) and drops the PTY handles.
+    fn hang_up(mut self) -> Termination {
+        let _ = self.killer.kill();
+        Termination {
+            pid: self.pid,
+            exited: self.exited,
+        }
+    }
+}
+
+struct Termination {
+    pid: Option<u32>,
+    exited: Arc<AtomicBool>,
+}
+
+impl Termination {
+    fn wait_or_kill(&self, grace: Duration) {
+        let deadline = Instant::now() + grace;
+        while Instant::now() < deadline {
+            if self.exited.load(Ordering::Relaxed) {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        if !self.exited.load(Ordering::Relaxed) {
+            force_kill(self.pid);
+        }
+    }
+}
+
+#[cfg(unix)]
+fn force_kill(pid: Option<u32>) {
+    if let Some(pid) = pid {
+        unsafe {
+            libc::kill(pid as i32, libc::SIGKILL);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn force_kill(_pid: Option<u32>) {
+    // ChildKiller::kill already terminates the process on Windows.
+}
+
+pub struct TerminalState {
+    sessions: Arc<Mutex<HashMap<String, TerminalSession>>>,
+}
+
+impl TerminalState {
+    pub fn new() -> Self {
+        Self {
+            sessions: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Hangs up every session and kills whatever is still running after a short grace period.
+    pub fn shutdown(&self) {
+        let sessions: Vec<TerminalSession> = {
+            let mut map = self.sessions.lock().unwrap();
+            map.drain().map(|(_, s)| s).collect()
+        };
+        let terminations: Vec<Termination> = sessions.into_iter().map(|s| s.hang_up()).collect();
+        let deadline = Instant::now() + SHUTDOWN_GRACE;
+        for t in terminations.iter() {
+            t.wait_or_kill(deadline.saturating_duration_since(Instant::now()));
+        }
+    }
+}
+
+#[derive(Serialize, Clone)]
+struct TerminalExit {
+    code: Option<u32>,
+    success: bool,
+    description: String,
+}
+
+#[derive(Serialize, Clone)]
+struct TerminalOutputChunk {
+    encoding: &'static str,
+    data: String,
+    offset: u64,
+}
+
+/// A shell configuration from `terminal.profiles` in config.json.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TerminalProfile {
+    pub id: String,
+    pub name: String,
+    /// Shell executable; falls back to `$SHELL` when empty.
+    pub shell: Option<String>,
+    pub args: Vec<String>,
+    pub env: HashMap<String, String>,
+    pub login: bool,
+    pub initial_command: Option<String>,
+}
+
+/// A named command template from `terminal.snippets` in config.json.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TerminalSnippet {
+    pub id: String,
+    pub name: String,
+    /// Text sent to the shell; may use `{{file}}`, `{{dir}}`, `{{workspace}}` (shell-quoted)
+    /// and `{{selection}}` (as is).
+    pub command: String,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct TerminalConfig {
+    profiles: Vec<TerminalProfile>,
+    default_profile: Option<String>,
+    scrollback_bytes: Option<usize>,
+    snippets: Vec<TerminalSnippet>,
+    /// Ask before sending text with more than one line; on unless set to false.
+    confirm_multiline_paste: Option<bool>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct ConfigWithTerminal {
+    terminal: TerminalConfig,
+}
+
+fn default_shell() -> String {
+    shell_or_fallback(std::env::var("SHELL").ok())
+}
+
+/// `$SHELL` if set, otherwise the platform's usual shell.
+fn shell_or_fallback(env_shell: Option<String>) -> String {
+    if let Some(shell) = env_shell.filter(|s| !s.trim().is_empty()) {
+        return shell;
+    }
+    if cfg!(target_os = "macos") {
+        "/bin/zsh".to_string()
+    } else if Path::new("/bin/bash").exists() {
+        "/bin/bash".to_string()
+    } else {
+        "/bin/sh".to_string()
+    }
+}
+
+fn default_profile() -> TerminalProfile {
+    let shell = default_shell();
+    let name = Path::new(&shell)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| shell.clone());
+    TerminalProfile {
+        id: "default".to_string(),
+        name,
+        shell: Some(shell),
+        ..Default::default()
+    }
+}
+
+fn load_terminal_config() -> TerminalConfig {
+    crate::get_config()
+        .ok()
+        .and_then(|text| serde_json::from_str::<ConfigWithTerminal>(&text).ok())
+        .map(|c| c.terminal)
+        .unwrap_or_default()
+}
+
+/// Configured profiles, or a single profile for the user's login shell if none are configured.
+fn available_profiles() -> (Vec<TerminalProfile>, Option<String>) {
+    profiles_of(load_terminal_config())
+}
+
+fn profiles_of(config: TerminalConfig) -> (Vec<TerminalProfile>, Option<String>) {
+    let profiles: Vec<TerminalProfile> = config
+        .profiles
+        .into_iter()
+        .filter(|p| !p.id.is_empty())
+        .collect();
+    if profiles.is_empty() {
+        return (vec![default_profile()], None);
+    }
+    (profiles, config.default_profile)
+}
+
+fn resolve_profile(id: Option<&str>) -> TerminalProfile {
+    let (profiles, default_id) = available_profiles();
+    pick_profile(profiles, default_id, id)
+}
+
+/// The profile `id`, else the configured default, else the first one.
+fn pick_profile(profiles: Vec<TerminalProfile>, default_id: Option<String>, id: Option<&str>) -> TerminalProfile {
+    let wanted = id.map(|s| s.to_string()).or(default_id);
+    wanted
+        .and_then(|wanted| profiles.iter().find(|p| p.id == wanted).cloned())
+        .or_else(|| profiles.into_iter().next())
+        .unwrap_or_else(default_profile)
+}
+
+fn build_command(profile: &TerminalProfile, cwd: Option<String>) -> CommandBuilder {
+    let shell = profile
+        .shell
+        .clone()
+        .filter(|s| !s.trim().is_empty())
+        .unwrap_or_else(default_shell);
+
+    let mut cmd = CommandBuilder::new(&shell);
+    if profile.login && !profile.args.iter().any(|a| a == "-l" || a == "--login") {
+        cmd.arg("-l");
+    }
+    cmd.args(&profile.args);
+
+    if let Some(path) = cwd {
+        if Path::new(&path).exists() {
+            cmd.cwd(path);
+        }
+    }
+
+    cmd.env("TERM", "xterm-256color");
+    // Apps started from Finder get no locale at all; only fill it in when missing so
+    // hosts without en_US.UTF-8 keep their own setting.
+    if let Some(lang) = missing_locale(std::env::var_os("LANG").as_deref(), std::env::var_os("LC_ALL").as_deref()) {
+        cmd.env("LANG", lang);
+    }
+    for (key, value) in profile.env.iter() {
+        cmd.env(key, value);
+    }
+
+    cmd
+}
+
+/// The `LANG` to set when the app was started without any locale.
+fn missing_locale(lang: Option<&OsStr>, lc_all: Option<&OsStr>) -> Option<&'static str> {
+    if lang.is_some() || lc_all.is_some() {
+        return None;
+    }
+    Some(if cfg!(target_os = "macos") { "en_US.UTF-8" } else { "C.UTF-8" })
+}
+
+fn last_paste_mode_switch(buf: &[u8]) -> Option<bool> {
+    buf.iter()
+        .enumerate()
+        .rev()
+        .filter(|(_, b)| **b == 0x1b)
+        .find_map(|(i, _)| {
+            if buf[i..].starts_with(PASTE_MODE_ON) {
+                Some(true)
+            } else if buf[i..].starts_with(PASTE_MODE_OFF) {
+                Some(false)
+            } else {
+                None
+            }
+        })
+}
+
+/// Follows the shell's bracketed paste mode switches in its output. `tail` carries the
+/// end of the previous batch so a switch split across two batches is still seen.
+fn track_paste_mode(tail: &mut Vec<u8>, bytes: &[u8], mode: &AtomicBool) {
+    let keep = PASTE_MODE_ON.len() - 1;
+    let switch = last_paste_mode_switch(bytes).or_else(|| {
+        let mut boundary = std::mem::take(tail);
+        boundary.extend_from_slice(&bytes[..bytes.len().min(keep)]);
+        last_paste_mode_switch(&boundary)
+    });
+    if let Some(on) = switch {
+        mode.store(on, Ordering::Relaxed);
+    }
+
+    tail.clear();
+    tail.extend_from_slice(&bytes[bytes.len().saturating_sub(keep)..]);
+}
+
+#[tauri::command]
+pub fn list_terminal_profiles() -> Result<Vec<TerminalProfile>, String> {
+    Ok(available_profiles().0)
+}
+
+#[tauri::command]
+pub fn create_terminal(
+    app: AppHandle,
+    state: tauri::State<'_, TerminalState>,
+    id: String,
+    cwd: Option<String>,
+    profile: Option<String>,
+) -> Result<(), String> {
+    let pty_system = NativePtySystem::default();
+    let size = PtySize {
+        rows: 24,
+        cols: 80,
+        pixel_width: 0,
+        pixel_height: 0,
+    };
+
+    let pair = pty_system.openpty(size).map_err(|e| e.to_string())?;
+
+    let profile = resolve_profile(profile.as_deref());
+    let cmd = build_command(&profile, cwd.clone());
+
+    let mut child = pair.slave.spawn_command(cmd).map_err(|e| e.to_string())?;
+    let killer = child.clone_killer();
+    let pid = child.process_id();
+    let exited = Arc::new(AtomicBool::new(false));
+
+    let mut reader = pair.master.try_clone_reader().map_err(|e| e.to_string())?;
+    let mut writer = pair.master.take_writer().map_err(|e| e.to_string())?;
+
+    if let Some(initial) = profile.initial_command.as_deref().filter(|c| !c.trim().is_empty()) {
+        writer
+            .write_all(format!("{}\n", initial).as_bytes())
+            .map_err(|e| e.to_string())?;
+    }
+
+    let capacity = load_terminal_config()
+        .scrollback_bytes
+        .unwrap_or(DEFAULT_SCROLLBACK_BYTES);
+    let scrollback = Arc::new(Mutex::new(Scrollback::new(capacity)));
+    let flow = Arc::new(FlowControl::new());
+    let output_channel: Arc<Mutex<Option<Channel>>> = Arc::new(Mutex::new(None));
+    let recorder: Arc<Mutex<Option<Recorder>>> = Arc::new(Mutex::new(None));
+    let bracketed_paste = Arc::new(AtomicBool::new(false));
+    let info = TerminalInfo {
+        id: id.clone(),
+        title: profile.name.clone(),
+        profile: profile.id.clone(),
+        cwd,
+        pid,
+        rows: size.rows,
+        cols: size.cols,
+        created_at: chrono::Local::now().to_rfc3339(),
+        recording: None,
+    };
+
+    // Store session. Ids come from the frontend; replacing a live session would orphan its
+    // shell, so a duplicate is refused and the shell just started for it is ended.
+    {
+        let mut sessions = state.sessions.lock().unwrap();
+        if sessions.contains_key(&id) {
+            drop(sessions);
+            let _ = child.kill();
+            force_kill(pid);
+            let _ = child.wait();
+            return Err(format!("terminal {} already exists", id));
+        }
+        sessions.insert(id.clone(), TerminalSession {
+             pty_master: pair.master,
+             writer,
+             killer,
+             pid,
+             exited: exited.clone(),
+             info,
+             scrollback: scrollback.clone(),
+             flow: flow.clone(),
+             output_channel: output_channel.clone(),
+             recorder: recorder.clone(),
+             bracketed_paste: bracketed_paste.clone(),
+        });
+    }
+
+    let app_handle = app.clone();
+    let session_id = id.clone();
+    let (reader_done_tx, reader_done_rx) = mpsc::channel::<()>();
+    // Bounded, so a lagging emitter stops the reader and the PTY applies backpressure to the shell.
+    let (chunk_tx, chunk_rx) = mpsc::sync_channel::<Vec<u8>>(READ_QUEUE_DEPTH);
+
+    // Spawn reader thread
+    std::thread::spawn(move || {
+        let mut buffer = vec![0u8; READ_BUFFER_BYTES];
+        loop {
+            match reader.read(&mut buffer) {
+                Ok(n) if n > 0 => {
+                     if chunk_tx.send(buffer[..n].to_vec()).is_err() {
+                         break;
+                     }
+                }
+                Ok(_) => break, // EOF
+                Err(_) => break,
+            }
+        }
+    });
+
+    // Spawn emitter thread: coalesces reads into batches and delivers them to the frontend
+    std::thread::spawn(move || {
+        let cfg = BatchConfig::default();
+        let mut paste_tail: Vec<u8> = Vec::new();
+        terminal_output::pump(chunk_rx, &cfg, &flow, |_, bytes| {
+            track_paste_mode(&mut paste_tail, bytes, &bracketed_paste);
+            // Emit while holding the scrollback lock so an attach never sees a
+            // chunk both in its replay and as a live event with a later offset.
+            let mut scrollback = scrollback.lock().unwrap();
+            let offset = scrollback.push(bytes);
+            if let Some(rec) = recorder.lock().unwrap().as_mut() {
+                rec.output(bytes);
+            }
+            let mut channel = output_channel.lock().unwrap();
+            if let Some(ch) = channel.as_ref() {
+                let mut message = Vec::with_capacity(8 + bytes.len());
+                message.extend_from_slice(&offset.to_be_bytes());
+                message.extend_from_slice(bytes);
+                if ch.send(InvokeResponseBody::Raw(message)).is_ok() {
+                    return;
+                }
+                // The webview that owned the channel is gone; fall back to events.
+                *channel = None;
+            }
+            let payload = TerminalOutputChunk {
+                encoding: "base64",
+                data: general_purpose::STANDARD.encode(bytes),
+                offset,
+            };
+            let _ = app_handle.emit(&format!("terminal-output:{}", session_id), payload);
+        });
+        // Finish the recording file once the last output is in.
+        recorder.lock().unwrap().take();
+        let _ = reader_done_tx.send(());
+    });
+
+    // Spawn waiter thread: owns the child so it is always reaped, and reports the exit
+    // status once the remaining output has been flushed.
+    let app_handle = app.clone();
+    let sessions = state.sessions.clone();
+    std::thread::spawn(move || {
+        let status = child.wait();
+        exited.store(true, Ordering::Relaxed);
+
+        // Background jobs can keep the PTY open after the shell exits; don't wait on them forever.
+        let _ = reader_done_rx.recv_timeout(Duration::from_millis(500));
+
+        let payload = match status {
+            Ok(status) => TerminalExit {
+                code: Some(status.exit_code()),
+                success: status.success(),
+                description: status.to_string(),
+            },
+            Err(err) => TerminalExit {
+                code: None,
+                success: false,
+                description: err.to_string(),
+            },
+        };
+
+        {
+            let mut sessions = sessions.lock().unwrap();
+            if sessions.get(&id).map(|s| Arc::ptr_eq(&s.exited, &exited)).unwrap_or(false) {
+                sessions.remove(&id);
+            }
+        }
+        let _ = app_handle.emit(&format!("terminal-exit:{}", id), payload);
+    });
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn write_to_terminal(
+    state: tauri::State<'_, TerminalState>,
+    id: String,
+    data: String,
+) -> Result<(), String> {
+    let mut sessions = state.sessions.lock().unwrap();
+    if let Some(session) = sessions.get_mut(&id) {
+        session.writer.write_all(data.as_bytes()).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn resize_terminal(
+    state: tauri::State<'_, TerminalState>,
+    id: String,
+    rows: u16,
+    cols: u16,
+) -> Result<(), String> {
+    let mut sessions = state.sessions.lock().unwrap();
+    if let Some(session) = sessions.get_mut(&id) {
+        session.pty_master.resize(PtySize {
+            rows,
+            cols,
+            pixel_width: 0,
+            pixel_height: 0,
+        }).map_err(|e| e.to_string())?;
+        session.info.rows = rows;
+        session.info.cols = cols;
+        if let Some(rec) = session.recorder.lock().unwrap().as_mut() {
+            rec.resize(cols, rows);
+        }
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn list_terminals(state: tauri::State<'_, TerminalState>) -> Result<Vec<TerminalInfo>, String> {
+    let sessions = state.sessions.lock().unwrap();
+    let mut out: Vec<TerminalInfo> = sessions.values().map(|s| s.info.clone()).collect();
+    out.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+    Ok(out)
+}
+
+/// Replays the session's scrollback. When `output` is given, subsequent output is sent
+/// through it as raw bytes (an 8-byte big-endian stream offset followed by the data)
+/// instead of base64 `terminal-output` events.
+#[tauri::command]
+pub fn attach_terminal(
+    state: tauri::State<'_, TerminalState>,
+    id: String,
+    output: Option<Channel>,
+) -> Result<TerminalAttach, String> {
+    let (info, scrollback, flow, output_channel) = {
+        let sessions = state.sessions.lock().unwrap();
+        let session = sessions.get(&id).ok_or_else(|| format!("terminal {} not found", id))?;
+        (
+            session.info.clone(),
+            session.scrollback.clone(),
+            session.flow.clone(),
+            session.output_channel.clone(),
+        )
+    };
+    let scrollback = scrollback.lock().unwrap();
+    *output_channel.lock().unwrap() = output;
+    flow.reset();
+    let (front, back) = scrollback.data.as_slices();
+    let mut bytes = Vec::with_capacity(scrollback.data.len());
+    bytes.extend_from_slice(front);
+    bytes.extend_from_slice(back);
+    Ok(TerminalAttach {
+        info,
+        encoding: "base64",
+        data: general_purpose::STANDARD.encode(bytes),
+        offset: scrollback.end_offset,
+    })
+}
+
+#[tauri::command]
+pub fn close_terminal(
+    state: tauri::State<'_, TerminalState>,
+    id: String,
+) -> Result<(), String> {
+    let session = state.sessions.lock().unwrap().remove(&id);
+    if let Some(session) = session {
+        let termination = session.hang_up();
+        std::thread::spawn(move || termination.wait_or_kill(CLOSE_GRACE));
+    }
+    Ok(())
+}
+
+/// Acknowledges that the frontend has rendered output up to `offset`; used for backpressure.
+#[tauri::command]
+pub fn ack_terminal_output(
+    state: tauri::State<'_, TerminalState>,
+    id: String,
+    offset: u64,
+) -> Result<(), String> {
+    let sessions = state.sessions.lock().unwrap();
+    if let Some(session) = sessions.get(&id) {
+        session.flow.ack(offset);
+    }
+    Ok(())
+}
+
+fn recording_file_name(id: &str) -> String {
+    let safe: String = id
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    format!("{}-{}.cast", chrono::Local::now().format("%Y%m%d-%H%M%S"), safe)
+}
+
+/// Starts recording the session's output as an asciicast v2 file under
+/// `.xnote_assets/terminal` in the workspace; returns the file path.
+#[tauri::command]
+pub fn start_terminal_recording(
+    state: tauri::State<'_, TerminalState>,
+    id: String,
+    root_path: String,
+) -> Result<String, String> {
+    let mut sessions = state.sessions.lock().unwrap();
+    let session = sessions.get_mut(&id).ok_or_else(|| format!("terminal {} not found", id))?;
+    let mut recorder = session.recorder.lock().unwrap();
+    if let Some(rec) = recorder.as_ref() {
+        return Ok(rec.path().to_string_lossy().to_string());
+    }
+
+    let path = Path::new(&root_path)
+        .join(".xnote_assets")
+        .join(RECORDING_DIR)
+        .join(recording_file_name(&id));
+    let rec = Recorder::create(&path, session.info.cols, session.info.rows, &session.info.title)?;
+    let path = rec.path().to_string_lossy().to_string();
+    *recorder = Some(rec);
+    session.info.recording = Some(path.clone());
+    Ok(path)
+}
+
+/// Stops recording and returns the path of the finished file, if a recording was running.
+#[tauri::command]
+pub fn stop_terminal_recording(
+    state: tauri::State<'_, TerminalState>,
+    id: String,
+) -> Result<Option<String>, String> {
+    let mut sessions = state.sessions.lock().unwrap();
+    let Some(session) = sessions.get_mut(&id) else { return Ok(None) };
+    session.info.recording = None;
+    let recorder = session.recorder.lock().unwrap().take();
+    Ok(recorder.map(|rec| rec.path().to_string_lossy().to_string()))
+}
+
+/// Converts a recording into a plain-text fenced code block with escape sequences removed.
+#[tauri::command]
+pub fn terminal_recording_to_markdown(path: String) -> Result<String, String> {
+    let raw = terminal_recording::read_output(Path::new(&path))?;
+    let text = terminal_recording::to_plain_text(&raw);
+    let fence = crate::markdown::code_fence(&text);
+    Ok(format!("{fence}{RECORDING_LANG}\n{text}\n{fence}"))
+}
+
+/// Inserts the transcript of a recording into a note as a code block, before the
+/// zero-based `line` or at the end of the note. Returns the inserted block.
+#[tauri::command]
+pub fn insert_terminal_recording(
+    path: String,
+    note_path: String,
+    line: Option<usize>,
+) -> Result<String, String> {
+    let block = terminal_recording_to_markdown(path)?;
+    let note_path = PathBuf::from(note_path);
+    let content = crate::encryption::read_note(&note_path)?;
+    let at = match line {
+        Some(n) => content.split_inclusive('\n').take(n).map(|l| l.len()).sum(),
+        None => content.len(),
+    };
+
+    let mut updated = String::with_capacity(content.len() + block.len() + 4);
+    updated.push_str(&content[..at]);
+    if !updated.is_empty() {
+        if !updated.ends_with('\n') {
+            updated.push('\n');
+        }
+        if !updated.ends_with("\n\n") {
+            updated.push('\n');
+        }
+    }
+    updated.push_str(&block);
+    updated.push('\n');
+    let rest = &content[at..];
+    if !rest.is_empty() && !rest.starts_with('\n') {
+        updated.push('\n');
+    }
+    updated.push_str(rest);
+
+    crate::encryption::write_note(&note_path, &updated)?;
+    Ok(block)
+}
+
+#[tauri::command]
+pub fn list_terminal_snippets() -> Result<Vec<TerminalSnippet>, String> {
+    Ok(load_terminal_config()
+        .snippets
+        .into_iter()
+        .filter(|s| !s.id.is_empty())
+        .collect())
+}
+
+/// Replaces the known `{{name}}` variables in `template`; other `{{...}}` text is left alone.
+fn expand_template(template: &str, vars: &[(&str, Option<&str>)]) -> Result<String, String> {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("}}") else {
+            rest = &rest[start..];
+            break;
+        };
+        let name = after[..end].trim();
+        match vars.iter().find(|(var, _)| *var == name) {
+            Some((_, Some(value))) => out.push_str(value),
+            Some((_, None)) => return Err(format!("`{{{{{}}}}}` is not available here", name)),
+            None => out.push_str(&rest[start..start + 2 + end + 2]),
+        }
+        rest = &after[end + 2..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Quotes `value` for a POSIX shell when it contains anything but plain path characters.
+fn shell_quote(value: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "/._-+=:,@%".contains(c);
+    if !value.is_empty() && value.chars().all(plain) {
+        return value.to_string();
+    }
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// Turns text into the bytes a terminal would send when it is pasted: line breaks become
+/// carriage returns and, in bracketed paste mode, the text is wrapped in paste markers so
+/// the shell inserts it without running each line.
+fn paste_input(text: &str, bracketed: bool, execute: bool) -> String {
+    let text = text.trim_end_matches(['\r', '\n']).replace("\r\n", "\r").replace('\n', "\r");
+    let mut input = if bracketed {
+        // Pasted text must not be able to end the paste early and run the rest.
+        let inner = text.replace(PASTE_START, "").replace(PASTE_END, "");
+        format!("{PASTE_START}{inner}{PASTE_END}")
+    } else {
+        text
+    };
+    if execute {
+        input.push('\r');
+    }
+    input
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TerminalSend {
+    sent: bool,
+    /// Set when the text spans several lines and has to be confirmed by sending again
+    /// with `confirmed`; nothing is written to the terminal in that case.
+    needs_confirmation: bool,
+    line_count: usize,
+    /// The text after variable substitution, for previewing in the confirmation prompt.
+    text: String,
+    bracketed: bool,
+}
+
+/// Sends a selection (`text`) or a configured snippet to a terminal session as a paste.
+/// A selection is sent as is. In snippets, `{{file}}`, `{{dir}}` and `{{workspace}}` are
+/// filled in, shell-quoted, from `file_path` and `workspace`, and `{{selection}}` with
+/// `text`. With `execute` the pasted text is followed by Enter.
+#[allow(clippy::too_many_arguments)]
+#[tauri::command]
+pub fn send_to_terminal(
+    state: tauri::State<'_, TerminalState>,
+    id: String,
+    text: Option<String>,
+    snippet: Option<String>,
+    file_path: Option<String>,
+    workspace: Option<String>,
+    execute: Option<bool>,
+    confirmed: Option<bool>,
+) -> Result<TerminalSend, String> {
+    let config = load_terminal_config();
+    let expanded = match snippet.as_deref() {
+        Some(snippet_id) => {
+            let template = config
+                .snippets
+                .iter()
+                .find(|s| s.id == snippet_id)
+                .map(|s| s.command.clone())
+                .ok_or_else(|| format!("snippet {} not found", snippet_id))?;
+            let file = file_path.as_deref().map(shell_quote);
+            let dir = file_path
+                .as_deref()
+                .and_then(|p| Path::new(p).parent())
+                .map(|p| shell_quote(&p.to_string_lossy()));
+            let workspace = workspace.as_deref().map(shell_quote);
+            let vars = [
+                ("file", file.as_deref()),
+                ("dir", dir.as_deref()),
+                ("workspace", workspace.as_deref()),
+                ("selection", text.as_deref()),
+            ];
+            expand_template(&template, &vars)?
+        }
+        None => text.ok_or_else(|| "Nothing to send".to_string())?,
+    };
+    let line_count = expanded.trim_end_matches(['\r', '\n']).lines().count();
+
+    let mut sessions = state.sessions.lock().unwrap();
+    let session = sessions.get_mut(&id).ok_or_else(|| format!("terminal {} not found", id))?;
+    let bracketed = session.bracketed_paste.load(Ordering::Relaxed);
+
+    let needs_confirmation = line_count > 1
+        && config.confirm_multiline_paste.unwrap_or(true)
+        && !confirmed.unwrap_or(false);
+    if !needs_confirmation {
+        let input = paste_input(&expanded, bracketed, execute.unwrap_or(false));
+        session.writer.write_all(input.as_bytes()).map_err(|e| e.to_string())?;
+        session.writer.flush().map_err(|e| e.to_string())?;
+    }
+
+    Ok(TerminalSend {
+        sent: !needs_confirmation,
+        needs_confirmation,
+        line_count,
+        text: expanded,
+        bracketed,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(id: &str, shell: Option<&str>) -> TerminalProfile {
+        TerminalProfile { id: id.to_string(), name: id.to_string(), shell: shell.map(str::to_string), ..Default::default() }
+    }
+
+    #[test]
+    fn profiles_resolve_by_id_then_default_then_first() {
+        let config = TerminalConfig {
+            profiles: vec![profile("", Some("/bin/ignored")), profile("bash", None), profile("zsh", None)],
+            default_profile: Some("zsh".to_string()),
+            ..Default::default()
+        };
+        let (profiles, default_id) = profiles_of(config);
+        let ids: Vec<&str> = profiles.iter().map(|p| p.id.as_str()).collect();
+        assert_eq!(ids, vec!["bash", "zsh"]);
+
+        let cases = [
+            (Some("bash"), Some("zsh"), "bash"),
+            (None, Some("zsh"), "zsh"),
+            (Some("gone"), Some("zsh"), "bash"),
+            (None, Some("gone"), "bash"),
+            (None, None, "bash"),
+        ];
+        for (id, default_id, expected) in cases {
+            let picked = pick_profile(profiles.clone(), default_id.map(str::to_string), id);
+            assert_eq!(picked.id, expected, "{:?} / {:?}", id, default_id);
+        }
+        assert_eq!(default_id.as_deref(), Some("zsh"));
+    }
+
+    #[test]
+    fn without_profiles_the_login_shell_is_used() {
+        let (profiles, default_id) = profiles_of(TerminalConfig { profiles: vec![profile("", None)], ..Default::default() });
+        assert_eq!(default_id, None);
+        assert_eq!(profiles.len(), 1);
+        assert_eq!(profiles[0].id, "default");
+        assert_eq!(profiles[0].shell.as_deref(), Some(default_shell().as_str()));
+        assert_eq!(pick_profile(Vec::new(), None, Some("any")).id, "default");
+    }
+
+    #[test]
+    fn shell_falls_back_when_unset_or_blank() {
+        assert_eq!(shell_or_fallback(Some("/usr/bin/fish".to_string())), "/usr/bin/fish");
+        let fallback = shell_or_fallback(None);
+        assert_eq!(shell_or_fallback(Some("  ".to_string())), fallback);
+        if cfg!(target_os = "macos") {
+            assert_eq!(fallback, "/bin/zsh");
+        } else {
+            assert!(fallback == "/bin/bash" || fallback == "/bin/sh", "{}", fallback);
+        }
+    }
+
+    #[test]
+    fn lang_is_only_filled_in_when_no_locale_is_set() {
+        let set = Some(OsStr::new("de_DE.UTF-8"));
+        assert_eq!(missing_locale(set, None), None);
+        assert_eq!(missing_locale(None, set), None);
+        let expected = if cfg!(target_os = "macos") { "en_US.UTF-8" } else { "C.UTF-8" };
+        assert_eq!(missing_locale(None, None), Some(expected));
+    }
+
+    #[test]
+    fn commands_follow_the_profile() {
+        let mut zsh = profile("zsh", Some("/bin/zsh"));
+        zsh.login = true;
+        zsh.args = vec!["-i".to_string()];
+        zsh.env.insert("LANG".to_string(), "de_DE.UTF-8".to_string());
+        let cmd = build_command(&zsh, None);
+        assert_eq!(cmd.get_argv(), &vec!["/bin/zsh", "-l", "-i"]);
+        assert_eq!(cmd.get_env("TERM"), Some(OsStr::new("xterm-256color")));
+        assert_eq!(cmd.get_env("LANG"), Some(OsStr::new("de_DE.UTF-8")));
+
+        zsh.args = vec!["--login".to_string()];
+        assert_eq!(build_command(&zsh, None).get_argv(), &vec!["/bin/zsh", "--login"]);
+
+        let blank = profile("blank", Some(" "));
+        assert_eq!(build_command(&blank, None).get_argv(), &vec![default_shell().as_str()]);
+    }
+
+    #[test]
+    fn expand_template_fills_known_variables() {
+        let vars = [("file", Some("/w/a.md")), ("dir", Some("/w")), ("selection", None)];
+        let cases: &[(&str, Result<&str, &str>)] = &[
+            ("cat {{file}}", Ok("cat /w/a.md")),
+            ("cd {{ dir }} && ls", Ok("cd /w && ls")),
+            ("{{file}}{{file}}", Ok("/w/a.md/w/a.md")),
+            ("echo {{unknown}} {{file}}", Ok("echo {{unknown}} /w/a.md")),
+            ("awk '{print $1}' {{file}}", Ok("awk '{print $1}' /w/a.md")),
+            ("echo {{file", Ok("echo {{file")),
+            ("{{dir}} then {{", Ok("/w then {{")),
+            ("no variables", Ok("no variables")),
+            ("echo {{selection}}", Err("`{{selection}}` is not available here")),
+        ];
+        for (template, expected) in cases {
+            assert_eq!(expand_template(template, &vars).as_deref(), expected.map_err(|e| e.to_string()).as_deref(), "{}", template);
+        }
+    }
+
+    #[test]
+    fn shell_quote_only_quotes_when_needed() {
+        let cases = [
+            ("/home/me/notes/a.md", "/home/me/notes/a.md"),
+            ("/home/me/My Notes", "'/home/me/My Notes'"),
+            ("it's.md", "'it'\\''s.md'"),
+            ("$(rm -rf ~).md", "'$(rm -rf ~).md'"),
+            ("a;b`c`", "'a;b`c`'"),
+            ("", "''"),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(shell_quote(value), expected, "{}", value);
+        }
+    }
+
+    #[test]
+    fn paste_input_wraps_and_normalizes_line_breaks() {
+        let cases = [
+            ("ls", false, false, "ls"),
+            ("ls\n", false, true, "ls\r"),
+            ("a\r\nb\nc\r\n\n", false, false, "a\rb\rc"),
+            ("a\nb", true, false, "\x1b[200~a\rb\x1b[201~"),
+            ("a\nb", true, true, "\x1b[200~a\rb\x1b[201~\r"),
+            // Markers inside the text cannot end the paste early.
+            ("safe\x1b[201~rm -rf ~\n", true, false, "\x1b[200~saferm -rf ~\x1b[201~"),
+            ("x\x1b[200~y", true, false, "\x1b[200~xy\x1b[201~"),
+            // Without bracketed paste there is nothing to strip.
+            ("x\x1b[201~y", false, false, "x\x1b[201~y"),
+        ];
+        for (text, bracketed, execute, expected) in cases {
+            assert_eq!(paste_input(text, bracketed, execute), expected, "{:?}", text);
+        }
+    }
+}
@@ -0,0 +1,244 @@
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// Tuning for coalescing PTY reads into output events.
+#[derive(Clone, Debug)]
+pub struct BatchConfig {
+    /// Flush as soon as a batch reaches this size.
+    pub max_batch_bytes: usize,
+    /// How long to keep collecting reads after the first byte of a batch arrived.
+    pub flush_window: Duration,
+    /// Stop emitting while more than this many bytes are unacknowledged by the frontend.
+    pub high_watermark: u64,
+    /// Resume once the unacknowledged backlog drops below this.
+    pub low_watermark: u64,
+    /// Give up waiting for acks after this long, so a frontend that stopped acking
+    /// (e.g. during a reload) cannot stall the shell forever.
+    pub ack_timeout: Duration,
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        Self {
+            max_batch_bytes: 256 * 1024,
+            flush_window: Duration::from_millis(8),
+            high_watermark: 2 * 1024 * 1024,
+            low_watermark: 512 * 1024,
+            ack_timeout: Duration::from_secs(2),
+        }
+    }
+}
+
+struct FlowState {
+    emitted_end: u64,
+    /// `None` until the frontend acknowledges output for the first time; frontends that
+    /// never ack are not throttled.
+    acked: Option<u64>,
+}
+
+/// Tracks how far the frontend is behind the emitted output stream.
+pub struct FlowControl {
+    state: Mutex<FlowState>,
+    cond: Condvar,
+}
+
+impl Default for FlowControl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FlowControl {
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(FlowState { emitted_end: 0, acked: None }),
+            cond: Condvar::new(),
+        }
+    }
+
+    /// Records that the frontend has consumed the stream up to `offset`.
+    pub fn ack(&self, offset: u64) {
+        let mut state = self.state.lock().unwrap();
+        let acked = state.acked.unwrap_or(0).max(offset).min(state.emitted_end);
+        state.acked = Some(acked);
+        self.cond.notify_all();
+    }
+
+    /// Forgets previous acks, e.g. when a new frontend attaches to the session.
+    pub fn reset(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.acked = None;
+        self.cond.notify_all();
+    }
+
+    /// Waits until the frontend has caught up enough, then counts `len` more bytes as
+    /// emitted. Both happen under one lock and before the bytes are sent, so an ack for
+    /// them can never arrive first and be cut back to the old end.
+    fn reserve(&self, len: u64, cfg: &BatchConfig) {
+        let mut state = self.state.lock().unwrap();
+        let backlog = |s: &FlowState| s.acked.map(|a| s.emitted_end.saturating_sub(a)).unwrap_or(0);
+        if backlog(&state) > cfg.high_watermark {
+            state = self
+                .cond
+                .wait_timeout_while(state, cfg.ack_timeout, |s| backlog(s) > cfg.low_watermark)
+                .unwrap()
+                .0;
+        }
+        state.emitted_end += len;
+    }
+}
+
+/// Coalesces chunks from `rx` into batches and hands each batch to `sink` together
+/// with its stream offset. Returns when the sender side is dropped and everything
+/// received has been flushed.
+pub fn pump(rx: Receiver<Vec<u8>>, cfg: &BatchConfig, flow: &FlowControl, mut sink: impl FnMut(u64, &[u8])) {
+    let mut offset = 0u64;
+    let mut disconnected = false;
+
+    while !disconnected {
+        let Ok(first) = rx.recv() else { break };
+        let mut batch = first;
+        let deadline = Instant::now() + cfg.flush_window;
+
+        while batch.len() < cfg.max_batch_bytes {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match rx.recv_timeout(remaining) {
+                Ok(chunk) => batch.extend_from_slice(&chunk),
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => {
+                    disconnected = true;
+                    break;
+                }
+            }
+        }
+
+        flow.reserve(batch.len() as u64, cfg);
+        sink(offset, &batch);
+        offset += batch.len() as u64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    fn config(high_watermark: u64, low_watermark: u64, ack_timeout: Duration) -> BatchConfig {
+        BatchConfig { high_watermark, low_watermark, ack_timeout, ..BatchConfig::default() }
+    }
+
+    fn acked(flow: &FlowControl) -> Option<u64> {
+        flow.state.lock().unwrap().acked
+    }
+
+    #[test]
+    fn acks_only_move_forward_within_the_emitted_stream() {
+        let flow = FlowControl::new();
+        let cfg = BatchConfig::default();
+        flow.reserve(100, &cfg);
+        flow.ack(40);
+        flow.ack(10);
+        assert_eq!(acked(&flow), Some(40));
+        flow.ack(500);
+        assert_eq!(acked(&flow), Some(100));
+        flow.reset();
+        assert_eq!(acked(&flow), None);
+    }
+
+    #[test]
+    fn an_ack_right_after_reserving_is_kept() {
+        // The frontend may ack a batch before `pump` gets the lock back after sending it.
+        let flow = FlowControl::new();
+        flow.reserve(64, &BatchConfig::default());
+        flow.ack(64);
+        assert_eq!(acked(&flow), Some(64));
+    }
+
+    #[test]
+    fn frontends_that_never_ack_are_not_throttled() {
+        let flow = FlowControl::new();
+        let cfg = config(1, 0, Duration::from_secs(10));
+        let started = Instant::now();
+        for _ in 0..10 {
+            flow.reserve(100, &cfg);
+        }
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn a_backlog_above_the_high_watermark_waits_for_acks() {
+        let flow = std::sync::Arc::new(FlowControl::new());
+        let cfg = config(100, 50, Duration::from_secs(10));
+        flow.reserve(200, &cfg);
+        flow.ack(0);
+
+        let acker = {
+            let flow = flow.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(100));
+                flow.ack(120); // Backlog 80: still above the low watermark.
+                std::thread::sleep(Duration::from_millis(100));
+                flow.ack(160);
+            })
+        };
+        let started = Instant::now();
+        flow.reserve(10, &cfg);
+        let waited = started.elapsed();
+        acker.join().unwrap();
+        assert!(waited >= Duration::from_millis(200), "{:?}", waited);
+        assert!(waited < Duration::from_secs(5), "{:?}", waited);
+        assert_eq!(flow.state.lock().unwrap().emitted_end, 210);
+    }
+
+    #[test]
+    fn a_frontend_that_stops_acking_only_stalls_until_the_timeout() {
+        let flow = FlowControl::new();
+        let cfg = config(100, 50, Duration::from_millis(100));
+        flow.reserve(200, &cfg);
+        flow.ack(0);
+        let started = Instant::now();
+        flow.reserve(10, &cfg);
+        let waited = started.elapsed();
+        assert!(waited >= Duration::from_millis(100) && waited < Duration::from_secs(5), "{:?}", waited);
+    }
+
+    fn pumped(cfg: &BatchConfig, send: impl FnOnce(mpsc::Sender<Vec<u8>>) + Send + 'static) -> Vec<(u64, String)> {
+        let (tx, rx) = mpsc::channel();
+        let sender = std::thread::spawn(move || send(tx));
+        let mut batches = Vec::new();
+        pump(rx, cfg, &FlowControl::new(), |offset, bytes| batches.push((offset, String::from_utf8_lossy(bytes).to_string())));
+        sender.join().unwrap();
+        batches
+    }
+
+    #[test]
+    fn pump_coalesces_reads_within_the_flush_window() {
+        let cfg = BatchConfig { flush_window: Duration::from_secs(5), ..BatchConfig::default() };
+        let batches = pumped(&cfg, |tx| {
+            for chunk in ["ab", "c", "def"] {
+                tx.send(chunk.as_bytes().to_vec()).unwrap();
+            }
+        });
+        assert_eq!(batches, vec![(0, "abcdef".to_string())]);
+    }
+
+    #[test]
+    fn pump_flushes_full_batches_and_after_the_window() {
+        let cfg = BatchConfig { max_batch_bytes: 3, flush_window: Duration::from_secs(5), ..BatchConfig::default() };
+        let batches = pumped(&cfg, |tx| {
+            for chunk in ["ab", "cd", "e", "f", "g"] {
+                tx.send(chunk.as_bytes().to_vec()).unwrap();
+            }
+        });
+        assert_eq!(batches, vec![(0, "abcd".to_string()), (4, "efg".to_string())]);
+
+        let cfg = BatchConfig { flush_window: Duration::from_millis(20), ..BatchConfig::default() };
+        let batches = pumped(&cfg, |tx| {
+            tx.send(b"one".to_vec()).unwrap();
+            std::thread::sleep(Duration::from_millis(300));
+            tx.send(b"two".to_vec()).unwrap();
+        });
+        assert_eq!(batches, vec![(0, "one".to_string()), (3, "two".to_string())]);
+    }
+}
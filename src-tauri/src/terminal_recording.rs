@@ -0,0 +1,347 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+#[derive(Serialize)]
+struct Header<'a> {
+    version: u8,
+    width: u16,
+    height: u16,
+    timestamp: i64,
+    title: &'a str,
+    env: HashMap<&'static str, &'static str>,
+}
+
+/// Writes PTY output to an asciicast v2 file: a JSON header line followed by one
+/// `[seconds, "o", data]` event per output batch and `[seconds, "r", "COLSxROWS"]`
+/// on resize. Keyboard input is never recorded.
+pub struct Recorder {
+    path: PathBuf,
+    out: BufWriter<File>,
+    started: Instant,
+    /// Tail of a UTF-8 sequence split across batches, held back until the rest arrives.
+    pending: Vec<u8>,
+}
+
+impl Recorder {
+    pub fn create(path: &Path, cols: u16, rows: u16, title: &str) -> Result<Self, String> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let file = File::create(path).map_err(|e| e.to_string())?;
+        let mut out = BufWriter::new(file);
+        let header = Header {
+            version: 2,
+            width: cols,
+            height: rows,
+            timestamp: chrono::Utc::now().timestamp(),
+            title,
+            env: HashMap::from([("TERM", "xterm-256color")]),
+        };
+        let header = serde_json::to_string(&header).map_err(|e| e.to_string())?;
+        writeln!(out, "{}", header).map_err(|e| e.to_string())?;
+        out.flush().map_err(|e| e.to_string())?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            out,
+            started: Instant::now(),
+            pending: Vec::new(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn output(&mut self, bytes: &[u8]) {
+        self.pending.extend_from_slice(bytes);
+        let complete = match std::str::from_utf8(&self.pending) {
+            Ok(_) => self.pending.len(),
+            // Cut off mid-character: keep the incomplete tail for the next batch.
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            // Genuinely invalid bytes are replaced below.
+            Err(_) => self.pending.len(),
+        };
+        if complete == 0 {
+            return;
+        }
+        let chunk: Vec<u8> = self.pending.drain(..complete).collect();
+        self.event("o", &String::from_utf8_lossy(&chunk));
+    }
+
+    pub fn resize(&mut self, cols: u16, rows: u16) {
+        self.event("r", &format!("{}x{}", cols, rows));
+    }
+
+    fn event(&mut self, kind: &str, data: &str) {
+        let secs = (self.started.elapsed().as_secs_f64() * 1_000_000.0).round() / 1_000_000.0;
+        let line = serde_json::json!([secs, kind, data]);
+        // Flushed per event so a crash loses at most the current batch.
+        let _ = writeln!(self.out, "{}", line);
+        let _ = self.out.flush();
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        if !self.pending.is_empty() {
+            let rest = std::mem::take(&mut self.pending);
+            self.event("o", &String::from_utf8_lossy(&rest));
+        }
+        let _ = self.out.flush();
+    }
+}
+
+/// Concatenates the output events of an asciicast v2 recording.
+pub fn read_output(path: &Path) -> Result<String, String> {
+    let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let mut lines = text.lines();
+    let header: serde_json::Value = lines
+        .next()
+        .and_then(|line| serde_json::from_str(line).ok())
+        .ok_or_else(|| "Not an asciicast recording".to_string())?;
+    if header.get("version").and_then(|v| v.as_u64()) != Some(2) {
+        return Err("Only asciicast v2 recordings are supported".to_string());
+    }
+
+    let mut out = String::new();
+    for line in lines {
+        let Ok(serde_json::Value::Array(event)) = serde_json::from_str::<serde_json::Value>(line) else {
+            continue;
+        };
+        if event.get(1).and_then(|k| k.as_str()) != Some("o") {
+            continue;
+        }
+        if let Some(data) = event.get(2).and_then(|d| d.as_str()) {
+            out.push_str(data);
+        }
+    }
+    Ok(out)
+}
+
+fn csi_count(params: &str) -> usize {
+    params.parse::<usize>().unwrap_or(1).max(1)
+}
+
+fn is_alt_screen(params: &str) -> bool {
+    params
+        .strip_prefix('?')
+        .map(|p| p.split(';').any(|m| matches!(m, "47" | "1047" | "1049")))
+        .unwrap_or(false)
+}
+
+fn finish_line(line: &[char]) -> String {
+    line.iter().collect::<String>().trim_end().to_string()
+}
+
+/// Renders raw terminal output as plain text. Escape sequences are dropped, carriage
+/// returns, backspaces and the common line-editing sequences overwrite the current line
+/// the way a terminal would, and full-screen programs (the alternate screen) are left out.
+pub fn to_plain_text(raw: &str) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    let mut line: Vec<char> = Vec::new();
+    let mut col = 0usize;
+    let mut alt_screen = false;
+    let mut chars = raw.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => match chars.next() {
+                Some('[') => {
+                    let mut params = String::new();
+                    let mut final_byte = None;
+                    for c in chars.by_ref() {
+                        if ('\x40'..='\x7e').contains(&c) {
+                            final_byte = Some(c);
+                            break;
+                        }
+                        params.push(c);
+                    }
+                    match final_byte {
+                        Some(f @ ('h' | 'l')) if is_alt_screen(&params) => alt_screen = f == 'h',
+                        _ if alt_screen => {}
+                        Some('K') => match params.as_str() {
+                            "" | "0" => line.truncate(col),
+                            // Up to and including the cursor column.
+                            "1" => line.iter_mut().take(col + 1).for_each(|c| *c = ' '),
+                            "2" => line.clear(),
+                            _ => {}
+                        },
+                        Some('C') => col += csi_count(&params),
+                        Some('D') => col = col.saturating_sub(csi_count(&params)),
+                        Some('G') => col = csi_count(&params) - 1,
+                        Some('P') => {
+                            let end = (col + csi_count(&params)).min(line.len());
+                            if col < end {
+                                line.drain(col..end);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                // OSC, DCS, APC and PM strings run until BEL or ST.
+                Some(']' | 'P' | '_' | '^') => {
+                    while let Some(c) = chars.next() {
+                        if c == '\x07' {
+                            break;
+                        }
+                        if c == '\x1b' {
+                            if chars.peek() == Some(&'\\') {
+                                chars.next();
+                            }
+                            break;
+                        }
+                    }
+                }
+                // Character set designations carry one more byte.
+                Some('(' | ')' | '*' | '+' | '#' | '%') => {
+                    chars.next();
+                }
+                _ => {}
+            },
+            _ if alt_screen => {}
+            '\r' => col = 0,
+            '\n' => {
+                lines.push(finish_line(&line));
+                line.clear();
+                col = 0;
+            }
+            '\x08' => col = col.saturating_sub(1),
+            c if c.is_control() && c != '\t' => {}
+            c => {
+                if col < line.len() {
+                    line[col] = c;
+                } else {
+                    line.resize(col, ' ');
+                    line.push(c);
+                }
+                col += 1;
+            }
+        }
+    }
+    if !line.is_empty() {
+        lines.push(finish_line(&line));
+    }
+
+    let first = lines.iter().position(|l| !l.is_empty()).unwrap_or(lines.len());
+    let last = lines.iter().rposition(|l| !l.is_empty()).map(|i| i + 1).unwrap_or(first);
+    lines[first..last].join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(path: &Path) -> Vec<serde_json::Value> {
+        fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn strips_csi_sequences_and_applies_line_editing() {
+        let cases = [
+            ("\x1b[1;31mred\x1b[0m plain", "red plain"),
+            ("progress 10%\rprogress 100%", "progress 100%"),
+            ("abc\x08\x08X", "aXc"),
+            ("hello world\x1b[5D\x1b[K", "hello"),
+            ("hello\x1b[1G\x1b[2Pyo", "yoo"),
+            ("abc\x1b[2C!", "abc  !"),
+            ("left\x1b[3G\x1b[1K", "   t"),
+            ("gone\x1b[2Kkept", "    kept"),
+            ("\x1b(Bascii", "ascii"),
+        ];
+        for (raw, expected) in cases {
+            assert_eq!(to_plain_text(raw), expected, "{raw:?}");
+        }
+    }
+
+    #[test]
+    fn strips_osc_strings() {
+        let cases = [
+            ("\x1b]0;title\x07prompt$ ", "prompt$"),
+            ("\x1b]8;;https://example.com\x1b\\link\x1b]8;;\x1b\\ text", "link text"),
+            ("\x1bPdevice control\x1b\\after", "after"),
+        ];
+        for (raw, expected) in cases {
+            assert_eq!(to_plain_text(raw), expected, "{raw:?}");
+        }
+    }
+
+    #[test]
+    fn leaves_out_the_alternate_screen_and_blank_edges() {
+        let raw = "\n\n$ vim\n\x1b[?1049h\x1b[Hfile contents\n~\n\x1b[?1049l$ echo done\ndone\n\n";
+        assert_eq!(to_plain_text(raw), "$ vim\n$ echo done\ndone");
+    }
+
+    #[test]
+    fn writes_header_and_events() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rec/session.cast");
+        {
+            let mut recorder = Recorder::create(&path, 120, 40, "zsh").unwrap();
+            assert_eq!(recorder.path(), path);
+            recorder.output(b"$ ls\r\n");
+            recorder.resize(100, 30);
+        }
+        let lines = lines(&path);
+        let header = &lines[0];
+        assert_eq!(header["version"], 2);
+        assert_eq!((header["width"].as_u64(), header["height"].as_u64()), (Some(120), Some(40)));
+        assert_eq!(header["title"], "zsh");
+        assert_eq!(header["env"]["TERM"], "xterm-256color");
+        assert!(header["timestamp"].as_i64().unwrap() > 0);
+
+        assert_eq!((lines[1][1].as_str(), lines[1][2].as_str()), (Some("o"), Some("$ ls\r\n")));
+        assert_eq!((lines[2][1].as_str(), lines[2][2].as_str()), (Some("r"), Some("100x30")));
+        let times: Vec<f64> = lines[1..].iter().map(|event| event[0].as_f64().unwrap()).collect();
+        assert!(times.windows(2).all(|t| t[0] <= t[1]), "{times:?}");
+    }
+
+    #[test]
+    fn sequences_split_across_batches_are_stripped() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("split.cast");
+        {
+            let mut recorder = Recorder::create(&path, 80, 24, "").unwrap();
+            // A colour, an OSC title and a two-byte character, each cut between batches.
+            for chunk in [&b"\x1b[3"[..], b"2mgreen\x1b[0m \x1b]0;ti", b"tle\x07caf\xc3", b"\xa9\r\n"] {
+                recorder.output(chunk);
+            }
+        }
+        let events = lines(&path).len() - 1;
+        assert_eq!(events, 4, "the split character is held back, not replaced");
+        let raw = read_output(&path).unwrap();
+        assert!(!raw.contains('\u{fffd}'), "{raw:?}");
+        assert_eq!(to_plain_text(&raw), "green café");
+    }
+
+    #[test]
+    fn invalid_utf8_is_replaced_and_a_cut_tail_is_written_on_drop() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("invalid.cast");
+        {
+            let mut recorder = Recorder::create(&path, 80, 24, "").unwrap();
+            recorder.output(b"a\xffb");
+            recorder.output(b"c\xe2\x82");
+        }
+        assert_eq!(read_output(&path).unwrap(), "a\u{fffd}bc\u{fffd}");
+    }
+
+    #[test]
+    fn read_output_requires_asciicast_v2() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("old.cast");
+        fs::write(&path, "{\"version\":1}\n[0.1,\"o\",\"x\"]\n").unwrap();
+        assert!(read_output(&path).is_err());
+        fs::write(&path, "plain text\n").unwrap();
+        assert!(read_output(&path).is_err());
+        fs::write(&path, "{\"version\":2}\n[0.1,\"o\",\"a\"]\nnot json\n[0.2,\"i\",\"typed\"]\n[0.3,\"o\",\"b\"]\n").unwrap();
+        assert_eq!(read_output(&path).unwrap(), "ab");
+    }
+}
@@ -0,0 +1,645 @@
+use percent_encoding::percent_decode_str;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::async_runtime;
+use tauri_plugin_http::reqwest::{self, header, Method, RequestBuilder, Response, StatusCode, Url};
+
+use crate::quarantine::QUARANTINE_DIR;
+use crate::site;
+use crate::sync::{self, FileInfo, Provider, CHANGED_DURING_SYNC};
+
+const DAV: &str = "DAV:";
+const PROPFIND_BODY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:"><d:prop><d:resourcetype/><d:getcontentlength/><d:getlastmodified/><d:getetag/></d:prop></d:propfind>"#;
+
+/// One `<d:response>` of a PROPFIND, with its path relative to the base URL.
+struct Entry {
+    path: String,
+    dir: bool,
+    info: FileInfo,
+}
+
+/// A folder on a WebDAV server such as Nextcloud. Changes are detected by ETag, and
+/// writes and removals are conditional on the ETag seen when listing, so edits made on
+/// the server during a sync are never overwritten. Removed files are moved into a
+/// quarantine folder on the server rather than being deleted, where the server allows it.
+pub struct WebDav {
+    /// Always ends in `/`, and carries no credentials.
+    base: Url,
+    client: reqwest::Client,
+    username: String,
+    password: String,
+    /// Folders known to exist, so writes only create what is missing.
+    dirs: Mutex<HashSet<String>>,
+    /// Quarantine folder for this sync's removals, e.g. `.xnote_quarantine/20261018-091500`.
+    trash: String,
+    /// Whether anything was moved into `trash`.
+    trashed: AtomicBool,
+}
+
+impl WebDav {
+    pub fn new(url: &str, username: &str, password: &str) -> Result<Self, String> {
+        let mut base = Url::parse(url.trim()).map_err(|e| format!("Invalid WebDAV URL `{}`: {}", url, e))?;
+        if !matches!(base.scheme(), "http" | "https") {
+            return Err(format!("Invalid WebDAV URL `{}`: expected http or https", url));
+        }
+        // Credentials in the URL would end up in reports and the sync state.
+        let decode = |s: &str| percent_decode_str(s).decode_utf8_lossy().to_string();
+        let username = if username.is_empty() { decode(base.username()) } else { username.to_string() };
+        let password = if password.is_empty() { decode(base.password().unwrap_or("")) } else { password.to_string() };
+        let _ = base.set_username("");
+        let _ = base.set_password(None);
+        base.set_query(None);
+        base.set_fragment(None);
+        if !base.path().ends_with('/') {
+            let path = format!("{}/", base.path());
+            base.set_path(&path);
+        }
+        let client = reqwest::Client::builder()
+            .connect_timeout(Duration::from_secs(15))
+            .timeout(Duration::from_secs(300))
+            .build()
+            .map_err(|e| e.to_string())?;
+        Ok(Self {
+            base,
+            client,
+            username,
+            password,
+            dirs: Mutex::new(HashSet::new()),
+            trash: format!("{}/{}", QUARANTINE_DIR, chrono::Local::now().format("%Y%m%d-%H%M%S")),
+            trashed: AtomicBool::new(false),
+        })
+    }
+
+    /// URL of a file, or with `dir` of a folder; `""` is the base folder.
+    fn url(&self, path: &str, dir: bool) -> Result<Url, String> {
+        if path.is_empty() {
+            return Ok(self.base.clone());
+        }
+        sync::check_path(path)?;
+        // Appended rather than joined, so a name like `a:b.md` is not read as a scheme.
+        let slash = if dir { "/" } else { "" };
+        Url::parse(&format!("{}{}{}", self.base, site::encode_path(path), slash)).map_err(|e| e.to_string())
+    }
+
+    fn request(&self, method: &str, url: Url) -> RequestBuilder {
+        let method = Method::from_bytes(method.as_bytes()).expect("valid HTTP method");
+        let request = self.client.request(method, url);
+        if self.username.is_empty() {
+            request
+        } else {
+            request.basic_auth(&self.username, Some(&self.password))
+        }
+    }
+
+    async fn send(&self, request: RequestBuilder) -> Result<Response, String> {
+        let response = request.send().await.map_err(|e| format!("{}: {}", self.base, e))?;
+        match response.status() {
+            StatusCode::UNAUTHORIZED => Err(format!("Signing in to {} failed; check the user name and password", self.host())),
+            StatusCode::PRECONDITION_FAILED => Err(CHANGED_DURING_SYNC.to_string()),
+            _ => Ok(response),
+        }
+    }
+
+    fn host(&self) -> &str {
+        self.base.host_str().unwrap_or("the server")
+    }
+
+    /// Lists `path` (`depth` 0) or its direct children as well (`depth` 1).
+    async fn propfind(&self, path: &str, dir: bool, depth: &str) -> Result<Vec<Entry>, String> {
+        let request = self
+            .request("PROPFIND", self.url(path, dir)?)
+            .header("Depth", depth)
+            .header(header::CONTENT_TYPE, "application/xml; charset=utf-8")
+            .body(PROPFIND_BODY);
+        let response = self.send(request).await?;
+        let status = response.status();
+        if status == StatusCode::NOT_FOUND {
+            return Err(format!("{} does not exist", self.url(path, dir)?));
+        }
+        if status != StatusCode::MULTI_STATUS {
+            return Err(format!("Listing {}: {}", self.url(path, dir)?, status));
+        }
+        let text = response.text().await.map_err(|e| e.to_string())?;
+        self.parse_multistatus(&text)
+    }
+
+    fn parse_multistatus(&self, text: &str) -> Result<Vec<Entry>, String> {
+        let doc = roxmltree::Document::parse(text).map_err(|e| format!("Invalid WebDAV response: {}", e))?;
+        let base_path = percent_decode_str(self.base.path()).decode_utf8_lossy().to_string();
+        let mut entries = Vec::new();
+        for response in doc.descendants().filter(|n| n.has_tag_name((DAV, "response"))) {
+            let Some(href) = child_text(response, "href") else { continue };
+            // Servers send absolute paths or full URLs, percent-encoded in their own way.
+            let Ok(url) = self.base.join(href.trim()) else { continue };
+            let decoded = percent_decode_str(url.path()).decode_utf8_lossy().to_string();
+            // With a trailing slash on both, the base folder itself is `""`.
+            let decoded = format!("{}/", decoded.trim_end_matches('/'));
+            let Some(path) = decoded.strip_prefix(&base_path) else { continue };
+            let path = path.trim_end_matches('/').to_string();
+
+            let ok = response
+                .children()
+                .filter(|n| n.has_tag_name((DAV, "propstat")))
+                .filter(|n| child_text(*n, "status").map(|s| s.contains(" 200 ")).unwrap_or(false))
+                .find_map(|n| child(n, "prop"));
+            let Some(prop) = ok else { continue };
+            let dir = child(prop, "resourcetype").and_then(|t| child(t, "collection")).is_some();
+            let size = child_text(prop, "getcontentlength").and_then(|s| s.trim().parse().ok()).unwrap_or(0);
+            let modified = child_text(prop, "getlastmodified")
+                .and_then(|s| chrono::DateTime::parse_from_rfc2822(s.trim()).ok())
+                .map(|t| t.timestamp_millis())
+                .unwrap_or(0);
+            let version = child_text(prop, "getetag").map(|s| s.trim().to_string()).unwrap_or_default();
+            entries.push(Entry { path, dir, info: FileInfo { size, modified, version } });
+        }
+        Ok(entries)
+    }
+
+    /// Creates the folders above `path` that are not known to exist, top down.
+    async fn make_parents(&self, path: &str) -> Result<(), String> {
+        let parts: Vec<&str> = path.split('/').collect();
+        for i in 1..parts.len() {
+            let dir = parts[..i].join("/");
+            if self.dirs.lock().unwrap().contains(&dir) {
+                continue;
+            }
+            let response = self.send(self.request("MKCOL", self.url(&dir, true)?)).await?;
+            // 405: the folder exists already.
+            if !response.status().is_success() && response.status() != StatusCode::METHOD_NOT_ALLOWED {
+                return Err(format!("Creating folder {}: {}", dir, response.status()));
+            }
+            self.dirs.lock().unwrap().insert(dir);
+        }
+        Ok(())
+    }
+}
+
+fn child<'a, 'input>(node: roxmltree::Node<'a, 'input>, name: &str) -> Option<roxmltree::Node<'a, 'input>> {
+    node.children().find(|c| c.has_tag_name((DAV, name)))
+}
+
+fn child_text(node: roxmltree::Node, name: &str) -> Option<String> {
+    child(node, name).map(|c| c.descendants().filter(|d| d.is_text()).filter_map(|d| d.text()).collect())
+}
+
+/// `If-Match` for a file as listed; servers without ETags get an unconditional request.
+fn if_match(request: RequestBuilder, info: &FileInfo) -> RequestBuilder {
+    if info.version.is_empty() {
+        request
+    } else {
+        request.header(header::IF_MATCH, &info.version)
+    }
+}
+
+impl Provider for WebDav {
+    fn location(&self) -> String {
+        self.base.to_string()
+    }
+
+    fn list(&self) -> Result<HashMap<String, FileInfo>, String> {
+        async_runtime::block_on(async {
+            let mut files = HashMap::new();
+            let mut dirs = HashSet::from([String::new()]);
+            // Folder by folder: many servers, Nextcloud included, refuse `Depth: infinity`.
+            let mut queue = VecDeque::from([String::new()]);
+            while let Some(dir) = queue.pop_front() {
+                for entry in self.propfind(&dir, true, "1").await? {
+                    let name = entry.path.rsplit('/').next().unwrap_or_default();
+                    if entry.path == dir || sync::is_skipped(name, entry.dir) {
+                        continue;
+                    }
+                    if entry.dir {
+                        if dirs.insert(entry.path.clone()) {
+                            queue.push_back(entry.path);
+                        }
+                    } else {
+                        files.insert(entry.path, entry.info);
+                    }
+                }
+            }
+            *self.dirs.lock().unwrap() = dirs;
+            Ok(files)
+        })
+    }
+
+    fn read(&self, path: &str) -> Result<Vec<u8>, String> {
+        async_runtime::block_on(async {
+            let response = self.send(self.request("GET", self.url(path, false)?)).await?;
+            if !response.status().is_success() {
+                return Err(format!("Downloading: {}", response.status()));
+            }
+            response.bytes().await.map(|b| b.to_vec()).map_err(|e| e.to_string())
+        })
+    }
+
+    fn write(&self, path: &str, data: &[u8], modified: i64, replaces: Option<&FileInfo>) -> Result<FileInfo, String> {
+        async_runtime::block_on(async {
+            self.make_parents(path).await?;
+            // Nextcloud and ownCloud keep the modification time sent along.
+            let request = self
+                .request("PUT", self.url(path, false)?)
+                .header("X-OC-Mtime", (modified / 1000).to_string())
+                .body(data.to_vec());
+            let request = match replaces {
+                Some(info) => if_match(request, info),
+                None => request.header(header::IF_NONE_MATCH, "*"),
+            };
+            let response = self.send(request).await?;
+            if !response.status().is_success() {
+                return Err(format!("Uploading: {}", response.status()));
+            }
+            // The PUT response's ETag, where there is one, is not always the one PROPFIND reports.
+            self.propfind(path, false, "0")
+                .await?
+                .into_iter()
+                .find(|e| !e.dir)
+                .map(|e| e.info)
+                .ok_or_else(|| "Uploaded file is missing on the server".to_string())
+        })
+    }
+
+    fn remove(&self, path: &str, listed: &FileInfo) -> Result<(), String> {
+        async_runtime::block_on(async {
+            let target = format!("{}/{}", self.trash, path);
+            self.make_parents(&target).await?;
+            let request = self
+                .request("MOVE", self.url(path, false)?)
+                .header("Destination", self.url(&target, false)?.as_str())
+                .header("Overwrite", "F");
+            let response = self.send(if_match(request, listed)).await?;
+            let status = response.status();
+            if status.is_success() {
+                self.trashed.store(true, Ordering::Relaxed);
+                return Ok(());
+            }
+            // Some servers do not allow moving; removing outright is all that is left.
+            if !matches!(status, StatusCode::FORBIDDEN | StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED | StatusCode::BAD_GATEWAY) {
+                return Err(format!("Moving to {}: {}", target, status));
+            }
+            let request = self.request("DELETE", self.url(path, false)?);
+            let response = self.send(if_match(request, listed)).await?;
+            if !response.status().is_success() {
+                return Err(format!("Deleting: {}", response.status()));
+            }
+            Ok(())
+        })
+    }
+
+    fn finish(&self) -> Result<Option<String>, String> {
+        if !self.trashed.swap(false, Ordering::Relaxed) {
+            return Ok(None);
+        }
+        Ok(Some(self.url(&self.trash, true)?.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+    use serde_json::Value;
+    use std::collections::{BTreeMap, BTreeSet};
+    use std::fs;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    use crate::sync::{run_sync, Folder};
+
+    const BASE: &str = "/dav/files/me/My Notes/";
+
+    /// What the test server holds: files as (contents, ETag number, mtime in seconds).
+    #[derive(Default)]
+    struct Store {
+        files: BTreeMap<String, (Vec<u8>, u64, i64)>,
+        dirs: BTreeSet<String>,
+        etag: u64,
+        /// Refuse MOVE, as some servers do, so removals fall back to DELETE.
+        no_move: bool,
+        /// Written just before the next PUT to that path, as if by someone else.
+        sneak_in: Option<(String, Vec<u8>)>,
+    }
+
+    impl Store {
+        fn put(&mut self, path: &str, data: &[u8], mtime: i64) {
+            self.etag += 1;
+            self.files.insert(path.to_string(), (data.to_vec(), self.etag, mtime));
+        }
+
+        fn text(&self, path: &str) -> Option<String> {
+            self.files.get(path).map(|f| String::from_utf8_lossy(&f.0).to_string())
+        }
+    }
+
+    /// A WebDAV server on localhost that accepts `me`/`pw` and answers PROPFIND, GET, PUT,
+    /// MKCOL, MOVE and DELETE with ETags and `If-Match`/`If-None-Match` checks.
+    struct Server {
+        url: String,
+        store: Arc<Mutex<Store>>,
+    }
+
+    impl Server {
+        fn start() -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let port = listener.local_addr().unwrap().port();
+            let store = Arc::new(Mutex::new(Store::default()));
+            let shared = store.clone();
+            std::thread::spawn(move || {
+                for stream in listener.incoming() {
+                    let Ok(stream) = stream else { continue };
+                    serve(&shared, stream);
+                }
+            });
+            let url = format!("http://127.0.0.1:{}{}", port, encode(BASE));
+            Server { url, store }
+        }
+
+        fn provider(&self) -> WebDav {
+            WebDav::new(&self.url, "me", "pw").unwrap()
+        }
+    }
+
+    fn encode(path: &str) -> String {
+        path.split('/').map(|s| utf8_percent_encode(s, NON_ALPHANUMERIC).to_string()).collect::<Vec<_>>().join("/")
+    }
+
+    fn serve(store: &Mutex<Store>, mut stream: std::net::TcpStream) {
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        let mut parts = line.split_whitespace();
+        let (method, target) = (parts.next().unwrap_or("").to_string(), parts.next().unwrap_or("").to_string());
+        let mut headers = HashMap::new();
+        loop {
+            let mut header = String::new();
+            reader.read_line(&mut header).unwrap();
+            let Some((name, value)) = header.trim_end().split_once(':') else { break };
+            headers.insert(name.to_lowercase(), value.trim().to_string());
+        }
+        let length = headers.get("content-length").and_then(|v| v.parse().ok()).unwrap_or(0);
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).unwrap();
+
+        let (status, out) = respond(&mut store.lock().unwrap(), &method, &target, &headers, body);
+        let _ = write!(stream, "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", status, out.len());
+        let _ = stream.write_all(&out);
+    }
+
+    fn respond(st: &mut Store, method: &str, target: &str, headers: &HashMap<String, String>, body: Vec<u8>) -> (&'static str, Vec<u8>) {
+        // `me:pw`
+        if headers.get("authorization").map(String::as_str) != Some("Basic bWU6cHc=") {
+            return ("401 Unauthorized", Vec::new());
+        }
+        let decoded = percent_decode_str(target).decode_utf8_lossy().to_string();
+        let decoded = format!("{}/", decoded.trim_end_matches('/'));
+        let Some(rel) = decoded.strip_prefix(BASE).map(|p| p.trim_end_matches('/').to_string()) else {
+            return ("404 Not Found", Vec::new());
+        };
+        let etag = |st: &Store, p: &str| st.files.get(p).map(|f| format!("\"e{}\"", f.1));
+        let precondition = |st: &Store, p: &str| {
+            let matches = headers.get("if-match").map(|m| etag(st, p).as_deref() == Some(m.as_str())).unwrap_or(true);
+            let absent = headers.get("if-none-match").map(|v| v != "*" || !st.files.contains_key(p)).unwrap_or(true);
+            matches && absent
+        };
+        let parent_exists = |st: &Store, p: &str| p.rfind('/').map(|i| st.dirs.contains(&p[..i])).unwrap_or(true);
+
+        match method {
+            "PROPFIND" => {
+                let entry = |p: &str| -> Option<String> {
+                    let href = encode(&format!("{}{}", BASE, p));
+                    if p.is_empty() || st.dirs.contains(p) {
+                        return Some(format!(
+                            "<d:response><d:href>{}/</d:href><d:propstat><d:prop><d:resourcetype><d:collection/></d:resourcetype>\
+                             </d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>",
+                            href.trim_end_matches('/')
+                        ));
+                    }
+                    st.files.get(p).map(|(data, tag, mtime)| {
+                        let date = chrono::DateTime::from_timestamp(*mtime, 0).unwrap().format("%a, %d %b %Y %H:%M:%S GMT");
+                        format!(
+                            "<d:response><d:href>{}</d:href><d:propstat><d:prop><d:resourcetype/><d:getcontentlength>{}</d:getcontentlength>\
+                             <d:getlastmodified>{}</d:getlastmodified><d:getetag>\"e{}\"</d:getetag></d:prop>\
+                             <d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>",
+                            href,
+                            data.len(),
+                            date,
+                            tag
+                        )
+                    })
+                };
+                let Some(own) = entry(&rel) else { return ("404 Not Found", Vec::new()) };
+                let mut xml = format!(r#"<?xml version="1.0"?><d:multistatus xmlns:d="DAV:">{}"#, own);
+                if headers.get("depth").map(String::as_str) == Some("1") {
+                    let prefix = if rel.is_empty() { String::new() } else { format!("{}/", rel) };
+                    let children = st.dirs.iter().chain(st.files.keys()).filter(|p| {
+                        p.strip_prefix(&prefix).map(|r| !r.is_empty() && !r.contains('/')).unwrap_or(false)
+                    });
+                    for child in children {
+                        xml.push_str(&entry(child).unwrap());
+                    }
+                }
+                xml.push_str("</d:multistatus>");
+                ("207 Multi-Status", xml.into_bytes())
+            }
+            "GET" => match st.files.get(&rel) {
+                Some(file) => ("200 OK", file.0.clone()),
+                None => ("404 Not Found", Vec::new()),
+            },
+            "PUT" => {
+                if let Some((path, data)) = st.sneak_in.take_if(|(path, _)| *path == rel) {
+                    st.put(&path, &data, 1_900_000_000);
+                }
+                if !precondition(st, &rel) {
+                    return ("412 Precondition Failed", Vec::new());
+                }
+                if !parent_exists(st, &rel) {
+                    return ("409 Conflict", Vec::new());
+                }
+                let mtime = headers.get("x-oc-mtime").and_then(|v| v.parse().ok()).unwrap_or(0);
+                st.put(&rel, &body, mtime);
+                ("201 Created", Vec::new())
+            }
+            "MKCOL" => {
+                if st.dirs.contains(&rel) || st.files.contains_key(&rel) {
+                    return ("405 Method Not Allowed", Vec::new());
+                }
+                if !parent_exists(st, &rel) {
+                    return ("409 Conflict", Vec::new());
+                }
+                st.dirs.insert(rel);
+                ("201 Created", Vec::new())
+            }
+            "MOVE" if st.no_move => ("405 Method Not Allowed", Vec::new()),
+            "MOVE" | "DELETE" => {
+                if !precondition(st, &rel) {
+                    return ("412 Precondition Failed", Vec::new());
+                }
+                let Some(file) = st.files.remove(&rel) else { return ("404 Not Found", Vec::new()) };
+                if method == "MOVE" {
+                    let destination = Url::parse(&headers["destination"]).unwrap();
+                    let destination = percent_decode_str(destination.path()).decode_utf8_lossy().to_string();
+                    st.files.insert(destination.strip_prefix(BASE).unwrap().to_string(), file);
+                }
+                ("201 Created", Vec::new())
+            }
+            _ => ("405 Method Not Allowed", Vec::new()),
+        }
+    }
+
+    fn sync(local: &Path, remote: &WebDav) -> Result<Value, String> {
+        let cancelled = AtomicBool::new(false);
+        let report = run_sync(local, &Folder::new(local), remote, false, &cancelled)?;
+        Ok(serde_json::to_value(report).unwrap())
+    }
+
+    fn actions(report: &Value) -> Vec<(String, String)> {
+        report["changes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|c| (c["path"].as_str().unwrap().to_string(), c["action"].as_str().unwrap().to_string()))
+            .collect()
+    }
+
+    fn pairs(list: &[(&str, &str)]) -> Vec<(String, String)> {
+        list.iter().map(|(a, b)| (a.to_string(), b.to_string())).collect()
+    }
+
+    #[test]
+    fn syncs_a_folder_with_the_server() {
+        let server = Server::start();
+        let temp = tempfile::tempdir().unwrap();
+        let local = temp.path();
+        fs::create_dir_all(local.join("sub dir")).unwrap();
+        fs::write(local.join("a.md"), "A").unwrap();
+        fs::write(local.join("sub dir/b é#?.md"), "B").unwrap();
+        fs::write(local.join("x:y.md"), "X").unwrap();
+
+        let report = sync(local, &server.provider()).unwrap();
+        assert_eq!(report["uploaded"], 3);
+        {
+            let st = server.store.lock().unwrap();
+            assert_eq!(st.text("sub dir/b é#?.md").as_deref(), Some("B"));
+            assert_eq!(st.text("x:y.md").as_deref(), Some("X"));
+        }
+        assert_eq!(sync(local, &server.provider()).unwrap()["changes"], Value::Array(Vec::new()));
+
+        server.store.lock().unwrap().put("new.md", b"from the server", 1_800_000_000);
+        let report = sync(local, &server.provider()).unwrap();
+        assert_eq!(actions(&report), pairs(&[("new.md", "download")]));
+        assert_eq!(fs::read_to_string(local.join("new.md")).unwrap(), "from the server");
+
+        // Removals are moved into a quarantine folder on the server.
+        fs::remove_file(local.join("sub dir/b é#?.md")).unwrap();
+        let report = sync(local, &server.provider()).unwrap();
+        assert_eq!(actions(&report), pairs(&[("sub dir/b é#?.md", "deleteRemote")]));
+        assert!(report["remoteQuarantine"].as_str().unwrap().contains(QUARANTINE_DIR));
+        let st = server.store.lock().unwrap();
+        assert!(!st.files.contains_key("sub dir/b é#?.md"));
+        assert!(st.files.keys().any(|p| p.starts_with(QUARANTINE_DIR) && p.ends_with("sub dir/b é#?.md")));
+    }
+
+    #[test]
+    fn removes_outright_where_moving_is_refused() {
+        let server = Server::start();
+        let temp = tempfile::tempdir().unwrap();
+        fs::write(temp.path().join("a.md"), "A").unwrap();
+        sync(temp.path(), &server.provider()).unwrap();
+
+        server.store.lock().unwrap().no_move = true;
+        fs::remove_file(temp.path().join("a.md")).unwrap();
+        fs::write(temp.path().join("keep.md"), "K").unwrap();
+        let report = sync(temp.path(), &server.provider()).unwrap();
+        assert!(actions(&report).contains(&("a.md".to_string(), "deleteRemote".to_string())));
+        assert_eq!(report["remoteQuarantine"], Value::Null);
+        assert!(server.store.lock().unwrap().files.keys().all(|p| p == "keep.md"));
+    }
+
+    #[test]
+    fn an_edit_on_the_server_during_sync_is_not_overwritten() {
+        let server = Server::start();
+        let temp = tempfile::tempdir().unwrap();
+        let local = temp.path();
+        fs::write(local.join("a.md"), "first").unwrap();
+        sync(local, &server.provider()).unwrap();
+
+        fs::write(local.join("a.md"), "local edit").unwrap();
+        server.store.lock().unwrap().sneak_in = Some(("a.md".to_string(), b"server edit".to_vec()));
+        let err = sync(local, &server.provider()).unwrap_err();
+        assert!(err.contains(CHANGED_DURING_SYNC), "{}", err);
+        assert_eq!(server.store.lock().unwrap().text("a.md").as_deref(), Some("server edit"));
+
+        // The next sync sees both edits and keeps both.
+        let report = sync(local, &server.provider()).unwrap();
+        assert_eq!(actions(&report), pairs(&[("a.md", "conflict")]));
+        let copy = report["changes"][0]["conflictCopy"].as_str().unwrap().to_string();
+        let mut both = vec![fs::read_to_string(local.join("a.md")).unwrap(), fs::read_to_string(local.join(&copy)).unwrap()];
+        both.sort();
+        assert_eq!(both, vec!["local edit", "server edit"]);
+    }
+
+    #[test]
+    fn conditional_writes_and_removals() {
+        let server = Server::start();
+        server.store.lock().unwrap().put("a.md", b"one", 1_700_000_000);
+        let remote = server.provider();
+        let listed = remote.list().unwrap()["a.md"].clone();
+        assert_eq!(listed.version, "\"e1\"");
+
+        server.store.lock().unwrap().put("a.md", b"two", 1_700_000_001);
+        assert_eq!(remote.write("a.md", b"mine", 0, Some(&listed)).unwrap_err(), CHANGED_DURING_SYNC);
+        assert_eq!(remote.remove("a.md", &listed).unwrap_err(), CHANGED_DURING_SYNC);
+        assert_eq!(remote.write("a.md", b"mine", 0, None).unwrap_err(), CHANGED_DURING_SYNC);
+        assert_eq!(server.store.lock().unwrap().text("a.md").as_deref(), Some("two"));
+
+        let wrong = WebDav::new(&server.url, "me", "nope").unwrap();
+        assert!(wrong.list().unwrap_err().contains("check the user name and password"));
+    }
+
+    #[test]
+    fn parses_multistatus_hrefs_however_encoded() {
+        let dav = WebDav::new("https://cloud.example.com/dav/files/me/My%20Notes", "", "").unwrap();
+        let xml = r#"<?xml version="1.0"?>
+            <d:multistatus xmlns:d="DAV:">
+              <d:response><d:href>/dav/files/me/My%20Notes/</d:href>
+                <d:propstat><d:prop><d:resourcetype><d:collection/></d:resourcetype></d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat>
+              </d:response>
+              <d:response><d:href>/dav/files/me/My%20Notes/sub%20dir/</d:href>
+                <d:propstat><d:prop><d:resourcetype><d:collection/></d:resourcetype></d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat>
+                <d:propstat><d:prop><d:getcontentlength/></d:prop><d:status>HTTP/1.1 404 Not Found</d:status></d:propstat>
+              </d:response>
+              <d:response><d:href>https://cloud.example.com/dav/files/me/My%20Notes/caf%C3%A9%20%231.md</d:href>
+                <d:propstat><d:prop><d:resourcetype/><d:getcontentlength>12</d:getcontentlength>
+                  <d:getlastmodified>Tue, 14 Nov 2023 22:13:20 GMT</d:getlastmodified><d:getetag>"abc"</d:getetag></d:prop>
+                  <d:status>HTTP/1.1 200 OK</d:status></d:propstat>
+              </d:response>
+              <d:response><d:href>/dav/files/me/My%20Notes/a%3Ab.md</d:href>
+                <d:propstat><d:prop><d:resourcetype/><d:getcontentlength>3</d:getcontentlength></d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat>
+              </d:response>
+              <d:response><d:href>/dav/files/me/Other/x.md</d:href>
+                <d:propstat><d:prop><d:resourcetype/></d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat>
+              </d:response>
+              <d:response><d:href>/dav/files/me/My%20Notes/gone.md</d:href>
+                <d:propstat><d:prop><d:resourcetype/></d:prop><d:status>HTTP/1.1 404 Not Found</d:status></d:propstat>
+              </d:response>
+            </d:multistatus>"#;
+        let entries = dav.parse_multistatus(xml).unwrap();
+        let summary: Vec<(&str, bool, u64, i64, &str)> = entries
+            .iter()
+            .map(|e| (e.path.as_str(), e.dir, e.info.size, e.info.modified, e.info.version.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("", true, 0, 0, ""),
+                ("sub dir", true, 0, 0, ""),
+                ("café #1.md", false, 12, 1_700_000_000_000, "\"abc\""),
+                ("a:b.md", false, 3, 0, ""),
+            ]
+        );
+    }
+}
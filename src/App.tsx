@@ -149,6 +149,14 @@ function App() {
     let unlistenCleanResult: () => void;
     let unlistenCleanLog: () => void;
     let unlistenDeleteResult: () => void;
+    let unlistenConfigChanged: () => void;
+    let unlistenSiteBuilt: () => void;
+    let unlistenJobFinished: () => void;
+    let unlistenEpubExported: () => void;
+    let unlistenImported: () => void;
+    let unlistenBackedUp: () => void;
+    let unlistenRestored: () => void;
+    let unlistenSynced: () => void;
     
     const setupListener = async () => {
         // @ts-ignore
@@ -213,6 +221,117 @@ function App() {
             setCleanRunning(false);
             setCleanBarVisible(true);
         });
+        unlistenSiteBuilt = await listen('site-build-result', (event: any) => {
+            const payload = event?.payload as any;
+            if (!payload) return;
+            useAppStore.getState().pushNotice(`Site built: ${payload.pages} pages, ${payload.assets} assets in ${payload.outDir}`, 'success');
+        });
+        unlistenEpubExported = await listen('epub-export-result', (event: any) => {
+            const payload = event?.payload as any;
+            if (!payload) return;
+            useAppStore.getState().pushNotice(`EPUB exported: ${payload.chapters} chapters to ${payload.path}`, 'success');
+        });
+        unlistenImported = await listen('import-result', async (event: any) => {
+            const payload = event?.payload as any;
+            if (!payload) return;
+            const store = useAppStore.getState();
+            const summary = `${payload.notes} notes and ${payload.attachments} attachments`;
+            if (!payload.dryRun) {
+                store.pushNotice(`Imported ${summary} into ${payload.targetDir}`, 'success');
+                await store.loadFiles(store.currentPath);
+                return;
+            }
+            const warnings: string[] = payload.warnings ?? [];
+            const details = warnings.length > 0
+                ? `\n\n${warnings.length} warning(s):\n${warnings.slice(0, 8).join('\n')}${warnings.length > 8 ? '\n…' : ''}`
+                : '';
+            if (!window.confirm(`Import ${summary} from ${payload.source} into ${payload.targetDir}?${details}`)) return;
+            try {
+                await invoke('import_notes', {
+                    source: payload.source,
+                    rootPath: store.currentPath,
+                    targetDir: payload.targetDir,
+                    format: payload.format,
+                    dryRun: false,
+                });
+                store.pushNotice('Importing notes…', 'info');
+            } catch (e) {
+                store.pushNotice(`Failed to import notes: ${e}`, 'error');
+            }
+        });
+        unlistenBackedUp = await listen('backup-result', (event: any) => {
+            const payload = event?.payload as any;
+            if (!payload) return;
+            const warnings: string[] = payload.warnings ?? [];
+            const message = `Backed up ${payload.files} files to ${payload.path}${warnings.length > 0 ? ` (${warnings.join('; ')})` : ''}`;
+            useAppStore.getState().pushNotice(message, warnings.length > 0 ? 'info' : 'success');
+        });
+        unlistenRestored = await listen('restore-result', async (event: any) => {
+            const payload = event?.payload as any;
+            if (!payload) return;
+            const store = useAppStore.getState();
+            const replaced = payload.replaced > 0 ? `, ${payload.replaced} replaced (old versions in quarantine)` : '';
+            const warnings: string[] = payload.warnings ?? [];
+            store.pushNotice(
+                `Restored ${payload.restored} files into ${payload.target}${replaced}${warnings.length > 0 ? `. ${warnings.join('; ')}` : ''}`,
+                warnings.length > 0 ? 'info' : 'success'
+            );
+            if (store.currentPath && payload.target === store.currentPath) {
+                await store.loadFiles(store.currentPath);
+            }
+        });
+        unlistenSynced = await listen('sync-result', async (event: any) => {
+            const payload = event?.payload as any;
+            if (!payload) return;
+            const store = useAppStore.getState();
+            const summary = `${payload.uploaded} to send, ${payload.downloaded} to receive, ${payload.deleted} to remove, ${payload.conflicts} conflicts`;
+            if (!payload.dryRun) {
+                const done = `Synced with ${payload.remote}: ${payload.uploaded} sent, ${payload.downloaded} received, ${payload.deleted} removed`;
+                store.pushNotice(payload.conflicts > 0 ? `${done}, ${payload.conflicts} conflict copies created` : done, payload.conflicts > 0 ? 'info' : 'success');
+                await store.loadFiles(store.currentPath);
+                return;
+            }
+            if (payload.changes.length === 0) {
+                store.pushNotice(`${payload.remote} is already in sync`, 'success');
+                return;
+            }
+            const lines = payload.changes.slice(0, 12).map((c: any) => `${c.action}: ${c.path}`);
+            const details = `\n\n${lines.join('\n')}${payload.changes.length > 12 ? '\n…' : ''}`;
+            if (!window.confirm(`Sync with ${payload.remote}? ${summary}.${details}`)) return;
+            try {
+                await invoke('sync_workspace', { rootPath: payload.root, remote: payload.remote, dryRun: false });
+            } catch (e) {
+                store.pushNotice(`Failed to sync: ${e}`, 'error');
+            }
+        });
+        unlistenJobFinished = await listen('job-finished', (event: any) => {
+            const payload = event?.payload as any;
+            if (payload?.status !== 'failed') return;
+            const label = payload.kind === 'build-site' ? 'build site'
+                : payload.kind === 'export-epub' ? 'export EPUB'
+                : payload.kind === 'import-notes' ? 'import notes'
+                : payload.kind === 'backup' ? 'back up the workspace'
+                : payload.kind === 'restore-backup' ? 'restore the backup'
+                : payload.kind === 'sync' ? 'sync'
+                : null;
+            if (label) {
+              useAppStore.getState().pushNotice(`Failed to ${label}: ${payload.error ?? 'unknown error'}`, 'error');
+            }
+        });
+        unlistenConfigChanged = await listen('config-changed', async (event: any) => {
+            const payload = event?.payload as any;
+            if (!payload?.config) return;
+            const store = useAppStore.getState();
+            await store.applyConfig({ ...payload.config, revision: payload.revision });
+            const problems = [...(payload.warnings ?? []), ...(payload.errors ?? [])];
+            if (problems.length > 0) {
+              store.pushNotice(`Config: ${problems.join('; ')}`, 'error');
+            } else if (payload.conflicts?.length > 0) {
+              store.pushNotice(`Config: kept your changes over edits on disk to ${payload.conflicts.join(', ')}`, 'info');
+            } else if (payload.source === 'external') {
+              store.pushNotice('Config reloaded from disk', 'info');
+            }
+        });
     };
 
     setupListener();
@@ -239,6 +358,30 @@ function App() {
         if (unlistenDeleteResult) {
             unlistenDeleteResult();
         }
+        if (unlistenConfigChanged) {
+            unlistenConfigChanged();
+        }
+        if (unlistenSiteBuilt) {
+            unlistenSiteBuilt();
+        }
+        if (unlistenJobFinished) {
+            unlistenJobFinished();
+        }
+        if (unlistenEpubExported) {
+            unlistenEpubExported();
+        }
+        if (unlistenImported) {
+            unlistenImported();
+        }
+        if (unlistenBackedUp) {
+            unlistenBackedUp();
+        }
+        if (unlistenRestored) {
+            unlistenRestored();
+        }
+        if (unlistenSynced) {
+            unlistenSynced();
+        }
     }
   }, []);
 
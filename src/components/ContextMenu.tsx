@@ -1,5 +1,5 @@
 import React, { useEffect, useRef } from 'react';
-import { Copy, FolderPlus, Trash2, FilePlus, FolderInput, Edit2 } from 'lucide-react';
+import { Copy, FolderPlus, Trash2, FilePlus, FolderInput, Edit2, FileDown, Globe, BookOpen, Import, Lock, LockOpen } from 'lucide-react';
 
 interface ContextMenuProps {
     x: number;
@@ -13,10 +13,16 @@ interface ContextMenuProps {
     onNewNote?: () => void;
     onMoveTo?: () => void;
     onRename?: () => void;
+    onExportHtml?: () => void;
+    onBuildSite?: () => void;
+    onExportEpub?: () => void;
+    onImport?: () => void;
+    onEncrypt?: () => void;
+    onDecrypt?: () => void;
 }
 
 export const ContextMenu: React.FC<ContextMenuProps> = (props) => {
-    const { x, y, target, type, onClose, onDelete, onNewGroup, onDuplicate, onNewNote, onMoveTo, onRename } = props;
+    const { x, y, target, type, onClose, onDelete, onNewGroup, onDuplicate, onNewNote, onMoveTo, onRename, onExportHtml, onBuildSite, onExportEpub, onImport, onEncrypt, onDecrypt } = props;
     const menuRef = useRef<HTMLDivElement>(null);
 
     useEffect(() => {
@@ -90,6 +96,60 @@ export const ContextMenu: React.FC<ContextMenuProps> = (props) => {
                 </button>
             )}
 
+            {onExportHtml && !target.isDir && (
+                <button 
+                    onClick={() => { onExportHtml(); onClose(); }}
+                    className="w-full text-left px-3 py-2 text-sm hover:bg-surfaceHighlight flex items-center"
+                >
+                    <FileDown size={14} className="mr-2" /> Export as HTML
+                </button>
+            )}
+
+            {onBuildSite && target.isDir && (
+                <button 
+                    onClick={() => { onBuildSite(); onClose(); }}
+                    className="w-full text-left px-3 py-2 text-sm hover:bg-surfaceHighlight flex items-center"
+                >
+                    <Globe size={14} className="mr-2" /> Build Site
+                </button>
+            )}
+
+            {onExportEpub && target.isDir && (
+                <button 
+                    onClick={() => { onExportEpub(); onClose(); }}
+                    className="w-full text-left px-3 py-2 text-sm hover:bg-surfaceHighlight flex items-center"
+                >
+                    <BookOpen size={14} className="mr-2" /> Export as EPUB
+                </button>
+            )}
+
+            {onImport && target.isDir && (
+                <button 
+                    onClick={() => { onImport(); onClose(); }}
+                    className="w-full text-left px-3 py-2 text-sm hover:bg-surfaceHighlight flex items-center"
+                >
+                    <Import size={14} className="mr-2" /> Import Notes…
+                </button>
+            )}
+
+            {onEncrypt && (
+                <button 
+                    onClick={() => { onEncrypt(); onClose(); }}
+                    className="w-full text-left px-3 py-2 text-sm hover:bg-surfaceHighlight flex items-center"
+                >
+                    <Lock size={14} className="mr-2" /> Encrypt
+                </button>
+            )}
+
+            {onDecrypt && (
+                <button 
+                    onClick={() => { onDecrypt(); onClose(); }}
+                    className="w-full text-left px-3 py-2 text-sm hover:bg-surfaceHighlight flex items-center"
+                >
+                    <LockOpen size={14} className="mr-2" /> Remove Encryption
+                </button>
+            )}
+
             {type !== 'root' && (
                 <>
                     <div className="h-px bg-border my-1" />
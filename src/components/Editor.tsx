@@ -6,7 +6,8 @@ import rehypeHighlight from 'rehype-highlight';
 import remarkGfm from 'remark-gfm';
 import { useAppStore } from '../store';
 import { invoke, convertFileSrc } from '@tauri-apps/api/core';
-import { Columns, Maximize, Eye, Table, Sparkles, Terminal } from 'lucide-react';
+import { listen } from '@tauri-apps/api/event';
+import { Columns, Maximize, Eye, Table, Sparkles, Terminal, Copy, ClipboardCopy, Lock } from 'lucide-react';
 import { clsx } from 'clsx';
 import 'highlight.js/styles/github-dark.css'; // or atom-one-dark
 // @ts-ignore
@@ -31,10 +32,14 @@ const hashString = (input: string) => {
 };
 
 export const NoteEditor: React.FC = () => {
-  const { selectedFile, editorMode, setEditorMode, currentPath, searchJump, setSearchJump, setLLMPanelOpen, llmPanelOpen, setChatInput, terminalOpen, toggleTerminal } =
+  const { selectedFile, editorMode, setEditorMode, currentPath, searchJump, setSearchJump, setLLMPanelOpen, llmPanelOpen, setChatInput, terminalOpen, toggleTerminal, pushNotice } =
     useAppStore();
   const [content, setContent] = useState('');
   const [isSaving, setIsSaving] = useState(false);
+  // The note is encrypted and its workspace locked; nothing is shown or saved.
+  const [locked, setLocked] = useState(false);
+  const [passphrase, setPassphrase] = useState('');
+  const [reloadKey, setReloadKey] = useState(0);
   const editorRef = useRef<any>(null);
   const [editorInstance, setEditorInstance] = useState<any>(null);
   const deferredContent = useDeferredValue(content);
@@ -96,6 +101,33 @@ export const NoteEditor: React.FC = () => {
           const items = event.clipboardData?.items;
           if (!items) return;
 
+          const types = Array.from(event.clipboardData?.types || []);
+          const isMockWorkspace = workspacePath.startsWith('/mock') || !isTauri;
+          // Copies from the editor itself carry HTML too; keep those as plain text.
+          const richPaste = !types.includes('vscode-editor-data')
+              && (types.includes('text/html') || types.includes('Files')
+                  || Array.from(items).some((item) => item.type.startsWith('image/')));
+          if (!isMockWorkspace && richPaste) {
+              event.preventDefault();
+              event.stopPropagation();
+              invoke<{ markdown: string; source: string; assets: string[] }>('paste_clipboard_markdown', {
+                  rootPath: workspacePath,
+                  notePath: file.path,
+              })
+                  .then(({ markdown }) => {
+                      const selection = editorInstance.getSelection();
+                      if (selection && markdown) {
+                          const op = { range: selection, text: markdown, forceMoveMarkers: true };
+                          editorInstance.executeEdits("paste-markdown", [op]);
+                      }
+                  })
+                  .catch((err) => {
+                      console.error("Failed to paste clipboard", err);
+                      alert("Failed to paste: " + err);
+                  });
+              return;
+          }
+
           for (const item of items) {
               if (item.type && item.type.indexOf('image') !== -1) {
                   event.preventDefault();
@@ -352,10 +384,37 @@ export const NoteEditor: React.FC = () => {
 
       // Load file content via Tauri
       invoke<string>('read_file', { path: selectedFile.path })
-        .then(text => setContent(text))
-        .catch(err => console.error(err));
+        .then(text => {
+            setLocked(false);
+            setContent(text);
+        })
+        .catch(err => {
+            if (String(err).includes('ENCRYPTION_LOCKED')) {
+                setLocked(true);
+                setContent('');
+            } else {
+                console.error(err);
+            }
+        });
     }
-  }, [selectedFile]);
+  }, [selectedFile, reloadKey]);
+
+  // Locking or unlocking changes what the open note can show.
+  useEffect(() => {
+      let unlisten: (() => void) | undefined;
+      listen('encryption-changed', () => setReloadKey(k => k + 1)).then((fn) => { unlisten = fn; });
+      return () => unlisten?.();
+  }, []);
+
+  const handleUnlock = async (e: React.FormEvent) => {
+      e.preventDefault();
+      try {
+          await invoke('unlock_encryption', { rootPath: currentPath, passphrase });
+          setPassphrase('');
+      } catch (err) {
+          pushNotice(`Unlock failed: ${err}`, 'error');
+      }
+  };
 
   useEffect(() => {
       if (!selectedFile || !searchJump) return;
@@ -373,7 +432,7 @@ export const NoteEditor: React.FC = () => {
 
   // Debounced Save
   useEffect(() => {
-      if (!selectedFile) return;
+      if (!selectedFile || locked) return;
       
       const timeoutId = setTimeout(async () => {
           setIsSaving(true);
@@ -394,7 +453,7 @@ export const NoteEditor: React.FC = () => {
       }, 1000); // 1s debounce
 
       return () => clearTimeout(timeoutId);
-  }, [content, selectedFile]);
+  }, [content, selectedFile, locked]);
 
   const handleEditorDidMount: OnMount = (editor, _monaco) => {
     editorRef.current = editor;
@@ -449,6 +508,32 @@ class Duck {
       return <div className="flex-1 flex items-center justify-center text-muted">Select a note to edit</div>;
   }
 
+  if (locked) {
+      return (
+          <div className="flex-1 flex flex-col items-center justify-center gap-3 bg-background text-muted">
+              <Lock size={28} />
+              <div className="text-sm">{selectedFile.name} is encrypted.</div>
+              <form onSubmit={handleUnlock} className="flex items-center gap-2">
+                  <input
+                      type="password"
+                      value={passphrase}
+                      onChange={(e) => setPassphrase(e.target.value)}
+                      className="w-56 bg-background/40 border border-border rounded px-2 py-1.5 text-xs text-text outline-none focus:border-accent"
+                      placeholder="Passphrase"
+                      autoFocus
+                  />
+                  <button
+                      type="submit"
+                      disabled={!passphrase}
+                      className="text-xs bg-accent text-white px-2 py-1.5 rounded hover:opacity-90 disabled:opacity-50"
+                  >
+                      Unlock
+                  </button>
+              </form>
+          </div>
+      );
+  }
+
   const isUml = selectedFile.name.endsWith('.uml') || selectedFile.name.endsWith('.puml');
   mermaidBlockIndexRef.current = 0;
 
@@ -508,6 +593,21 @@ class Duck {
               >
                   <Eye size={16} />
               </button>
+              <button 
+                onClick={async () => {
+                    if (!selectedFile) return;
+                    try {
+                        await invoke('copy_note_as_rich_text', { path: selectedFile.path, rootPath: currentPath, content });
+                        pushNotice('Copied as rich text', 'success');
+                    } catch (e) {
+                        pushNotice(`Copy failed: ${e}`, 'error');
+                    }
+                }}
+                className="p-1.5 rounded hover:bg-surfaceHighlight"
+                title="Copy as Rich Text"
+              >
+                  <ClipboardCopy size={16} />
+              </button>
               <button 
                 onClick={() => setLLMPanelOpen(!llmPanelOpen)}
                 className={clsx("p-1.5 rounded hover:bg-surfaceHighlight", llmPanelOpen && "bg-surfaceHighlight text-accent")}
@@ -575,6 +675,22 @@ class Duck {
                   {/* Special handling for PlantUML files */}
                   {(selectedFile?.name.endsWith('.uml') || selectedFile?.name.endsWith('.puml')) ? (
                       <div>
+                        <div className="flex justify-end mb-2">
+                          <button
+                              onClick={async () => {
+                                  try {
+                                      await invoke('copy_diagram_image', { lang: 'plantuml', source: content });
+                                      pushNotice('Diagram copied as image', 'success');
+                                  } catch (e) {
+                                      pushNotice(`Copy failed: ${e}`, 'error');
+                                  }
+                              }}
+                              className="flex items-center text-xs text-muted hover:text-text px-2 py-1 rounded hover:bg-surfaceHighlight"
+                              title="Render with the local PlantUML and copy as PNG"
+                          >
+                              <Copy size={12} className="mr-1" /> Copy as image
+                          </button>
+                        </div>
                         <img 
                             src={`https://www.plantuml.com/plantuml/svg/${plantumlEncoder.encode(content)}`} 
                             alt="PlantUML Diagram" 
@@ -5,6 +5,8 @@ interface InputModalProps {
     title: string;
     defaultValue?: string;
     placeholder?: string;
+    /** `password` hides what is typed, e.g. for passphrases. */
+    inputType?: 'text' | 'password';
     onClose: () => void;
     onSubmit: (value: string) => void;
 }
@@ -14,6 +16,7 @@ export const InputModal: React.FC<InputModalProps> = ({
     title, 
     defaultValue = '', 
     placeholder = '', 
+    inputType = 'text',
     onClose, 
     onSubmit 
 }) => {
@@ -33,7 +36,8 @@ export const InputModal: React.FC<InputModalProps> = ({
     const handleSubmit = (e: React.FormEvent) => {
         e.preventDefault();
         if (value.trim()) {
-            onSubmit(value.trim());
+            // Passphrases are taken exactly as typed.
+            onSubmit(inputType === 'password' ? value : value.trim());
             onClose();
         }
     };
@@ -45,7 +49,7 @@ export const InputModal: React.FC<InputModalProps> = ({
                 <form onSubmit={handleSubmit}>
                     <input
                         ref={inputRef}
-                        type="text"
+                        type={inputType}
                         className="w-full bg-background border border-border rounded px-3 py-2 text-text focus:outline-none focus:border-accent mb-4"
                         placeholder={placeholder}
                         value={value}
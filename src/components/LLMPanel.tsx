@@ -3,9 +3,17 @@ import { useAppStore } from '../store';
 import ReactMarkdown from 'react-markdown';
 import remarkGfm from 'remark-gfm';
 import rehypeHighlight from 'rehype-highlight';
-import { X, Send, Copy, Bot, User, Eraser } from 'lucide-react';
+import { X, Send, Square, Copy, Bot, User, Eraser } from 'lucide-react';
 import { clsx } from 'clsx';
-import { chatWithLLM } from '../utils/llm';
+import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
+
+// The reply being streamed. `id` stays null until `chat_stream` returns the job id.
+interface PendingReply {
+  id: number | null;
+  msgId: string;
+  content: string;
+}
 
 export const LLMPanel: React.FC = () => {
   const { 
@@ -25,6 +33,7 @@ export const LLMPanel: React.FC = () => {
   } = useAppStore();
 
   const [isSending, setIsSending] = useState(false);
+  const pendingRef = useRef<PendingReply | null>(null);
   const messagesEndRef = useRef<HTMLDivElement>(null);
   const textareaRef = useRef<HTMLTextAreaElement>(null);
 
@@ -39,6 +48,39 @@ export const LLMPanel: React.FC = () => {
     scrollToBottom();
   }, [chatHistory]);
 
+  // Replies are requested by the backend, which streams them back as events.
+  useEffect(() => {
+      const isPending = (id: number) => {
+          const pending = pendingRef.current;
+          return pending !== null && (pending.id === null || pending.id === id);
+      };
+      let unlistenDelta: (() => void) | undefined;
+      let unlistenFinished: (() => void) | undefined;
+      listen<{ jobId: number; delta: string }>('llm-delta', (event) => {
+          const pending = pendingRef.current;
+          if (!pending || !isPending(event.payload.jobId)) return;
+          pending.content += event.payload.delta;
+          useAppStore.getState().updateChatMessage(pending.msgId, pending.content);
+      }).then((fn) => { unlistenDelta = fn; });
+      listen<{ id: number; kind: string; status: string; error?: string }>('job-finished', (event) => {
+          const pending = pendingRef.current;
+          if (event.payload.kind !== 'llm' || !pending || !isPending(event.payload.id)) return;
+          const { updateChatMessage } = useAppStore.getState();
+          if (event.payload.status === 'failed') {
+              const error = `**Error**: ${event.payload.error ?? 'unknown error'}`;
+              updateChatMessage(pending.msgId, pending.content ? `${pending.content}\n\n${error}` : error);
+          } else if (event.payload.status === 'cancelled' && !pending.content) {
+              updateChatMessage(pending.msgId, '_Stopped_');
+          }
+          pendingRef.current = null;
+          setIsSending(false);
+      }).then((fn) => { unlistenFinished = fn; });
+      return () => {
+          unlistenDelta?.();
+          unlistenFinished?.();
+      };
+  }, []);
+
   const handleSend = async () => {
       if (!chatInput.trim() || !activeConfig || isSending) return;
 
@@ -62,29 +104,34 @@ export const LLMPanel: React.FC = () => {
           timestamp: Date.now()
       });
 
+      const messages = [...chatHistory, userMsg].map(m => ({ role: m.role, content: m.content }));
+
+      // Prepend system prompt if exists
+      if (activeSystemPrompt) {
+          messages.unshift({ role: 'system', content: activeSystemPrompt.content });
+      }
+
+      pendingRef.current = { id: null, msgId: botMsgId, content: '' };
       try {
-          const messages = [...chatHistory, userMsg];
-          
-          // Prepend system prompt if exists
-          if (activeSystemPrompt) {
-              messages.unshift({
-                  id: 'system',
-                  role: 'system',
-                  content: activeSystemPrompt.content,
-                  timestamp: Date.now()
-              });
+          const id = await invoke<number>('chat_stream', { configId: activeConfig.id, messages });
+          // The job may already have finished and cleared this.
+          if (pendingRef.current?.msgId === botMsgId) {
+              pendingRef.current.id = id;
           }
-
-          await chatWithLLM(messages, activeConfig, (content) => {
-              updateChatMessage(botMsgId, content);
-          });
       } catch (err) {
+          pendingRef.current = null;
           updateChatMessage(botMsgId, `**Error**: ${String(err)}`);
-      } finally {
           setIsSending(false);
       }
   };
 
+  const handleStop = () => {
+      const id = pendingRef.current?.id;
+      if (id != null) {
+          invoke('cancel_job', { id }).catch(() => {});
+      }
+  };
+
   const handleKeyDown = (e: React.KeyboardEvent) => {
       if (e.key === 'Enter' && !e.shiftKey) {
           e.preventDefault();
@@ -199,14 +246,24 @@ export const LLMPanel: React.FC = () => {
                   rows={1}
                   style={{ height: 'auto', minHeight: '36px' }} 
               />
-              <button 
-                  onClick={handleSend}
-                  disabled={!chatInput.trim() || isSending}
-                  className={clsx("absolute right-1.5 bottom-1 h-7 w-7 flex items-center justify-center rounded-lg transition-colors", 
-                      chatInput.trim() && !isSending ? "bg-accent text-white hover:opacity-90" : "text-muted bg-transparent cursor-not-allowed")}
-              >
-                  <Send size={14} />
-              </button>
+              {isSending ? (
+                  <button
+                      onClick={handleStop}
+                      className="absolute right-1.5 bottom-1 h-7 w-7 flex items-center justify-center rounded-lg transition-colors bg-surfaceHighlight text-text hover:opacity-90"
+                      title="Stop"
+                  >
+                      <Square size={12} />
+                  </button>
+              ) : (
+                  <button 
+                      onClick={handleSend}
+                      disabled={!chatInput.trim()}
+                      className={clsx("absolute right-1.5 bottom-1 h-7 w-7 flex items-center justify-center rounded-lg transition-colors", 
+                          chatInput.trim() ? "bg-accent text-white hover:opacity-90" : "text-muted bg-transparent cursor-not-allowed")}
+                  >
+                      <Send size={14} />
+                  </button>
+              )}
           </div>
       </div>
     </div>
@@ -1,7 +1,8 @@
 import React, { useCallback, useEffect, useMemo, useRef, useState } from 'react';
-import { ZoomIn, ZoomOut, RotateCcw, Copy } from 'lucide-react';
+import { ZoomIn, ZoomOut, RotateCcw, Copy, ImageDown } from 'lucide-react';
 import { invoke } from '@tauri-apps/api/core';
 import { openExternalUrl } from '../utils/openExternalUrl';
+import { useAppStore } from '../store';
 
 type MermaidDiagramProps = {
   code: string;
@@ -428,6 +429,25 @@ export const MermaidDiagram: React.FC<MermaidDiagramProps> = ({ code, diagramKey
     }
   };
 
+  // Print-quality PNG (300 DPI, white background) saved next to the note's other images;
+  // the markdown reference is copied so it can be pasted into the note.
+  const saveAsPng = useCallback(async () => {
+    const { selectedFile, currentPath, pushNotice } = useAppStore.getState();
+    if (!selectedFile || !currentPath) return;
+    try {
+      const prepared = await prepareExportSvgFromCode();
+      const result = await invoke<{ path: string }>('render_svg', {
+        svgText: prepared.svgText,
+        options: { dpi: 300, background: '#ffffff' },
+        output: { kind: 'file', rootPath: currentPath, notePath: selectedFile.path }
+      });
+      await navigator.clipboard?.writeText(`![diagram](${result.path})`).catch(() => {});
+      pushNotice(`Saved ${result.path}; markdown link copied`, 'success');
+    } catch (err) {
+      pushNotice(`Save failed: ${err}`, 'error');
+    }
+  }, [prepareExportSvgFromCode]);
+
   const copyAsImage = useCallback(async () => {
     const displayedSvgEl = getSvgElement();
     if (!displayedSvgEl) return;
@@ -710,6 +730,16 @@ export const MermaidDiagram: React.FC<MermaidDiagramProps> = ({ code, diagramKey
         >
           <Copy size={14} />
         </button>
+        {Boolean((window as any).__TAURI_INTERNALS__) && (
+          <button
+            type="button"
+            className="h-6 w-6 rounded-md text-gray-600 hover:text-gray-900 hover:bg-gray-100 transition-colors inline-flex items-center justify-center"
+            title="Save as PNG (300 DPI)"
+            onClick={saveAsPng}
+          >
+            <ImageDown size={14} />
+          </button>
+        )}
         <button
           type="button"
           className="h-6 w-6 rounded-md text-gray-600 hover:text-gray-900 hover:bg-gray-100 transition-colors inline-flex items-center justify-center"
@@ -1,7 +1,9 @@
-import React, { useEffect, useMemo, useRef, useState } from 'react';
-import { useAppStore, type AppTheme, type LLMConfig, type SystemPrompt } from '../store';
+import React, { useCallback, useEffect, useMemo, useRef, useState } from 'react';
+import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
+import { useAppStore, type AppTheme, type BackupSettings, type LLMConfig, type SyncSettings, type SystemPrompt } from '../store';
 import { clsx } from 'clsx';
-import { Trash2, Plus, Check, Monitor, Keyboard, Bot, MessageSquare } from 'lucide-react';
+import { Trash2, Plus, Check, Monitor, Keyboard, Bot, MessageSquare, Archive, RefreshCw, Lock } from 'lucide-react';
 
 const formatShortcutSymbols = (shortcut: string) => {
   const parts = shortcut.split('+').filter(Boolean);
@@ -48,6 +50,20 @@ interface SettingsModalProps {
   onSave: (next: { searchShortcut: string; sidebarShortcut: string; closeEditorShortcut: string; llmPanelShortcut: string; terminalShortcut: string; theme: AppTheme }) => void;
 }
 
+interface BackupInfo {
+  path: string;
+  name: string;
+  format: string;
+  size: number;
+  modified: string;
+}
+
+const formatSize = (bytes: number) => {
+  if (bytes < 1024) return `${bytes} B`;
+  if (bytes < 1024 * 1024) return `${(bytes / 1024).toFixed(1)} KB`;
+  return `${(bytes / (1024 * 1024)).toFixed(1)} MB`;
+};
+
 const THEME_OPTIONS: Array<{ value: AppTheme; label: string; hint: string }> = [
   { value: 'zinc', label: 'Zinc', hint: 'Neutral dark' },
   { value: 'midnight', label: 'Midnight', hint: 'Blue-tinted dark' },
@@ -55,8 +71,8 @@ const THEME_OPTIONS: Array<{ value: AppTheme; label: string; hint: string }> = [
 ];
 
 export const SettingsModal: React.FC<SettingsModalProps> = ({ isOpen, searchShortcut, sidebarShortcut, closeEditorShortcut, llmPanelShortcut, terminalShortcut, theme, onClose, onSave }) => {
-  const { llmConfigs, activeLLMConfigId, setLLMConfigs, setActiveLLMConfigId, systemPrompts, activeSystemPromptId, setSystemPrompts, setActiveSystemPromptId } = useAppStore();
-  const [activeTab, setActiveTab] = useState<'general' | 'shortcuts' | 'llm' | 'prompts'>('general');
+  const { llmConfigs, activeLLMConfigId, setLLMConfigs, setActiveLLMConfigId, systemPrompts, activeSystemPromptId, setSystemPrompts, setActiveSystemPromptId, backupSettings, setBackupSettings, syncSettings, setSyncSettings, currentPath, pushNotice } = useAppStore();
+  const [activeTab, setActiveTab] = useState<'general' | 'shortcuts' | 'llm' | 'prompts' | 'backup' | 'sync' | 'encryption'>('general');
 
   const [value, setValue] = useState(searchShortcut || 'Cmd+G');
   const [sidebarVal, setSidebarVal] = useState(sidebarShortcut || 'Cmd+1');
@@ -78,6 +94,19 @@ export const SettingsModal: React.FC<SettingsModalProps> = ({ isOpen, searchShor
   const [localPrompts, setLocalPrompts] = useState<SystemPrompt[]>([]);
   const [localActivePromptId, setLocalActivePromptId] = useState<string | null>(null);
 
+  const [localBackup, setLocalBackup] = useState<BackupSettings>(backupSettings);
+  const [backups, setBackups] = useState<BackupInfo[]>([]);
+  const [restoring, setRestoring] = useState<string | null>(null);
+  const [restoreTarget, setRestoreTarget] = useState('');
+  const [restoreSettings, setRestoreSettings] = useState(false);
+
+  const [localSync, setLocalSync] = useState<SyncSettings>(syncSettings);
+
+  const [encryption, setEncryption] = useState<{ configured: boolean; unlocked: boolean } | null>(null);
+  const [currentPassphrase, setCurrentPassphrase] = useState('');
+  const [newPassphrase, setNewPassphrase] = useState('');
+  const [confirmPassphrase, setConfirmPassphrase] = useState('');
+
   const inputRef = useRef<HTMLInputElement>(null);
   const sidebarInputRef = useRef<HTMLInputElement>(null);
   const closeInputRef = useRef<HTMLInputElement>(null);
@@ -103,8 +132,56 @@ export const SettingsModal: React.FC<SettingsModalProps> = ({ isOpen, searchShor
     setLocalActiveId(activeLLMConfigId);
     setLocalPrompts(JSON.parse(JSON.stringify(systemPrompts || [])));
     setLocalActivePromptId(activeSystemPromptId);
+    setLocalBackup({ ...backupSettings, workspace: backupSettings.workspace || currentPath });
+    setRestoring(null);
+    setLocalSync(syncSettings);
     setActiveTab('general');
-  }, [isOpen, searchShortcut, sidebarShortcut, closeEditorShortcut, llmPanelShortcut, theme, llmConfigs, activeLLMConfigId, systemPrompts, activeSystemPromptId]);
+  }, [isOpen, searchShortcut, sidebarShortcut, closeEditorShortcut, llmPanelShortcut, theme, llmConfigs, activeLLMConfigId, systemPrompts, activeSystemPromptId, backupSettings, syncSettings]);
+
+  const refreshBackups = useCallback(() => {
+    invoke<BackupInfo[]>('list_backups', { directory: localBackup.directory || null })
+      .then(setBackups)
+      .catch(() => setBackups([]));
+  }, [localBackup.directory]);
+
+  useEffect(() => {
+    if (!isOpen || activeTab !== 'backup') return;
+    refreshBackups();
+    let unlisten: (() => void) | undefined;
+    listen('backup-result', () => refreshBackups()).then((fn) => { unlisten = fn; });
+    return () => unlisten?.();
+  }, [isOpen, activeTab, refreshBackups]);
+
+  useEffect(() => {
+    if (!isOpen || activeTab !== 'encryption' || !currentPath) return;
+    const refresh = () => {
+      invoke<{ configured: boolean; unlocked: boolean }>('encryption_status', { rootPath: currentPath })
+        .then(setEncryption)
+        .catch(() => setEncryption(null));
+    };
+    refresh();
+    setCurrentPassphrase('');
+    setNewPassphrase('');
+    setConfirmPassphrase('');
+    let unlisten: (() => void) | undefined;
+    listen('encryption-changed', refresh).then((fn) => { unlisten = fn; });
+    return () => unlisten?.();
+  }, [isOpen, activeTab, currentPath]);
+
+  const handleSetPassphrase = () => {
+    invoke('set_encryption_passphrase', {
+      rootPath: currentPath,
+      currentPassphrase: encryption?.configured ? currentPassphrase : null,
+      passphrase: newPassphrase
+    })
+      .then(() => {
+        pushNotice(encryption?.configured ? 'Passphrase changed' : 'Encryption is set up; encrypt notes from their context menu', 'success');
+        setCurrentPassphrase('');
+        setNewPassphrase('');
+        setConfirmPassphrase('');
+      })
+      .catch((err) => pushNotice(String(err), 'error'));
+  };
 
   useMemo(() => {
     const v = value || 'Cmd+G';
@@ -228,6 +305,32 @@ export const SettingsModal: React.FC<SettingsModalProps> = ({ isOpen, searchShor
     }
   };
 
+  const handleBackupNow = () => {
+    invoke('create_backup', {
+      rootPath: localBackup.workspace || null,
+      directory: localBackup.directory || null,
+      format: localBackup.format,
+      includeSecrets: localBackup.includeSecrets
+    }).catch((err) => pushNotice(`Backup failed: ${String(err)}`, 'error'));
+  };
+
+  const handleRestore = (archive: string) => {
+    if (!restoreTarget.trim()) return;
+    invoke('restore_backup', { archive, target: restoreTarget.trim(), restoreSettings })
+      .then(() => setRestoring(null))
+      .catch((err) => pushNotice(`Restore failed: ${String(err)}`, 'error'));
+  };
+
+  const syncRemote = localSync.provider === 'webdav' ? localSync.webdav.url.trim() : localSync.folder.trim();
+  // The server is signed in to with the saved credentials.
+  const syncUnsaved = localSync.provider === 'webdav' && JSON.stringify(localSync.webdav) !== JSON.stringify(syncSettings.webdav);
+
+  const handleSync = (dryRun: boolean) => {
+    if (!syncRemote || syncUnsaved) return;
+    invoke('sync_workspace', { rootPath: currentPath, remote: syncRemote, dryRun })
+      .catch((err) => pushNotice(`Sync failed: ${String(err)}`, 'error'));
+  };
+
   const handleSave = () => {
     onSave({ 
         searchShortcut: value || 'Cmd+G', 
@@ -241,6 +344,8 @@ export const SettingsModal: React.FC<SettingsModalProps> = ({ isOpen, searchShor
     setActiveLLMConfigId(localActiveId);
     setSystemPrompts(localPrompts);
     setActiveSystemPromptId(localActivePromptId);
+    setBackupSettings(localBackup);
+    setSyncSettings(localSync);
     onClose();
   };
 
@@ -279,6 +384,24 @@ export const SettingsModal: React.FC<SettingsModalProps> = ({ isOpen, searchShor
                 >
                     <MessageSquare size={16} /> System Prompts
                 </button>
+                <button
+                    onClick={() => setActiveTab('backup')}
+                    className={clsx("flex items-center gap-2 px-3 py-2 rounded-lg text-sm font-medium transition-colors", activeTab === 'backup' ? "bg-surfaceHighlight text-text" : "text-muted hover:text-text hover:bg-surfaceHighlight/50")}
+                >
+                    <Archive size={16} /> Backup
+                </button>
+                <button
+                    onClick={() => setActiveTab('sync')}
+                    className={clsx("flex items-center gap-2 px-3 py-2 rounded-lg text-sm font-medium transition-colors", activeTab === 'sync' ? "bg-surfaceHighlight text-text" : "text-muted hover:text-text hover:bg-surfaceHighlight/50")}
+                >
+                    <RefreshCw size={16} /> Sync
+                </button>
+                <button
+                    onClick={() => setActiveTab('encryption')}
+                    className={clsx("flex items-center gap-2 px-3 py-2 rounded-lg text-sm font-medium transition-colors", activeTab === 'encryption' ? "bg-surfaceHighlight text-text" : "text-muted hover:text-text hover:bg-surfaceHighlight/50")}
+                >
+                    <Lock size={16} /> Encryption
+                </button>
             </div>
 
             {/* Content */}
@@ -433,7 +556,19 @@ export const SettingsModal: React.FC<SettingsModalProps> = ({ isOpen, searchShor
                                     </div>
                                     
                                     <div className="grid grid-cols-2 gap-3">
-                                        <div className="space-y-1 col-span-2">
+                                        <div className="space-y-1">
+                                            <label className="text-xs text-muted">Provider</label>
+                                            <select 
+                                                value={config.provider}
+                                                onChange={(e) => handleUpdateConfig(config.id, { provider: e.target.value as LLMConfig['provider'] })}
+                                                className="w-full bg-background/40 border border-border rounded px-2 py-1.5 text-xs text-text outline-none focus:border-accent"
+                                            >
+                                                <option value="openai">OpenAI</option>
+                                                <option value="custom">OpenAI-compatible</option>
+                                                <option value="ollama">Ollama</option>
+                                            </select>
+                                        </div>
+                                        <div className="space-y-1">
                                             <label className="text-xs text-muted">Model Type</label>
                                             <select 
                                                 value={config.type || 'text'}
@@ -452,17 +587,18 @@ export const SettingsModal: React.FC<SettingsModalProps> = ({ isOpen, searchShor
                                                 value={config.baseUrl}
                                                 onChange={(e) => handleUpdateConfig(config.id, { baseUrl: e.target.value })}
                                                 className="w-full bg-background/40 border border-border rounded px-2 py-1.5 text-xs text-text outline-none focus:border-accent"
-                                                placeholder="https://api.openai.com/v1"
+                                                placeholder={config.provider === 'ollama' ? 'http://localhost:11434' : 'https://api.openai.com/v1'}
                                             />
                                         </div>
                                         <div className="space-y-1">
                                             <label className="text-xs text-muted">API Key</label>
+                                            {/* A saved key is only a `secret:` handle here; typing replaces it. */}
                                             <input 
-                                                value={config.apiKey}
+                                                value={config.apiKey.startsWith('secret:') ? '' : config.apiKey}
                                                 onChange={(e) => handleUpdateConfig(config.id, { apiKey: e.target.value })}
                                                 type="password"
                                                 className="w-full bg-background/40 border border-border rounded px-2 py-1.5 text-xs text-text outline-none focus:border-accent"
-                                                placeholder="sk-..."
+                                                placeholder={config.apiKey.startsWith('secret:') ? 'Saved (type to replace)' : 'sk-...'}
                                             />
                                         </div>
                                         <div className="space-y-1 col-span-2">
@@ -531,6 +667,302 @@ export const SettingsModal: React.FC<SettingsModalProps> = ({ isOpen, searchShor
                         </div>
                     </div>
                 )}
+
+                {activeTab === 'backup' && (
+                    <div className="space-y-6">
+                        <div className="flex items-center justify-between">
+                            <h3 className="text-base font-medium text-text">Backup</h3>
+                            <button onClick={handleBackupNow} className="flex items-center gap-1 text-xs bg-accent text-white px-2 py-1.5 rounded hover:opacity-90">
+                                <Archive size={14} /> Back Up Now
+                            </button>
+                        </div>
+
+                        <div className="flex items-center justify-between gap-4 border border-border rounded-xl px-4 py-3 bg-background/20">
+                            <div className="min-w-0">
+                            <div className="text-sm text-text font-semibold">Scheduled Backups</div>
+                            <div className="text-xs text-muted mt-1">Back up the workspace and settings in the background.</div>
+                            </div>
+                            <input
+                                type="checkbox"
+                                checked={localBackup.enabled}
+                                onChange={(e) => setLocalBackup({ ...localBackup, enabled: e.target.checked })}
+                                className="w-4 h-4 accent-accent cursor-pointer"
+                            />
+                        </div>
+
+                        <div className="border border-border rounded-xl p-4 bg-background/20 space-y-3">
+                            <div className="space-y-1">
+                                <label className="text-xs text-muted">Workspace</label>
+                                <input
+                                    value={localBackup.workspace}
+                                    onChange={(e) => setLocalBackup({ ...localBackup, workspace: e.target.value })}
+                                    className="w-full bg-background/40 border border-border rounded px-2 py-1.5 text-xs text-text outline-none focus:border-accent"
+                                    placeholder="~/.xnote/doc"
+                                />
+                            </div>
+                            <div className="space-y-1">
+                                <label className="text-xs text-muted">Backup Folder</label>
+                                <input
+                                    value={localBackup.directory}
+                                    onChange={(e) => setLocalBackup({ ...localBackup, directory: e.target.value })}
+                                    className="w-full bg-background/40 border border-border rounded px-2 py-1.5 text-xs text-text outline-none focus:border-accent"
+                                    placeholder="~/.xnote/backups"
+                                />
+                            </div>
+                            <div className="grid grid-cols-3 gap-3">
+                                <div className="space-y-1">
+                                    <label className="text-xs text-muted">Every (hours)</label>
+                                    <input
+                                        type="number"
+                                        min={1}
+                                        value={localBackup.intervalHours}
+                                        onChange={(e) => setLocalBackup({ ...localBackup, intervalHours: Math.max(1, parseInt(e.target.value) || 1) })}
+                                        className="w-full bg-background/40 border border-border rounded px-2 py-1.5 text-xs text-text outline-none focus:border-accent"
+                                    />
+                                </div>
+                                <div className="space-y-1">
+                                    <label className="text-xs text-muted">Keep (0 = all)</label>
+                                    <input
+                                        type="number"
+                                        min={0}
+                                        value={localBackup.keep}
+                                        onChange={(e) => setLocalBackup({ ...localBackup, keep: Math.max(0, parseInt(e.target.value) || 0) })}
+                                        className="w-full bg-background/40 border border-border rounded px-2 py-1.5 text-xs text-text outline-none focus:border-accent"
+                                    />
+                                </div>
+                                <div className="space-y-1">
+                                    <label className="text-xs text-muted">Format</label>
+                                    <select
+                                        value={localBackup.format}
+                                        onChange={(e) => setLocalBackup({ ...localBackup, format: e.target.value as BackupSettings['format'] })}
+                                        className="w-full bg-background/40 border border-border rounded px-2 py-1.5 text-xs text-text outline-none focus:border-accent"
+                                    >
+                                        <option value="zip">zip</option>
+                                        <option value="tar.zst">tar.zst</option>
+                                    </select>
+                                </div>
+                            </div>
+                            <label className="flex items-center gap-2 text-xs text-muted cursor-pointer">
+                                <input
+                                    type="checkbox"
+                                    checked={localBackup.includeSecrets}
+                                    onChange={(e) => setLocalBackup({ ...localBackup, includeSecrets: e.target.checked })}
+                                    className="accent-accent"
+                                />
+                                Include API keys and other secrets
+                            </label>
+                        </div>
+
+                        <div className="space-y-2">
+                            <div className="text-sm text-text font-semibold">Backups</div>
+                            {backups.map((backup) => (
+                                <div key={backup.path} className="border border-border rounded-xl px-4 py-3 bg-background/20 space-y-2">
+                                    <div className="flex items-center justify-between gap-3">
+                                        <div className="min-w-0">
+                                            <div className="text-xs text-text truncate">{backup.name}</div>
+                                            <div className="text-xs text-muted">{new Date(backup.modified).toLocaleString()} · {formatSize(backup.size)}</div>
+                                        </div>
+                                        <button
+                                            onClick={() => {
+                                                setRestoring(restoring === backup.path ? null : backup.path);
+                                                setRestoreTarget(localBackup.workspace || currentPath);
+                                                setRestoreSettings(false);
+                                            }}
+                                            className="text-xs px-2 py-1 rounded border border-border text-muted hover:text-text hover:bg-surfaceHighlight"
+                                        >
+                                            Restore…
+                                        </button>
+                                    </div>
+                                    {restoring === backup.path && (
+                                        <div className="space-y-2">
+                                            <input
+                                                value={restoreTarget}
+                                                onChange={(e) => setRestoreTarget(e.target.value)}
+                                                className="w-full bg-background/40 border border-border rounded px-2 py-1.5 text-xs text-text outline-none focus:border-accent"
+                                                placeholder="Folder to restore into"
+                                            />
+                                            <div className="text-xs text-muted">Files that differ in an existing folder are moved to its quarantine first.</div>
+                                            <div className="flex items-center justify-between">
+                                                <label className="flex items-center gap-2 text-xs text-muted cursor-pointer">
+                                                    <input
+                                                        type="checkbox"
+                                                        checked={restoreSettings}
+                                                        onChange={(e) => setRestoreSettings(e.target.checked)}
+                                                        className="accent-accent"
+                                                    />
+                                                    Also restore settings
+                                                </label>
+                                                <button
+                                                    onClick={() => handleRestore(backup.path)}
+                                                    className="text-xs bg-accent text-white px-2 py-1 rounded hover:opacity-90"
+                                                >
+                                                    Restore
+                                                </button>
+                                            </div>
+                                        </div>
+                                    )}
+                                </div>
+                            ))}
+
+                            {backups.length === 0 && (
+                                <div className="text-center py-8 text-muted text-sm">
+                                    No backups yet.
+                                </div>
+                            )}
+                        </div>
+                    </div>
+                )}
+
+                {activeTab === 'sync' && (
+                    <div className="space-y-6">
+                        <h3 className="text-base font-medium text-text mb-4">Sync</h3>
+
+                        <div className="border border-border rounded-xl p-4 bg-background/20 space-y-3">
+                            <div className="space-y-1">
+                                <label className="text-xs text-muted">Sync With</label>
+                                <select
+                                    value={localSync.provider}
+                                    onChange={(e) => setLocalSync({ ...localSync, provider: e.target.value as SyncSettings['provider'] })}
+                                    className="w-full bg-background/40 border border-border rounded px-2 py-1.5 text-xs text-text outline-none focus:border-accent"
+                                >
+                                    <option value="folder">Folder</option>
+                                    <option value="webdav">WebDAV server (e.g. Nextcloud)</option>
+                                </select>
+                            </div>
+                            {localSync.provider === 'folder' ? (
+                                <div className="space-y-1">
+                                    <label className="text-xs text-muted">Folder</label>
+                                    <input
+                                        value={localSync.folder}
+                                        onChange={(e) => setLocalSync({ ...localSync, folder: e.target.value })}
+                                        className="w-full bg-background/40 border border-border rounded px-2 py-1.5 text-xs text-text outline-none focus:border-accent"
+                                        placeholder="/Volumes/NAS/notes"
+                                    />
+                                </div>
+                            ) : (
+                                <div className="grid grid-cols-2 gap-3">
+                                    <div className="space-y-1 col-span-2">
+                                        <label className="text-xs text-muted">URL</label>
+                                        <input
+                                            value={localSync.webdav.url}
+                                            onChange={(e) => setLocalSync({ ...localSync, webdav: { ...localSync.webdav, url: e.target.value } })}
+                                            className="w-full bg-background/40 border border-border rounded px-2 py-1.5 text-xs text-text outline-none focus:border-accent"
+                                            placeholder="https://cloud.example.com/remote.php/dav/files/me/Notes"
+                                        />
+                                    </div>
+                                    <div className="space-y-1">
+                                        <label className="text-xs text-muted">User Name</label>
+                                        <input
+                                            value={localSync.webdav.username}
+                                            onChange={(e) => setLocalSync({ ...localSync, webdav: { ...localSync.webdav, username: e.target.value } })}
+                                            className="w-full bg-background/40 border border-border rounded px-2 py-1.5 text-xs text-text outline-none focus:border-accent"
+                                        />
+                                    </div>
+                                    <div className="space-y-1">
+                                        <label className="text-xs text-muted">Password</label>
+                                        <input
+                                            value={localSync.webdav.password}
+                                            onChange={(e) => setLocalSync({ ...localSync, webdav: { ...localSync.webdav, password: e.target.value } })}
+                                            type="password"
+                                            className="w-full bg-background/40 border border-border rounded px-2 py-1.5 text-xs text-text outline-none focus:border-accent"
+                                            placeholder="App password"
+                                        />
+                                    </div>
+                                </div>
+                            )}
+                            <div className="text-xs text-muted">
+                                Changes on either side are copied to the other. When a note changed on both, the newer version keeps its name and the other is saved as a conflict copy. Removed files are moved to quarantine.
+                                {syncUnsaved && ' Save to sync with the new server settings.'}
+                            </div>
+                            <div className="flex items-center justify-end gap-2">
+                                <button
+                                    onClick={() => handleSync(true)}
+                                    disabled={!syncRemote || syncUnsaved}
+                                    className="text-xs px-2 py-1.5 rounded border border-border text-muted hover:text-text hover:bg-surfaceHighlight disabled:opacity-50"
+                                >
+                                    Preview…
+                                </button>
+                                <button
+                                    onClick={() => handleSync(false)}
+                                    disabled={!syncRemote || syncUnsaved}
+                                    className="flex items-center gap-1 text-xs bg-accent text-white px-2 py-1.5 rounded hover:opacity-90 disabled:opacity-50"
+                                >
+                                    <RefreshCw size={14} /> Sync Now
+                                </button>
+                            </div>
+                        </div>
+                    </div>
+                )}
+
+                {activeTab === 'encryption' && (
+                    <div className="space-y-6">
+                        <h3 className="text-base font-medium text-text mb-4">Encryption</h3>
+
+                        <div className="border border-border rounded-xl p-4 bg-background/20 space-y-3">
+                            <div className="flex items-center justify-between gap-4">
+                                <div className="text-xs text-muted">
+                                    {!encryption?.configured
+                                        ? 'Set a passphrase to encrypt notes and folders in this workspace. Encrypted notes stay encrypted on disk, in backups and when synced. There is no way to recover them without the passphrase.'
+                                        : encryption.unlocked
+                                          ? 'Encrypted notes are unlocked for this session.'
+                                          : 'Encrypted notes are locked. Open one to unlock them.'}
+                                </div>
+                                {encryption?.configured && encryption.unlocked && (
+                                    <button
+                                        onClick={() => invoke('lock_encryption', { rootPath: currentPath }).catch((err) => pushNotice(String(err), 'error'))}
+                                        className="flex items-center gap-1 text-xs px-2 py-1.5 rounded border border-border text-muted hover:text-text hover:bg-surfaceHighlight shrink-0"
+                                    >
+                                        <Lock size={14} /> Lock Now
+                                    </button>
+                                )}
+                            </div>
+                            <div className="grid grid-cols-2 gap-3">
+                                {encryption?.configured && (
+                                    <div className="space-y-1 col-span-2">
+                                        <label className="text-xs text-muted">Current Passphrase</label>
+                                        <input
+                                            type="password"
+                                            value={currentPassphrase}
+                                            onChange={(e) => setCurrentPassphrase(e.target.value)}
+                                            className="w-full bg-background/40 border border-border rounded px-2 py-1.5 text-xs text-text outline-none focus:border-accent"
+                                        />
+                                    </div>
+                                )}
+                                <div className="space-y-1">
+                                    <label className="text-xs text-muted">{encryption?.configured ? 'New Passphrase' : 'Passphrase'}</label>
+                                    <input
+                                        type="password"
+                                        value={newPassphrase}
+                                        onChange={(e) => setNewPassphrase(e.target.value)}
+                                        className="w-full bg-background/40 border border-border rounded px-2 py-1.5 text-xs text-text outline-none focus:border-accent"
+                                        placeholder="At least 8 characters"
+                                    />
+                                </div>
+                                <div className="space-y-1">
+                                    <label className="text-xs text-muted">Repeat</label>
+                                    <input
+                                        type="password"
+                                        value={confirmPassphrase}
+                                        onChange={(e) => setConfirmPassphrase(e.target.value)}
+                                        className="w-full bg-background/40 border border-border rounded px-2 py-1.5 text-xs text-text outline-none focus:border-accent"
+                                    />
+                                </div>
+                            </div>
+                            <div className="flex items-center justify-end gap-2">
+                                {confirmPassphrase && newPassphrase !== confirmPassphrase && (
+                                    <span className="text-xs text-error">The passphrases do not match</span>
+                                )}
+                                <button
+                                    onClick={handleSetPassphrase}
+                                    disabled={!currentPath || !newPassphrase || newPassphrase !== confirmPassphrase || (!!encryption?.configured && !currentPassphrase)}
+                                    className="flex items-center gap-1 text-xs bg-accent text-white px-2 py-1.5 rounded hover:opacity-90 disabled:opacity-50"
+                                >
+                                    <Lock size={14} /> {encryption?.configured ? 'Change Passphrase' : 'Set Passphrase'}
+                                </button>
+                            </div>
+                        </div>
+                    </div>
+                )}
             </div>
         </div>
 
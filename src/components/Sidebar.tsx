@@ -1,6 +1,6 @@
 import React, { useEffect, useState } from 'react';
 import { useAppStore, FileNode } from '../store';
-import { Folder, FileText, ChevronRight, ChevronDown, Search } from 'lucide-react';
+import { Folder, FileText, ChevronRight, ChevronDown, Search, Lock } from 'lucide-react';
 import { clsx } from 'clsx';
 import { invoke } from '@tauri-apps/api/core';
 import { ContextMenu } from './ContextMenu';
@@ -41,6 +41,7 @@ const FileItem = ({ node, level, expanded, selectedFile, onToggle, onSelect, onC
                     <FileText size={14} className={clsx("mr-2 ml-4", isSelected ? "text-accent" : "text-muted")} />
                 )}
                 <span className="flex-1 truncate font-medium opacity-90">{node.name}</span>
+                {node.encrypted && <Lock size={12} className="ml-1 text-muted" />}
             </div>
         </div>
     );
@@ -59,6 +60,7 @@ export const Sidebar: React.FC<SidebarProps> = () => {
       isOpen: boolean;
       title: string;
       defaultValue?: string;
+      inputType?: 'text' | 'password';
       onSubmit: (value: string) => void;
   }>({ isOpen: false, title: '', onSubmit: () => {} });
 
@@ -320,6 +322,91 @@ export const Sidebar: React.FC<SidebarProps> = () => {
       }
   };
 
+  const handleExportHtml = async () => {
+      if (!contextMenu) return;
+      const { target } = contextMenu;
+      if (target.is_dir) return;
+      try {
+          const output = await invoke<string>('export_note_html', { path: target.path, rootPath: currentPath });
+          pushNotice(`Exported to ${output}`, 'success');
+      } catch (e) {
+          console.error('Export failed:', e);
+          pushNotice(`Failed to export: ${e}`, 'error');
+      }
+  };
+
+  const handleBuildSite = async () => {
+      if (!contextMenu) return;
+      const folder = contextMenu.target.path as string;
+      try {
+          await invoke('build_site', { folder, outDir: `${folder}-site`, rootPath: currentPath });
+          pushNotice('Building site…', 'info');
+      } catch (e) {
+          console.error('Build site failed:', e);
+          pushNotice(`Failed to build site: ${e}`, 'error');
+      }
+  };
+
+  const handleExportEpub = async () => {
+      if (!contextMenu) return;
+      const folder = contextMenu.target.path as string;
+      try {
+          await invoke('export_epub', { folder, rootPath: currentPath, outputPath: `${folder}.epub` });
+          pushNotice('Exporting EPUB…', 'info');
+      } catch (e) {
+          console.error('EPUB export failed:', e);
+          pushNotice(`Failed to export EPUB: ${e}`, 'error');
+      }
+  };
+
+  const runEncryption = async (path: string, encrypt: boolean) => {
+      try {
+          const count = await invoke<number>(encrypt ? 'encrypt_path' : 'decrypt_path', { rootPath: currentPath, path });
+          pushNotice(`${encrypt ? 'Encrypted' : 'Decrypted'} ${count} note${count === 1 ? '' : 's'}`, 'success');
+          await loadFiles(currentPath);
+      } catch (e) {
+          if (!String(e).includes('ENCRYPTION_LOCKED')) {
+              pushNotice(`Failed to ${encrypt ? 'encrypt' : 'decrypt'}: ${e}`, 'error');
+              return;
+          }
+          setModalConfig({
+              isOpen: true,
+              title: "Passphrase for encrypted notes",
+              inputType: 'password',
+              onSubmit: async (passphrase) => {
+                  try {
+                      await invoke('unlock_encryption', { rootPath: currentPath, passphrase });
+                      await runEncryption(path, encrypt);
+                  } catch (err) {
+                      pushNotice(`Unlock failed: ${err}`, 'error');
+                  }
+              }
+          });
+      }
+  };
+
+  const handleImport = () => {
+      if (!contextMenu) return;
+      const folder = contextMenu.target.path as string;
+      setModalConfig({
+          isOpen: true,
+          title: "Path to an Obsidian vault, Notion export or .enex file",
+          onSubmit: async (value) => {
+              const source = value.trim().replace(/[/\\]+$/, '');
+              if (!source) return;
+              const name = source.split(/[/\\]/).pop()!.replace(/\.(zip|enex)$/i, '');
+              try {
+                  // Dry run first; the confirmation is shown when the report arrives.
+                  await invoke('import_notes', { source, rootPath: currentPath, targetDir: `${folder}/${name}`, dryRun: true });
+                  pushNotice('Checking import…', 'info');
+              } catch (e) {
+                  console.error('Import failed:', e);
+                  pushNotice(`Failed to import notes: ${e}`, 'error');
+              }
+          }
+      });
+  };
+
   const handleMoveTo = (targetPath: string) => {
       if (!moveTargetNode) return;
       const fileName = moveTargetNode.name;
@@ -461,6 +548,12 @@ export const Sidebar: React.FC<SidebarProps> = () => {
               }
               onMoveTo={contextMenu.type !== 'root' ? openMoveToModal : undefined}
               onRename={contextMenu.type !== 'root' ? handleRename : undefined}
+              onExportEpub={contextMenu.type === 'folder' ? handleExportEpub : undefined}
+              onImport={contextMenu.type === 'folder' ? handleImport : undefined}
+              onBuildSite={contextMenu.type === 'folder' ? handleBuildSite : undefined}
+              onExportHtml={contextMenu.type === 'file' && String(contextMenu.target.path).toLowerCase().endsWith('.md') ? handleExportHtml : undefined}
+              onEncrypt={contextMenu.type !== 'root' && !contextMenu.target.encrypted ? () => runEncryption(contextMenu.target.path, true) : undefined}
+              onDecrypt={contextMenu.type !== 'root' && contextMenu.target.encrypted ? () => runEncryption(contextMenu.target.path, false) : undefined}
           />
       )}
 
@@ -478,6 +571,7 @@ export const Sidebar: React.FC<SidebarProps> = () => {
           isOpen={modalConfig.isOpen}
           title={modalConfig.title}
           defaultValue={modalConfig.defaultValue}
+          inputType={modalConfig.inputType}
           onClose={() => setModalConfig(prev => ({ ...prev, isOpen: false }))}
           onSubmit={modalConfig.onSubmit}
       />
@@ -4,7 +4,7 @@ import { FitAddon } from '@xterm/addon-fit';
 import { WebLinksAddon } from '@xterm/addon-web-links';
 import '@xterm/xterm/css/xterm.css';
 import { useAppStore } from '../store';
-import { invoke } from '@tauri-apps/api/core';
+import { Channel, invoke } from '@tauri-apps/api/core';
 import { listen } from '@tauri-apps/api/event';
 import { Plus, X } from 'lucide-react';
 
@@ -17,7 +17,7 @@ export function TerminalPanel({ isOpen }: { isOpen: boolean }) {
     const { terminalHeight, currentPath, selectedFile, setTerminalHeight } = useAppStore();
     const [sessions, setSessions] = useState<TerminalSession[]>([]);
     const [activeSessionId, setActiveSessionId] = useState<string | null>(null);
-    const terminalRefs = useRef<Record<string, { term: Terminal; fitAddon: FitAddon; decoder: TextDecoder; container: HTMLDivElement | null }>>({});
+    const terminalRefs = useRef<Record<string, { term: Terminal; fitAddon: FitAddon; decoder: TextDecoder; container: HTMLDivElement | null; replayedUpTo: number }>>({});
     const containerRef = useRef<HTMLDivElement>(null);
     const [isResizing, setIsResizing] = useState(false);
     const hasAutoCreatedSessionRef = useRef(false);
@@ -49,7 +49,16 @@ export function TerminalPanel({ isOpen }: { isOpen: boolean }) {
         if (!isOpen) return;
         if (sessions.length !== 0) return;
         hasAutoCreatedSessionRef.current = true;
-        createSession();
+        (async () => {
+            // Sessions survive a webview reload in the backend; reattach to them first.
+            const existing = await invoke<{ id: string; title: string }[]>('list_terminals').catch(() => []);
+            if (existing.length > 0) {
+                setSessions(existing.map((t, i) => ({ id: t.id, title: `${t.title} (${i + 1})` })));
+                setActiveSessionId(existing[existing.length - 1].id);
+                return;
+            }
+            createSession();
+        })();
     }, [createSession, isOpen, sessions.length]);
 
     // Handle session switching and rendering
@@ -61,7 +70,7 @@ export function TerminalPanel({ isOpen }: { isOpen: boolean }) {
         if (!session) return;
 
         // We need to wait for the DOM element to be ready
-        requestAnimationFrame(() => {
+        requestAnimationFrame(async () => {
             const container = document.getElementById(`terminal-container-${activeSessionId}`);
             if (!container) return;
 
@@ -94,24 +103,74 @@ export function TerminalPanel({ isOpen }: { isOpen: boolean }) {
                 const { rows, cols } = fitAddon.proposeDimensions() || { rows: 24, cols: 80 };
                 invoke('resize_terminal', { id: activeSessionId, rows, cols });
 
-                terminalRefs.current[activeSessionId] = { term, fitAddon, decoder: new TextDecoder('utf-8'), container: container as HTMLDivElement };
+                terminalRefs.current[activeSessionId] = { term, fitAddon, decoder: new TextDecoder('utf-8'), container: container as HTMLDivElement, replayedUpTo: 0 };
+
+                const sessionId = activeSessionId;
 
-                // Listen for output
-                listen(`terminal-output:${activeSessionId}`, (event) => {
+                // Writes output and acknowledges it once xterm has parsed it, so the backend
+                // can throttle when rendering falls behind.
+                const writeBytes = (bytes: Uint8Array, end: number) => {
+                    const ref = terminalRefs.current[sessionId];
+                    if (!ref) return;
+                    const text = ref.decoder.decode(bytes, { stream: true });
+                    term.write(text, () => {
+                        invoke('ack_terminal_output', { id: sessionId, offset: end }).catch(() => {});
+                    });
+                };
+                const decodeBase64 = (data: string) => {
+                    const binStr = atob(data);
+                    const bytes = new Uint8Array(binStr.length);
+                    for (let i = 0; i < binStr.length; i++) bytes[i] = binStr.charCodeAt(i);
+                    return bytes;
+                };
+
+                // Live output is queued until the scrollback replay has been written.
+                let pending: { offset: number; bytes: Uint8Array }[] | null = [];
+                const onOutput = (offset: number, bytes: Uint8Array) => {
+                    if (pending) {
+                        pending.push({ offset, bytes });
+                        return;
+                    }
+                    // Already part of the scrollback replay.
+                    if (offset < terminalRefs.current[sessionId].replayedUpTo) return;
+                    writeBytes(bytes, offset + bytes.length);
+                };
+
+                // Binary channel: 8-byte big-endian stream offset followed by raw output.
+                const output = new Channel<ArrayBuffer>();
+                output.onmessage = (message) => {
+                    const buffer = message instanceof ArrayBuffer ? message : new Uint8Array(message as any).buffer;
+                    if (buffer.byteLength < 8) return;
+                    const offset = Number(new DataView(buffer).getBigUint64(0));
+                    onOutput(offset, new Uint8Array(buffer, 8));
+                };
+
+                // Event fallback, used by the backend if the channel is unavailable.
+                await listen(`terminal-output:${sessionId}`, (event) => {
                     const payload: any = event.payload as any;
                     if (typeof payload === 'string') {
                         term.write(payload);
                         return;
                     }
                     if (payload && payload.encoding === 'base64' && typeof payload.data === 'string') {
-                        const binStr = atob(payload.data);
-                        const bytes = new Uint8Array(binStr.length);
-                        for (let i = 0; i < binStr.length; i++) bytes[i] = binStr.charCodeAt(i);
-                        const ref = terminalRefs.current[activeSessionId];
-                        const text = ref.decoder.decode(bytes, { stream: true });
-                        if (text) term.write(text);
+                        const offset = typeof payload.offset === 'number' ? payload.offset : Infinity;
+                        onOutput(offset, decodeBase64(payload.data));
                     }
                 });
+
+                // Replay the backend scrollback (non-empty after a webview reload).
+                try {
+                    const attach = await invoke<{ data: string; offset: number }>('attach_terminal', { id: sessionId, output });
+                    terminalRefs.current[sessionId].replayedUpTo = attach.offset;
+                    if (attach.data) writeBytes(decodeBase64(attach.data), attach.offset);
+                } catch (err) {
+                    console.error("Failed to attach terminal:", err);
+                }
+                const queued = pending;
+                pending = null;
+                for (const chunk of queued) {
+                    onOutput(chunk.offset, chunk.bytes);
+                }
             } else {
                 // Re-fit on switch
                  terminalRefs.current[activeSessionId].fitAddon.fit();
@@ -8,6 +8,7 @@ export interface FileNode {
   is_dir: boolean;
   children?: FileNode[];
   last_modified?: String;
+  encrypted?: boolean;
 }
 
 export type NoticeType = 'info' | 'success' | 'error';
@@ -24,6 +25,52 @@ export interface LLMConfig {
   type?: 'text' | 'image' | 'video';
 }
 
+export interface TerminalProfile {
+  id: string;
+  name: string;
+  shell?: string;
+  args?: string[];
+  env?: Record<string, string>;
+  login?: boolean;
+  initialCommand?: string;
+}
+
+export interface BackupSettings {
+  enabled: boolean;
+  workspace: string;
+  intervalHours: number;
+  keep: number;
+  format: 'zip' | 'tar.zst';
+  directory: string;
+  includeSecrets: boolean;
+}
+
+const defaultBackupSettings: BackupSettings = {
+  enabled: false,
+  workspace: '',
+  intervalHours: 24,
+  keep: 7,
+  format: 'zip',
+  directory: '',
+  includeSecrets: false
+};
+
+export interface SyncSettings {
+  provider: 'folder' | 'webdav';
+  folder: string;
+  webdav: {
+    url: string;
+    username: string;
+    password: string;
+  };
+}
+
+export const defaultSyncSettings: SyncSettings = {
+  provider: 'folder',
+  folder: '',
+  webdav: { url: '', username: '', password: '' }
+};
+
 export interface ChatMessage {
   id: string;
   role: 'user' | 'assistant' | 'system';
@@ -38,6 +85,9 @@ export interface SystemPrompt {
   content: string;
 }
 
+// Revision of the config last loaded from the backend; lets saves merge edits made on disk.
+let configRevision: string | null = null;
+
 const applyTheme = (theme: AppTheme) => {
   if (typeof document === 'undefined') return;
   document.documentElement.dataset.theme = theme;
@@ -71,11 +121,15 @@ interface AppState {
   // Terminal State
   terminalOpen: boolean;
   terminalHeight: number;
+  terminalProfiles: TerminalProfile[];
+  defaultTerminalProfile: string | null;
 
   chatHistory: ChatMessage[];
   chatInput: string;
   systemPrompts: SystemPrompt[];
   activeSystemPromptId: string | null;
+  backupSettings: BackupSettings;
+  syncSettings: SyncSettings;
 
   // Actions
   setFiles: (files: FileNode[]) => void;
@@ -110,12 +164,15 @@ interface AppState {
   setChatInput: (input: string) => void;
   setSystemPrompts: (prompts: SystemPrompt[]) => void;
   setActiveSystemPromptId: (id: string | null) => void;
+  setBackupSettings: (settings: BackupSettings) => void;
+  setSyncSettings: (settings: SyncSettings) => void;
   loadFiles: (path: string) => Promise<void>;
   moveFile: (source: string, target: string) => Promise<void>;
   renameFile: (path: string, newName: string) => Promise<void>;
   deleteFile: (path: string) => Promise<void>;
   copyFile: (source: string, target: string) => Promise<void>;
   loadConfig: () => Promise<void>;
+  applyConfig: (config: any) => Promise<void>;
   saveConfig: () => Promise<void>;
 }
 
@@ -142,10 +199,14 @@ export const useAppStore = create<AppState>((set, get) => ({
   llmPanelWidth: 256,
   terminalOpen: false,
   terminalHeight: 300,
+  terminalProfiles: [],
+  defaultTerminalProfile: null,
   chatHistory: [],
   chatInput: '',
   systemPrompts: [],
   activeSystemPromptId: null,
+  backupSettings: defaultBackupSettings,
+  syncSettings: defaultSyncSettings,
 
   setFiles: (files) => set({ files }),
   setCurrentPath: (path) => set({ currentPath: path }),
@@ -236,6 +297,14 @@ export const useAppStore = create<AppState>((set, get) => ({
     set({ activeSystemPromptId: id });
     get().saveConfig();
   },
+  setBackupSettings: (settings) => {
+    set({ backupSettings: settings });
+    get().saveConfig();
+  },
+  setSyncSettings: (settings) => {
+    set({ syncSettings: settings });
+    get().saveConfig();
+  },
   
   loadConfig: async () => {
       try {
@@ -243,26 +312,12 @@ export const useAppStore = create<AppState>((set, get) => ({
           if (window.__TAURI_INTERNALS__) {
               const configStr = await invoke<string>('get_config');
               if (configStr) {
-                  const config = JSON.parse(configStr);
-                  const theme = (config.theme as AppTheme | undefined) ?? 'zinc';
-                  set({
-                      sidebarWidth: config.sidebarWidth ?? 256,
-                      sidebarOpen: config.sidebarOpen ?? true,
-                      editorMode: config.editorMode ?? 'split',
-                      searchShortcut: config.shortcuts?.search ?? 'Cmd+G',
-                      sidebarShortcut: config.shortcuts?.sidebar ?? 'Cmd+1',
-                      closeEditorShortcut: config.shortcuts?.closeEditor ?? config.shortcuts?.close ?? 'Cmd+W',
-                      llmPanelShortcut: config.shortcuts?.llmPanel ?? 'Cmd+2',
-                      terminalShortcut: config.shortcuts?.terminal ?? 'Cmd+3',
-                      theme,
-                      llmConfigs: config.llm?.configs ?? [],
-                      activeLLMConfigId: config.llm?.activeId ?? null,
-                      llmPanelWidth: config.llm?.panelWidth ?? 300,
-                      terminalHeight: config.terminal?.height ?? 300,
-                      systemPrompts: config.llm?.systemPrompts ?? [],
-                      activeSystemPromptId: config.llm?.activeSystemPromptId ?? null
-                  });
-                  applyTheme(theme);
+                  await get().applyConfig(JSON.parse(configStr));
+              }
+              const report = await invoke<{ version: number; warnings: string[]; errors: string[] }>('check_config');
+              const problems = [...report.warnings, ...report.errors];
+              if (problems.length > 0) {
+                  get().pushNotice(`Config: ${problems.join('; ')}`, 'error');
               }
           }
       } catch (err) {
@@ -270,8 +325,40 @@ export const useAppStore = create<AppState>((set, get) => ({
       }
   },
 
+  applyConfig: async (config) => {
+      const theme = (config.theme as AppTheme | undefined) ?? 'zinc';
+      const webdav = { ...defaultSyncSettings.webdav, ...(config.sync?.webdav ?? {}) };
+      if (webdav.password.startsWith('secret:')) {
+          webdav.password = (await invoke<string | null>('get_secret', { id: webdav.password }).catch(() => null)) ?? webdav.password;
+      }
+      configRevision = config.revision ?? null;
+      set({
+          sidebarWidth: config.sidebarWidth ?? 256,
+          sidebarOpen: config.sidebarOpen ?? true,
+          editorMode: config.editorMode ?? 'split',
+          searchShortcut: config.shortcuts?.search ?? 'Cmd+G',
+          sidebarShortcut: config.shortcuts?.sidebar ?? 'Cmd+1',
+          closeEditorShortcut: config.shortcuts?.closeEditor ?? config.shortcuts?.close ?? 'Cmd+W',
+          llmPanelShortcut: config.shortcuts?.llmPanel ?? 'Cmd+2',
+          terminalShortcut: config.shortcuts?.terminal ?? 'Cmd+3',
+          theme,
+          // API keys stay `secret:<id>` handles; the backend resolves them when sending.
+          llmConfigs: config.llm?.configs ?? [],
+          activeLLMConfigId: config.llm?.activeId ?? null,
+          llmPanelWidth: config.llm?.panelWidth ?? 300,
+          terminalHeight: config.terminal?.height ?? 300,
+          terminalProfiles: config.terminal?.profiles ?? [],
+          defaultTerminalProfile: config.terminal?.defaultProfile ?? null,
+          systemPrompts: config.llm?.systemPrompts ?? [],
+          activeSystemPromptId: config.llm?.activeSystemPromptId ?? null,
+          backupSettings: { ...defaultBackupSettings, ...(config.backup ?? {}) },
+          syncSettings: { ...defaultSyncSettings, ...(config.sync ?? {}), webdav }
+      });
+      applyTheme(theme);
+  },
+
   saveConfig: async () => {
-      const { sidebarWidth, sidebarOpen, editorMode, searchShortcut, sidebarShortcut, closeEditorShortcut, llmPanelShortcut, terminalShortcut, theme, llmConfigs, activeLLMConfigId, llmPanelWidth, terminalHeight, systemPrompts, activeSystemPromptId } = get();
+      const { sidebarWidth, sidebarOpen, editorMode, searchShortcut, sidebarShortcut, closeEditorShortcut, llmPanelShortcut, terminalShortcut, theme, llmConfigs, activeLLMConfigId, llmPanelWidth, terminalHeight, terminalProfiles, defaultTerminalProfile, systemPrompts, activeSystemPromptId, backupSettings, syncSettings } = get();
       try {
           // @ts-ignore
           if (window.__TAURI_INTERNALS__) {
@@ -295,13 +382,31 @@ export const useAppStore = create<AppState>((set, get) => ({
                       activeSystemPromptId
                   },
                   terminal: {
-                      height: terminalHeight
-                  }
+                      height: terminalHeight,
+                      profiles: terminalProfiles,
+                      defaultProfile: defaultTerminalProfile
+                  },
+                  backup: backupSettings,
+                  sync: syncSettings
               };
-              await invoke('save_config', { config: JSON.stringify(config) });
+              const saved = await invoke<{ revision: string; conflicts: string[] }>('save_config', {
+                  config: JSON.stringify(config),
+                  baseRevision: configRevision
+              });
+              configRevision = saved.revision;
+              // Typed-in API keys are now in the secret store; keep only their handles here.
+              set({
+                  llmConfigs: get().llmConfigs.map(c =>
+                      c.apiKey && !c.apiKey.startsWith('secret:') ? { ...c, apiKey: `secret:llm/${c.id}` } : c
+                  )
+              });
+              if (saved.conflicts.length > 0) {
+                  get().pushNotice(`Config: kept your changes over edits on disk to ${saved.conflicts.join(', ')}`, 'info');
+              }
           }
       } catch (err) {
           console.error("Failed to save config:", err);
+          get().pushNotice(`Config not saved: ${String(err)}`, 'error');
       }
   },
 